@@ -0,0 +1,267 @@
+//! Interactive model viewer.
+//!
+//! ```sh
+//! cargo run --release --example viewer --features viewer -- models/river.xml 128
+//! ```
+//!
+//! The model is reloaded whenever its file changes. The run restarts with the
+//! same seed, or carries on from the same point if "Resume on reload" is
+//! checked and the model's node tree still has the same shape.
+
+use std::time::Duration;
+
+use eframe::egui;
+
+use crabby_markov::model::{size_parameters, Model};
+use crabby_markov::node::{AnyNode, Threshold};
+use crabby_markov::runner::Runner;
+use crabby_markov::watch::ModelWatcher;
+use crabby_markov::Grid;
+
+struct Viewer {
+    watcher: ModelWatcher,
+    model: Model,
+    size: usize,
+    seed: u64,
+    runner: Runner,
+    texture: Option<egui::TextureHandle>,
+    playing: bool,
+    finished: bool,
+    steps_per_frame: usize,
+    resume_on_reload: bool,
+    reload_error: Option<String>,
+}
+
+impl Viewer {
+    fn new(watcher: ModelWatcher, model: Model, size: usize) -> Self {
+        let seed = 0;
+        let runner = Self::make_runner(&model, size, seed);
+        Self {
+            watcher,
+            model,
+            size,
+            seed,
+            runner,
+            texture: None,
+            playing: false,
+            finished: false,
+            steps_per_frame: 16,
+            resume_on_reload: false,
+            reload_error: None,
+        }
+    }
+
+    fn make_runner(model: &Model, size: usize, seed: u64) -> Runner {
+        let grid = model.make_grid(size, size);
+        Runner::with_seed(&model.root, grid, seed)
+    }
+
+    fn restart(&mut self) {
+        self.runner = Self::make_runner(&self.model, self.size, self.seed);
+        self.finished = false;
+    }
+
+    /// Swaps in the model if its file has changed.
+    fn reload(&mut self) {
+        let model = match self.watcher.poll() {
+            Some(Ok(model)) => model,
+            Some(Err(err)) => {
+                self.reload_error = Some(err.to_string());
+                return;
+            }
+            None => return,
+        };
+
+        self.reload_error = None;
+        self.model = model;
+        let checkpoint = self.runner.checkpoint();
+        self.restart();
+        if self.resume_on_reload && self.runner.restore(&checkpoint).is_ok() {
+            self.finished = false;
+        }
+    }
+
+    fn step(&mut self, steps: usize) {
+        for _ in 0..steps {
+            if !self.runner.step() {
+                self.finished = true;
+                self.playing = false;
+                break;
+            }
+        }
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let label = if self.playing { "Pause" } else { "Play" };
+            if ui
+                .add_enabled(!self.finished, egui::Button::new(label))
+                .clicked()
+            {
+                self.playing = !self.playing;
+            }
+
+            if ui
+                .add_enabled(!self.finished, egui::Button::new("Step"))
+                .clicked()
+            {
+                self.step(1);
+            }
+
+            if ui.button("Restart").clicked() {
+                self.restart();
+            }
+
+            ui.separator();
+            ui.label("Seed");
+            if ui.add(egui::DragValue::new(&mut self.seed)).changed() {
+                self.restart();
+            }
+
+            ui.separator();
+            ui.label("Steps per frame");
+            ui.add(egui::Slider::new(&mut self.steps_per_frame, 1..=1024).logarithmic(true));
+
+            ui.separator();
+            ui.checkbox(&mut self.resume_on_reload, "Resume on reload");
+        });
+
+        if let Some(err) = &self.reload_error {
+            ui.colored_label(egui::Color32::from_rgb(0xff, 0x00, 0x4d), err);
+        }
+
+        let progress = self.runner.progress();
+        ui.label(format!(
+            "{} steps, {} applications{}",
+            progress.steps,
+            progress.applications,
+            if self.finished { " (finished)" } else { "" }
+        ));
+    }
+
+    fn node_tree(&self, ui: &mut egui::Ui) {
+        let active = &self.runner.progress().path;
+        show_node(ui, &self.model.root, &[], active);
+    }
+
+    fn grid_image(&mut self, ui: &mut egui::Ui) {
+        let image = color_image(&self.runner.grid);
+        let options = egui::TextureOptions::NEAREST;
+        let texture = match &mut self.texture {
+            Some(texture) => {
+                texture.set(image, options);
+                texture
+            }
+            None => self
+                .texture
+                .insert(ui.ctx().load_texture("grid", image, options)),
+        };
+
+        let available = ui.available_size();
+        let scale = (available.x / self.size as f32).min(available.y / self.size as f32);
+        let size = egui::Vec2::splat(self.size as f32 * scale.max(1.0).floor());
+        ui.image((texture.id(), size));
+    }
+}
+
+impl eframe::App for Viewer {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.reload();
+        ctx.request_repaint_after(Duration::from_millis(250));
+
+        if self.playing {
+            self.step(self.steps_per_frame);
+            ctx.request_repaint();
+        }
+
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| self.controls(ui));
+
+        egui::SidePanel::left("nodes").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| self.node_tree(ui));
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| self.grid_image(ui));
+    }
+}
+
+fn show_node(ui: &mut egui::Ui, node: &AnyNode, path: &[usize], active: &[usize]) {
+    let label = match node {
+        AnyNode::Markov(_) => "markov".to_string(),
+        AnyNode::Sequence(_) => "sequence".to_string(),
+        AnyNode::One(node) => rule_label("one", node.rules.len(), node.steps),
+        AnyNode::All(node) => rule_label("all", node.rules.len(), node.steps),
+        AnyNode::Prl(node) => rule_label("prl", node.rules.len(), None),
+        AnyNode::Path(_) => "path".to_string(),
+        AnyNode::Walk(node) => format!("walk ({} moves)", node.length),
+        AnyNode::Repeat(node) => match node.times {
+            Some(times) => format!("repeat ({} times)", times),
+            None => "repeat".to_string(),
+        },
+        AnyNode::If(node) if node.present => "if present".to_string(),
+        AnyNode::If(_) => "if absent".to_string(),
+        AnyNode::Until(node) => match node.threshold {
+            Threshold::Count(count) => format!("until {} {}", count, node.symbol.to_char()),
+            Threshold::Percent(percent) => {
+                format!("until {}% {}", percent, node.symbol.to_char())
+            }
+        },
+        AnyNode::Dyn(node) => node.name().to_string(),
+    };
+
+    let is_active = active.starts_with(path);
+    let mut text = egui::RichText::new(label).monospace();
+    if is_active {
+        text = text
+            .strong()
+            .color(egui::Color32::from_rgb(0xff, 0x00, 0x4d));
+    }
+
+    ui.label(text);
+
+    if !node.children().is_empty() {
+        ui.indent(path.to_vec(), |ui| {
+            for (index, child) in node.children().iter().enumerate() {
+                let child_path = [path, &[index]].concat();
+                show_node(ui, child, &child_path, active);
+            }
+        });
+    }
+}
+
+fn rule_label(kind: &str, rules: usize, steps: Option<usize>) -> String {
+    match steps {
+        Some(steps) => format!("{} ({} rules, {} steps)", kind, rules, steps),
+        None => format!("{} ({} rules)", kind, rules),
+    }
+}
+
+fn color_image(grid: &Grid) -> egui::ColorImage {
+    let pixels = grid.render_rgba(1);
+    egui::ColorImage::from_rgba_unmultiplied([grid.width, grid.height], &pixels)
+}
+
+fn main() -> eframe::Result {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: viewer <MODEL> [SIZE]");
+        std::process::exit(1);
+    };
+
+    let size = args.next().and_then(|size| size.parse().ok()).unwrap_or(64);
+    let model = match Model::load_with(&path, &size_parameters(size, size)) {
+        Ok(model) => model,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    eframe::run_native(
+        "crabby-markov viewer",
+        eframe::NativeOptions::default(),
+        Box::new(move |_cc| {
+            let watcher = ModelWatcher::new(path).with_parameters(size_parameters(size, size));
+            Ok(Box::new(Viewer::new(watcher, model, size)))
+        }),
+    )
+}
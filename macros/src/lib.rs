@@ -0,0 +1,128 @@
+//! Compile-time embedding of crabby-markov models.
+//!
+//! [include_model!] loads a model file while the crate using it compiles, so
+//! a model that doesn't parse or validate fails the build rather than the
+//! program that ships it:
+//!
+//! ```ignore
+//! use crabby_markov_macros::include_model;
+//!
+//! let river = include_model!("models/river.xml");
+//! let dungeon = include_model!("models/dungeon.xml", width = 64, height = 64, rooms = 12);
+//! ```
+
+use std::path::PathBuf;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Ident, LitInt, LitStr, Token};
+
+use crabby_markov::model::{Model, Parameters};
+
+/// Loads a model file at compile time, evaluating to its
+/// [Model](crabby_markov::model::Model).
+///
+/// The path is relative to the directory holding the crate's `Cargo.toml`.
+/// Parameters may follow it as `NAME = VALUE`, like the CLI's `--param`.
+/// The model is validated as well as parsed, with patterns checked against
+/// the grid's `width` and `height` if they're given.
+///
+/// The file's text is embedded in the binary and parsed again at runtime
+/// with the same parameters, which can't fail once it has succeeded here.
+/// Models with `include` elements can't be embedded, since the files they
+/// include would still be needed at runtime.
+#[proc_macro]
+pub fn include_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Input);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(message) => syn::Error::new(input.path.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// A model's path, followed by its parameters.
+struct Input {
+    path: LitStr,
+    parameters: Vec<(Ident, i64)>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let mut parameters = Vec::new();
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let negative = input.parse::<Option<Token![-]>>()?.is_some();
+            let value = input.parse::<LitInt>()?.base10_parse::<i64>()?;
+            parameters.push((name, if negative { -value } else { value }));
+        }
+
+        if !input.is_empty() {
+            return Err(input.error("expected `,`"));
+        }
+
+        Ok(Self { path, parameters })
+    }
+}
+
+/// Loads and validates the model, then writes the code that loads it again
+/// at runtime.
+fn expand(input: &Input) -> Result<proc_macro2::TokenStream, String> {
+    let root = std::env::var("CARGO_MANIFEST_DIR").map_err(|_| "CARGO_MANIFEST_DIR is not set")?;
+    let path = PathBuf::from(root).join(input.path.value());
+    let text = std::fs::read_to_string(&path)
+        .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+
+    // Without a file to be relative to, includes would be looked up in the
+    // working directory of whatever runs the binary.
+    if let Ok(document) = roxmltree::Document::parse(&text) {
+        if document
+            .descendants()
+            .any(|node| node.has_tag_name("include"))
+        {
+            return Err(format!(
+                "{} includes other files, so it can't be embedded",
+                path.display()
+            ));
+        }
+    }
+
+    let parameters: Parameters = input
+        .parameters
+        .iter()
+        .map(|(name, value)| (name.to_string(), *value))
+        .collect();
+
+    let model = Model::from_xml_with(&text, &parameters)
+        .map_err(|err| format!("couldn't load {}: {}", path.display(), err))?;
+
+    let size = |name: &str| match parameters.get(name) {
+        Some(size) => usize::try_from(*size).map_err(|_| format!("{} can't be negative", name)),
+        None => Ok(usize::MAX),
+    };
+
+    let diagnostics = model.validate(size("width")?, size("height")?);
+    if !diagnostics.is_empty() {
+        let lines: Vec<_> = diagnostics.iter().map(ToString::to_string).collect();
+        return Err(format!(
+            "{} is invalid:\n{}",
+            path.display(),
+            lines.join("\n")
+        ));
+    }
+
+    let file = path.to_str().ok_or("model paths must be valid UTF-8")?;
+    let names = input.parameters.iter().map(|(name, _)| name.to_string());
+    let values = input.parameters.iter().map(|(_, value)| value);
+    Ok(quote! {
+        ::crabby_markov::model::Model::from_xml_with(
+            ::core::include_str!(#file),
+            &::crabby_markov::model::Parameters::from([#((#names.to_string(), #values)),*]),
+        )
+        .expect("model was validated when compiled")
+    })
+}
@@ -0,0 +1,17 @@
+use crabby_markov::model::{size_parameters, Model};
+use crabby_markov_macros::include_model;
+
+#[test]
+fn include_models() {
+    let river = include_model!("../models/river.xml");
+    let expected = Model::from_xml(include_str!("../../models/river.xml")).unwrap();
+    assert_eq!(river, expected);
+
+    let dungeon = include_model!("../models/nystrom-dungeon.xml", width = 32, height = 24,);
+    let expected = Model::from_xml_with(
+        include_str!("../../models/nystrom-dungeon.xml"),
+        &size_parameters(32, 24),
+    )
+    .unwrap();
+    assert_eq!(dungeon, expected);
+}
@@ -0,0 +1,236 @@
+//! Quantitative summaries of finished grids.
+//!
+//! Every analysis here looks at each cell's [visible](Grid::visible) symbol,
+//! as a rendered frame would show it, so multi-layer grids are summarized the
+//! way they look rather than by their hidden layers.
+
+use super::*;
+
+const SYMBOLS: usize = Symbol::ALL.len();
+
+/// How many cells show each symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [usize; SYMBOLS],
+}
+
+impl Histogram {
+    pub fn get(&self, symbol: Symbol) -> usize {
+        self.counts[symbol.palette_index() as usize]
+    }
+
+    /// The number of cells counted.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// The proportion of cells that show `symbol`, or zero for an empty grid.
+    pub fn fraction(&self, symbol: Symbol) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => self.get(symbol) as f64 / total as f64,
+        }
+    }
+
+    /// Each symbol that appears at least once with its count, in palette
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, usize)> + '_ {
+        Symbol::ALL
+            .into_iter()
+            .zip(self.counts)
+            .filter(|(_, count)| *count > 0)
+    }
+
+    /// The Shannon entropy of the symbol distribution, in bits.
+    pub fn entropy(&self) -> f64 {
+        entropy(&self.counts)
+    }
+
+    /// The total variation distance between the two symbol distributions,
+    /// from zero for identical proportions to one for disjoint symbols.
+    pub fn distance(&self, other: &Histogram) -> f64 {
+        let half: f64 = Symbol::ALL
+            .iter()
+            .map(|symbol| (self.fraction(*symbol) - other.fraction(*symbol)).abs())
+            .sum();
+
+        half / 2.0
+    }
+}
+
+/// How often each pair of symbols are orthogonal neighbors, including pairs
+/// across the edges of periodic grids.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Adjacency {
+    /// Pairs indexed by the symbols of the left or upper cell, then the right
+    /// or lower cell.
+    counts: Box<[[usize; SYMBOLS]; SYMBOLS]>,
+}
+
+impl Adjacency {
+    /// The number of neighboring pairs made of `a` and `b`, in either order.
+    pub fn get(&self, a: Symbol, b: Symbol) -> usize {
+        let (a, b) = (a.palette_index() as usize, b.palette_index() as usize);
+        match a == b {
+            true => self.counts[a][a],
+            false => self.counts[a][b] + self.counts[b][a],
+        }
+    }
+
+    /// The number of neighboring pairs counted.
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    /// The proportion of neighboring pairs that show the same symbol. High
+    /// values mean large uniform regions, low values mean noise.
+    pub fn uniformity(&self) -> f64 {
+        let same: usize = (0..SYMBOLS).map(|idx| self.counts[idx][idx]).sum();
+        match self.total() {
+            0 => 0.0,
+            total => same as f64 / total as f64,
+        }
+    }
+}
+
+/// A summary of how two grids of the same size differ.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    /// The number of cells whose visible symbols differ.
+    pub changed: usize,
+
+    /// The total number of cells in each grid.
+    pub cells: usize,
+
+    /// The [Histogram::distance] between the two grids.
+    pub histogram_distance: f64,
+}
+
+impl Comparison {
+    /// The proportion of cells that show the same symbol in both grids.
+    pub fn similarity(&self) -> f64 {
+        match self.cells {
+            0 => 1.0,
+            cells => 1.0 - self.changed as f64 / cells as f64,
+        }
+    }
+}
+
+impl Grid {
+    pub fn histogram(&self) -> Histogram {
+        let mut counts = [0; SYMBOLS];
+        for at in self.enumerate_points() {
+            counts[self.visible(at).palette_index() as usize] += 1;
+        }
+
+        Histogram { counts }
+    }
+
+    pub fn adjacency(&self) -> Adjacency {
+        let mut counts = Box::new([[0; SYMBOLS]; SYMBOLS]);
+        for at in self.enumerate_points() {
+            let here = self.visible(at).palette_index() as usize;
+            for offset in [Offset { dx: 1, dy: 0 }, Offset { dx: 0, dy: 1 }] {
+                if let Some(next) = self.offset(at, offset) {
+                    counts[here][self.visible(next).palette_index() as usize] += 1;
+                }
+            }
+        }
+
+        Adjacency { counts }
+    }
+
+    /// Computes the Shannon entropy, in bits, of the symbols within `radius`
+    /// cells of each cell, clipped to the grid's edges.
+    ///
+    /// Uniform areas have zero entropy, while noisy or detailed areas score
+    /// higher.
+    pub fn entropy_map(&self, radius: usize) -> GenericGrid<f64> {
+        let mut map = GenericGrid::new(self.width, self.height);
+        for at in self.enumerate_points() {
+            let left = at.x.saturating_sub(radius);
+            let top = at.y.saturating_sub(radius);
+            let right = (at.x + radius + 1).min(self.width);
+            let bottom = (at.y + radius + 1).min(self.height);
+
+            let mut counts = [0; SYMBOLS];
+            for near in Rect::new(Point { x: left, y: top }, right - left, bottom - top).points() {
+                counts[self.visible(near).palette_index() as usize] += 1;
+            }
+
+            map[at] = entropy(&counts);
+        }
+
+        map
+    }
+
+    /// Compares this grid to another of the same size.
+    ///
+    /// Panics if the grids have different sizes.
+    pub fn compare(&self, other: &Grid) -> Comparison {
+        Comparison {
+            changed: self.diff(other).len(),
+            cells: self.width * self.height,
+            histogram_distance: self.histogram().distance(&other.histogram()),
+        }
+    }
+}
+
+fn entropy(counts: &[usize]) -> f64 {
+    let total: usize = counts.iter().sum();
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze() {
+        let grid = Grid::from_display_str(
+            "
+            BBWW
+            BBWW
+            RRRR
+            ",
+        )
+        .unwrap();
+
+        let histogram = grid.histogram();
+        assert_eq!(histogram.total(), 12);
+        assert_eq!(histogram.get(Symbol::Red), 4);
+        assert_eq!(
+            histogram.iter().collect::<Vec<_>>(),
+            [(Symbol::Black, 4), (Symbol::White, 4), (Symbol::Red, 4)]
+        );
+        assert!((histogram.entropy() - 3f64.log2()).abs() < 1e-9);
+
+        let adjacency = grid.adjacency();
+        assert_eq!(adjacency.total(), 17);
+        assert_eq!(adjacency.get(Symbol::Black, Symbol::Black), 4);
+        assert_eq!(adjacency.get(Symbol::White, Symbol::Black), 2);
+        assert_eq!(adjacency.get(Symbol::Red, Symbol::Red), 3);
+        assert_eq!(adjacency.get(Symbol::Black, Symbol::Red), 2);
+
+        let map = grid.entropy_map(0);
+        assert!(map.grid.iter().all(|entropy| *entropy == 0.0));
+        let map = grid.entropy_map(1);
+        assert_eq!(map[Point { x: 0, y: 0 }], 0.0);
+        assert!(map[Point { x: 1, y: 1 }] > map[Point { x: 1, y: 0 }]);
+
+        let mut other = grid.clone();
+        other[Point { x: 0, y: 0 }] = Symbol::Red;
+        let comparison = grid.compare(&other);
+        assert_eq!(comparison.changed, 1);
+        assert!((comparison.similarity() - 11.0 / 12.0).abs() < 1e-9);
+        assert!((comparison.histogram_distance - 1.0 / 12.0).abs() < 1e-9);
+        assert_eq!(grid.compare(&grid).similarity(), 1.0);
+    }
+}
@@ -0,0 +1,170 @@
+//! Rendering grids with sprites instead of solid colors.
+//!
+//! An [Atlas] maps symbols to small square RGBA sprites, such as wall,
+//! floor, and door tiles for a dungeon. Symbols without a sprite are drawn
+//! as solid tiles of their palette color.
+
+use super::*;
+
+/// A set of same-sized square sprites, one per symbol at most.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Atlas {
+    tile_size: u16,
+    sprites: Vec<Option<Vec<u8>>>,
+}
+
+impl Atlas {
+    /// Creates an atlas without any sprites.
+    pub fn new(tile_size: u16) -> Self {
+        Self {
+            tile_size,
+            sprites: vec![None; Symbol::ALL.len()],
+        }
+    }
+
+    /// Cuts a sprite sheet into tiles, reading them left to right and top to
+    /// bottom. The tile at index `i` becomes the sprite of the symbol with
+    /// palette index `i`; leftover tiles are ignored.
+    ///
+    /// `pixels` is RGBA, `width` pixels wide. Panics if the sheet's width or
+    /// height isn't a multiple of the tile size.
+    pub fn from_sheet(pixels: &[u8], width: usize, tile_size: u16) -> Self {
+        let tile = tile_size as usize;
+        let height = pixels.len() / 4 / width.max(1);
+        if !width.is_multiple_of(tile)
+            || !height.is_multiple_of(tile)
+            || pixels.len() != width * height * 4
+        {
+            panic!(
+                "{}x{} sprite sheet doesn't divide into {}x{} tiles",
+                width, height, tile, tile
+            );
+        }
+
+        let mut atlas = Self::new(tile_size);
+        let columns = width / tile;
+        let tiles = columns * (height / tile);
+        for index in 0..tiles.min(Symbol::ALL.len()) {
+            let (tx, ty) = (index % columns * tile, index / columns * tile);
+            let sprite = (ty..ty + tile)
+                .flat_map(|y| &pixels[(y * width + tx) * 4..][..tile * 4])
+                .copied()
+                .collect();
+
+            atlas.sprites[index] = Some(sprite);
+        }
+
+        atlas
+    }
+
+    pub fn tile_size(&self) -> u16 {
+        self.tile_size
+    }
+
+    /// Sets a symbol's sprite from `tile_size * tile_size` RGBA pixels.
+    pub fn with_sprite(mut self, symbol: Symbol, pixels: Vec<u8>) -> Self {
+        let tile = self.tile_size as usize;
+        if pixels.len() != tile * tile * 4 {
+            panic!(
+                "sprite for {:?} has {} bytes but a {}x{} tile needs {}",
+                symbol,
+                pixels.len(),
+                tile,
+                tile,
+                tile * tile * 4
+            );
+        }
+
+        self.sprites[symbol.palette_index() as usize] = Some(pixels);
+        self
+    }
+
+    /// Removes a symbol's sprite so that it is drawn as a solid tile again.
+    pub fn without_sprite(mut self, symbol: Symbol) -> Self {
+        self.sprites[symbol.palette_index() as usize] = None;
+        self
+    }
+
+    pub fn sprite(&self, symbol: Symbol) -> Option<&[u8]> {
+        self.sprites[symbol.palette_index() as usize].as_deref()
+    }
+}
+
+impl Grid {
+    /// Renders the visible cells as RGBA pixels using an atlas's sprites.
+    pub fn render_atlas(&self, atlas: &Atlas) -> Vec<u8> {
+        let tile = atlas.tile_size as usize;
+        let width = self.width * tile;
+        let mut pixels = vec![0; width * self.height * tile * 4];
+        for at in self.enumerate_points() {
+            let symbol = self.visible(at);
+            for row in 0..tile {
+                let start = ((at.y * tile + row) * width + at.x * tile) * 4;
+                let dst = &mut pixels[start..][..tile * 4];
+                match atlas.sprite(symbol) {
+                    Some(sprite) => dst.copy_from_slice(&sprite[row * tile * 4..][..tile * 4]),
+                    None => {
+                        let rgb = &Symbol::PALETTE[symbol.palette_index() as usize * 3..][..3];
+                        for pixel in dst.chunks_mut(4) {
+                            pixel.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 0xff]);
+                        }
+                    }
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Like [Grid::render_gif_frame], but draws sprites from an atlas. The
+    /// frame gets its own palette, quantized from the sprites' colors.
+    ///
+    /// Panics if the frame is too large for a GIF.
+    pub fn render_atlas_gif_frame(&self, atlas: &Atlas) -> gif::Frame<'static> {
+        let (width, height) = gif_size(
+            self.width.saturating_mul(atlas.tile_size as usize),
+            self.height.saturating_mul(atlas.tile_size as usize),
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+        let mut pixels = self.render_atlas(atlas);
+        gif::Frame::from_rgba_speed(width, height, &mut pixels, 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprites() {
+        // A 2x2 checker for black and a horizontal stripe for white.
+        let dark = [1, 2, 3, 255];
+        let light = [200, 201, 202, 255];
+        let sheet: Vec<u8> = [
+            dark, light, light, light, //
+            light, dark, dark, dark,
+        ]
+        .concat();
+
+        let atlas = Atlas::from_sheet(&sheet, 4, 2);
+        assert_eq!(
+            atlas.sprite(Symbol::Black),
+            Some(&[dark, light, light, dark].concat()[..])
+        );
+        assert_eq!(atlas.sprite(Symbol::Red), None);
+
+        let mut grid = Grid::new(2, 1);
+        grid[Point { x: 1, y: 0 }] = Symbol::Red;
+        let pixels = grid.render_atlas(&atlas);
+        assert_eq!(pixels.len(), 4 * 2 * 4);
+        assert_eq!(&pixels[..8], &[dark, light].concat()[..]);
+        assert_eq!(&pixels[8..12], &[0xff, 0x00, 0x4d, 0xff]);
+        assert_eq!(&pixels[16..24], &[light, dark].concat()[..]);
+
+        let atlas = atlas.with_sprite(Symbol::Red, light.repeat(4));
+        assert_eq!(&grid.render_atlas(&atlas)[24..], &light.repeat(2)[..]);
+
+        let frame = grid.render_atlas_gif_frame(&atlas);
+        assert_eq!((frame.width, frame.height), (4, 2));
+    }
+}
@@ -0,0 +1,237 @@
+//! Running the same model over many seeds.
+//!
+//! [Runner::run_batch] runs a model once per seed from the same initial grid,
+//! hands each final grid to a callback, and summarizes how the runs varied.
+//! With the `rayon` feature, [Runner::run_batch_parallel] spreads the seeds
+//! across threads instead. Each run has its own RNG and results are gathered
+//! in seed order, so a parallel batch gives the same results as a serial one.
+
+use rand::{Rng, SeedableRng};
+
+use super::*;
+use node::AnyNode;
+use runner::{Budget, Outcome, Runner};
+
+/// The smallest, largest, and mean of a set of counts.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Spread {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl Spread {
+    /// Summarizes a set of counts, or returns all zeroes if there are none.
+    pub fn of(counts: impl IntoIterator<Item = usize>) -> Self {
+        let mut spread = Spread {
+            min: usize::MAX,
+            ..Default::default()
+        };
+
+        let mut len = 0;
+        let mut total = 0;
+        for count in counts {
+            spread.min = spread.min.min(count);
+            spread.max = spread.max.max(count);
+            total += count;
+            len += 1;
+        }
+
+        if len == 0 {
+            return Self::default();
+        }
+
+        spread.mean = total as f64 / len as f64;
+        spread
+    }
+}
+
+/// Summary statistics over a batch of runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchSummary {
+    pub runs: usize,
+
+    /// How many runs were stopped by the budget before the model finished.
+    pub incomplete: usize,
+
+    pub steps: Spread,
+    pub applications: Spread,
+
+    /// The number of cells of each symbol in the final grids, counted over
+    /// every layer. Symbols that never appear are left out.
+    pub symbols: Vec<(Symbol, Spread)>,
+}
+
+/// The results of [Runner::run_batch].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Batch<T> {
+    /// The callback's result for each seed, in seed order.
+    pub outputs: Vec<T>,
+
+    pub summary: BatchSummary,
+}
+
+/// What is kept of each run for its batch's summary.
+struct RunRecord {
+    outcome: Outcome,
+    steps: usize,
+    applications: usize,
+    symbols: [usize; Symbol::ALL.len()],
+}
+
+impl RunRecord {
+    fn new<R: Rng>(runner: &Runner<R>, outcome: Outcome) -> Self {
+        Self {
+            outcome,
+            steps: runner.progress().steps,
+            applications: runner.progress().applications,
+            symbols: Symbol::ALL.map(|symbol| runner.grid.count(symbol)),
+        }
+    }
+}
+
+impl BatchSummary {
+    fn new(records: &[RunRecord]) -> Self {
+        let symbols = Symbol::ALL
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| records.iter().any(|record| record.symbols[*idx] > 0))
+            .map(|(idx, symbol)| {
+                let counts = records.iter().map(|record| record.symbols[idx]);
+                (*symbol, Spread::of(counts))
+            })
+            .collect();
+
+        Self {
+            runs: records.len(),
+            incomplete: records
+                .iter()
+                .filter(|record| record.outcome != Outcome::Finished)
+                .count(),
+            steps: Spread::of(records.iter().map(|record| record.steps)),
+            applications: Spread::of(records.iter().map(|record| record.applications)),
+            symbols,
+        }
+    }
+}
+
+impl<R: SeedableRng + Rng> Runner<R> {
+    /// Runs `model` from `grid` once for each seed, within `budget`, passing
+    /// every final grid to `f` along with its seed.
+    pub fn run_batch<T>(
+        model: &AnyNode,
+        grid: &Grid,
+        budget: &Budget,
+        seeds: impl IntoIterator<Item = u64>,
+        mut f: impl FnMut(u64, &Grid) -> T,
+    ) -> Batch<T> {
+        let results = seeds.into_iter().map(|seed| {
+            let (runner, record) = Self::run_seed(model, grid, budget, seed);
+            (f(seed, &runner.grid), record)
+        });
+
+        Batch::new(results.collect())
+    }
+
+    /// Like [Runner::run_batch], but runs the seeds in parallel on rayon's
+    /// global thread pool. Outputs are still returned in seed order.
+    #[cfg(feature = "rayon")]
+    pub fn run_batch_parallel<T: Send>(
+        model: &AnyNode,
+        grid: &Grid,
+        budget: &Budget,
+        seeds: impl IntoIterator<Item = u64>,
+        f: impl Fn(u64, &Grid) -> T + Sync,
+    ) -> Batch<T> {
+        use rayon::prelude::*;
+
+        let seeds: Vec<u64> = seeds.into_iter().collect();
+        let results = seeds.into_par_iter().map(|seed| {
+            let (runner, record) = Self::run_seed(model, grid, budget, seed);
+            (f(seed, &runner.grid), record)
+        });
+
+        Batch::new(results.collect())
+    }
+
+    fn run_seed(model: &AnyNode, grid: &Grid, budget: &Budget, seed: u64) -> (Self, RunRecord) {
+        let mut runner = Self::with_seed(model, grid.clone(), seed);
+        runner.set_budget(budget.clone());
+        let outcome = runner.run();
+        let record = RunRecord::new(&runner, outcome);
+        (runner, record)
+    }
+}
+
+impl<T> Batch<T> {
+    fn new(results: Vec<(T, RunRecord)>) -> Self {
+        let (outputs, records): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+        Self {
+            outputs,
+            summary: BatchSummary::new(&records),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+
+    #[test]
+    fn run_batch() {
+        let model = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        let grid = model.make_grid(24, 24);
+        let batch = Runner::<rng::DefaultRng>::run_batch(
+            &model.root,
+            &grid,
+            &Budget::default(),
+            0..5,
+            |seed, grid| (seed, grid.clone()),
+        );
+
+        let summary = &batch.summary;
+        assert_eq!(summary.runs, 5);
+        assert_eq!(summary.incomplete, 0);
+        assert!(summary.steps.min <= summary.steps.max);
+        assert!(summary.symbols.iter().all(|(_, spread)| spread.max > 0));
+
+        for (seed, grid) in &batch.outputs {
+            let mut runner: Runner = Runner::with_seed(&model.root, model.make_grid(24, 24), *seed);
+            runner.run();
+            assert_eq!(&runner.grid, grid);
+        }
+
+        let limited = Budget {
+            applications: Some(10),
+            ..Default::default()
+        };
+
+        let batch =
+            Runner::<rng::DefaultRng>::run_batch(&model.root, &grid, &limited, [1, 2], |_, _| ());
+        assert_eq!(batch.summary.incomplete, 2);
+        assert_eq!(batch.summary.applications.max, 10);
+        assert_eq!(Spread::of([]), Spread::default());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_serial() {
+        let model = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        let grid = model.make_grid(24, 24);
+        let budget = Budget::default();
+        let serial =
+            Runner::<rng::DefaultRng>::run_batch(&model.root, &grid, &budget, 0..16, |_, grid| {
+                grid.clone()
+            });
+        let parallel = Runner::<rng::DefaultRng>::run_batch_parallel(
+            &model.root,
+            &grid,
+            &budget,
+            0..16,
+            |_, grid| grid.clone(),
+        );
+
+        assert_eq!(parallel, serial);
+    }
+}
@@ -0,0 +1,84 @@
+//! A Bevy plugin that steps runners live and uploads their grids as textures.
+//!
+//! Spawn a [MarkovGrid] alongside a [Sprite] showing the image returned by
+//! [MarkovGrid::make_image], and [MarkovPlugin] will advance the runner each
+//! frame and keep the image up to date.
+
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use super::*;
+use runner::Runner;
+
+pub struct MarkovPlugin;
+
+impl Plugin for MarkovPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, step_grids);
+    }
+}
+
+/// A runner driven by [MarkovPlugin].
+#[derive(Component)]
+pub struct MarkovGrid {
+    pub runner: Runner,
+
+    /// How many steps to take each frame.
+    pub steps_per_frame: usize,
+
+    /// Whether the runner has finished.
+    pub finished: bool,
+}
+
+impl MarkovGrid {
+    pub fn new(runner: Runner, steps_per_frame: usize) -> Self {
+        Self {
+            runner,
+            steps_per_frame,
+            finished: false,
+        }
+    }
+
+    /// Creates an image sized to the grid, with one pixel per cell.
+    pub fn make_image(&self) -> Image {
+        let grid = &self.runner.grid;
+        let size = Extent3d {
+            width: grid.width as u32,
+            height: grid.height as u32,
+            depth_or_array_layers: 1,
+        };
+
+        let mut image = Image::new(
+            size,
+            TextureDimension::D2,
+            grid.render_rgba(1),
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+
+        image.sampler = ImageSampler::nearest();
+        image
+    }
+}
+
+fn step_grids(mut grids: Query<(&mut MarkovGrid, &Sprite)>, mut images: ResMut<Assets<Image>>) {
+    for (mut grid, sprite) in grids.iter_mut() {
+        if grid.finished {
+            continue;
+        }
+
+        let grid = grid.as_mut();
+        for _ in 0..grid.steps_per_frame {
+            if !grid.runner.step() {
+                grid.finished = true;
+                break;
+            }
+        }
+
+        if let Some(image) = images.get_mut(&sprite.image) {
+            image.data = Some(grid.runner.grid.render_rgba(1));
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Images of rules, for reviewing a model's rules at a glance.
+//!
+//! [Catalogue::render_rule] draws a rule and its symmetric variants side by
+//! side, each as its find pattern, an arrow, and its replace pattern, with
+//! the variant's index beneath it. [Catalogue::render_model] does the same
+//! for every rule node in a model, one row per rule as written, under a
+//! heading of the node's ID. Rules are labeled with their index within
+//! their node, as in traces and reports.
+//!
+//! Wildcard cells are drawn as a checkerboard. The layers of multi-layer
+//! patterns are stacked from the bottom layer down. Like
+//! [Montage](crate::montage::Montage), catalogues are rendered as palette
+//! indices.
+
+use super::*;
+use montage::{label_width, Sheet, LABEL_HEIGHT, LABEL_SCALE};
+use node::AnyNode;
+use rule_set::RuleSet;
+
+/// A right-pointing arrow in the style of the label digits.
+const ARROW: [u8; 5] = [0b100, 0b110, 0b111, 0b110, 0b100];
+
+/// How a catalogue is laid out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Catalogue {
+    /// Pixel size of each pattern cell.
+    pub tile_size: u16,
+
+    /// Pixels of background around and between rules.
+    pub gap: usize,
+
+    pub background: Symbol,
+    pub text: Symbol,
+
+    /// The color of node headings.
+    pub heading: Symbol,
+
+    /// The color checkered with the background in wildcard cells.
+    pub wildcard: Symbol,
+}
+
+impl Default for Catalogue {
+    fn default() -> Self {
+        Self {
+            tile_size: 8,
+            gap: 4,
+            background: Symbol::Dark,
+            text: Symbol::White,
+            heading: Symbol::Yellow,
+            wildcard: Symbol::Gray,
+        }
+    }
+}
+
+impl Catalogue {
+    /// Renders `rule` and its variants under `symmetry` in a single row,
+    /// labeled from 0 for the rule as written.
+    pub fn render_rule(&self, rule: &Rule, symmetry: Symmetry) -> Sheet {
+        let variants = RuleSet::new(symmetry.expand(rule.clone()));
+        let labels: Vec<_> = (0..variants.len()).collect();
+        self.strip(&variants, &labels)
+    }
+
+    /// Renders every `one`, `all`, and `prl` node in the tree, in pre-order.
+    ///
+    /// A node's rules have already been expanded, so a row holds a rule and
+    /// the rules right after it that are among its symmetric variants.
+    pub fn render_model(&self, root: &AnyNode) -> Sheet {
+        let mut nodes = Vec::new();
+        collect(root, &mut 0, &mut nodes);
+
+        let mut blocks = Vec::new();
+        for (id, rules) in nodes {
+            let strips: Vec<_> = groups(rules)
+                .into_iter()
+                .map(|group| {
+                    let variants: Vec<_> = group.clone().map(|idx| rules[idx].clone()).collect();
+                    let labels: Vec<_> = group.collect();
+                    self.strip(&variants, &labels)
+                })
+                .collect();
+
+            blocks.push((id, strips));
+        }
+
+        let mut width = 0;
+        let mut height = self.gap;
+        for (id, strips) in blocks.iter() {
+            width = width.max(label_width(*id as u64));
+            height += LABEL_HEIGHT + self.gap;
+            for strip in strips {
+                width = width.max(strip.width);
+                height += strip.height + self.gap;
+            }
+        }
+
+        let mut sheet = Sheet::new(width + self.gap * 2, height, self.background);
+        let mut top = self.gap;
+        for (id, strips) in blocks {
+            sheet.label(self.gap, top, id as u64, self.heading);
+            top += LABEL_HEIGHT + self.gap;
+            for strip in strips {
+                sheet.paste(self.gap, top, &strip.pixels, strip.width);
+                top += strip.height + self.gap;
+            }
+        }
+
+        sheet
+    }
+
+    /// Renders rules in a row, each with its label beneath it.
+    fn strip(&self, rules: &[Rule], labels: &[usize]) -> Sheet {
+        let arrow_width = 3 * LABEL_SCALE;
+        let cells: Vec<_> = rules
+            .iter()
+            .map(|rule| (self.pattern(&rule.find), self.pattern(&rule.replace)))
+            .collect();
+
+        let widths: Vec<_> = cells
+            .iter()
+            .zip(labels)
+            .map(|((find, replace), label)| {
+                let rule = find.width + self.gap + arrow_width + self.gap + replace.width;
+                rule.max(label_width(*label as u64))
+            })
+            .collect();
+
+        let patterns_height = cells
+            .iter()
+            .map(|(find, replace)| find.height.max(replace.height).max(LABEL_HEIGHT))
+            .max()
+            .unwrap_or(0);
+
+        let width = widths.iter().sum::<usize>() + self.gap * 2 * widths.len().saturating_sub(1);
+        let height = patterns_height + self.gap + LABEL_HEIGHT;
+        let mut sheet = Sheet::new(width, height, self.background);
+
+        let mut left = 0;
+        for (((find, replace), label), cell_width) in cells.iter().zip(labels).zip(widths) {
+            sheet.paste(left, 0, &find.pixels, find.width);
+            let arrow_left = left + find.width + self.gap;
+            let arrow_top = (find.height.max(replace.height)).saturating_sub(LABEL_HEIGHT) / 2;
+            sheet.glyph(arrow_left, arrow_top, ARROW, self.text);
+            let replace_left = arrow_left + arrow_width + self.gap;
+            sheet.paste(replace_left, 0, &replace.pixels, replace.width);
+
+            let label_top = patterns_height + self.gap;
+            sheet.label(left, label_top, *label as u64, self.text);
+            left += cell_width + self.gap * 2;
+        }
+
+        sheet
+    }
+
+    /// Renders a pattern with its layers stacked from the bottom layer down.
+    fn pattern(&self, pattern: &Pattern) -> Sheet {
+        let tile = self.tile_size as usize;
+        let layer_height = pattern.height * tile;
+        let height = pattern.layers * (layer_height + self.gap) - self.gap;
+        let mut sheet = Sheet::new(pattern.width * tile, height, self.background);
+        for layer in 0..pattern.layers {
+            let top = layer * (layer_height + self.gap);
+            for at in Rect::new(Point::ZERO, pattern.width, pattern.height).points() {
+                for y in 0..tile {
+                    for x in 0..tile {
+                        let color = match pattern[(at, layer)] {
+                            Some(symbol) => symbol,
+                            None if (x + y) % 2 == 0 => self.wildcard,
+                            None => self.background,
+                        };
+
+                        let offset = (top + at.y * tile + y) * sheet.width + at.x * tile + x;
+                        sheet.pixels[offset] = color.palette_index();
+                    }
+                }
+            }
+        }
+
+        sheet
+    }
+}
+
+/// Finds every node with rules, along with its pre-order ID.
+fn collect<'a>(node: &'a AnyNode, next_id: &mut usize, out: &mut Vec<(usize, &'a RuleSet)>) {
+    let id = *next_id;
+    *next_id += 1;
+
+    match node {
+        AnyNode::One(node) => out.push((id, &node.rules)),
+        AnyNode::All(node) => out.push((id, &node.rules)),
+        AnyNode::Prl(node) => out.push((id, &node.rules)),
+        _ => {}
+    }
+
+    for child in node.children() {
+        collect(child, next_id, out);
+    }
+}
+
+/// Splits expanded rules into runs of a rule followed by its variants.
+fn groups(rules: &RuleSet) -> Vec<std::ops::Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    while start < rules.len() {
+        let variants = Symmetry::Square.expand(rules[start].clone());
+        let mut end = start + 1;
+        while end < rules.len() && variants.contains(&rules[end]) {
+            end += 1;
+        }
+
+        groups.push(start..end);
+        start = end;
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::{PrlNode, SequenceNode};
+
+    #[test]
+    fn render_rules() {
+        let catalogue = Catalogue {
+            tile_size: 2,
+            gap: 1,
+            ..Default::default()
+        };
+
+        let rule = Rule::from_strings("RB*", "RRW");
+        let sheet = catalogue.render_rule(&rule, Symmetry::MirrorX);
+
+        // Two variants of 6 + 1 + 6 + 1 + 6 pixels, two gaps apart, above
+        // their labels.
+        assert_eq!((sheet.width, sheet.height), (20 + 2 + 20, 10 + 1 + 10));
+        let at = |sheet: &Sheet, x: usize, y: usize| {
+            Symbol::from_palette_index(sheet.pixels[y * sheet.width + x]).unwrap()
+        };
+        assert_eq!(at(&sheet, 0, 0), Symbol::Red);
+        assert_eq!(at(&sheet, 2, 1), Symbol::Black);
+        assert_eq!(at(&sheet, 4, 0), Symbol::Gray);
+        assert_eq!(at(&sheet, 5, 0), Symbol::Dark);
+        assert_eq!(at(&sheet, 7, 0), Symbol::White);
+        assert_eq!(at(&sheet, 14, 0), Symbol::Red);
+        assert_eq!(at(&sheet, 22, 0), Symbol::Gray);
+        assert_eq!(at(&sheet, 24, 0), Symbol::Black);
+        assert_eq!(at(&sheet, 0, 11), Symbol::White);
+        assert_eq!(at(&sheet, 24, 11), Symbol::White);
+
+        let model = AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: vec![
+                AnyNode::Prl(PrlNode {
+                    name: None,
+                    rules: RuleSet::with_symmetry(
+                        [Rule::from_strings("RB", "WR"), Rule::from_strings("W", "R")],
+                        Symmetry::Rotations,
+                    ),
+                }),
+                AnyNode::Prl(PrlNode {
+                    name: None,
+                    rules: RuleSet::new([rule]),
+                }),
+            ],
+        });
+
+        let AnyNode::Sequence(sequence) = &model else {
+            unreachable!();
+        };
+        let AnyNode::Prl(first) = &sequence.children[0] else {
+            unreachable!();
+        };
+        assert_eq!(groups(&first.rules), [0..4, 4..5]);
+
+        // Each node's heading, then a row per rule as written.
+        let sheet = catalogue.render_model(&model);
+        assert_eq!(sheet.height, 1 + 11 + 22 + 22 + 11 + 22);
+        assert_eq!(at(&sheet, 3, 1), Symbol::Yellow);
+        assert_eq!(at(&sheet, 1, 12), Symbol::Red);
+        assert_eq!(at(&sheet, 1, 56), Symbol::Yellow);
+        assert_eq!(at(&sheet, 1, 67), Symbol::Red);
+    }
+}
@@ -0,0 +1,185 @@
+//! Custom characters for writing and reading grids as text.
+//!
+//! Grids normally use each symbol's own letter, as in [Symbol::to_char]. A
+//! [Charset] maps symbols to any characters instead, such as `#` for walls
+//! and `.` for floors, for tools that expect the usual roguelike glyphs.
+//! Characters may be any non-whitespace Unicode scalar, including ones that
+//! take several bytes in UTF-8.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use super::*;
+
+/// A two-way mapping between symbols and characters.
+///
+/// Each character stands for at most one symbol. Symbols without a
+/// character can't be written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Charset {
+    chars: [Option<char>; Symbol::ALL.len()],
+}
+
+impl Default for Charset {
+    /// Each symbol's own letter.
+    fn default() -> Self {
+        Self {
+            chars: Symbol::ALL.map(|symbol| Some(symbol.to_char())),
+        }
+    }
+}
+
+impl Charset {
+    /// A charset without any characters.
+    pub fn empty() -> Self {
+        Self {
+            chars: [None; Symbol::ALL.len()],
+        }
+    }
+
+    /// Maps `symbol` to `c`, taking `c` away from any other symbol.
+    ///
+    /// Panics if `c` is whitespace, which text grids use as separators.
+    pub fn with(mut self, symbol: Symbol, c: char) -> Self {
+        if c.is_whitespace() {
+            panic!("whitespace can't stand for {:?}", symbol);
+        }
+
+        for other in self.chars.iter_mut().filter(|other| **other == Some(c)) {
+            *other = None;
+        }
+
+        self.chars[symbol.palette_index() as usize] = Some(c);
+        self
+    }
+
+    /// The character written for `symbol`.
+    pub fn char(&self, symbol: Symbol) -> Option<char> {
+        self.chars[symbol.palette_index() as usize]
+    }
+
+    /// The symbol `c` stands for.
+    pub fn symbol(&self, c: char) -> Option<Symbol> {
+        let index = self.chars.iter().position(|other| *other == Some(c))?;
+        Symbol::from_palette_index(index as u8)
+    }
+}
+
+/// Why a charset description couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseCharsetError {
+    /// An entry wasn't a symbol character, `=`, and one more character.
+    InvalidEntry(String),
+    UnknownSymbol(char),
+}
+
+impl Display for ParseCharsetError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ParseCharsetError::*;
+        match self {
+            InvalidEntry(entry) => {
+                write!(
+                    fmt,
+                    "invalid charset entry '{}', expected SYMBOL=CHAR",
+                    entry
+                )
+            }
+            UnknownSymbol(c) => write!(fmt, "unrecognized symbol '{}'", c),
+        }
+    }
+}
+
+impl std::error::Error for ParseCharsetError {}
+
+impl FromStr for Charset {
+    type Err = ParseCharsetError;
+
+    /// Parses whitespace- or comma-separated `SYMBOL=CHAR` entries, such as
+    /// `"B=. W=# R=+"`, on top of the [default](Charset::default) letters.
+    fn from_str(string: &str) -> Result<Self, ParseCharsetError> {
+        let mut charset = Self::default();
+        let entries = string
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty());
+
+        for entry in entries {
+            let invalid = || ParseCharsetError::InvalidEntry(entry.to_string());
+            let mut chars = entry.chars();
+            let (Some(symbol), Some('='), Some(c), None) =
+                (chars.next(), chars.next(), chars.next(), chars.next())
+            else {
+                return Err(invalid());
+            };
+
+            let symbol =
+                Symbol::try_from_char(symbol).ok_or(ParseCharsetError::UnknownSymbol(symbol))?;
+            charset = charset.with(symbol, c);
+        }
+
+        Ok(charset)
+    }
+}
+
+impl Grid {
+    /// Writes the grid like its [Display] implementation, but with the
+    /// characters of `charset`. Returns `None` if the grid holds a symbol
+    /// without a character.
+    pub fn to_text(&self, charset: &Charset) -> Option<String> {
+        let mut string = String::new();
+        for (idx, row) in self.grid.chunks(self.width.max(1)).enumerate() {
+            if idx > 0 && idx % self.height == 0 {
+                string.push('\n');
+            }
+
+            for symbol in row.iter() {
+                string.push(charset.char(*symbol)?);
+            }
+
+            string.push('\n');
+        }
+
+        Some(string)
+    }
+
+    /// Parses text written by [Grid::to_text] with the same charset, as
+    /// [Grid::from_display_str] does with each symbol's own letter.
+    pub fn from_text(string: &str, charset: &Charset) -> Result<Self, ParseGridError> {
+        Self::parse_text(string, |c| charset.symbol(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_chars() {
+        let charset: Charset = "B=. W=# R=+, U=≈".parse().unwrap();
+        assert_eq!(charset.char(Symbol::White), Some('#'));
+        assert_eq!(charset.symbol('≈'), Some(Symbol::Blue));
+        assert_eq!(charset.symbol('B'), None);
+        assert_eq!(charset.char(Symbol::Green), Some('G'));
+
+        let grid = Grid::from_display_str("WWW\nBRU\nWWW").unwrap();
+        let text = grid.to_text(&charset).unwrap();
+        assert_eq!(text, "###\n.+≈\n###\n");
+        assert_eq!(Grid::from_text(&text, &charset), Ok(grid.clone()));
+        assert_eq!(grid.to_text(&Charset::default()), Some(grid.to_string()));
+
+        let walls = Charset::empty().with(Symbol::White, '#');
+        assert_eq!(grid.to_text(&walls), None);
+        assert_eq!(
+            Grid::from_text("#.", &walls),
+            Err(ParseGridError::UnknownSymbol('.'))
+        );
+
+        assert_eq!(
+            "W#".parse::<Charset>(),
+            Err(ParseCharsetError::InvalidEntry("W#".to_string()))
+        );
+        assert_eq!(
+            "Q=#".parse::<Charset>(),
+            Err(ParseCharsetError::UnknownSymbol('Q'))
+        );
+    }
+}
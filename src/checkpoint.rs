@@ -0,0 +1,358 @@
+//! Saving and resuming runs.
+//!
+//! A [Checkpoint] holds everything a [Runner](crate::runner::Runner) needs to carry on exactly
+//! where it left off: the grid, the node states' progress counters, the
+//! goals and field potentials their rule nodes have cached, and the RNG.
+//! Node trees aren't saved, so a checkpoint must be restored into a runner
+//! for the same model, which is checked against the
+//! [model hash](crate::report::model_hash) it was saved with.
+//!
+//! Checkpoints of runners whose RNG implements [RngState] can also be
+//! written to bytes, starting with the magic bytes `CMC` and a format
+//! version byte.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::*;
+use encoding::DecodeError;
+
+const MAGIC: &[u8; 3] = b"CMC";
+const VERSION: u8 = 2;
+
+/// An RNG whose state can be saved to and loaded from bytes.
+pub trait RngState: Sized {
+    fn save_state(&self) -> Vec<u8>;
+
+    /// Returns `None` if `bytes` is not a state saved by this RNG type.
+    fn load_state(bytes: &[u8]) -> Option<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckpointError {
+    /// The checkpoint was saved from a different model.
+    ModelMismatch,
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    BadRngState,
+    Grid(DecodeError),
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use CheckpointError::*;
+        match self {
+            ModelMismatch => write!(fmt, "checkpoint was saved from a different model"),
+            BadMagic => write!(fmt, "not a checkpoint"),
+            UnsupportedVersion(version) => {
+                write!(fmt, "unsupported checkpoint format version {}", version)
+            }
+            UnexpectedEnd => write!(fmt, "checkpoint is truncated"),
+            BadRngState => write!(fmt, "checkpoint has an invalid RNG state"),
+            Grid(err) => write!(fmt, "checkpoint has an invalid grid: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// A snapshot of a run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint<R> {
+    pub grid: Grid,
+
+    /// The [hash](crate::report::model_hash) of the model the run was for.
+    pub model_hash: u64,
+
+    /// The node states' progress counters, as saved by
+    /// [AnyState::save_counters](crate::node::AnyState::save_counters).
+    pub counters: Vec<usize>,
+
+    /// The node states' cached goals and field potentials, as saved by
+    /// [AnyState::save_caches](crate::node::AnyState::save_caches).
+    pub caches: Vec<usize>,
+
+    /// The number of successful steps taken.
+    pub steps: usize,
+
+    /// The seed the run's RNG was created from, if it was seeded.
+    pub seed: Option<u64>,
+
+    pub rng: R,
+}
+
+impl<R: RngState> Checkpoint<R> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+
+        let mut push = |word: u64| bytes.extend(word.to_le_bytes());
+        push(self.model_hash);
+        push(self.steps as u64);
+        push(self.seed.is_some() as u64);
+        push(self.seed.unwrap_or(0));
+        push(self.counters.len() as u64);
+        self.counters
+            .iter()
+            .for_each(|counter| push(*counter as u64));
+        push(self.caches.len() as u64);
+        self.caches.iter().for_each(|word| push(*word as u64));
+
+        let rng = self.rng.save_state();
+        bytes.extend((rng.len() as u64).to_le_bytes());
+        bytes.extend(rng);
+        bytes.extend(self.grid.to_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CheckpointError> {
+        let Some(bytes) = bytes.strip_prefix(MAGIC) else {
+            return Err(CheckpointError::BadMagic);
+        };
+
+        let mut reader = Reader(bytes);
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+
+        let model_hash = reader.word()?;
+        let steps = reader.word()? as usize;
+        let seeded = reader.word()? != 0;
+        let seed = reader.word()?;
+        let counters = (0..reader.word()?)
+            .map(|_| reader.word().map(|counter| counter as usize))
+            .collect::<Result<_, _>>()?;
+        let caches = (0..reader.word()?)
+            .map(|_| reader.word().map(|word| word as usize))
+            .collect::<Result<_, _>>()?;
+
+        let rng_len = reader.word()? as usize;
+        let rng = R::load_state(reader.take(rng_len)?).ok_or(CheckpointError::BadRngState)?;
+        let grid = Grid::from_bytes(reader.0).map_err(CheckpointError::Grid)?;
+
+        Ok(Self {
+            grid,
+            model_hash,
+            counters,
+            caches,
+            steps,
+            seed: seeded.then_some(seed),
+            rng,
+        })
+    }
+}
+
+/// Appends a grid's size and cells to `words`, writing each cell with
+/// `word`, for saving the grids that node states cache.
+pub(crate) fn save_grid<T>(
+    grid: &GenericGrid<T>,
+    words: &mut Vec<usize>,
+    word: impl Fn(&T) -> usize,
+) {
+    words.extend([grid.width, grid.height, grid.layers, grid.periodic as usize]);
+    words.extend(grid.grid.iter().map(word));
+}
+
+/// Reads a grid written by [save_grid], reading each cell with `cell`.
+pub(crate) fn load_grid<T>(
+    words: &mut impl Iterator<Item = usize>,
+    cell: impl Fn(usize) -> Option<T>,
+) -> Option<GenericGrid<T>> {
+    let (width, height, layers) = (words.next()?, words.next()?, words.next()?);
+    let periodic = words.next()? != 0;
+    let cells = width.checked_mul(height)?.checked_mul(layers)?;
+    let grid = (0..cells)
+        .map(|_| words.next().and_then(&cell))
+        .collect::<Option<_>>()?;
+
+    Some(GenericGrid {
+        grid,
+        width,
+        height,
+        layers,
+        periodic,
+    })
+}
+
+/// Writes a potential as a word, with zero for `None`.
+pub(crate) fn potential_word(potential: &Option<u32>) -> usize {
+    potential.map_or(0, |potential| potential as usize + 1)
+}
+
+/// Reads a potential written by [potential_word].
+pub(crate) fn word_potential(word: usize) -> Option<Option<u32>> {
+    match word {
+        0 => Some(None),
+        word => u32::try_from(word - 1).ok().map(Some),
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CheckpointError> {
+        if self.0.len() < len {
+            return Err(CheckpointError::UnexpectedEnd);
+        }
+
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn word(&mut self) -> Result<u64, CheckpointError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use node::{AnyNode, OneNode};
+    use runner::Runner;
+    use sampler::AnySampler;
+
+    /// A tiny xorshift generator, for testing RNG persistence.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct XorShift(u64);
+
+    impl rand::RngCore for XorShift {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let bytes = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl RngState for XorShift {
+        fn save_state(&self) -> Vec<u8> {
+            self.0.to_le_bytes().to_vec()
+        }
+
+        fn load_state(bytes: &[u8]) -> Option<Self> {
+            Some(Self(u64::from_le_bytes(bytes.try_into().ok()?)))
+        }
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let model = AnyNode::One(OneNode {
+            name: None,
+            rules: Rule::from_strings("WB", "WW").make_rotations().into(),
+            steps: Some(40),
+            sampler: AnySampler::FarthestFromLast(None),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let mut grid = Grid::new(16, 16);
+        grid[Point { x: 8, y: 8 }] = Symbol::White;
+
+        let mut runner = Runner::new(&model, grid.clone(), XorShift(7));
+        (0..15).for_each(|_| assert!(runner.step()));
+        let bytes = runner.checkpoint().to_bytes();
+        runner.run();
+
+        let checkpoint = Checkpoint::from_bytes(&bytes).unwrap();
+        assert_eq!(checkpoint.steps, 15);
+
+        let mut resumed = Runner::new(&model, grid, XorShift(0));
+        resumed.restore(&checkpoint).unwrap();
+        assert_eq!(resumed.progress().steps, 15);
+        resumed.run();
+        assert_eq!(resumed.grid, runner.grid);
+        assert_eq!(resumed.progress(), runner.progress());
+
+        let other = AnyNode::One(OneNode {
+            name: None,
+            rules: vec![Rule::from_strings("WB", "WW")].into(),
+            steps: None,
+            sampler: AnySampler::Uniform,
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let mut mismatched = Runner::new(&other, Grid::new(16, 16), XorShift(0));
+        assert_eq!(
+            mismatched.restore(&checkpoint),
+            Err(CheckpointError::ModelMismatch)
+        );
+
+        // Same node states as the model, but with different rules.
+        let rewritten = AnyNode::One(OneNode {
+            name: None,
+            rules: Rule::from_strings("WB", "WR").make_rotations().into(),
+            steps: Some(40),
+            sampler: AnySampler::FarthestFromLast(None),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let mut mismatched = Runner::new(&rewritten, Grid::new(16, 16), XorShift(0));
+        assert_eq!(
+            mismatched.restore(&checkpoint),
+            Err(CheckpointError::ModelMismatch)
+        );
+        assert_eq!(mismatched.progress().steps, 0);
+        assert_eq!(
+            Checkpoint::<XorShift>::from_bytes(&bytes[..20]),
+            Err(CheckpointError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn resume_goals_and_fields() {
+        let model = Model::from_xml(
+            r#"<sequence values="BRWG">
+                <one in="RB" out="WR" steps="8">
+                    <field for="R" to="G" on="B"/>
+                </one>
+                <one in="RB" out="RR">
+                    <observe value="W" from="B" to="R"/>
+                </one>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        let mut grid = model.make_grid(12, 12);
+        grid[Point { x: 1, y: 1 }] = Symbol::Red;
+        grid[Point { x: 10, y: 10 }] = Symbol::Green;
+
+        let mut uninterrupted = Runner::new(&model.root, grid.clone(), XorShift(11));
+        uninterrupted.run();
+
+        // Checkpoint partway through each node's run.
+        for steps in [4, 12] {
+            let mut runner = Runner::new(&model.root, grid.clone(), XorShift(11));
+            (0..steps).for_each(|_| assert!(runner.step()));
+            let bytes = runner.checkpoint().to_bytes();
+            let checkpoint = Checkpoint::from_bytes(&bytes).unwrap();
+            assert_eq!(checkpoint, runner.checkpoint());
+
+            let mut resumed = Runner::new(&model.root, grid.clone(), XorShift(0));
+            resumed.restore(&checkpoint).unwrap();
+            resumed.run();
+            assert_eq!(resumed.grid, uninterrupted.grid);
+            assert_eq!(resumed.progress(), uninterrupted.progress());
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Extra requirements a rule checks where its pattern matches.
+//!
+//! Pattern equality can't express "at least three of these eight cells are
+//! walls" without a rule for every arrangement. A [NeighborCount] checks it
+//! directly, counting the neighbors of one cell of the match on the bottom
+//! layer.
+
+use super::*;
+
+/// Requires one cell of a match to have between `min` and `max` neighbors
+/// holding `symbol`, inclusive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NeighborCount {
+    /// The cell whose neighbors are counted, relative to the top-left corner
+    /// of the find pattern.
+    pub at: Point,
+
+    pub symbol: Symbol,
+    pub min: usize,
+    pub max: usize,
+
+    /// Also count the four diagonal neighbors, for eight in total.
+    pub diagonals: bool,
+}
+
+impl NeighborCount {
+    /// Whether the condition holds for a match placed at `at`.
+    pub fn test(&self, grid: &Grid, at: Point) -> bool {
+        let center = grid.wrap(at + self.at);
+        let count = if self.diagonals {
+            (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| Offset { dx, dy }))
+                .filter(|offset| *offset != Offset::ZERO)
+                .filter_map(|offset| grid.offset(center, offset))
+                .filter(|neighbor| grid[*neighbor] == self.symbol)
+                .count()
+        } else {
+            grid.neighbors(center)
+                .filter(|neighbor| grid[*neighbor] == self.symbol)
+                .count()
+        };
+
+        (self.min..=self.max).contains(&count)
+    }
+
+    /// Moves the counted cell to where `map` takes it, for transformed rules.
+    pub(crate) fn map(&self, map: impl Fn(Point) -> Point) -> Self {
+        Self {
+            at: map(self.at),
+            ..self.clone()
+        }
+    }
+}
+
+impl Rule {
+    /// Whether every one of the rule's conditions holds for a match at `at`.
+    pub fn test_conditions(&self, grid: &Grid, at: Point) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.test(grid, at))
+    }
+
+    /// Whether the rule matches at `at`, pattern and conditions both.
+    pub fn test_match(&self, grid: &Grid, at: Point) -> bool {
+        grid.test_match(&self.find, at) && self.test_conditions(grid, at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_neighbors() {
+        let grid = Grid::from_display_str(
+            "
+            WWB
+            WBB
+            BBW
+            WWW
+            ",
+        )
+        .unwrap();
+
+        let walls = |min, max, diagonals| NeighborCount {
+            at: Point::ZERO,
+            symbol: Symbol::White,
+            min,
+            max,
+            diagonals,
+        };
+
+        let center = Point { x: 1, y: 1 };
+        assert!(walls(2, 2, false).test(&grid, center));
+        assert!(!walls(3, 4, false).test(&grid, center));
+        assert!(walls(4, 8, true).test(&grid, center));
+        assert!(walls(0, 1, false).test(&grid, Point { x: 2, y: 2 }));
+
+        let rule = Rule {
+            conditions: vec![walls(4, 8, true)],
+            ..Rule::from_strings("BB", "RR")
+        };
+
+        assert!(rule.test_match(&grid, Point { x: 1, y: 1 }));
+        assert!(!rule.test_match(&grid, Point { x: 0, y: 2 }));
+
+        let second = Rule {
+            conditions: vec![NeighborCount {
+                at: Point { x: 1, y: 0 },
+                ..walls(3, 8, true)
+            }],
+            ..rule
+        };
+
+        assert_eq!(second.rotate_cw().conditions[0].at, Point { x: 0, y: 1 });
+        assert_eq!(second.mirror_x().conditions[0].at, Point::ZERO);
+        assert_eq!(second.transpose().conditions[0].at, Point { x: 0, y: 1 });
+    }
+}
@@ -0,0 +1,326 @@
+//! Checking models for mistakes before running them.
+//!
+//! [AnyNode::validate] walks a node tree and reports every problem it finds
+//! as a [Diagnostic], rather than leaving them to surface as panics or
+//! silently dead rules partway through a run.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::*;
+use field::Field;
+use model::Model;
+use node::AnyNode;
+use observe::Observation;
+use rule_set::{Extent, RuleSet};
+
+/// A problem found in a model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The pre-order ID of the node the problem is in, as given to states by
+    /// [AnyNode::make_state].
+    pub node: usize,
+
+    /// The index of the offending rule within the node's rule set, if the
+    /// problem is with a single rule.
+    pub rule: Option<usize>,
+
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// A rule's find and replace patterns have different dimensions.
+    MismatchedRule { find: Extent, replace: Extent },
+
+    /// A pattern is larger than the grid, so it can never match.
+    PatternTooLarge { pattern: Extent, grid: Extent },
+
+    /// A rule node has no rules, so it never does anything.
+    NoRules,
+
+    /// A node is limited to zero steps, so it never does anything.
+    ZeroSteps,
+
+    /// A symbol that isn't in the model's alphabet.
+    UnknownSymbol(Symbol),
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "node {}", self.node)?;
+        if let Some(rule) = self.rule {
+            write!(fmt, ", rule {}", rule)?;
+        }
+
+        use DiagnosticKind::*;
+        match &self.kind {
+            MismatchedRule { find, replace } => write!(
+                fmt,
+                ": find pattern is {} but replace pattern is {}",
+                find, replace
+            ),
+            PatternTooLarge { pattern, grid } => {
+                write!(fmt, ": {} pattern can't fit on a {} grid", pattern, grid)
+            }
+            NoRules => write!(fmt, ": node has no rules"),
+            ZeroSteps => write!(fmt, ": node is limited to zero steps"),
+            UnknownSymbol(symbol) => write!(
+                fmt,
+                ": symbol '{}' is not in the alphabet",
+                symbol.to_char()
+            ),
+        }
+    }
+}
+
+impl AnyNode {
+    /// Checks this node tree against the grid size and alphabet it will be
+    /// run with, returning every problem found in pre-order. Symbols aren't
+    /// checked without an alphabet.
+    ///
+    /// User-defined nodes are not checked.
+    pub fn validate(&self, grid: Extent, alphabet: Option<&[Symbol]>) -> Vec<Diagnostic> {
+        let mut validator = Validator {
+            grid,
+            alphabet,
+            diagnostics: Vec::new(),
+        };
+
+        validator.node(self, &mut 0);
+        validator.diagnostics
+    }
+}
+
+impl Model {
+    /// Validates the model for a grid of the given size, using its declared
+    /// values, if any, as the alphabet.
+    pub fn validate(&self, width: usize, height: usize) -> Vec<Diagnostic> {
+        let grid = Extent {
+            width,
+            height,
+            layers: self.layers,
+        };
+
+        self.root.validate(grid, self.values.as_deref())
+    }
+}
+
+struct Validator<'a> {
+    grid: Extent,
+    alphabet: Option<&'a [Symbol]>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn node(&mut self, node: &AnyNode, next_id: &mut usize) {
+        let id = *next_id;
+        *next_id += 1;
+
+        use AnyNode::*;
+        match node {
+            One(node) => {
+                self.rules(id, &node.rules);
+                self.steps(id, node.steps);
+                self.fields(id, &node.fields);
+                self.observations(id, &node.observations);
+            }
+            All(node) => {
+                self.rules(id, &node.rules);
+                self.steps(id, node.steps);
+                self.fields(id, &node.fields);
+                self.observations(id, &node.observations);
+            }
+            Prl(node) => self.rules(id, &node.rules),
+            Path(node) => {
+                let on = node.on.iter().map(|(symbol, _)| symbol);
+                let symbols = node.from.iter().chain(&node.to).chain(on);
+                self.symbols(id, None, symbols.chain([&node.color]).copied());
+            }
+            Walk(node) => {
+                let symbols = node.from.iter().chain(&node.on).chain([&node.color]);
+                self.symbols(id, None, symbols.copied());
+                self.steps(id, node.steps);
+            }
+            Repeat(node) => {
+                if node.times == Some(0) {
+                    self.report(id, None, DiagnosticKind::ZeroSteps);
+                }
+            }
+            If(node) => {
+                self.pattern(id, None, &node.guard);
+                self.symbols(id, None, node.guard.grid.iter().flatten().copied());
+            }
+            Until(node) => self.symbols(id, None, [node.symbol].into_iter()),
+            Markov(_) | Sequence(_) | Dyn(_) => {}
+        }
+
+        for child in node.children() {
+            self.node(child, next_id);
+        }
+    }
+
+    fn rules(&mut self, id: usize, rules: &RuleSet) {
+        if rules.is_empty() {
+            self.report(id, None, DiagnosticKind::NoRules);
+        }
+
+        for (idx, rule) in rules.iter().enumerate() {
+            let (find, replace) = (extent(&rule.find), extent(&rule.replace));
+            if find != replace {
+                self.report(
+                    id,
+                    Some(idx),
+                    DiagnosticKind::MismatchedRule { find, replace },
+                );
+            }
+
+            self.pattern(id, Some(idx), &rule.find);
+            let cells = rule.find.grid.iter().chain(&rule.replace.grid);
+            let conditions = rule.conditions.iter().map(|condition| condition.symbol);
+            self.symbols(id, Some(idx), cells.flatten().copied().chain(conditions));
+        }
+    }
+
+    fn fields(&mut self, id: usize, fields: &[Field]) {
+        for field in fields {
+            let symbols = field.to.iter().chain(&field.on).chain([&field.symbol]);
+            self.symbols(id, None, symbols.copied());
+        }
+    }
+
+    fn observations(&mut self, id: usize, observations: &[Observation]) {
+        for observation in observations {
+            let symbols = observation.from.iter().chain(&observation.to);
+            self.symbols(id, None, symbols.chain([&observation.value]).copied());
+        }
+    }
+
+    fn pattern(&mut self, id: usize, rule: Option<usize>, pattern: &Pattern) {
+        let pattern = extent(pattern);
+        let grid = self.grid;
+        if pattern.width > grid.width
+            || pattern.height > grid.height
+            || pattern.layers > grid.layers
+        {
+            self.report(id, rule, DiagnosticKind::PatternTooLarge { pattern, grid });
+        }
+    }
+
+    fn steps(&mut self, id: usize, steps: Option<usize>) {
+        if steps == Some(0) {
+            self.report(id, None, DiagnosticKind::ZeroSteps);
+        }
+    }
+
+    /// Reports each distinct symbol outside the alphabet once.
+    fn symbols(&mut self, id: usize, rule: Option<usize>, symbols: impl Iterator<Item = Symbol>) {
+        let Some(alphabet) = self.alphabet else {
+            return;
+        };
+
+        let mut unknown = Vec::new();
+        for symbol in symbols {
+            if !alphabet.contains(&symbol) && !unknown.contains(&symbol) {
+                unknown.push(symbol);
+            }
+        }
+
+        for symbol in unknown {
+            self.report(id, rule, DiagnosticKind::UnknownSymbol(symbol));
+        }
+    }
+
+    fn report(&mut self, node: usize, rule: Option<usize>, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic { node, rule, kind });
+    }
+}
+
+fn extent(pattern: &Pattern) -> Extent {
+    Extent {
+        width: pattern.width,
+        height: pattern.height,
+        layers: pattern.layers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::{OneNode, SequenceNode};
+    use sampler::AnySampler;
+
+    #[test]
+    fn diagnostics() {
+        let mismatched = Rule {
+            find: Pattern::from_string("BW"),
+            replace: Pattern::from_string("W"),
+            conditions: Vec::new(),
+        };
+
+        let one = |rules: Vec<Rule>, steps| {
+            AnyNode::One(OneNode {
+                name: None,
+                rules: rules.into(),
+                steps,
+                sampler: AnySampler::Uniform,
+                fields: Vec::new(),
+                observations: Vec::new(),
+            })
+        };
+
+        let model = AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: vec![
+                one(vec![Rule::from_strings("BW", "WW")], None),
+                one(vec![mismatched, Rule::from_strings("BBBBB", "RRRRR")], None),
+                one(Vec::new(), Some(0)),
+            ],
+        });
+
+        let grid = Extent {
+            width: 4,
+            height: 4,
+            layers: 1,
+        };
+
+        use DiagnosticKind::*;
+        let kinds: Vec<_> = model
+            .validate(grid, Some(&[Symbol::Black, Symbol::White]))
+            .into_iter()
+            .map(|diagnostic| (diagnostic.node, diagnostic.rule, diagnostic.kind))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            [
+                (
+                    2,
+                    Some(0),
+                    MismatchedRule {
+                        find: extent(&Pattern::from_string("BW")),
+                        replace: extent(&Pattern::from_string("W")),
+                    }
+                ),
+                (
+                    2,
+                    Some(1),
+                    PatternTooLarge {
+                        pattern: extent(&Pattern::from_string("BBBBB")),
+                        grid,
+                    }
+                ),
+                (2, Some(1), UnknownSymbol(Symbol::Red)),
+                (3, None, NoRules),
+                (3, None, ZeroSteps),
+            ]
+        );
+
+        let valid = Model::from_xml(r#"<one values="BW" in="B" out="W"/>"#).unwrap();
+        assert_eq!(valid.validate(8, 8), []);
+
+        // Without declared values, any symbol may be used.
+        let undeclared = Model::from_xml(r#"<one in="B" out="W"/>"#).unwrap();
+        assert_eq!(undeclared.values, None);
+        assert_eq!(undeclared.validate(8, 8), []);
+    }
+}
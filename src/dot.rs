@@ -0,0 +1,178 @@
+//! Graphviz diagrams of node trees.
+//!
+//! [AnyNode::to_dot] writes a model's structure in the DOT language, with a
+//! box per node listing its ID, kind, name, and limits, for getting an
+//! overview of large models that is hard to see from their XML:
+//!
+//! ```ignore
+//! std::fs::write("model.dot", model.root.to_dot())?;
+//! // dot -Tsvg model.dot -o model.svg
+//! ```
+//!
+//! Node IDs are the pre-order IDs used by states, traces, and diagnostics.
+//! Edges out of `markov` and `sequence` nodes are labeled with the child's
+//! index, which is the order children are tried or run in.
+
+use std::fmt::Write;
+
+use super::*;
+use node::AnyNode;
+
+impl AnyNode {
+    /// Writes this node tree as a Graphviz `digraph`. See the
+    /// [module](crate::dot) docs.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph model {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        write_node(&mut dot, self, &mut 0);
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Writes `node` and the edges to its children, then its children, and
+/// returns the node's ID.
+fn write_node(dot: &mut String, node: &AnyNode, next_id: &mut usize) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    let mut lines = vec![format!("{}: {}", id, node.kind())];
+    if let Some(name) = node.name() {
+        lines.push(format!("\"{}\"", name));
+    }
+
+    lines.extend(details(node));
+    let label: Vec<_> = lines.iter().map(|line| escape(line)).collect();
+    writeln!(dot, "    n{} [label=\"{}\"];", id, label.join("\\n")).unwrap();
+
+    let ordered = matches!(node, AnyNode::Markov(_) | AnyNode::Sequence(_));
+    for (index, child) in node.children().iter().enumerate() {
+        let child_id = write_node(dot, child, next_id);
+        match ordered {
+            true => writeln!(dot, "    n{} -> n{} [label=\"{}\"];", id, child_id, index),
+            false => writeln!(dot, "    n{} -> n{};", id, child_id),
+        }
+        .unwrap();
+    }
+
+    id
+}
+
+/// The lines describing a node's rules and limits.
+pub(crate) fn details(node: &AnyNode) -> Vec<String> {
+    let rules = |count: usize| match count {
+        1 => "1 rule".to_string(),
+        count => format!("{} rules", count),
+    };
+
+    let steps = |steps: Option<usize>| steps.map(|steps| format!("steps: {}", steps));
+
+    use AnyNode::*;
+    match node {
+        One(node) => [Some(rules(node.rules.len())), steps(node.steps)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        All(node) => [Some(rules(node.rules.len())), steps(node.steps)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        Prl(node) => vec![rules(node.rules.len())],
+        Path(node) => vec![format!(
+            "{} to {}",
+            chars(node.from.iter()),
+            chars(node.to.iter())
+        )],
+        Walk(node) => [Some(format!("length: {}", node.length)), steps(node.steps)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        Repeat(node) => match node.times {
+            Some(times) => vec![format!("times: {}", times)],
+            None => vec!["until idle".to_string()],
+        },
+        If(node) => match node.present {
+            true => vec!["guard present".to_string()],
+            false => vec!["guard absent".to_string()],
+        },
+        Until(node) => vec![format!(
+            "{} reaches {}",
+            node.symbol.to_char(),
+            node.threshold
+        )],
+        Markov(_) | Sequence(_) | Dyn(_) => Vec::new(),
+    }
+}
+
+/// Writes symbols as their characters, e.g. `WR`.
+fn chars<'a>(symbols: impl Iterator<Item = &'a Symbol>) -> String {
+    symbols.map(Symbol::to_char).collect()
+}
+
+/// Escapes a line for use inside a quoted DOT string.
+fn escape(line: &str) -> String {
+    line.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::{AllNode, OneNode, RepeatNode, SequenceNode, Threshold, UntilNode};
+    use rule_set::RuleSet;
+
+    #[test]
+    fn dot_export() {
+        let one = AnyNode::One(OneNode {
+            name: None,
+            rules: RuleSet::with_symmetry([Rule::from_strings("WB", "WW")], Symmetry::Rotations),
+            steps: Some(10),
+            sampler: Default::default(),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let all = AnyNode::All(AllNode {
+            name: None,
+            rules: RuleSet::new([Rule::from_strings("B", "R")]),
+            steps: None,
+            conflict: Default::default(),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let model = AnyNode::Sequence(SequenceNode {
+            name: Some("say \"hi\"".to_string()),
+            children: vec![
+                AnyNode::Repeat(RepeatNode {
+                    name: None,
+                    child: Box::new(one),
+                    times: Some(3),
+                }),
+                AnyNode::Until(UntilNode {
+                    name: None,
+                    symbol: Symbol::Red,
+                    threshold: Threshold::Percent(30),
+                    child: Box::new(all),
+                }),
+            ],
+        });
+
+        assert_eq!(
+            model.to_dot(),
+            r#"digraph model {
+    node [shape=box, fontname="monospace"];
+    n0 [label="0: sequence\n\"say \"hi\"\""];
+    n1 [label="1: repeat\ntimes: 3"];
+    n2 [label="2: one\n4 rules\nsteps: 10"];
+    n1 -> n2;
+    n0 -> n1 [label="0"];
+    n3 [label="3: until\nR reaches 30%"];
+    n4 [label="4: all\n1 rule"];
+    n3 -> n4;
+    n0 -> n3 [label="1"];
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! A compact binary format for grids.
+//!
+//! An encoded grid starts with the magic bytes `CMG`, a format version byte,
+//! and the width, height, and layer count as little-endian `u32`s. The cells
+//! follow in storage order as runs, each a LEB128 run length followed by
+//! the symbol's palette index.
+//!
+//! Whether a grid is [periodic](Grid::periodic) belongs to the run rather
+//! than the cells, so it isn't encoded and decoded grids never wrap.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::*;
+
+const MAGIC: &[u8; 3] = b"CMG";
+const VERSION: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    UnknownSymbol(u8),
+    RunTooLong,
+    WrongLength { expected: usize, found: usize },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use DecodeError::*;
+        match self {
+            BadMagic => write!(fmt, "not an encoded grid"),
+            UnsupportedVersion(version) => {
+                write!(fmt, "unsupported grid format version {}", version)
+            }
+            UnexpectedEnd => write!(fmt, "encoded grid is truncated"),
+            UnknownSymbol(index) => write!(fmt, "unknown symbol index {}", index),
+            RunTooLong => write!(fmt, "encoded grid has an oversized run"),
+            WrongLength { expected, found } => write!(
+                fmt,
+                "encoded grid has {} cells but its header says {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Grid {
+    /// Encodes this grid in the binary format described in the
+    /// [module docs](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        for size in [self.width, self.height, self.layers] {
+            bytes.extend((size as u32).to_le_bytes());
+        }
+
+        for run in self.grid.chunk_by(|a, b| a == b) {
+            let mut len = run.len();
+            while len >= 0x80 {
+                bytes.push((len & 0x7f) as u8 | 0x80);
+                len >>= 7;
+            }
+
+            bytes.push(len as u8);
+            bytes.push(run[0].palette_index());
+        }
+
+        bytes
+    }
+
+    /// Decodes a grid encoded by [Grid::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut bytes = bytes.iter().copied();
+        let mut next = || bytes.next().ok_or(DecodeError::UnexpectedEnd);
+
+        let mut magic = [0; 3];
+        for byte in magic.iter_mut() {
+            *byte = next().map_err(|_| DecodeError::BadMagic)?;
+        }
+
+        if magic != *MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+
+        let version = next()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let mut size = || -> Result<usize, DecodeError> {
+            let mut word = [0; 4];
+            for byte in word.iter_mut() {
+                *byte = next()?;
+            }
+
+            Ok(u32::from_le_bytes(word) as usize)
+        };
+
+        let (width, height, layers) = (size()?, size()?, size()?);
+        let expected = width * height * layers;
+        let mut grid = Vec::with_capacity(expected);
+
+        while let Ok(mut byte) = next() {
+            let mut len = 0;
+            let mut shift = 0;
+            loop {
+                len |= ((byte & 0x7f) as usize) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+
+                shift += 7;
+                if shift >= usize::BITS {
+                    return Err(DecodeError::RunTooLong);
+                }
+
+                byte = next()?;
+            }
+
+            let index = next()?;
+            let symbol =
+                Symbol::from_palette_index(index).ok_or(DecodeError::UnknownSymbol(index))?;
+            let found = grid.len().saturating_add(len);
+            if found > expected {
+                return Err(DecodeError::WrongLength { expected, found });
+            }
+
+            grid.resize(found, symbol);
+        }
+
+        if grid.len() != expected {
+            return Err(DecodeError::WrongLength {
+                expected,
+                found: grid.len(),
+            });
+        }
+
+        Ok(Self {
+            width,
+            height,
+            layers,
+            grid,
+            periodic: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut grid = Grid::with_layers(300, 200, 2);
+        grid[Point { x: 5, y: 7 }] = Symbol::Red;
+        grid[(Point { x: 299, y: 199 }, 1)] = Symbol::Purple;
+
+        let bytes = grid.to_bytes();
+        assert!(bytes.len() < 32);
+        assert_eq!(Grid::from_bytes(&bytes), Ok(grid));
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let bytes = Grid::new(4, 4).to_bytes();
+        assert_eq!(Grid::from_bytes(b"GIF89a"), Err(DecodeError::BadMagic));
+        assert_eq!(
+            Grid::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+
+        let mut short = bytes.clone();
+        short[bytes.len() - 2] = 15;
+        assert_eq!(
+            Grid::from_bytes(&short),
+            Err(DecodeError::WrongLength {
+                expected: 16,
+                found: 15
+            })
+        );
+
+        let mut unknown = bytes;
+        *unknown.last_mut().unwrap() = 200;
+        assert_eq!(
+            Grid::from_bytes(&unknown),
+            Err(DecodeError::UnknownSymbol(200))
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Integer arithmetic in model attributes.
+//!
+//! Numeric attributes can give an expression such as `width * height / 40`
+//! instead of a constant, so that step counts and thresholds scale with the
+//! grid. Expressions combine integers and named variables with `+`, `-`,
+//! `*`, `/`, and `%`, unary minus, and parentheses, with the usual
+//! precedence. Division rounds toward zero.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpressionError {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    UnknownVariable(String),
+    DivisionByZero,
+    Overflow,
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ExpressionError::*;
+        match self {
+            UnexpectedChar(c) => write!(fmt, "unexpected '{}'", c),
+            UnexpectedEnd => write!(fmt, "unexpected end of expression"),
+            UnknownVariable(name) => write!(fmt, "unknown variable '{}'", name),
+            DivisionByZero => write!(fmt, "division by zero"),
+            Overflow => write!(fmt, "arithmetic overflow"),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+pub type ExpressionResult<T> = Result<T, ExpressionError>;
+
+/// Evaluates `text`, looking up each variable it names with `variable`.
+pub fn evaluate(text: &str, variable: impl Fn(&str) -> Option<i64>) -> ExpressionResult<i64> {
+    let mut parser = Parser {
+        text,
+        at: 0,
+        variable: &variable,
+    };
+
+    let value = parser.sum()?;
+    match parser.peek() {
+        Some(c) => Err(ExpressionError::UnexpectedChar(c)),
+        None => Ok(value),
+    }
+}
+
+/// A recursive descent parser that evaluates as it goes.
+struct Parser<'a> {
+    text: &'a str,
+    at: usize,
+    variable: &'a dyn Fn(&str) -> Option<i64>,
+}
+
+impl Parser<'_> {
+    /// The next character that isn't whitespace, without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.text[self.at..];
+        self.at += rest.len() - rest.trim_start().len();
+        self.text[self.at..].chars().next()
+    }
+
+    /// Consumes the next character if it is one of `chars`.
+    fn eat(&mut self, chars: &str) -> Option<char> {
+        let c = self.peek().filter(|c| chars.contains(*c))?;
+        self.at += c.len_utf8();
+        Some(c)
+    }
+
+    /// Consumes characters while `f` holds for them, returning them.
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &str {
+        let start = self.at;
+        let rest = &self.text[start..];
+        self.at += rest.find(|c| !f(c)).unwrap_or(rest.len());
+        &self.text[start..self.at]
+    }
+
+    fn sum(&mut self) -> ExpressionResult<i64> {
+        let mut value = self.product()?;
+        while let Some(op) = self.eat("+-") {
+            let rhs = self.product()?;
+            value = match op {
+                '+' => value.checked_add(rhs),
+                _ => value.checked_sub(rhs),
+            }
+            .ok_or(ExpressionError::Overflow)?;
+        }
+
+        Ok(value)
+    }
+
+    fn product(&mut self) -> ExpressionResult<i64> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.eat("*/%") {
+            let rhs = self.unary()?;
+            if op != '*' && rhs == 0 {
+                return Err(ExpressionError::DivisionByZero);
+            }
+
+            value = match op {
+                '*' => value.checked_mul(rhs),
+                '/' => value.checked_div(rhs),
+                _ => value.checked_rem(rhs),
+            }
+            .ok_or(ExpressionError::Overflow)?;
+        }
+
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> ExpressionResult<i64> {
+        if self.eat("-").is_some() {
+            return self.unary()?.checked_neg().ok_or(ExpressionError::Overflow);
+        }
+
+        self.atom()
+    }
+
+    fn atom(&mut self) -> ExpressionResult<i64> {
+        match self.peek() {
+            Some('(') => {
+                self.at += 1;
+                let value = self.sum()?;
+                match self.eat(")") {
+                    Some(_) => Ok(value),
+                    None => Err(self.unexpected()),
+                }
+            }
+            Some(c) if c.is_ascii_digit() => self
+                .take_while(|c| c.is_ascii_digit())
+                .parse()
+                .map_err(|_| ExpressionError::Overflow),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+                let name = name.to_string();
+                (self.variable)(&name).ok_or(ExpressionError::UnknownVariable(name))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn unexpected(&mut self) -> ExpressionError {
+        match self.peek() {
+            Some(c) => ExpressionError::UnexpectedChar(c),
+            None => ExpressionError::UnexpectedEnd,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_expressions() {
+        let variable = |name: &str| match name {
+            "width" => Some(64),
+            "height" => Some(48),
+            _ => None,
+        };
+
+        let eval = |text| evaluate(text, variable);
+        assert_eq!(eval("12"), Ok(12));
+        assert_eq!(eval(" width * height / 40 "), Ok(76));
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("-width % 10"), Ok(-4));
+        assert_eq!(eval("10 - 4 - 3"), Ok(3));
+
+        assert_eq!(
+            eval("depth"),
+            Err(ExpressionError::UnknownVariable("depth".to_string()))
+        );
+        assert_eq!(
+            eval("width / (height - 48)"),
+            Err(ExpressionError::DivisionByZero)
+        );
+        assert_eq!(eval("(1 + 2"), Err(ExpressionError::UnexpectedEnd));
+        assert_eq!(eval("2 $ 3"), Err(ExpressionError::UnexpectedChar('$')));
+        assert_eq!(eval(""), Err(ExpressionError::UnexpectedEnd));
+    }
+}
@@ -0,0 +1,282 @@
+//! Potential fields that steer rule nodes toward or away from targets, as in
+//! MarkovJunior's `<field>` elements.
+//!
+//! A [Field] gives every cell a potential for one symbol: its distance to the
+//! nearest target cell, moving only through substrate cells. Matches are
+//! scored by how the potentials of the cells they write change, and only the
+//! best-scoring matches are offered to a [OneNode](crate::node::OneNode)'s
+//! sampler. [AllNode](crate::node::AllNode)s apply their matches best first.
+//!
+//! Potentials are computed from the bottom layer, and only the bottom layer
+//! of each rule's replacement is scored.
+
+use super::*;
+use rule_set::RuleSet;
+
+/// A potential field for cells that hold or are rewritten to `symbol`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub symbol: Symbol,
+
+    /// The symbols the field's potential is zero on.
+    pub to: Vec<Symbol>,
+
+    /// The symbols the field's potential spreads through.
+    pub on: Vec<Symbol>,
+
+    /// Prefer moving `symbol` away from the targets instead of toward them.
+    pub repel: bool,
+
+    /// Recompute the potential before every step, rather than only once the
+    /// first time the node steps.
+    pub recompute: bool,
+
+    /// Fail the node's step if no target is on the grid. Otherwise, a field
+    /// without targets is ignored.
+    pub essential: bool,
+}
+
+impl Field {
+    /// Creates a field attracting `symbol` toward `to` through `on`,
+    /// computed once.
+    pub fn new(symbol: Symbol, to: Vec<Symbol>, on: Vec<Symbol>) -> Self {
+        Self {
+            symbol,
+            to,
+            on,
+            repel: false,
+            recompute: false,
+            essential: false,
+        }
+    }
+}
+
+/// The potentials a node's fields have computed, kept between steps for
+/// fields that aren't recomputed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Potentials {
+    /// Each field's potential, or `None` if it hasn't been computed or had
+    /// no targets when it was last computed.
+    cached: Vec<Option<GenericGrid<Option<u32>>>>,
+}
+
+impl Potentials {
+    /// Appends the cached potentials to `words`, for
+    /// [checkpoints](crate::checkpoint).
+    pub fn save(&self, words: &mut Vec<usize>) {
+        words.push(self.cached.len());
+        for cached in &self.cached {
+            match cached {
+                Some(potential) => {
+                    words.push(1);
+                    checkpoint::save_grid(potential, words, checkpoint::potential_word);
+                }
+                None => words.push(0),
+            }
+        }
+    }
+
+    /// Reads potentials written by [Potentials::save].
+    pub fn load(words: &mut impl Iterator<Item = usize>) -> Option<Self> {
+        let cached = (0..words.next()?)
+            .map(|_| match words.next()? {
+                0 => Some(None),
+                _ => checkpoint::load_grid(words, checkpoint::word_potential).map(Some),
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self { cached })
+    }
+
+    /// Narrows `matches` to the ones that score best under `fields`, which
+    /// are then offered to a sampler.
+    ///
+    /// Returns no matches if an essential field has no targets.
+    pub fn best(
+        &mut self,
+        fields: &[Field],
+        grid: &Grid,
+        rules: &RuleSet,
+        matches: Vec<Match>,
+    ) -> Vec<Match> {
+        let Some(scores) = self.scores(fields, grid, rules, &matches) else {
+            return Vec::new();
+        };
+
+        let Some(best) = scores.iter().flatten().min().copied() else {
+            return Vec::new();
+        };
+
+        matches
+            .into_iter()
+            .zip(scores)
+            .filter(|(_, score)| *score == Some(best))
+            .map(|(matched, _)| matched)
+            .collect()
+    }
+
+    /// Sorts `matches` best first under `fields`, keeping the existing order
+    /// between equal scores, and drops those that can never score.
+    ///
+    /// Drops every match if an essential field has no targets.
+    pub fn sort(
+        &mut self,
+        fields: &[Field],
+        grid: &Grid,
+        rules: &RuleSet,
+        matches: &mut Vec<Match>,
+    ) {
+        let Some(scores) = self.scores(fields, grid, rules, matches) else {
+            matches.clear();
+            return;
+        };
+
+        let mut scored: Vec<_> = matches
+            .drain(..)
+            .zip(scores)
+            .filter_map(|(matched, score)| Some((score?, matched)))
+            .collect();
+
+        scored.sort_by_key(|(score, _)| *score);
+        matches.extend(scored.into_iter().map(|(_, matched)| matched));
+    }
+
+    /// Scores each match by the change in potential of the cells it writes,
+    /// lower being better. Matches that write a cell the target can't be
+    /// reached from are `None`.
+    fn scores(
+        &mut self,
+        fields: &[Field],
+        grid: &Grid,
+        rules: &RuleSet,
+        matches: &[Match],
+    ) -> Option<Vec<Option<i64>>> {
+        self.cached.resize(fields.len(), None);
+        for (field, cached) in fields.iter().zip(self.cached.iter_mut()) {
+            if field.recompute || cached.is_none() {
+                let potential = grid.distance_field(&field.to, &field.on);
+                let has_target = potential.grid.contains(&Some(0));
+                *cached = has_target.then_some(potential);
+            }
+
+            if field.essential && cached.is_none() {
+                return None;
+            }
+        }
+
+        let potential = |symbol: Symbol, at: Point| -> Option<Option<i64>> {
+            let (field, potential) = fields
+                .iter()
+                .zip(self.cached.iter())
+                .find(|(field, _)| field.symbol == symbol)?;
+
+            let potential = potential.as_ref()?;
+            let sign = if field.repel { -1 } else { 1 };
+            Some(potential[at].map(|distance| sign * distance as i64))
+        };
+
+        let score = |matched: &Match| -> Option<i64> {
+            let (rule, at) = matched.unpack(grid.width);
+            let replace = &rules[rule].replace;
+            let mut score = 0;
+            for (idx, cell) in replace.grid[..replace.width * replace.height]
+                .iter()
+                .enumerate()
+            {
+                let Some(new) = *cell else {
+                    continue;
+                };
+
+                let at = Point {
+                    x: at.x + idx % replace.width,
+                    y: at.y + idx / replace.width,
+                };
+
+                let old = grid[at];
+                if new == old {
+                    continue;
+                }
+
+                if let Some(potential) = potential(new, at) {
+                    score += potential?;
+                }
+
+                if let Some(Some(potential)) = potential(old, at) {
+                    score -= potential;
+                }
+            }
+
+            Some(score)
+        };
+
+        Some(matches.iter().map(score).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steer_toward_target() {
+        let grid = Grid::from_display_str(
+            "
+            RBBBBBBW
+            BBBBBBBB
+            BBBBBBBB
+            BBBBBBBB
+            ",
+        )
+        .unwrap();
+
+        let rules: RuleSet = Rule::with_symmetry("RB", "BR", Symmetry::Rotations).into();
+        let matches = |grid: &Grid| -> Vec<Match> {
+            (0..rules.len())
+                .flat_map(|rule| {
+                    let matches = grid.find_matches(&rules[rule].find);
+                    let width = grid.width;
+                    matches
+                        .into_iter()
+                        .map(move |at| Match::new(rule, at, width))
+                })
+                .collect()
+        };
+
+        let attract = [Field::new(
+            Symbol::Red,
+            vec![Symbol::White],
+            vec![Symbol::Black],
+        )];
+        let mut potentials = Potentials::default();
+        let best = potentials.best(&attract, &grid, &rules, matches(&grid));
+        assert_eq!(best.len(), 1);
+        assert_eq!(rules[best[0].rule()].replace, Pattern::from_string("BR"));
+
+        let repel = Field {
+            repel: true,
+            ..attract[0].clone()
+        };
+
+        let mut all = matches(&grid);
+        Potentials::default().sort(&[repel], &grid, &rules, &mut all);
+        assert_eq!(all.len(), 2);
+        assert_ne!(rules[all[0].rule()].replace, Pattern::from_string("BR"));
+
+        let absent = [Field::new(
+            Symbol::Red,
+            vec![Symbol::Green],
+            vec![Symbol::Black],
+        )];
+        let ignored = Potentials::default().best(&absent, &grid, &rules, matches(&grid));
+        assert_eq!(ignored.len(), 2);
+
+        let essential = Field {
+            essential: true,
+            ..absent[0].clone()
+        };
+
+        assert!(Potentials::default()
+            .best(&[essential], &grid, &rules, matches(&grid))
+            .is_empty());
+    }
+}
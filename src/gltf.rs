@@ -0,0 +1,214 @@
+//! Exporting meshes as binary glTF 2.0 (`.glb`) files.
+//!
+//! [Mesh::to_glb] writes a single self-contained file: a JSON chunk
+//! describing the scene and a binary chunk holding the vertex positions,
+//! normals, and triangle indices. The mesh is split into one primitive per
+//! symbol, all sharing the same vertices, and each primitive uses a material
+//! named after its symbol whose base color is taken from the
+//! [palette](Symbol::PALETTE). glTF's Y-up, right-handed axes match those of
+//! [Grid::mesh], so grids import upright.
+
+use std::fmt::Write;
+
+use super::*;
+use mesh::Mesh;
+use obj::material_name;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+impl Mesh {
+    /// Writes the mesh as a binary glTF file. See the [module](self) docs.
+    pub fn to_glb(&self) -> Vec<u8> {
+        let mut bin = Vec::new();
+        for vertex in self.vertices.iter() {
+            bin.extend(vertex.position.iter().flat_map(|c| c.to_le_bytes()));
+        }
+
+        for vertex in self.vertices.iter() {
+            bin.extend(vertex.normal.iter().flat_map(|c| c.to_le_bytes()));
+        }
+
+        bin.extend(self.indices.iter().flat_map(|index| index.to_le_bytes()));
+
+        let json = self.gltf_json(bin.len());
+        let mut json = json.into_bytes();
+        pad(&mut json, b' ');
+        pad(&mut bin, 0);
+
+        let length = match bin.len() {
+            0 => 20 + json.len(),
+            bin => 28 + json.len() + bin,
+        };
+
+        let mut glb = Vec::with_capacity(length);
+        glb.extend(b"glTF");
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((length as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(json);
+
+        // Empty meshes have no buffer, so they leave out the binary chunk.
+        if !bin.is_empty() {
+            glb.extend((bin.len() as u32).to_le_bytes());
+            glb.extend(b"BIN\0");
+            glb.extend(bin);
+        }
+
+        glb
+    }
+
+    /// The JSON chunk, describing a binary chunk of `length` bytes laid out
+    /// as positions, then normals, then indices.
+    fn gltf_json(&self, length: usize) -> String {
+        let mut json = String::from(r#"{"asset":{"version":"2.0","generator":"crabby-markov"}"#);
+        if self.vertices.is_empty() {
+            json.push_str(r#","scene":0,"scenes":[{"nodes":[0]}],"nodes":[{}]}"#);
+            return json;
+        }
+
+        json.push_str(r#","scene":0,"scenes":[{"nodes":[0]}],"nodes":[{"mesh":0}]"#);
+        write!(json, r#","buffers":[{{"byteLength":{}}}]"#, length).unwrap();
+
+        let vertices = self.vertices.len() * 12;
+        write!(
+            json,
+            r#","bufferViews":[{{"buffer":0,"byteOffset":0,"byteLength":{vertices},"target":{ARRAY_BUFFER}}},{{"buffer":0,"byteOffset":{vertices},"byteLength":{vertices},"target":{ARRAY_BUFFER}}},{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{ELEMENT_ARRAY_BUFFER}}}]"#,
+            vertices * 2,
+            self.indices.len() * 4
+        )
+        .unwrap();
+
+        // glTF requires the bounds of positions.
+        let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+        for vertex in self.vertices.iter() {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
+            }
+        }
+
+        let count = self.vertices.len();
+        write!(
+            json,
+            r#","accessors":[{{"bufferView":0,"componentType":{FLOAT},"count":{count},"type":"VEC3","min":{},"max":{}}},{{"bufferView":1,"componentType":{FLOAT},"count":{count},"type":"VEC3"}}"#,
+            vector(min),
+            vector(max)
+        )
+        .unwrap();
+
+        let ranges = self.symbol_ranges();
+        for (_, range) in ranges.iter() {
+            write!(
+                json,
+                r#",{{"bufferView":2,"byteOffset":{},"componentType":{UNSIGNED_INT},"count":{},"type":"SCALAR"}}"#,
+                range.start * 4,
+                range.len()
+            )
+            .unwrap();
+        }
+
+        json.push_str(r#"],"meshes":[{"primitives":["#);
+        for idx in 0..ranges.len() {
+            if idx > 0 {
+                json.push(',');
+            }
+
+            write!(
+                json,
+                r#"{{"attributes":{{"POSITION":0,"NORMAL":1}},"indices":{},"material":{}}}"#,
+                idx + 2,
+                idx
+            )
+            .unwrap();
+        }
+
+        json.push_str(r#"]}],"materials":["#);
+        for (idx, (symbol, _)) in ranges.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+
+            let rgb = &Symbol::PALETTE[symbol.palette_index() as usize * 3..][..3];
+            let [r, g, b] = [0, 1, 2].map(|i| linear(rgb[i]));
+            write!(
+                json,
+                r#"{{"name":"{}","pbrMetallicRoughness":{{"baseColorFactor":[{:.4},{:.4},{:.4},1],"metallicFactor":0,"roughnessFactor":1}}}}"#,
+                material_name(*symbol),
+                r,
+                g,
+                b
+            )
+            .unwrap();
+        }
+
+        json.push_str("]}");
+        json
+    }
+}
+
+fn vector([x, y, z]: [f32; 3]) -> String {
+    format!("[{},{},{}]", x, y, z)
+}
+
+/// Converts an sRGB palette channel to the linear value glTF colors use.
+fn linear(channel: u8) -> f32 {
+    let channel = channel as f32 / 255.0;
+    match channel <= 0.04045 {
+        true => channel / 12.92,
+        false => ((channel + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+/// Pads a chunk to a multiple of four bytes, as GLB requires.
+fn pad(chunk: &mut Vec<u8>, with: u8) {
+    while !chunk.len().is_multiple_of(4) {
+        chunk.push(with);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u32(bytes: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn write_glb() {
+        let mut grid = Grid::new(3, 1);
+        grid[Point { x: 0, y: 0 }] = Symbol::Red;
+        grid[Point { x: 2, y: 0 }] = Symbol::White;
+        let mesh = grid.mesh();
+
+        let glb = mesh.to_glb();
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(read_u32(&glb, 4), 2);
+        assert_eq!(read_u32(&glb, 8), glb.len());
+
+        let json_length = read_u32(&glb, 12);
+        assert_eq!(&glb[16..20], b"JSON");
+        let json = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+        assert!(json.contains(r#""min":[0,0,0],"max":[3,1,1]"#), "{}", json);
+        assert!(
+            json.contains(r#"{"name":"white","pbrMetallicRoughness":{"baseColorFactor":[1.0000,"#)
+        );
+        assert!(json.contains(r#""indices":3,"material":1"#));
+        assert!(json.trim_end().ends_with("]}"));
+
+        let bin = 20 + json_length;
+        let bin_length = read_u32(&glb, bin);
+        assert_eq!(&glb[bin + 4..bin + 8], b"BIN\0");
+        assert_eq!(bin_length, 48 * 12 * 2 + 72 * 4);
+        assert_eq!(glb.len(), bin + 8 + bin_length);
+
+        let empty = Mesh::default().to_glb();
+        assert_eq!(read_u32(&empty, 8), empty.len());
+        assert_eq!(empty.len(), 20 + read_u32(&empty, 12));
+        assert!(!std::str::from_utf8(&empty[20..]).unwrap().contains("mesh"));
+    }
+}
@@ -0,0 +1,410 @@
+//! Finding matches on the GPU.
+//!
+//! A [GpuBackend] uploads the grid and every rule's find pattern and tests
+//! all rules at all positions in one compute dispatch. [GpuBackend::accelerate]
+//! swaps the [AllNode] and [PrlNode]s of a tree for [GpuNode]s that scan
+//! this way. Matches are still applied on the CPU, since the order they are
+//! applied in depends on the runner's random number generator.
+
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+
+use rand::RngCore;
+use wgpu::util::DeviceExt;
+
+use super::*;
+use node::{AllNode, AnyNode, AnyState, PrlNode};
+use observer::Observer;
+use plugin::{DynNode, DynState};
+use rule_set::RuleSet;
+
+#[derive(Debug)]
+pub enum GpuError {
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+}
+
+impl Display for GpuError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            GpuError::NoAdapter => write!(fmt, "no suitable GPU adapter found"),
+            GpuError::RequestDevice(err) => write!(fmt, "failed to open GPU device: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+/// A GPU device set up to scan grids for rule matches.
+///
+/// Cloning a backend shares the same device.
+#[derive(Clone)]
+pub struct GpuBackend(Arc<Backend>);
+
+struct Backend {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl GpuBackend {
+    /// Opens the default GPU adapter, blocking until it is ready.
+    pub fn new() -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .ok_or(GpuError::NoAdapter)?;
+
+        let (device, queue) =
+            block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .map_err(GpuError::RequestDevice)?;
+
+        let module = device.create_shader_module(wgpu::include_wgsl!("gpu_scan.wgsl"));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("scan"),
+            layout: None,
+            module: &module,
+            entry_point: Some("scan"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Ok(Self(Arc::new(Backend {
+            device,
+            queue,
+            pipeline,
+        })))
+    }
+
+    /// Replaces every [AllNode] and [PrlNode] in a tree with a [GpuNode]
+    /// using this backend.
+    pub fn accelerate(&self, node: AnyNode) -> AnyNode {
+        let accelerate = |children: Vec<AnyNode>| {
+            children
+                .into_iter()
+                .map(|child| self.accelerate(child))
+                .collect()
+        };
+
+        match node {
+            AnyNode::All(_) | AnyNode::Prl(_) => AnyNode::from_dyn(GpuNode {
+                node,
+                backend: self.clone(),
+            }),
+            AnyNode::Markov(mut markov) => {
+                markov.children = accelerate(markov.children);
+                AnyNode::Markov(markov)
+            }
+            AnyNode::Sequence(mut sequence) => {
+                sequence.children = accelerate(sequence.children);
+                AnyNode::Sequence(sequence)
+            }
+            AnyNode::Repeat(mut repeat) => {
+                repeat.child = Box::new(self.accelerate(*repeat.child));
+                AnyNode::Repeat(repeat)
+            }
+            AnyNode::If(mut guarded) => {
+                guarded.child = Box::new(self.accelerate(*guarded.child));
+                AnyNode::If(guarded)
+            }
+            AnyNode::Until(mut until) => {
+                until.child = Box::new(self.accelerate(*until.child));
+                AnyNode::Until(until)
+            }
+            node => node,
+        }
+    }
+
+    /// Finds every match of every rule that fits on the grid, in the same
+    /// order as a CPU scan.
+    ///
+    /// The scan shader doesn't wrap, so periodic grids are scanned on the
+    /// CPU.
+    pub fn find_all_matches(
+        &self,
+        grid: &Grid,
+        observer: &mut dyn Observer,
+        node: usize,
+        rules: &RuleSet,
+    ) -> Vec<Match> {
+        if grid.periodic {
+            return node::find_all_matches(grid, observer, node, rules);
+        }
+
+        let fitting: Vec<_> = rules.fitting(grid).collect();
+        if fitting.is_empty() {
+            return Vec::new();
+        }
+
+        let Backend {
+            device,
+            queue,
+            pipeline,
+        } = &*self.0;
+
+        let mut headers = Vec::new();
+        let mut patterns = Vec::new();
+        for idx in fitting.iter() {
+            let find = &rules[*idx].find;
+            headers.extend([patterns.len(), find.width, find.height, find.layers]);
+            patterns.extend(find.grid.iter().map(|cell| match cell {
+                Some(symbol) => *symbol as usize,
+                None => 0xff,
+            }));
+        }
+
+        let params = [grid.width, grid.height, grid.layers, fitting.len()];
+        let cells: Vec<_> = grid.grid.iter().map(|symbol| *symbol as usize).collect();
+
+        let upload = |label, contents: &[usize], usage| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: &to_bytes(contents),
+                usage,
+            })
+        };
+
+        let params = upload("params", &params, wgpu::BufferUsages::UNIFORM);
+        let cells = upload("cells", &cells, wgpu::BufferUsages::STORAGE);
+        let headers = upload("rules", &headers, wgpu::BufferUsages::STORAGE);
+        let patterns = upload("patterns", &patterns, wgpu::BufferUsages::STORAGE);
+
+        let size = (fitting.len() * grid.width * grid.height * 4) as u64;
+        let matches = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("matches"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let entries: Vec<_> = [&params, &cells, &headers, &patterns, &matches]
+            .into_iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("scan"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                grid.width.div_ceil(8) as u32,
+                grid.height.div_ceil(8) as u32,
+                fitting.len() as u32,
+            );
+        }
+
+        encoder.copy_buffer_to_buffer(&matches, 0, &readback, 0, size);
+        queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("failed to read back GPU matches")
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        let flags = slice.get_mapped_range();
+        let mut matched = Vec::new();
+        for (slot, idx) in fitting.into_iter().enumerate() {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::trace_span!("match", node, rule = idx).entered();
+
+            let start = matched.len();
+            for x in 0..grid.width {
+                for y in 0..grid.height {
+                    let offset = ((slot * grid.height + y) * grid.width + x) * 4;
+                    let at = Point { x, y };
+                    if flags[offset] != 0 && rules[idx].test_conditions(grid, at) {
+                        matched.push(Match::new(idx, at, grid.width));
+                    }
+                }
+            }
+
+            #[cfg(feature = "tracing")]
+            tracing::trace!(matches = matched.len() - start);
+
+            observer.on_scan(node, idx, matched.len() - start);
+        }
+
+        matched
+    }
+}
+
+impl Debug for GpuBackend {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_tuple("GpuBackend").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for GpuBackend {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// An [AllNode] or [PrlNode] that finds its matches on the GPU.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpuNode {
+    node: AnyNode,
+    backend: GpuBackend,
+}
+
+impl GpuNode {
+    pub fn all(node: AllNode, backend: GpuBackend) -> Self {
+        let node = AnyNode::All(node);
+        Self { node, backend }
+    }
+
+    pub fn prl(node: PrlNode, backend: GpuBackend) -> Self {
+        let node = AnyNode::Prl(node);
+        Self { node, backend }
+    }
+}
+
+impl DynNode for GpuNode {
+    fn name(&self) -> &'static str {
+        match self.node {
+            AnyNode::All(_) => "gpu all",
+            _ => "gpu prl",
+        }
+    }
+
+    fn make_state(&self) -> Box<dyn DynState> {
+        Box::new(GpuState {
+            state: self.node.make_state(),
+            backend: self.backend.clone(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct GpuState {
+    state: AnyState,
+    backend: GpuBackend,
+}
+
+impl DynState for GpuState {
+    fn step(
+        &mut self,
+        id: usize,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        let backend = &self.backend;
+        let scan = |grid: &Grid, observer: &mut dyn Observer, node, rules: &RuleSet| {
+            backend.find_all_matches(grid, observer, node, rules)
+        };
+
+        match &mut self.state {
+            AnyState::All(state) => {
+                state.id = id;
+                state.step_with(rng, grid, observer, scan)
+            }
+            AnyState::Prl(state) => {
+                state.id = id;
+                state.step_with(rng, grid, observer, scan)
+            }
+            _ => unreachable!("GPU nodes wrap all and prl nodes"),
+        }
+    }
+
+    fn applications(&self) -> usize {
+        self.state.applications()
+    }
+
+    fn save_counters(&self, counters: &mut Vec<usize>) {
+        self.state.save_counters(counters);
+    }
+
+    fn load_counters(&mut self, mut counters: &mut dyn Iterator<Item = usize>) {
+        self.state.load_counters(&mut counters);
+    }
+}
+
+fn to_bytes(words: &[usize]) -> Vec<u8> {
+    words
+        .iter()
+        .flat_map(|word| (*word as u32).to_le_bytes())
+        .collect()
+}
+
+/// Runs a future to completion on the current thread.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(Unpark(thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::find_all_matches;
+
+    #[test]
+    fn gpu_matches() {
+        use rand::Rng;
+
+        let backend = match GpuBackend::new() {
+            Ok(backend) => backend,
+            Err(err) => {
+                eprintln!("skipping GPU test: {}", err);
+                return;
+            }
+        };
+
+        let mut rng = crate::tests::make_rng();
+        let mut grid = Grid::with_layers(40, 30, 2);
+        for cell in grid.grid.iter_mut() {
+            *cell = Symbol::from_palette_index(rng.gen_range(0..3)).unwrap();
+        }
+
+        let rules = RuleSet::with_symmetry(
+            [
+                Rule::from_strings("BW", "WB"),
+                Rule::from_strings("R*B/BBB", "WWW/WWW"),
+                Rule::from_strings("B R", "W *"),
+            ],
+            Symmetry::Rotations,
+        );
+
+        assert_eq!(
+            backend.find_all_matches(&grid, &mut (), 0, &rules),
+            find_all_matches(&grid, &mut (), 0, &rules)
+        );
+    }
+}
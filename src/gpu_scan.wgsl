@@ -0,0 +1,53 @@
+// Tests every rule at every grid position in parallel.
+//
+// Invocation (x, y, r) writes 1 to `matches[(r * height + y) * width + x]`
+// if rule r's find pattern matches with its top-left corner at (x, y), and 0
+// otherwise. Positions are limited the same way as `Grid::find_matches`.
+
+struct Params {
+    width: u32,
+    height: u32,
+    layers: u32,
+    rules: u32,
+}
+
+struct Rule {
+    offset: u32,
+    width: u32,
+    height: u32,
+    layers: u32,
+}
+
+// Pattern cells hold a symbol, or this for a wildcard.
+const WILDCARD: u32 = 0xffu;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> cells: array<u32>;
+@group(0) @binding(2) var<storage, read> rules: array<Rule>;
+@group(0) @binding(3) var<storage, read> patterns: array<u32>;
+@group(0) @binding(4) var<storage, read_write> matches: array<u32>;
+
+@compute @workgroup_size(8, 8, 1)
+fn scan(@builtin(global_invocation_id) id: vec3<u32>) {
+    let x = id.x;
+    let y = id.y;
+    let r = id.z;
+    if x >= params.width || y >= params.height || r >= params.rules {
+        return;
+    }
+
+    let rule = rules[r];
+    var matched = x + rule.width + 1u < params.width && y + rule.height + 1u < params.height;
+
+    for (var layer = 0u; layer < rule.layers && matched; layer++) {
+        for (var py = 0u; py < rule.height && matched; py++) {
+            for (var px = 0u; px < rule.width && matched; px++) {
+                let expected = patterns[rule.offset + (layer * rule.height + py) * rule.width + px];
+                let actual = cells[(layer * params.height + y + py) * params.width + x + px];
+                matched = expected == WILDCARD || expected == actual;
+            }
+        }
+    }
+
+    matches[(r * params.height + y) * params.width + x] = select(0u, 1u, matched);
+}
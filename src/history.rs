@@ -0,0 +1,107 @@
+use super::*;
+use observer::{Application, Observer};
+use runner::Progress;
+
+/// The cells overwritten by a single pattern application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// The ID of the applying node.
+    pub node: usize,
+
+    /// The index of the applied rule within its node.
+    pub rule: usize,
+
+    /// Where the replacement pattern was applied.
+    pub at: Point,
+
+    /// The previous symbols of every cell the replacement wrote to.
+    pub overwritten: Pattern,
+}
+
+/// Everything needed to undo a single step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct StepRecord {
+    /// The index of this step's first change.
+    first_change: usize,
+
+    /// The state's progress counters before the step.
+    counters: Vec<usize>,
+
+    /// The runner's progress before the step.
+    progress: Progress,
+}
+
+/// A log of applied changes that can be undone step by step.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct History {
+    changes: Vec<Change>,
+    steps: Vec<StepRecord>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of steps that can be rewound.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Every recorded change, oldest first.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Marks the start of a new step.
+    pub fn begin_step(&mut self, counters: Vec<usize>, progress: Progress) {
+        self.steps.push(StepRecord {
+            first_change: self.changes.len(),
+            counters,
+            progress,
+        });
+    }
+
+    /// Discards the most recent step's record without undoing it.
+    ///
+    /// Used when a step turns out to have done nothing.
+    pub fn cancel_step(&mut self) {
+        if let Some(step) = self.steps.pop() {
+            self.changes.truncate(step.first_change);
+        }
+    }
+
+    /// Undoes the most recent step's changes on the grid.
+    ///
+    /// Returns the counters and progress from before that step, or None if
+    /// the history is empty.
+    pub fn undo_step(&mut self, grid: &mut Grid) -> Option<(Vec<usize>, Progress)> {
+        let step = self.steps.pop()?;
+        for change in self.changes.drain(step.first_change..).rev() {
+            grid.apply_pattern(&change.overwritten, change.at);
+        }
+
+        Some((step.counters, step.progress))
+    }
+
+    /// Forgets all recorded steps.
+    pub fn clear(&mut self) {
+        self.changes.clear();
+        self.steps.clear();
+    }
+}
+
+impl Observer for History {
+    fn before_apply(&mut self, grid: &Grid, application: &Application) {
+        self.changes.push(Change {
+            node: application.node,
+            rule: application.rule,
+            at: application.at,
+            overwritten: application.read_cells(grid),
+        });
+    }
+}
@@ -0,0 +1,177 @@
+//! Conversion between grids and images from the `image` crate.
+//!
+//! Palettes are flat RGB triples laid out like [Symbol::PALETTE], where the
+//! triple at index `i` is the color of the symbol with palette index `i`.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::Path;
+
+use image::{DynamicImage, RgbImage, Rgba, RgbaImage};
+
+use super::*;
+use atlas::Atlas;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Image(image::ImageError),
+    UnknownColor {
+        at: Point,
+        color: [u8; 3],
+    },
+    SheetSize {
+        width: u32,
+        height: u32,
+        tile_size: u16,
+    },
+}
+
+impl Display for ImageError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            ImageError::Image(err) => write!(fmt, "failed to load image: {}", err),
+            ImageError::UnknownColor { at, color } => write!(
+                fmt,
+                "color #{:02x}{:02x}{:02x} at {} is not in the palette",
+                color[0], color[1], color[2], at
+            ),
+            ImageError::SheetSize {
+                width,
+                height,
+                tile_size,
+            } => write!(
+                fmt,
+                "{}x{} sprite sheet doesn't divide into {}x{} tiles",
+                width, height, tile_size, tile_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<image::ImageError> for ImageError {
+    fn from(err: image::ImageError) -> Self {
+        ImageError::Image(err)
+    }
+}
+
+impl Grid {
+    /// Loads an image file as a grid, one cell per pixel.
+    pub fn from_image(path: impl AsRef<Path>, palette: &[u8]) -> Result<Self, ImageError> {
+        let image = image::open(path)?;
+        Self::from_dynamic_image(&image, palette)
+    }
+
+    /// Converts an image to a grid by matching each pixel's color exactly
+    /// against the palette. Alpha is ignored.
+    pub fn from_dynamic_image(image: &DynamicImage, palette: &[u8]) -> Result<Self, ImageError> {
+        Self::from_rgb_image(&image.to_rgb8(), palette)
+    }
+
+    pub fn from_rgb_image(image: &RgbImage, palette: &[u8]) -> Result<Self, ImageError> {
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+        let mut grid = Grid::new(width, height);
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let at = Point {
+                x: x as usize,
+                y: y as usize,
+            };
+
+            grid[at] = palette
+                .chunks_exact(3)
+                .position(|rgb| rgb == pixel.0)
+                .and_then(|index| Symbol::from_palette_index(index as u8))
+                .ok_or(ImageError::UnknownColor { at, color: pixel.0 })?;
+        }
+
+        Ok(grid)
+    }
+
+    /// Renders the grid as an image, one pixel per cell, compositing layers
+    /// with [Grid::visible].
+    pub fn to_image(&self, palette: &[u8]) -> RgbaImage {
+        RgbaImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let at = Point {
+                x: x as usize,
+                y: y as usize,
+            };
+
+            let rgb = &palette[self.visible(at).palette_index() as usize * 3..][..3];
+            Rgba([rgb[0], rgb[1], rgb[2], 0xff])
+        })
+    }
+}
+
+impl Atlas {
+    /// Loads a sprite sheet image as an atlas, as in [Atlas::from_sheet].
+    pub fn open(path: impl AsRef<Path>, tile_size: u16) -> Result<Self, ImageError> {
+        let sheet = image::open(path)?.to_rgba8();
+        let (width, height) = sheet.dimensions();
+        let tile = tile_size as u32;
+        if tile == 0 || !width.is_multiple_of(tile) || !height.is_multiple_of(tile) {
+            return Err(ImageError::SheetSize {
+                width,
+                height,
+                tile_size,
+            });
+        }
+
+        Ok(Self::from_sheet(&sheet, sheet.width() as usize, tile_size))
+    }
+}
+
+impl From<&Grid> for RgbaImage {
+    fn from(grid: &Grid) -> Self {
+        grid.to_image(Symbol::PALETTE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_rgb_image() {
+        let mut image = RgbImage::new(3, 2);
+        image.put_pixel(1, 0, image::Rgb([0xff, 0x00, 0x4d]));
+        image.put_pixel(2, 1, image::Rgb([0xff, 0xf1, 0xe8]));
+
+        let grid = Grid::from_rgb_image(&image, Symbol::PALETTE).unwrap();
+        assert_eq!(grid.to_string(), "BRB\nBBW\n");
+
+        image.put_pixel(0, 1, image::Rgb([0x12, 0x34, 0x56]));
+        assert!(matches!(
+            Grid::from_rgb_image(&image, Symbol::PALETTE),
+            Err(ImageError::UnknownColor {
+                at: Point { x: 0, y: 1 },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn image_round_trip() {
+        let grid = Grid {
+            width: 4,
+            height: 2,
+            layers: 1,
+            grid: Symbol::from_string("BWRGUEPB")
+                .into_iter()
+                .flatten()
+                .collect(),
+            periodic: false,
+        };
+
+        let image = RgbaImage::from(&grid);
+        assert_eq!(image.dimensions(), (4, 2));
+        assert_eq!(image.get_pixel(2, 0), &Rgba([0xff, 0x00, 0x4d, 0xff]));
+
+        let image = DynamicImage::ImageRgba8(image);
+        assert_eq!(
+            Grid::from_dynamic_image(&image, Symbol::PALETTE).unwrap(),
+            grid
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Rendering grids in isometric projection, as MarkovJunior does.
+//!
+//! Cells are drawn as diamonds twice as wide as they are tall, with the
+//! grid's top-left corner at the top of the image. [IsometricStyle::Cubes]
+//! raises every cell that isn't [Symbol::Black] into a cube, stacking the
+//! layers of multi-layer grids on top of each other, so that layered models
+//! read as shallow 3D scenes.
+//!
+//! Images are RGBA with a transparent background. Cube sides are shaded
+//! darker than their tops so that neighboring cubes of one color stay
+//! distinguishable.
+
+use super::*;
+use palette::Palette;
+
+/// How [Grid::render_isometric] draws each cell.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IsometricStyle {
+    /// Flat diamonds of each cell's [visible](Grid::visible) symbol.
+    Tiles,
+
+    /// A cube for every cell that isn't black, with each layer one cube
+    /// higher than the last. Black cells on the bottom layer are drawn as
+    /// flat floor tiles and black cells above it are left empty.
+    #[default]
+    Cubes,
+}
+
+impl Grid {
+    /// The size in pixels of [Grid::render_isometric]'s output.
+    pub fn isometric_size(&self, tile_size: u16, style: IsometricStyle) -> (usize, usize) {
+        let unit = unit(tile_size);
+        let across = self.width + self.height;
+        (across * unit * 2, across * unit + raised(self, unit, style))
+    }
+
+    /// Renders the grid as RGBA pixels in isometric projection.
+    ///
+    /// `tile_size` is the width of each diamond in pixels, rounded down to a
+    /// multiple of four but at least four.
+    pub fn render_isometric(&self, tile_size: u16, style: IsometricStyle) -> Vec<u8> {
+        self.render_isometric_with(tile_size, style, Palette::Default)
+    }
+
+    /// Like [Grid::render_isometric], but in the colors of `palette`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self),
+            fields(width = self.width, height = self.height)
+        )
+    )]
+    pub fn render_isometric_with(
+        &self,
+        tile_size: u16,
+        style: IsometricStyle,
+        palette: Palette,
+    ) -> Vec<u8> {
+        let rgb = |symbol| palette.rgb(symbol);
+        let unit = unit(tile_size);
+        let (width, height) = self.isometric_size(tile_size, style);
+        let mut canvas = Canvas {
+            pixels: vec![0; width * height * 4],
+            width,
+            unit,
+        };
+
+        // Drawing in order of distance from the viewer lets nearer cells
+        // simply paint over farther ones.
+        let ground = raised(self, unit, style);
+        let mut cells: Vec<_> = (0..self.layers)
+            .flat_map(|layer| self.enumerate_points().map(move |at| (at, layer)))
+            .collect();
+        cells.sort_by_key(|(at, layer)| at.x + at.y + layer);
+
+        for (at, layer) in cells {
+            let left = (at.x + self.height - 1 - at.y) * unit * 2;
+            let top = (at.x + at.y) * unit + ground;
+            match style {
+                IsometricStyle::Tiles if layer == 0 => {
+                    canvas.diamond(left, top, rgb(self.visible(at)))
+                }
+                IsometricStyle::Tiles => {}
+                IsometricStyle::Cubes => match self[(at, layer)] {
+                    Symbol::Black if layer == 0 => canvas.diamond(left, top, rgb(Symbol::Black)),
+                    Symbol::Black => {}
+                    symbol => canvas.cube(left, top - (layer + 1) * unit * 2, rgb(symbol)),
+                },
+            }
+        }
+
+        canvas.pixels
+    }
+
+    /// Like [Grid::render_gif_frame], but in isometric projection. The frame
+    /// gets its own palette, quantized from the shaded colors.
+    ///
+    /// Panics if the frame is too large for a GIF.
+    pub fn render_isometric_gif_frame(
+        &self,
+        tile_size: u16,
+        style: IsometricStyle,
+    ) -> gif::Frame<'static> {
+        let (width, height) = self.isometric_size(tile_size, style);
+        let (width, height) = gif_size(width, height).unwrap_or_else(|err| panic!("{}", err));
+        let mut pixels = self.render_isometric(tile_size, style);
+        gif::Frame::from_rgba_speed(width, height, &mut pixels, 10)
+    }
+}
+
+/// A quarter of a diamond's width, which is also half its height.
+fn unit(tile_size: u16) -> usize {
+    (tile_size as usize / 4).max(1)
+}
+
+/// How far above the ground the tallest possible stack of cubes reaches.
+fn raised(grid: &Grid, unit: usize, style: IsometricStyle) -> usize {
+    match style {
+        IsometricStyle::Tiles => 0,
+        IsometricStyle::Cubes => grid.layers * unit * 2,
+    }
+}
+
+fn shade([r, g, b]: [u8; 3], numerator: u16) -> [u8; 3] {
+    [r, g, b].map(|c| (c as u16 * numerator / 4) as u8)
+}
+
+struct Canvas {
+    pixels: Vec<u8>,
+    width: usize,
+    unit: usize,
+}
+
+impl Canvas {
+    /// The rows of a diamond's column `column`, relative to its top.
+    fn span(&self, column: usize) -> (usize, usize) {
+        let edge = column.min(self.unit * 4 - 1 - column);
+        let extent = edge / 2 + 1;
+        (self.unit - extent, self.unit + extent)
+    }
+
+    /// Fills a diamond whose bounding box has its top-left corner at
+    /// (`left`, `top`).
+    fn diamond(&mut self, left: usize, top: usize, color: [u8; 3]) {
+        for column in 0..self.unit * 4 {
+            let (start, end) = self.span(column);
+            self.fill(left + column, top + start..top + end, color);
+        }
+    }
+
+    /// Fills a cube whose top face's bounding box has its top-left corner at
+    /// (`left`, `top`).
+    fn cube(&mut self, left: usize, top: usize, color: [u8; 3]) {
+        for column in 0..self.unit * 4 {
+            let (_, end) = self.span(column);
+            let side = match column < self.unit * 2 {
+                true => shade(color, 3),
+                false => shade(color, 2),
+            };
+
+            self.fill(left + column, top + end..top + end + self.unit * 2, side);
+        }
+
+        self.diamond(left, top, color);
+    }
+
+    fn fill(&mut self, x: usize, rows: std::ops::Range<usize>, [r, g, b]: [u8; 3]) {
+        for y in rows {
+            let offset = (y * self.width + x) * 4;
+            self.pixels[offset..offset + 4].copy_from_slice(&[r, g, b, 0xff]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_isometric() {
+        let mut grid = Grid::with_layers(2, 1, 2);
+        grid[(Point { x: 1, y: 0 }, 1)] = Symbol::Red;
+
+        let pixel = |pixels: &[u8], width: usize, x: usize, y: usize| {
+            pixels[(y * width + x) * 4..][..4].to_vec()
+        };
+
+        let (width, height) = grid.isometric_size(8, IsometricStyle::Tiles);
+        assert_eq!((width, height), (12, 6));
+        let tiles = grid.render_isometric(8, IsometricStyle::Tiles);
+        assert_eq!(tiles.len(), width * height * 4);
+        assert_eq!(pixel(&tiles, width, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(&tiles, width, 3, 1), [0, 0, 0, 0xff]);
+        assert_eq!(pixel(&tiles, width, 7, 3), [0xff, 0x00, 0x4d, 0xff]);
+
+        let (width, height) = grid.isometric_size(8, IsometricStyle::Cubes);
+        assert_eq!((width, height), (12, 14));
+        let cubes = grid.render_isometric(8, IsometricStyle::Cubes);
+        assert_eq!(pixel(&cubes, width, 7, 3), [0xff, 0x00, 0x4d, 0xff]);
+        assert_eq!(pixel(&cubes, width, 5, 7), [0xbf, 0x00, 0x39, 0xff]);
+        assert_eq!(pixel(&cubes, width, 9, 7), [0x7f, 0x00, 0x26, 0xff]);
+        assert_eq!(pixel(&cubes, width, 3, 9), [0, 0, 0, 0xff]);
+
+        let frame = grid.render_isometric_gif_frame(8, IsometricStyle::Cubes);
+        assert_eq!((frame.width, frame.height), (12, 14));
+    }
+}
@@ -0,0 +1,121 @@
+//! Exporting grids as [LDtk](https://ldtk.io) projects.
+//!
+//! [Grid::to_ldtk] writes a project file (`.ldtk`) holding a single level
+//! with an IntGrid layer for each layer of the grid, topmost first as LDtk
+//! lists them. A cell's IntGrid value is its symbol's
+//! [palette index](Symbol::palette_index), so [Symbol::Black] cells are left
+//! empty and the other values are the same for every model. Each value is
+//! named after its symbol, e.g. `red`, and shown in its palette color.
+//!
+//! Projects are written in the format of LDtk 1.5.3, which later versions
+//! upgrade when they open them.
+
+use std::fmt::Write;
+
+use super::*;
+use obj::material_name;
+
+const JSON_VERSION: &str = "1.5.3";
+
+impl Grid {
+    /// Writes the grid as an LDtk project whose cells are `grid_size` pixels
+    /// square. See the [module](self) docs.
+    pub fn to_ldtk(&self, grid_size: u16) -> String {
+        // LDtk identifies everything by a unique number and by an IID,
+        // which is a UUID. Sequential ones keep the output reproducible.
+        let mut next = 0;
+        let mut iid = || {
+            next += 1;
+            format!("00000000-0000-4000-8000-{:012x}", next)
+        };
+
+        let mut values = String::new();
+        for symbol in &Symbol::ALL[1..] {
+            if !values.is_empty() {
+                values.push(',');
+            }
+
+            let rgb = &Symbol::PALETTE[symbol.palette_index() as usize * 3..][..3];
+            write!(
+                values,
+                r##"{{"value":{},"identifier":"{}","color":"#{:02X}{:02X}{:02X}","tile":null,"groupUid":0}}"##,
+                symbol.palette_index(),
+                material_name(*symbol),
+                rgb[0],
+                rgb[1],
+                rgb[2]
+            )
+            .unwrap();
+        }
+
+        // Layer definitions take UIDs 1 to `layers`, and the level the next.
+        let level_uid = self.layers + 1;
+        let mut definitions = Vec::new();
+        let mut instances = Vec::new();
+        for layer in (0..self.layers).rev() {
+            let uid = layer + 1;
+            definitions.push(format!(
+                r#"{{"__type":"IntGrid","identifier":"Layer{layer}","type":"IntGrid","uid":{uid},"doc":null,"uiColor":null,"gridSize":{grid_size},"guideGridWid":0,"guideGridHei":0,"displayOpacity":1,"inactiveOpacity":1,"hideInList":false,"hideFieldsWhenInactive":true,"canSelectWhenInactive":true,"renderInWorldView":true,"pxOffsetX":0,"pxOffsetY":0,"parallaxFactorX":0,"parallaxFactorY":0,"parallaxScaling":true,"requiredTags":[],"excludedTags":[],"autoTilesKilledByOtherLayerUid":null,"uiFilterTags":[],"useAsyncRender":false,"intGridValues":[{values}],"intGridValuesGroups":[],"autoRuleGroups":[],"autoSourceLayerDefUid":null,"tilesetDefUid":null,"tilePivotX":0,"tilePivotY":0,"biomeFieldUid":null}}"#
+            ));
+
+            let cells: Vec<_> = self.grid[layer * self.width * self.height..]
+                [..self.width * self.height]
+                .iter()
+                .map(|symbol| symbol.palette_index().to_string())
+                .collect();
+            instances.push(format!(
+                r#"{{"__identifier":"Layer{layer}","__type":"IntGrid","__cWid":{},"__cHei":{},"__gridSize":{grid_size},"__opacity":1,"__pxTotalOffsetX":0,"__pxTotalOffsetY":0,"__tilesetDefUid":null,"__tilesetRelPath":null,"iid":"{}","levelId":{level_uid},"layerDefUid":{uid},"pxOffsetX":0,"pxOffsetY":0,"visible":true,"optionalRules":[],"intGridCsv":[{}],"autoLayerTiles":[],"seed":0,"overrideTilesetUid":null,"gridTiles":[],"entityInstances":[]}}"#,
+                self.width,
+                self.height,
+                iid(),
+                cells.join(",")
+            ));
+        }
+
+        let (width, height) = (
+            self.width * grid_size as usize,
+            self.height * grid_size as usize,
+        );
+        let level = format!(
+            r##"{{"identifier":"Level_0","iid":"{}","uid":{level_uid},"worldX":0,"worldY":0,"worldDepth":0,"pxWid":{width},"pxHei":{height},"__bgColor":"#000000","bgColor":"#000000","useAutoIdentifier":true,"bgRelPath":null,"bgPos":null,"bgPivotX":0.5,"bgPivotY":0.5,"__smartColor":"#ADADB5","__bgPos":null,"externalRelPath":null,"fieldInstances":[],"layerInstances":[{}],"__neighbours":[]}}"##,
+            iid(),
+            instances.join(",")
+        );
+
+        format!(
+            r##"{{"__header__":{{"fileType":"LDtk Project JSON","app":"LDtk","doc":"https://ldtk.io/json","schema":"https://ldtk.io/files/JSON_SCHEMA.json","appAuthor":"crabby-markov","appVersion":"{JSON_VERSION}","url":"https://ldtk.io"}},"iid":"{}","jsonVersion":"{JSON_VERSION}","appBuildId":0,"nextUid":{},"identifierStyle":"Capitalize","toc":[],"worldLayout":"Free","worldGridWidth":{width},"worldGridHeight":{height},"defaultLevelWidth":{width},"defaultLevelHeight":{height},"defaultPivotX":0,"defaultPivotY":0,"defaultGridSize":{grid_size},"defaultEntityWidth":{grid_size},"defaultEntityHeight":{grid_size},"bgColor":"#40465B","defaultLevelBgColor":"#000000","minifyJson":false,"externalLevels":false,"exportTiled":false,"simplifiedExport":false,"imageExportMode":"None","exportLevelBg":true,"pngFilePattern":null,"backupOnSave":false,"backupLimit":10,"backupRelPath":null,"levelNamePattern":"Level_%idx","tutorialDesc":null,"customCommands":[],"flags":[],"defs":{{"layers":[{}],"entities":[],"tilesets":[],"enums":[],"externalEnums":[],"levelFields":[]}},"levels":[{}],"worlds":[],"dummyWorldIid":"{}"}}"##,
+            iid(),
+            level_uid + 1,
+            definitions.join(","),
+            level,
+            iid()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_ldtk() {
+        let mut grid = Grid::from_display_str("BWR\nRWB").unwrap().to_layer(0, 2);
+        grid[(Point { x: 1, y: 1 }, 1)] = Symbol::Green;
+
+        let ldtk = grid.to_ldtk(8);
+        assert!(ldtk.starts_with(r#"{"__header__":{"fileType":"LDtk Project JSON""#));
+        assert!(ldtk.contains(r#""jsonVersion":"1.5.3","appBuildId":0,"nextUid":4,"#));
+        assert!(ldtk.contains(r#""pxWid":24,"pxHei":16,"#));
+        assert!(ldtk.contains(r##"{"value":2,"identifier":"red","color":"#FF004D""##));
+        assert!(!ldtk.contains(r#""identifier":"black""#));
+
+        let top = ldtk.find(r#""intGridCsv":[0,0,0,0,3,0]"#).unwrap();
+        let bottom = ldtk.find(r#""intGridCsv":[0,1,2,2,1,0]"#).unwrap();
+        assert!(top < bottom);
+        assert!(
+            ldtk.contains(r#""__identifier":"Layer1","__type":"IntGrid","__cWid":3,"__cHei":2,"#)
+        );
+        assert_eq!(ldtk.matches(r#""iid":"#).count(), 4);
+        assert_eq!(ldtk.matches('{').count(), ldtk.matches('}').count());
+    }
+}
@@ -0,0 +1,128 @@
+//! Deriving rewrite rules from examples of what they should do.
+//!
+//! Given pairs of grids showing a state before and after some rules ran,
+//! [candidates] finds each connected patch of changed cells and turns it
+//! into a rule that rewrites the patch's surroundings in `before` into its
+//! cells in `after`. Identical rules from different patches are merged and
+//! counted, so that the rules explaining the most changes come first.
+//!
+//! Only the bottom layer of each grid is compared.
+
+use super::*;
+use std::cmp::Reverse;
+
+/// A rule that explains some of the changes between example grids.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// Matches a changed patch and the `context` cells around it, and
+    /// writes only the cells that changed.
+    pub rule: Rule,
+
+    /// How many patches this rule explains, across every example.
+    pub count: usize,
+}
+
+/// Extracts candidate rules from `(before, after)` example pairs, most
+/// frequent first, keeping the order they were found in between equally
+/// frequent rules.
+///
+/// Each rule's find pattern covers its patch's bounding box grown by up to
+/// `context` cells on every side, clipped to the grid's edges.
+///
+/// Panics if the grids of any pair have different sizes.
+pub fn candidates(examples: &[(Grid, Grid)], context: usize) -> Vec<Candidate> {
+    let mut found: Vec<Candidate> = Vec::new();
+    for (before, after) in examples {
+        for rule in patches(before, after, context) {
+            match found.iter_mut().find(|candidate| candidate.rule == rule) {
+                Some(candidate) => candidate.count += 1,
+                None => found.push(Candidate { rule, count: 1 }),
+            }
+        }
+    }
+
+    found.sort_by_key(|candidate| Reverse(candidate.count));
+    found
+}
+
+/// Makes a rule for every connected patch of changed cells, in row order.
+fn patches(before: &Grid, after: &Grid, context: usize) -> Vec<Rule> {
+    if (before.width, before.height) != (after.width, after.height) {
+        panic!(
+            "cannot learn from a {}x{} grid changing into a {}x{} grid",
+            before.width, before.height, after.width, after.height
+        );
+    }
+
+    let mut changed = Grid::new(before.width, before.height);
+    for (at, cell) in changed.iter_mut() {
+        if before[at] != after[at] {
+            *cell = Symbol::White;
+        }
+    }
+
+    let components = changed.components(&[Symbol::White]);
+    (0..components.len() as u32)
+        .map(|component| {
+            let rect = bounds(components.points(component), context, before);
+            let find = before.extract_pattern(rect);
+            let mut replace = after.extract_pattern(rect);
+            for (cell, old) in replace.grid.iter_mut().zip(find.grid.iter()) {
+                if cell == old {
+                    *cell = None;
+                }
+            }
+
+            Rule {
+                find,
+                replace,
+                conditions: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// The bounding box of `points`, grown by `context` and clipped to `grid`.
+fn bounds(points: impl Iterator<Item = Point>, context: usize, grid: &Grid) -> Rect {
+    let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+    for at in points {
+        left = left.min(at.x);
+        top = top.min(at.y);
+        right = right.max(at.x + 1);
+        bottom = bottom.max(at.y + 1);
+    }
+
+    let left = left.saturating_sub(context);
+    let top = top.saturating_sub(context);
+    let right = (right + context).min(grid.width);
+    let bottom = (bottom + context).min(grid.height);
+    Rect::new(Point { x: left, y: top }, right - left, bottom - top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learn_rules() {
+        let grid = |rows: &str| Grid::from_display_str(&rows.replace('/', "\n")).unwrap();
+        let example = |before, after| (grid(before), grid(after));
+
+        let examples = [
+            example("BRBBB/BBBBB/BBBRB", "BWBBB/BBBBB/BBBWB"),
+            example("BBBBB/BBBBR/BBRBB", "BBBBB/BBBBW/BBRWB"),
+        ];
+
+        let found = candidates(&examples, 0);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].count, 3);
+        assert_eq!(found[0].rule, Rule::from_strings("R", "W"));
+        assert_eq!(found[1].count, 1);
+        assert_eq!(found[1].rule, Rule::from_strings("B", "W"));
+
+        let found = candidates(&examples[..1], 1);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].rule.find, Pattern::from_string("BRB/BBB"));
+        assert_eq!(found[0].rule.replace, Pattern::from_string("*W*/***"));
+    }
+}
@@ -1,10 +1,88 @@
+use std::collections::VecDeque;
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
-use std::ops::{Add, Index, IndexMut};
+use std::ops::{Add, Index, IndexMut, Sub};
 
 use rand::seq::SliceRandom;
 use rand::Rng;
 
+pub mod analysis;
+pub mod atlas;
+pub mod batch;
+pub mod catalogue;
+pub mod charset;
+pub mod checkpoint;
+pub mod condition;
+pub mod diagnostic;
+pub mod dot;
+pub mod encoding;
+pub mod expression;
+pub mod field;
+pub mod gltf;
+pub mod history;
+pub mod isometric;
+pub mod ldtk;
+pub mod learn;
+pub mod macros;
+pub mod mesh;
+pub mod model;
+pub mod montage;
 pub mod node;
+pub mod obj;
+pub mod observe;
+pub mod observer;
+pub mod packed;
+pub mod palette;
+pub mod path;
+pub mod plugin;
+pub mod postprocess;
+pub mod pretty;
+pub mod recorder;
+pub mod region;
+pub mod remap;
+pub mod replay;
+pub mod report;
+pub mod rng;
+pub mod rule_set;
+pub mod runner;
+pub mod sampler;
+pub mod seed;
+pub mod stats;
+pub mod terminal;
+pub mod terrain;
+pub mod testing;
+pub mod tiled;
+pub mod trace;
+pub mod validate;
+pub mod video;
+pub mod view;
+pub mod watch;
+
+#[cfg(feature = "bevy")]
+pub mod bevy_plugin;
+
+#[cfg(feature = "gpu")]
+pub mod gpu;
+
+#[cfg(feature = "image")]
+pub mod image_io;
+
+#[cfg(feature = "schematic")]
+pub mod schematic;
+
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "threads")]
+pub mod spawn;
+
+#[cfg(feature = "tokio")]
+pub mod stream;
+
+#[cfg(feature = "tui")]
+pub mod tui;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Point {
@@ -29,12 +107,183 @@ impl Display for Point {
     }
 }
 
+impl Sub<Point> for Point {
+    type Output = Offset;
+
+    fn sub(self, other: Self) -> Offset {
+        Offset {
+            dx: self.x as isize - other.x as isize,
+            dy: self.y as isize - other.y as isize,
+        }
+    }
+}
+
 impl Point {
     pub const ZERO: Self = Self { x: 0, y: 0 };
+
+    /// Moves this point by an offset, or returns `None` if either coordinate
+    /// would leave the range of `usize`.
+    pub fn checked_add(self, offset: Offset) -> Option<Self> {
+        Some(Self {
+            x: self.x.checked_add_signed(offset.dx)?,
+            y: self.y.checked_add_signed(offset.dy)?,
+        })
+    }
+
+    /// Moves this point by an offset, clamping each coordinate to the range
+    /// of `usize`.
+    pub fn saturating_add(self, offset: Offset) -> Self {
+        Self {
+            x: self.x.saturating_add_signed(offset.dx),
+            y: self.y.saturating_add_signed(offset.dy),
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// A signed distance between two [Point]s.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Offset {
+    pub dx: isize,
+    pub dy: isize,
+}
+
+impl Offset {
+    pub const ZERO: Self = Self { dx: 0, dy: 0 };
+    pub const LEFT: Self = Self { dx: -1, dy: 0 };
+    pub const UP: Self = Self { dx: 0, dy: -1 };
+    pub const RIGHT: Self = Self { dx: 1, dy: 0 };
+    pub const DOWN: Self = Self { dx: 0, dy: 1 };
+
+    /// The four orthogonal directions, in the order neighbors are visited.
+    pub const ORTHOGONAL: [Self; 4] = [Self::LEFT, Self::UP, Self::RIGHT, Self::DOWN];
+}
+
+impl Add<Offset> for Offset {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            dx: self.dx + other.dx,
+            dy: self.dy + other.dy,
+        }
+    }
+}
+
+impl Display for Offset {
+    fn fmt(&self, formatter: &mut Formatter) -> FmtResult {
+        write!(formatter, "({:+}, {:+})", self.dx, self.dy)
+    }
+}
+
+/// A rectangle of cells, given by its top-left corner and size.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(at: Point, width: usize, height: usize) -> Self {
+        Self {
+            x: at.x,
+            y: at.y,
+            width,
+            height,
+        }
+    }
+
+    /// The top-left corner.
+    pub fn origin(&self) -> Point {
+        Point {
+            x: self.x,
+            y: self.y,
+        }
+    }
+
+    pub fn contains(&self, at: Point) -> bool {
+        (self.x..self.x + self.width).contains(&at.x)
+            && (self.y..self.y + self.height).contains(&at.y)
+    }
+
+    /// Every point inside this rectangle, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+
+        (y..y + height).flat_map(move |y| (x..x + width).map(move |x| Point { x, y }))
+    }
+}
+
+impl Display for Rect {
+    fn fmt(&self, formatter: &mut Formatter) -> FmtResult {
+        write!(
+            formatter,
+            "{}x{} at {}",
+            self.width,
+            self.height,
+            self.origin()
+        )
+    }
+}
+
+/// A rule matched somewhere on a grid, packed into eight bytes.
+///
+/// Match lists are the largest allocations made while stepping, so positions
+/// are stored as offsets into the bottom layer instead of [Point]s. Grids
+/// must have fewer than 2^32 cells, and converting back to a point needs the
+/// width of the grid the match was found on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Match {
+    /// The index of the matched rule.
+    pub rule: u32,
+
+    /// The match's top-left corner, as `y * width + x`.
+    pub offset: u32,
+}
+
+impl Match {
+    /// Panics if the rule index or the position's offset doesn't fit in 32
+    /// bits, rather than pointing at the wrong rule or cell.
+    pub fn new(rule: usize, at: Point, width: usize) -> Self {
+        let offset =
+            at.y.checked_mul(width)
+                .and_then(|row| row.checked_add(at.x));
+        Self {
+            rule: u32::try_from(rule).expect("too many rules to match"),
+            offset: offset
+                .and_then(|offset| u32::try_from(offset).ok())
+                .expect("grids must have fewer than 2^32 cells"),
+        }
+    }
+
+    pub fn rule(&self) -> usize {
+        self.rule as usize
+    }
+
+    /// The match's top-left corner on a grid `width` cells wide.
+    pub fn at(&self, width: usize) -> Point {
+        point_at(self.offset as usize, width)
+    }
+
+    /// The rule index and position together.
+    pub fn unpack(&self, width: usize) -> (usize, Point) {
+        (self.rule(), self.at(width))
+    }
+}
+
+/// A cell's color, named after MarkovJunior's standard palette.
+///
+/// The uppercase symbols are the PICO-8 colors and the lowercase ones are
+/// darker or muted variants, as in MarkovJunior's `palette.xml`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub enum Symbol {
+    #[default]
     Black,
     White,
     Red,
@@ -42,15 +291,50 @@ pub enum Symbol {
     Blue,
     Emerald,
     Purple,
-}
-
-impl Default for Symbol {
-    fn default() -> Self {
-        Symbol::Black
-    }
+    Indigo,
+    Brown,
+    Dark,
+    Gray,
+    Orange,
+    Yellow,
+    Slate,
+    Pink,
+    Fawn,
+    Umber,
+    Navy,
+    Plum,
+    Teal,
+    Rust,
+    Charcoal,
+    Taupe,
+    Cream,
+    Crimson,
+    Tangerine,
+    Lime,
+    Jade,
+    Cobalt,
+    Mauve,
+    Coral,
+    Peach,
 }
 
 impl Symbol {
+    /// Every symbol, in palette order.
+    pub const ALL: [Symbol; 32] = {
+        use Symbol::*;
+        [
+            Black, White, Red, Green, Blue, Emerald, Purple, Indigo, Brown, Dark, Gray, Orange,
+            Yellow, Slate, Pink, Fawn, Umber, Navy, Plum, Teal, Rust, Charcoal, Taupe, Cream,
+            Crimson, Tangerine, Lime, Jade, Cobalt, Mauve, Coral, Peach,
+        ]
+    };
+
+    /// Each symbol's character, in palette order.
+    const CHARS: [char; 32] = [
+        'B', 'W', 'R', 'G', 'U', 'E', 'P', 'I', 'N', 'D', 'A', 'O', 'Y', 'S', 'K', 'F', 'b', 'i',
+        'p', 'e', 'n', 'd', 'a', 'w', 'r', 'o', 'y', 'g', 'u', 's', 'k', 'f',
+    ];
+
     pub const PALETTE: &[u8] = &[
         0x00, 0x00, 0x00, // Black
         0xff, 0xf1, 0xe8, // White
@@ -58,46 +342,93 @@ impl Symbol {
         0x00, 0xe4, 0x36, // Green
         0x29, 0xad, 0xff, // Blue
         0x00, 0x87, 0x51, // Emerald
-        0x7e, 0x25, 0x53, // Purple,
+        0x7e, 0x25, 0x53, // Purple
+        0x1d, 0x2b, 0x53, // Indigo
+        0xab, 0x52, 0x36, // Brown
+        0x5f, 0x57, 0x4f, // Dark
+        0xc2, 0xc3, 0xc7, // Gray
+        0xff, 0xa3, 0x00, // Orange
+        0xff, 0xec, 0x27, // Yellow
+        0x83, 0x76, 0x9c, // Slate
+        0xff, 0x77, 0xa8, // Pink
+        0xff, 0xcc, 0xaa, // Fawn
+        0x29, 0x18, 0x14, // Umber
+        0x11, 0x1d, 0x35, // Navy
+        0x42, 0x21, 0x36, // Plum
+        0x12, 0x53, 0x59, // Teal
+        0x74, 0x2f, 0x29, // Rust
+        0x49, 0x33, 0x3b, // Charcoal
+        0xa2, 0x88, 0x79, // Taupe
+        0xf3, 0xef, 0x7d, // Cream
+        0xbe, 0x12, 0x50, // Crimson
+        0xff, 0x6c, 0x24, // Tangerine
+        0xa8, 0xe7, 0x2e, // Lime
+        0x00, 0xb5, 0x43, // Jade
+        0x06, 0x5a, 0xb5, // Cobalt
+        0x75, 0x46, 0x65, // Mauve
+        0xff, 0x6e, 0x59, // Coral
+        0xff, 0x9d, 0x81, // Peach
     ];
 
+    /// Parses a pattern cell, where `*` is a wildcard.
+    ///
+    /// Panics on characters that aren't symbols.
     pub fn from_char(c: char) -> Option<Self> {
         match c {
-            'B' => Some(Symbol::Black),
-            'W' => Some(Symbol::White),
-            'R' => Some(Symbol::Red),
-            'G' => Some(Symbol::Green),
-            'U' => Some(Symbol::Blue),
-            'E' => Some(Symbol::Emerald),
-            'P' => Some(Symbol::Purple),
             '*' => None,
-            c => panic!("unrecognized symbol '{}'", c),
+            c => Some(
+                Self::try_from_char(c).unwrap_or_else(|| panic!("unrecognized symbol '{}'", c)),
+            ),
         }
     }
 
+    /// Parses a symbol's character, or returns `None` if it isn't one.
+    pub fn try_from_char(c: char) -> Option<Self> {
+        let index = Self::CHARS.iter().position(|symbol| *symbol == c)?;
+        Some(Self::ALL[index])
+    }
+
+    /// The character [Symbol::from_char] parses as this symbol.
+    pub fn to_char(&self) -> char {
+        Self::CHARS[self.palette_index() as usize]
+    }
+
+    /// Parses every symbol in a string, skipping `/` row and ` ` layer
+    /// separators.
     pub fn from_string(string: &str) -> Vec<Option<Self>> {
-        string.chars().map(|c| Self::from_char(c)).collect()
+        string
+            .chars()
+            .filter(|c| !"/ ".contains(*c))
+            .map(Self::from_char)
+            .collect()
+    }
+
+    pub fn from_palette_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
     }
 
     pub fn palette_index(&self) -> u8 {
-        use Symbol::*;
-        match self {
-            Black => 0,
-            White => 1,
-            Red => 2,
-            Green => 3,
-            Blue => 4,
-            Emerald => 5,
-            Purple => 6,
-        }
+        *self as u8
     }
 }
 
+/// A grid of cells, optionally stacked into several layers.
+///
+/// Cells are stored row by row, one layer after another. Indexing by a bare
+/// [Point] addresses the bottom layer; index by `(Point, layer)` to reach the
+/// others.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GenericGrid<T> {
     pub width: usize,
     pub height: usize,
+    pub layers: usize,
     pub grid: Vec<T>,
+
+    /// Whether the grid wraps around at its edges, so that the cells past
+    /// its right and bottom edges are the ones on its left and top.
+    /// Matching, neighbors, distance fields, and paths all wrap on periodic
+    /// grids, which makes their output tile seamlessly.
+    pub periodic: bool,
 }
 
 impl<T> Index<Point> for GenericGrid<T> {
@@ -116,39 +447,256 @@ impl<T> IndexMut<Point> for GenericGrid<T> {
     }
 }
 
+impl<T> Index<(Point, usize)> for GenericGrid<T> {
+    type Output = T;
+
+    fn index(&self, (at, layer): (Point, usize)) -> &T {
+        let offset = self.find_layer_offset(at, layer);
+        &self.grid[offset]
+    }
+}
+
+impl<T> IndexMut<(Point, usize)> for GenericGrid<T> {
+    fn index_mut(&mut self, (at, layer): (Point, usize)) -> &mut T {
+        let offset = self.find_layer_offset(at, layer);
+        &mut self.grid[offset]
+    }
+}
+
 impl<T> GenericGrid<T> {
     pub fn find_offset(&self, at: Point) -> usize {
+        self.find_layer_offset(at, 0)
+    }
+
+    pub fn find_layer_offset(&self, at: Point, layer: usize) -> usize {
+        let at = self.wrap(at);
         if at.x >= self.width || at.y >= self.height {
             panic!("at {} is out-of-bounds", at);
         }
 
-        at.y * self.width + at.x
+        if layer >= self.layers {
+            panic!("layer {} is out-of-bounds", layer);
+        }
+
+        (layer * self.height + at.y) * self.width + at.x
+    }
+
+    /// Wraps a point past the right or bottom edge of a periodic grid back
+    /// onto it. Points on other grids are returned unchanged.
+    pub fn wrap(&self, at: Point) -> Point {
+        match self.periodic && self.width > 0 && self.height > 0 {
+            true => Point {
+                x: at.x % self.width,
+                y: at.y % self.height,
+            },
+            false => at,
+        }
+    }
+
+    /// The in-bounds cells orthogonally adjacent to `at`, wrapping around
+    /// the edges of periodic grids.
+    pub fn neighbors(&self, at: Point) -> impl Iterator<Item = Point> {
+        let (width, height, periodic) = (self.width, self.height, self.periodic);
+        Offset::ORTHOGONAL
+            .into_iter()
+            .filter_map(move |offset| move_within(at, offset, width, height, periodic))
+    }
+
+    /// Moves a point by an offset, or returns `None` if it leaves the grid.
+    /// Points never leave periodic grids, and wrap around instead.
+    pub fn offset(&self, at: Point, offset: Offset) -> Option<Point> {
+        move_within(at, offset, self.width, self.height, self.periodic)
+    }
+
+    /// Every point on the grid, row by row.
+    pub fn enumerate_points(&self) -> impl Iterator<Item = Point> {
+        Rect::new(Point::ZERO, self.width, self.height).points()
+    }
+
+    /// The cells of the bottom layer with their positions, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        let width = self.width;
+        self.grid[..width * self.height]
+            .iter()
+            .enumerate()
+            .map(move |(idx, cell)| (point_at(idx, width), cell))
+    }
+
+    /// Mutable access to the cells of the bottom layer with their
+    /// positions, row by row.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Point, &mut T)> {
+        let width = self.width;
+        self.grid[..width * self.height]
+            .iter_mut()
+            .enumerate()
+            .map(move |(idx, cell)| (point_at(idx, width), cell))
     }
 }
 
+fn point_at(offset: usize, width: usize) -> Point {
+    Point {
+        x: offset % width,
+        y: offset / width,
+    }
+}
+
+fn move_within(
+    at: Point,
+    offset: Offset,
+    width: usize,
+    height: usize,
+    periodic: bool,
+) -> Option<Point> {
+    if periodic && width > 0 && height > 0 {
+        let wrap = |at: usize, by: isize, size: usize| {
+            (at as isize + by).rem_euclid(size as isize) as usize
+        };
+
+        return Some(Point {
+            x: wrap(at.x, offset.dx, width),
+            y: wrap(at.y, offset.dy, height),
+        });
+    }
+
+    at.checked_add(offset)
+        .filter(|pt| pt.x < width && pt.y < height)
+}
+
 impl<T: Clone + Default> GenericGrid<T> {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_layers(width, height, 1)
+    }
+
+    pub fn with_layers(width: usize, height: usize, layers: usize) -> Self {
         Self {
-            grid: vec![Default::default(); width * height],
+            grid: vec![Default::default(); width * height * layers],
             width,
             height,
+            layers,
+            periodic: false,
         }
     }
 
-    pub fn rotate_cw(&self) -> Self {
-        let mut grid = Vec::with_capacity(self.width * self.height);
-
-        for x in 0..self.width {
-            for y in (0..self.height).rev() {
-                let pt = Point { x, y };
-                grid.push(self[pt].clone());
+    /// Builds a `width` by `height` grid with the same layers, copying each
+    /// cell from the position `source` maps it to in this grid.
+    fn transform(&self, width: usize, height: usize, source: impl Fn(Point) -> Point) -> Self {
+        let mut grid = Vec::with_capacity(self.grid.len());
+        for layer in 0..self.layers {
+            for y in 0..height {
+                for x in 0..width {
+                    grid.push(self[(source(Point { x, y }), layer)].clone());
+                }
             }
         }
 
         Self {
             grid,
-            width: self.height,
-            height: self.width,
+            width,
+            height,
+            layers: self.layers,
+            periodic: self.periodic,
+        }
+    }
+
+    pub fn rotate_cw(&self) -> Self {
+        self.transform(self.height, self.width, |at| Point {
+            x: at.y,
+            y: self.height - 1 - at.x,
+        })
+    }
+
+    /// Flips the grid left to right.
+    pub fn mirror_x(&self) -> Self {
+        self.transform(self.width, self.height, |at| Point {
+            x: self.width - 1 - at.x,
+            y: at.y,
+        })
+    }
+
+    /// Flips the grid top to bottom.
+    pub fn mirror_y(&self) -> Self {
+        self.transform(self.width, self.height, |at| Point {
+            x: at.x,
+            y: self.height - 1 - at.y,
+        })
+    }
+
+    /// Swaps the grid's rows and columns, mirroring it along its diagonal.
+    pub fn transpose(&self) -> Self {
+        self.transform(self.height, self.width, |at| Point { x: at.y, y: at.x })
+    }
+
+    /// Moves this grid's layers up to start at `layer` of a grid with
+    /// `layers` layers, filling the other layers with the default value.
+    ///
+    /// For patterns this leaves the other layers as wildcards, so that a
+    /// rule only addresses its own layers.
+    pub fn to_layer(&self, layer: usize, layers: usize) -> Self {
+        if layer + self.layers > layers {
+            panic!("layer {} is out-of-bounds", layer + self.layers - 1);
+        }
+
+        let mut moved = Self::with_layers(self.width, self.height, layers);
+        let start = moved.find_layer_offset(Point::ZERO, layer);
+        moved.grid[start..(start + self.grid.len())].clone_from_slice(&self.grid);
+        moved
+    }
+
+    /// Copies out the cells inside `rect`, across every layer.
+    ///
+    /// Panics if `rect` doesn't fit inside this grid.
+    pub fn crop(&self, rect: Rect) -> Self {
+        if rect.x + rect.width > self.width || rect.y + rect.height > self.height {
+            panic!("{} is out-of-bounds", rect);
+        }
+
+        let mut cropped = Self::with_layers(rect.width, rect.height, self.layers);
+        let at = Offset {
+            dx: -(rect.x as isize),
+            dy: -(rect.y as isize),
+        };
+
+        cropped.paste(self, at);
+        cropped
+    }
+
+    /// Changes this grid's size, keeping its top-left corner in place and
+    /// filling any new cells with `fill`.
+    pub fn resize(&self, width: usize, height: usize, fill: T) -> Self {
+        let mut resized = Self {
+            grid: vec![fill; width * height * self.layers],
+            width,
+            height,
+            layers: self.layers,
+            periodic: self.periodic,
+        };
+
+        resized.paste(self, Offset::ZERO);
+        resized
+    }
+
+    /// Repeats the grid `columns` times across and `rows` times down, for
+    /// checking that a periodic grid tiles without visible seams.
+    pub fn tile(&self, columns: usize, rows: usize) -> Self {
+        self.transform(self.width * columns, self.height * rows, |at| Point {
+            x: at.x % self.width,
+            y: at.y % self.height,
+        })
+    }
+
+    /// Copies `other` over this grid with its top-left corner offset by
+    /// `at`, layer by layer. Cells that land outside this grid, and layers
+    /// missing from either grid, are skipped, even if this grid is periodic.
+    pub fn paste(&mut self, other: &Self, at: Offset) {
+        for layer in 0..self.layers.min(other.layers) {
+            for y in 0..other.height {
+                for x in 0..other.width {
+                    let from = Point { x, y };
+                    if let Some(to) = move_within(from, at, self.width, self.height, false) {
+                        self[(to, layer)] = other[(from, layer)].clone();
+                    }
+                }
+            }
         }
     }
 }
@@ -156,36 +704,133 @@ impl<T: Clone + Default> GenericGrid<T> {
 pub type Pattern = GenericGrid<Option<Symbol>>;
 
 impl Pattern {
+    /// Parses a pattern from rows of symbols separated by `/`, e.g.
+    /// `"BU/UB"`. Multi-layer patterns list their layers from the bottom up,
+    /// separated by spaces, e.g. `"GG/GG B*/*B"`.
+    ///
+    /// Every row must have the same length and every layer the same number
+    /// of rows. Panics if the pattern is invalid; see [Pattern::parse].
     pub fn from_string(string: &str) -> Self {
-        let mut grid = Vec::new();
-        let mut width = 0;
-        let mut height = 1;
-        let mut row_len = 0;
-
-        for c in string.chars() {
-            if c == '/' {
-                if width == 0 {
-                    width = row_len;
-                } else if row_len != width {
-                    panic!("inconsistent row length");
-                }
+        Self::parse(string).unwrap_or_else(|err| panic!("invalid pattern '{}': {}", string, err))
+    }
 
-                height += 1;
-                row_len = 0;
-            } else {
-                grid.push(Symbol::from_char(c));
-                row_len += 1;
-            }
+    /// Like [Pattern::from_string], but fails on invalid patterns instead of
+    /// panicking.
+    pub fn parse(string: &str) -> Result<Self, ParsePatternError> {
+        let layers: Vec<Vec<_>> = string
+            .split(' ')
+            .map(|layer| layer.split('/').collect())
+            .collect();
+
+        if layers.iter().flatten().any(|row| row.is_empty()) {
+            return Err(ParsePatternError::EmptyRow);
         }
 
-        if width == 0 {
-            width = row_len;
+        let height = layers[0].len();
+        let width = layers[0][0].chars().count();
+        for layer in layers.iter() {
+            if layer.len() != height {
+                return Err(ParsePatternError::InconsistentLayers);
+            }
+
+            if layer.iter().any(|row| row.chars().count() != width) {
+                return Err(ParsePatternError::InconsistentRows);
+            }
         }
 
-        Self {
+        let grid = string
+            .chars()
+            .filter(|c| !"/ ".contains(*c))
+            .map(|c| match c {
+                '*' => Ok(None),
+                c => Symbol::try_from_char(c)
+                    .map(Some)
+                    .ok_or(ParsePatternError::UnknownSymbol(c)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
             grid,
             width,
             height,
+            layers: layers.len(),
+            periodic: false,
+        })
+    }
+}
+
+/// Why [Pattern::parse] rejected its input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParsePatternError {
+    /// A layer or row has no symbols, such as in `""` or `"B/"`.
+    EmptyRow,
+    UnknownSymbol(char),
+    InconsistentRows,
+    InconsistentLayers,
+}
+
+impl Display for ParsePatternError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ParsePatternError::*;
+        match self {
+            EmptyRow => write!(fmt, "pattern has an empty row"),
+            UnknownSymbol(c) => write!(fmt, "unrecognized symbol '{}'", c),
+            InconsistentRows => write!(fmt, "pattern has inconsistent row lengths"),
+            InconsistentLayers => write!(fmt, "pattern has inconsistent layer heights"),
+        }
+    }
+}
+
+impl std::error::Error for ParsePatternError {}
+
+/// Which symmetric variants of a rule are matched.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Only the rule as written, e.g. for gravity-like rules.
+    Anisotropic,
+
+    /// The rule and its left-to-right mirror image.
+    MirrorX,
+
+    /// The rule and its top-to-bottom mirror image.
+    MirrorY,
+
+    /// The rule mirrored left to right, top to bottom, and both.
+    Mirrors,
+
+    /// All four rotations of the rule.
+    #[default]
+    Rotations,
+
+    /// All four rotations of the rule and of its transpose, covering every
+    /// symmetry of a square.
+    Square,
+}
+
+impl Symmetry {
+    pub fn expand(self, rule: Rule) -> Vec<Rule> {
+        match self {
+            Symmetry::Anisotropic => vec![rule],
+            Symmetry::MirrorX => {
+                let x = rule.mirror_x();
+                vec![rule, x]
+            }
+            Symmetry::MirrorY => {
+                let y = rule.mirror_y();
+                vec![rule, y]
+            }
+            Symmetry::Mirrors => {
+                let (x, y) = (rule.mirror_x(), rule.mirror_y());
+                let xy = x.mirror_y();
+                vec![rule, x, y, xy]
+            }
+            Symmetry::Rotations => rule.make_rotations(),
+            Symmetry::Square => {
+                let transposed = rule.transpose().make_rotations();
+                let mut rules = rule.make_rotations();
+                rules.extend(transposed);
+                rules
+            }
         }
     }
 }
@@ -194,16 +839,128 @@ impl Pattern {
 pub struct Rule {
     pub find: Pattern,
     pub replace: Pattern,
+
+    /// Extra requirements checked wherever the find pattern matches.
+    pub conditions: Vec<condition::NeighborCount>,
 }
 
 impl Rule {
     pub fn rotate_cw(&self) -> Self {
+        let height = self.find.height;
         Self {
             find: self.find.rotate_cw(),
             replace: self.replace.rotate_cw(),
+            conditions: self.map_conditions(|at| Point {
+                x: height - 1 - at.y,
+                y: at.x,
+            }),
         }
     }
 
+    pub fn mirror_x(&self) -> Self {
+        let width = self.find.width;
+        Self {
+            find: self.find.mirror_x(),
+            replace: self.replace.mirror_x(),
+            conditions: self.map_conditions(|at| Point {
+                x: width - 1 - at.x,
+                y: at.y,
+            }),
+        }
+    }
+
+    pub fn mirror_y(&self) -> Self {
+        let height = self.find.height;
+        Self {
+            find: self.find.mirror_y(),
+            replace: self.replace.mirror_y(),
+            conditions: self.map_conditions(|at| Point {
+                x: at.x,
+                y: height - 1 - at.y,
+            }),
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self {
+            find: self.find.transpose(),
+            replace: self.replace.transpose(),
+            conditions: self.map_conditions(|at| Point { x: at.y, y: at.x }),
+        }
+    }
+
+    fn map_conditions(&self, map: impl Fn(Point) -> Point) -> Vec<condition::NeighborCount> {
+        self.conditions
+            .iter()
+            .map(|condition| condition.map(&map))
+            .collect()
+    }
+
+    /// Swaps the find and replace patterns, so that the returned rule undoes
+    /// this one.
+    ///
+    /// Cells this rule leaves unchanged keep their find pattern's symbol or
+    /// wildcard. Cells it overwrites after matching a wildcard can't be
+    /// restored, so the inverse leaves them unchanged. Conditions describe
+    /// the grid before this rule applies, so the inverse has none.
+    pub fn inverse(&self) -> Self {
+        let cells = self.find.grid.iter().zip(self.replace.grid.iter());
+        Self {
+            find: Pattern {
+                grid: cells
+                    .clone()
+                    .map(|(find, replace)| replace.or(*find))
+                    .collect(),
+                ..self.replace.clone()
+            },
+            replace: Pattern {
+                grid: cells.map(|(find, replace)| replace.and(*find)).collect(),
+                ..self.find.clone()
+            },
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Combines this rule with `then`, applied to the same cells right
+    /// after, into a single rule.
+    ///
+    /// Returns `None` if the rules have different sizes, if `then` can
+    /// never match where this rule has just been applied, or if `then` has
+    /// conditions, which can't be checked before this rule applies.
+    pub fn compose(&self, then: &Rule) -> Option<Self> {
+        let size = |rule: &Rule| (rule.find.width, rule.find.height, rule.find.layers);
+        if size(self) != size(then) || !then.conditions.is_empty() {
+            return None;
+        }
+
+        let mut find = Vec::with_capacity(self.find.grid.len());
+        let mut replace = Vec::with_capacity(self.replace.grid.len());
+        for idx in 0..self.find.grid.len() {
+            let (first, second) = (self.replace.grid[idx], then.find.grid[idx]);
+            let required = match (self.find.grid[idx], first, second) {
+                (_, Some(written), Some(needed)) if written != needed => return None,
+                (Some(have), None, Some(needed)) if have != needed => return None,
+                (have, None, needed) => have.or(needed),
+                (have, Some(_), _) => have,
+            };
+
+            find.push(required);
+            replace.push(then.replace.grid[idx].or(first));
+        }
+
+        Some(Self {
+            find: Pattern {
+                grid: find,
+                ..self.find.clone()
+            },
+            replace: Pattern {
+                grid: replace,
+                ..self.replace.clone()
+            },
+            conditions: self.conditions.clone(),
+        })
+    }
+
     pub fn make_rotations(self) -> Vec<Self> {
         let cw = self.rotate_cw();
         let turn = cw.rotate_cw();
@@ -215,6 +972,21 @@ impl Rule {
         Self {
             find: Pattern::from_string(find),
             replace: Pattern::from_string(replace),
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Parses a rule and expands it into its symmetric variants.
+    pub fn with_symmetry(find: &str, replace: &str, symmetry: Symmetry) -> Vec<Self> {
+        symmetry.expand(Self::from_strings(find, replace))
+    }
+
+    /// Moves a rule up to start at `layer` of a multi-layer grid.
+    pub fn to_layer(&self, layer: usize, layers: usize) -> Self {
+        Self {
+            find: self.find.to_layer(layer, layers),
+            replace: self.replace.to_layer(layer, layers),
+            conditions: self.conditions.clone(),
         }
     }
 }
@@ -223,45 +995,156 @@ pub type Grid = GenericGrid<Symbol>;
 
 impl Display for Grid {
     fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
-        let mut string = String::new();
-
-        for row in self.grid.chunks(self.width) {
-            for symbol in row.iter() {
-                let character = match symbol {
-                    Symbol::Black => 'B',
-                    Symbol::White => 'W',
-                    Symbol::Red => 'R',
-                    Symbol::Green => 'G',
-                    Symbol::Blue => 'U',
-                    Symbol::Emerald => 'E',
-                    Symbol::Purple => 'P',
-                };
+        let text = self.to_text(&charset::Charset::default());
+        write!(fmt, "{}", text.expect("every symbol has a letter"))
+    }
+}
+
+/// Why [Grid::from_display_str] rejected its input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseGridError {
+    Empty,
+    UnknownSymbol(char),
+    InconsistentRows,
+    InconsistentLayers,
+}
+
+impl Display for ParseGridError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ParseGridError::*;
+        match self {
+            Empty => write!(fmt, "grid has no cells"),
+            UnknownSymbol(c) => write!(fmt, "unrecognized symbol '{}'", c),
+            InconsistentRows => write!(fmt, "grid has inconsistent row lengths"),
+            InconsistentLayers => write!(fmt, "grid has inconsistent layer heights"),
+        }
+    }
+}
+
+impl std::error::Error for ParseGridError {}
+
+impl Grid {
+    /// Parses the text written by the [Display] implementation: one line of
+    /// symbol characters per row, with layers separated by blank lines.
+    /// Leading and trailing whitespace is ignored, as is indentation, so
+    /// grids can be written as indented string literals.
+    pub fn from_display_str(string: &str) -> Result<Self, ParseGridError> {
+        Self::parse_text(string, Symbol::try_from_char)
+    }
+
+    /// Parses text grids, reading each cell's character with `symbol`.
+    fn parse_text(
+        string: &str,
+        symbol: impl Fn(char) -> Option<Symbol>,
+    ) -> Result<Self, ParseGridError> {
+        let mut layers: Vec<Vec<&str>> = vec![Vec::new()];
+        for line in string.trim().lines().map(str::trim) {
+            match line.is_empty() {
+                true => layers.push(Vec::new()),
+                false => layers.last_mut().unwrap().push(line),
+            }
+        }
+
+        let height = layers[0].len();
+        let width = layers[0].first().map_or(0, |row| row.chars().count());
+        if width == 0 {
+            return Err(ParseGridError::Empty);
+        }
+
+        let mut grid = Vec::with_capacity(width * height * layers.len());
+        for layer in layers.iter() {
+            if layer.len() != height {
+                return Err(ParseGridError::InconsistentLayers);
+            }
 
-                string.push(character);
+            for row in layer.iter() {
+                if row.chars().count() != width {
+                    return Err(ParseGridError::InconsistentRows);
+                }
+
+                for c in row.chars() {
+                    grid.push(symbol(c).ok_or(ParseGridError::UnknownSymbol(c))?);
+                }
             }
-            string.push('\n');
         }
 
-        write!(fmt, "{}", string)
+        Ok(Self {
+            width,
+            height,
+            layers: layers.len(),
+            grid,
+            periodic: false,
+        })
+    }
+}
+
+/// How many positions a pattern `size` cells long can start at along a
+/// grid axis `length` cells long without running off its end. Matching and
+/// [Extent::fits](rule_set::Extent::fits) both go by this, so a rule that
+/// fits is always scanned at every position it could match.
+pub(crate) fn placements(length: usize, size: usize) -> usize {
+    (length + 1).saturating_sub(size)
+}
+
+/// Converts an image's size in pixels to a GIF's, failing if it is more than
+/// 65535 pixels across either way, the most a GIF can hold.
+pub(crate) fn gif_size(width: usize, height: usize) -> std::io::Result<(u16, u16)> {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("a {}x{} image is too large for a GIF", width, height),
+        )),
     }
 }
 
 impl Grid {
+    /// Panics unless `pattern` fits on the grid at `at`. On periodic grids
+    /// patterns may run over the edges, so only their size is checked.
     pub fn assert_pattern_fit(&self, pattern: &Pattern, at: Point) {
-        if pattern.width + at.x > self.width || pattern.width + at.y > self.height {
+        let fits = match self.periodic {
+            true => {
+                pattern.width <= self.width
+                    && pattern.height <= self.height
+                    && at.x < self.width
+                    && at.y < self.height
+            }
+            false => pattern.width + at.x <= self.width && pattern.height + at.y <= self.height,
+        };
+
+        if !fits {
             panic!("pattern is out-of-bounds");
         }
+
+        if pattern.layers > self.layers {
+            panic!("pattern has more layers than the grid");
+        }
     }
 
+    /// Tests a pattern against the grid. Multi-layer patterns are always
+    /// matched starting from the bottom layer.
     pub fn test_match(&self, pattern: &Pattern, at: Point) -> bool {
         self.assert_pattern_fit(pattern, at);
+        self.test_match_unchecked(pattern, at)
+    }
+
+    /// [Grid::test_match] without the bounds check, for callers that have
+    /// already made sure the pattern fits.
+    fn test_match_unchecked(&self, pattern: &Pattern, at: Point) -> bool {
+        if at.x + pattern.width > self.width || at.y + pattern.height > self.height {
+            return self.test_match_wrapping(pattern, at);
+        }
 
-        for x in 0..pattern.width {
+        let mut cells = pattern.grid.iter();
+        for layer in 0..pattern.layers {
             for y in 0..pattern.height {
-                let test_pt = Point { x, y };
-                if let Some(expected) = pattern[test_pt] {
-                    let grid_pt = test_pt + at;
-                    if expected != self[grid_pt] {
+                let row = (layer * self.height + at.y + y) * self.width + at.x;
+                for actual in &self.grid[row..(row + pattern.width)] {
+                    if cells
+                        .next()
+                        .unwrap()
+                        .is_some_and(|expected| expected != *actual)
+                    {
                         return false;
                     }
                 }
@@ -271,30 +1154,94 @@ impl Grid {
         true
     }
 
+    /// [Grid::test_match_unchecked] for patterns that run over the edges of
+    /// a periodic grid, one cell at a time.
+    fn test_match_wrapping(&self, pattern: &Pattern, at: Point) -> bool {
+        (0..pattern.layers).all(|layer| {
+            Rect::new(Point::ZERO, pattern.width, pattern.height)
+                .points()
+                .all(|pt| {
+                    pattern[(pt, layer)].is_none_or(|expected| expected == self[(pt + at, layer)])
+                })
+        })
+    }
+
     pub fn apply_pattern(&mut self, pattern: &Pattern, at: Point) {
         self.assert_pattern_fit(pattern, at);
-        for x in 0..pattern.width {
-            for y in 0..pattern.height {
-                let test_pt = Point { x, y };
-                if let Some(new_symbol) = pattern[test_pt] {
-                    let grid_pt = test_pt + at;
-                    self[grid_pt] = new_symbol;
+        for layer in 0..pattern.layers {
+            for x in 0..pattern.width {
+                for y in 0..pattern.height {
+                    let test_pt = Point { x, y };
+                    if let Some(new_symbol) = pattern[(test_pt, layer)] {
+                        let grid_pt = test_pt + at;
+                        self[(grid_pt, layer)] = new_symbol;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Computes the number of orthogonal steps from every cell to the nearest
+    /// cell holding one of `from`, moving only through cells holding one of
+    /// `through`. Unreachable cells are `None`.
+    ///
+    /// Only the bottom layer is considered.
+    pub fn distance_field(&self, from: &[Symbol], through: &[Symbol]) -> GenericGrid<Option<u32>> {
+        let mut distances = GenericGrid::new(self.width, self.height);
+        distances.periodic = self.periodic;
+        let mut queue = VecDeque::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let at = Point { x, y };
+                if from.contains(&self[at]) {
+                    distances[at] = Some(0);
+                    queue.push_back(at);
                 }
             }
         }
+
+        while let Some(at) = queue.pop_front() {
+            let next = distances[at].unwrap() + 1;
+            for neighbor in self.neighbors(at) {
+                if distances[neighbor].is_none() && through.contains(&self[neighbor]) {
+                    distances[neighbor] = Some(next);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// The symbol seen from above a cell: the one on the topmost layer that
+    /// is not [Symbol::Black], which is treated as transparent.
+    pub fn visible(&self, at: Point) -> Symbol {
+        (0..self.layers)
+            .rev()
+            .map(|layer| self[(at, layer)])
+            .find(|symbol| *symbol != Symbol::Black)
+            .unwrap_or_default()
     }
 
+    /// Finds every position `pattern` matches at. On periodic grids every
+    /// cell is tried, including those where the pattern wraps around.
     pub fn find_matches(&self, pattern: &Pattern) -> Vec<Point> {
         self.assert_pattern_fit(pattern, Point::ZERO);
 
         let mut found = Vec::new();
-        let free_width = self.width - pattern.width - 1;
-        let free_height = self.height - pattern.height - 1;
+        let (free_width, free_height) = match self.periodic {
+            true => (self.width, self.height),
+            false => (
+                placements(self.width, pattern.width),
+                placements(self.height, pattern.height),
+            ),
+        };
 
         for x in 0..free_width {
             for y in 0..free_height {
                 let test_pt = Point { x, y };
-                if self.test_match(pattern, test_pt) {
+                if self.test_match_unchecked(pattern, test_pt) {
                     found.push(test_pt);
                 }
             }
@@ -303,24 +1250,147 @@ impl Grid {
         found
     }
 
+    /// Renders the grid as a GIF frame of palette indices, as drawn by
+    /// [Grid::render_indexed].
+    ///
+    /// Panics if the frame is too large for a GIF.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self),
+            fields(width = self.width, height = self.height)
+        )
+    )]
     pub fn render_gif_frame(&self, tile_size: u16) -> gif::Frame<'static> {
-        let width = self.width as u16 * tile_size;
-        let height = self.height as u16 * tile_size;
-        let mut pixels = vec![0; width as usize * height as usize];
+        let (width, height) = gif_size(
+            self.width.saturating_mul(tile_size as usize),
+            self.height.saturating_mul(tile_size as usize),
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+        let pixels = self.render_indexed(tile_size);
+        gif::Frame::from_indexed_pixels(width, height, &pixels, None)
+    }
+
+    /// Renders each cell as a square tile of palette indices. Layers are
+    /// composited using [Grid::visible].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self),
+            fields(width = self.width, height = self.height)
+        )
+    )]
+    pub fn render_indexed(&self, tile_size: u16) -> Vec<u8> {
+        let width = self.width * tile_size as usize;
+        let height = self.height * tile_size as usize;
+        let mut pixels = vec![0; width * height];
         let mut cursor = 0;
+        let mut row = vec![0; self.width];
         for y in 0..self.height {
+            for (x, index) in row.iter_mut().enumerate() {
+                *index = self.visible(Point { x, y }).palette_index();
+            }
+
             for _ in 0..tile_size {
-                for x in 0..self.width {
-                    let test_pt = Point { x, y };
-                    let index = self[test_pt].palette_index();
+                for index in row.iter() {
                     let dst_range = cursor..(cursor + tile_size as usize);
-                    pixels[dst_range].fill(index);
+                    pixels[dst_range].fill(*index);
                     cursor += tile_size as usize;
                 }
             }
         }
 
-        gif::Frame::from_indexed_pixels(width, height, &pixels, None)
+        pixels
+    }
+
+    /// Renders each cell as a square tile of RGBA pixels.
+    pub fn render_rgba(&self, tile_size: u16) -> Vec<u8> {
+        self.render_rgba_with(tile_size, palette::Palette::Default)
+    }
+
+    /// Like [Grid::render_rgba], but in the colors of `palette`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self),
+            fields(width = self.width, height = self.height)
+        )
+    )]
+    pub fn render_rgba_with(&self, tile_size: u16, palette: palette::Palette) -> Vec<u8> {
+        palette.to_rgba(&self.render_indexed(tile_size))
+    }
+
+    /// Copies the cells inside `rect` into a pattern that matches exactly
+    /// them, across every layer, for use as a rule's find or replace
+    /// pattern or as a stamp.
+    ///
+    /// Panics if `rect` doesn't fit inside this grid.
+    pub fn extract_pattern(&self, rect: Rect) -> Pattern {
+        let cropped = self.crop(rect);
+        Pattern {
+            width: cropped.width,
+            height: cropped.height,
+            layers: cropped.layers,
+            grid: cropped.grid.into_iter().map(Some).collect(),
+            periodic: false,
+        }
+    }
+
+    /// Counts the cells holding `symbol`, across every layer.
+    pub fn count(&self, symbol: Symbol) -> usize {
+        self.grid.iter().filter(|cell| **cell == symbol).count()
+    }
+
+    /// Lists every cell whose visible symbol differs between this grid and
+    /// `other`, as (position, this symbol, other symbol) triples in row
+    /// order.
+    ///
+    /// Panics if the grids have different sizes.
+    pub fn diff(&self, other: &Grid) -> Vec<(Point, Symbol, Symbol)> {
+        if (self.width, self.height) != (other.width, other.height) {
+            panic!(
+                "cannot diff a {}x{} grid against a {}x{} grid",
+                self.width, self.height, other.width, other.height
+            );
+        }
+
+        self.enumerate_points()
+            .map(|at| (at, self.visible(at), other.visible(at)))
+            .filter(|(_, before, after)| before != after)
+            .collect()
+    }
+
+    /// Renders `other` as RGBA tiles with the cells that differ from this
+    /// grid at full brightness and the unchanged cells faded towards grey.
+    pub fn render_diff(&self, other: &Grid, tile_size: u16) -> Vec<u8> {
+        let mut changed = GenericGrid::new(self.width, self.height);
+        for (at, _, _) in self.diff(other) {
+            changed[at] = true;
+        }
+
+        let tile_size = tile_size as usize;
+        let width = self.width * tile_size;
+        let pixels = other.render_rgba(tile_size as u16);
+        pixels
+            .chunks(4)
+            .enumerate()
+            .flat_map(|(idx, pixel)| {
+                let at = Point {
+                    x: idx % width / tile_size,
+                    y: idx / width / tile_size,
+                };
+
+                let fade = |c: u8| match changed[at] {
+                    true => c,
+                    false => c / 4 + 0x30,
+                };
+
+                [fade(pixel[0]), fade(pixel[1]), fade(pixel[2]), 0xff]
+            })
+            .collect()
     }
 
     #[deprecated]
@@ -350,10 +1420,12 @@ pub struct Step {
 mod tests {
     use super::*;
 
-    use rand::{rngs::SmallRng, SeedableRng};
+    use rand::SeedableRng;
+    use rng::Pcg32;
+    use rule_set::RuleSet;
 
-    pub fn make_rng() -> SmallRng {
-        SmallRng::seed_from_u64(2)
+    pub fn make_rng() -> Pcg32 {
+        Pcg32::seed_from_u64(2)
     }
 
     #[test]
@@ -367,10 +1439,364 @@ mod tests {
     }
 
     #[test]
+    fn mirror_transpose() {
+        let grid = make_grid("RBB/WGB");
+        assert_eq!(grid.mirror_x(), make_grid("BBR/BGW"));
+        assert_eq!(grid.mirror_y(), make_grid("WGB/RBB"));
+        assert_eq!(grid.transpose(), make_grid("RW/BG/BB"));
+        assert_eq!(grid.rotate_cw(), make_grid("WR/GB/BB"));
+        assert_eq!(grid.rotate_cw(), grid.transpose().mirror_x());
+
+        let rule = Rule::from_strings("RG/BB", "WW/WW");
+        assert_eq!(Symmetry::Mirrors.expand(rule.clone()).len(), 4);
+        let square = RuleSet::with_symmetry([rule], Symmetry::Square);
+        assert_eq!(square.len(), 8);
+    }
+
+    #[test]
+    fn inverse_compose() {
+        let grow = Rule::from_strings("WB*", "WW*");
+        assert_eq!(grow.inverse(), Rule::from_strings("WW*", "WB*"));
+
+        let paint = Rule::from_strings("***", "R**");
+        assert_eq!(paint.inverse(), Rule::from_strings("R**", "***"));
+
+        let spread = Rule::from_strings("*WB", "**W");
+        assert_eq!(
+            grow.compose(&spread),
+            Some(Rule::from_strings("WBB", "WWW"))
+        );
+        assert_eq!(grow.compose(&grow), None);
+        assert_eq!(grow.compose(&Rule::from_strings("W", "B")), None);
+
+        let rules = RuleSet::concat([&vec![grow.clone()].into(), &vec![spread, grow].into()]);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules.inverse()[0], Rule::from_strings("WW*", "WB*"));
+    }
+
+    #[test]
+    fn offsets() {
+        let at = Point { x: 0, y: 2 };
+        assert_eq!(at.checked_add(Offset::LEFT), None);
+        assert_eq!(at.checked_add(Offset::UP), Some(Point { x: 0, y: 1 }));
+        assert_eq!(at.saturating_add(Offset { dx: -3, dy: -3 }), Point::ZERO);
+        assert_eq!(Point { x: 3, y: 1 } - at, Offset { dx: 3, dy: -1 });
+
+        let grid = Grid::new(3, 3);
+        assert_eq!(grid.offset(at, Offset::DOWN), None);
+        assert_eq!(grid.offset(at, Offset::RIGHT), Some(Point { x: 1, y: 2 }));
+        assert_eq!(grid.neighbors(at).count(), 2);
+        assert_eq!(grid.neighbors(Point { x: 1, y: 1 }).count(), 4);
+    }
+
+    #[test]
+    fn crop_resize_paste() {
+        let grid = make_grid("RBBB/BWWB/BBBG");
+        let cropped = grid.crop(Rect::new(Point { x: 1, y: 1 }, 3, 2));
+        assert_eq!(cropped, make_grid("WWB/BBG"));
+
+        let resized = cropped.resize(4, 1, Symbol::Blue);
+        assert_eq!(resized, make_grid("WWBU"));
+
+        let mut pasted = Grid::new(3, 3);
+        pasted.paste(&cropped, Offset { dx: -1, dy: 1 });
+        assert_eq!(pasted, make_grid("BBB/WBB/BGB"));
+
+        let mut periodic = grid.clone();
+        periodic.periodic = true;
+        let shrunk = periodic.resize(2, 2, Symbol::Black);
+        assert_eq!(shrunk.grid, make_grid("RB/BW").grid);
+        assert!(shrunk.periodic);
+
+        let mut pasted = Grid::new(3, 3);
+        pasted.periodic = true;
+        pasted.paste(&cropped, Offset { dx: -1, dy: 1 });
+        assert_eq!(pasted.grid, make_grid("BBB/WBB/BGB").grid);
+    }
+
+    #[test]
+    fn extract_pattern() {
+        let grid = make_grid("RBBB/BWWB/BBBG");
+        let rect = Rect::new(Point { x: 1, y: 1 }, 3, 2);
+        let pattern = grid.extract_pattern(rect);
+        assert_eq!(pattern, Pattern::from_string("WWB/BBG"));
+        assert_eq!(grid.find_matches_in(&pattern, rect), [rect.origin()]);
+
+        let mut stamped = Grid::new(4, 3);
+        stamped.apply_pattern(&pattern, Point::ZERO);
+        assert_eq!(stamped, make_grid("WWBB/BBGB/BBBB"));
+    }
+
+    fn make_grid(rows: &str) -> Grid {
+        let pattern = Pattern::from_string(rows);
+        Grid {
+            width: pattern.width,
+            height: pattern.height,
+            layers: 1,
+            grid: pattern.grid.into_iter().flatten().collect(),
+            periodic: false,
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let before = make_grid("RBB/BWB");
+        let after = make_grid("RBU/BBB");
+        assert_eq!(
+            before.diff(&after),
+            [
+                (Point { x: 2, y: 0 }, Symbol::Black, Symbol::Blue),
+                (Point { x: 1, y: 1 }, Symbol::White, Symbol::Black),
+            ]
+        );
+
+        assert!(before.diff(&before).is_empty());
+
+        let pixels = before.render_diff(&after, 2);
+        assert_eq!(pixels.len(), 3 * 2 * 2 * 2 * 4);
+        assert_eq!(pixels[2 * 4..][..4], [0x30, 0x30, 0x30, 0xff]);
+        assert_eq!(pixels[(2 * 6 + 2) * 4..][..4], [0, 0, 0, 0xff]);
+    }
+
+    #[test]
+    fn parse_display() {
+        let mut grid = Grid::with_layers(5, 3, 2);
+        grid[Point { x: 1, y: 2 }] = Symbol::Emerald;
+        grid[(Point { x: 4, y: 0 }, 1)] = Symbol::Purple;
+        assert_eq!(Grid::from_display_str(&grid.to_string()), Ok(grid));
+
+        let indented = Grid::from_display_str(
+            "
+            RBB
+            BUW
+            ",
+        );
+        assert_eq!(indented, Ok(make_grid("RBB/BUW")));
+
+        use ParseGridError::*;
+        assert_eq!(Grid::from_display_str(" \n "), Err(Empty));
+        assert_eq!(Grid::from_display_str("BB\nB"), Err(InconsistentRows));
+        assert_eq!(
+            Grid::from_display_str("BB\n\nBB\nBB"),
+            Err(InconsistentLayers)
+        );
+        assert_eq!(Grid::from_display_str("B*"), Err(UnknownSymbol('*')));
+    }
+
+    #[test]
+    fn extended_alphabet() {
+        assert_eq!(Symbol::PALETTE.len(), Symbol::ALL.len() * 3);
+        for (index, symbol) in Symbol::ALL.into_iter().enumerate() {
+            assert_eq!(symbol.palette_index() as usize, index);
+            assert_eq!(Symbol::from_palette_index(index as u8), Some(symbol));
+            assert_eq!(Symbol::try_from_char(symbol.to_char()), Some(symbol));
+        }
+
+        let grid = make_grid("OYK/bgf");
+        assert_eq!(grid[Point { x: 1, y: 0 }], Symbol::Yellow);
+        assert_eq!(grid[Point { x: 2, y: 1 }], Symbol::Peach);
+        assert_eq!(Grid::from_display_str(&grid.to_string()), Ok(grid));
+        assert_eq!(Symbol::try_from_char('X'), None);
+        assert_eq!(Symbol::from_palette_index(32), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "a 70000x10 image is too large for a GIF")]
+    fn gif_frame_too_large() {
+        Grid::new(7000, 1).render_gif_frame(10);
+    }
+
+    #[test]
+    #[should_panic(expected = "fewer than 2^32 cells")]
+    fn match_past_u32() {
+        Match::new(0, Point { x: 0, y: 1 << 16 }, 1 << 16);
+    }
+
+    #[test]
+    #[should_panic]
+    fn crop_out_of_bounds() {
+        Grid::new(4, 4).crop(Rect::new(Point { x: 2, y: 2 }, 3, 1));
+    }
+
+    #[test]
+    fn distance_field() {
+        let grid = Grid {
+            width: 4,
+            height: 3,
+            layers: 1,
+            grid: Symbol::from_string("RBBB/WWWB/BBBB")
+                .into_iter()
+                .flatten()
+                .collect(),
+            periodic: false,
+        };
+
+        let field = grid.distance_field(&[Symbol::Red], &[Symbol::Black]);
+        let distances: Vec<_> = field
+            .grid
+            .iter()
+            .map(|d| d.map_or(-1, |d| d as i32))
+            .collect();
+        assert_eq!(distances, [0, 1, 2, 3, -1, -1, -1, 4, 8, 7, 6, 5]);
+    }
+
+    #[test]
+    fn matches_reach_edges() {
+        let grid = Grid::from_display_str("WBB\nBBW").unwrap();
+        let pair = Pattern::from_string("BB");
+        assert_eq!(
+            grid.find_matches(&pair),
+            [Point { x: 0, y: 1 }, Point { x: 1, y: 0 }]
+        );
+
+        // A pattern as large as the grid matches at its origin only.
+        let whole = Pattern::from_string("WBB/BBW");
+        assert_eq!(grid.find_matches(&whole), [Point::ZERO]);
+        assert_eq!(
+            packed::PackedGrid::new(&grid).find_matches(&packed::PackedPattern::new(&whole)),
+            [Point::ZERO]
+        );
+    }
+
+    #[test]
+    fn periodic() {
+        let mut grid = Grid::from_display_str(
+            "
+            RBBB
+            WWWB
+            BBBB
+            ",
+        )
+        .unwrap();
+
+        let wrapping = Pattern::from_string("BR");
+        assert!(grid.find_matches(&wrapping).is_empty());
+        assert_eq!(grid.neighbors(Point::ZERO).count(), 2);
+
+        grid.periodic = true;
+        assert_eq!(grid.find_matches(&wrapping), [Point { x: 3, y: 0 }]);
+        assert_eq!(grid.neighbors(Point::ZERO).count(), 4);
+        assert_eq!(
+            grid.offset(Point::ZERO, Offset { dx: -1, dy: -1 }),
+            Some(Point { x: 3, y: 2 })
+        );
+
+        let field = grid.distance_field(&[Symbol::Red], &[Symbol::Black]);
+        let distances: Vec<_> = field
+            .grid
+            .iter()
+            .map(|d| d.map_or(-1, |d| d as i32))
+            .collect();
+        assert_eq!(distances, [0, 1, 2, 1, -1, -1, -1, 2, 1, 2, 3, 2]);
+
+        let tiled = grid.tile(2, 1);
+        assert_eq!((tiled.width, tiled.height), (8, 3));
+        assert_eq!(tiled[Point { x: 4, y: 0 }], Symbol::Red);
+
+        grid.apply_pattern(&Pattern::from_string("GG"), Point { x: 3, y: 0 });
+        assert_eq!(grid[Point::ZERO], Symbol::Green);
+        assert_eq!(grid[Point { x: 3, y: 0 }], Symbol::Green);
+    }
+
+    #[test]
+    fn iterate_cells() {
+        let mut grid = make_grid("RB/BW");
+        let cells: Vec<_> = grid.iter().map(|(at, cell)| (at, *cell)).collect();
+        assert_eq!(cells[1], (Point { x: 1, y: 0 }, Symbol::Black));
+        assert_eq!(cells[3], (Point { x: 1, y: 1 }, Symbol::White));
+        assert_eq!(grid.enumerate_points().nth(2), Some(Point { x: 0, y: 1 }));
+
+        for (at, cell) in grid.iter_mut() {
+            if at.x == at.y {
+                *cell = Symbol::Green;
+            }
+        }
+
+        assert_eq!(grid, make_grid("GB/BG"));
+
+        let layered = Pattern::from_string("RR B*");
+        assert_eq!(layered.iter().count(), 2);
+    }
+
+    #[test]
+    fn pattern_rows() {
+        let pattern = Pattern::from_string("BU/UB/**");
+        assert_eq!((pattern.width, pattern.height), (2, 3));
+        assert_eq!(pattern[Point { x: 1, y: 0 }], Some(Symbol::Blue));
+        assert_eq!(pattern[Point { x: 0, y: 1 }], Some(Symbol::Blue));
+        assert_eq!(pattern[Point { x: 1, y: 2 }], None);
+        assert_eq!(Symbol::from_string("B/W").len(), 2);
+
+        let rotated = Pattern::from_string("RB/BB").rotate_cw();
+        assert_eq!(rotated, Pattern::from_string("BR/BB"));
+    }
+
+    #[test]
+    fn pattern_layers() {
+        let pattern = Pattern::from_string("GG/GG B*/*R");
+        assert_eq!((pattern.width, pattern.height, pattern.layers), (2, 2, 2));
+        assert_eq!(pattern[Point { x: 1, y: 1 }], Some(Symbol::Green));
+        assert_eq!(pattern[(Point { x: 1, y: 1 }, 1)], Some(Symbol::Red));
+        assert_eq!(pattern[(Point { x: 1, y: 0 }, 1)], None);
+
+        let moved = Pattern::from_string("R").to_layer(1, 3);
+        assert_eq!(moved, Pattern::from_string("* R *"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn pattern_inconsistent_layers() {
+        Pattern::from_string("BB/BB BB");
+    }
+
+    #[test]
+    #[should_panic]
+    fn pattern_inconsistent_rows() {
+        Pattern::from_string("BB/B");
+    }
+
+    #[test]
+    fn parse_pattern() {
+        use ParsePatternError::*;
+        assert_eq!(Pattern::parse("B*/*W"), Ok(Pattern::from_string("B*/*W")));
+        assert_eq!(Pattern::parse(""), Err(EmptyRow));
+        assert_eq!(Pattern::parse("B/"), Err(EmptyRow));
+        assert_eq!(Pattern::parse("BB/B"), Err(InconsistentRows));
+        assert_eq!(Pattern::parse("BB/BB BB"), Err(InconsistentLayers));
+        assert_eq!(Pattern::parse("BQ"), Err(UnknownSymbol('Q')));
+    }
+
+    #[test]
+    fn layers() {
+        let mut grid = Grid::with_layers(3, 3, 2);
+        grid[Point { x: 1, y: 1 }] = Symbol::Green;
+        grid[(Point { x: 1, y: 1 }, 1)] = Symbol::Red;
+        grid[(Point { x: 2, y: 2 }, 1)] = Symbol::White;
+        assert_eq!(grid.to_string(), "BBB\nBGB\nBBB\n\nBBB\nBRB\nBBW\n");
+        assert_eq!(grid.visible(Point { x: 1, y: 1 }), Symbol::Red);
+        assert_eq!(grid.visible(Point { x: 2, y: 2 }), Symbol::White);
+
+        let rule = Rule::from_strings("R", "W").to_layer(1, 2);
+        assert_eq!(rule.find.grid, [None, Some(Symbol::Red)]);
+        assert_eq!(grid.rotate_cw().rotate_cw().rotate_cw().rotate_cw(), grid);
+
+        let mut below = Pattern::with_layers(1, 1, 2);
+        below[(Point::ZERO, 0)] = Some(Symbol::Green);
+        below[(Point::ZERO, 1)] = Some(Symbol::Red);
+        assert!(grid.test_match(&below, Point { x: 1, y: 1 }));
+        assert!(!grid.test_match(&rule.find, Point { x: 2, y: 2 }));
+
+        grid.apply_pattern(&rule.replace, Point { x: 1, y: 1 });
+        assert_eq!(grid[Point { x: 1, y: 1 }], Symbol::Green);
+        assert_eq!(grid[(Point { x: 1, y: 1 }, 1)], Symbol::White);
+    }
+
+    #[test]
+    #[allow(deprecated)]
     fn maze_backtracker() {
         let mut rng = make_rng();
         let mut grid = Grid::new(16, 16);
-        grid.grid[50] = Symbol::Red;
+        seed::Seed::At(Point { x: 2, y: 3 }, Symbol::Red).apply(&mut grid);
 
         let rule_one = Rule::from_strings("RBB", "GGR");
         let step_one = Step {
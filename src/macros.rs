@@ -0,0 +1,303 @@
+//! Macros for writing node trees in Rust.
+//!
+//! Building a tree from node structs takes a struct literal per node, most
+//! of it defaults. [markov!], [seq!], [one!], [all!], and [prl!] build the
+//! same [AnyNode]s from just the parts that matter:
+//!
+//! ```ignore
+//! use crabby_markov::{all, one, seq, Symmetry};
+//!
+//! let model = seq![
+//!     one!("B" => "W"; steps = 1),
+//!     one!("RB" => "RR", "WB" => "WW"),
+//!     all!("RW" => "UU"; symmetry = Symmetry::Anisotropic),
+//! ];
+//! ```
+//!
+//! Rules are written as `FIND => REPLACE` strings, in the format of
+//! [Rule::from_strings], and are expanded with the [Symmetry::default]
+//! unless given a `symmetry` option. Options follow the rules after a `;`:
+//!
+//! - `steps = N` limits the node to `N` steps.
+//! - `symmetry = SYMMETRY` expands the rules with another [Symmetry].
+//! - `conflict = CONFLICT` sets an `all` node's [Conflict]. Other nodes
+//!   ignore it.
+//!
+//! Nodes can be named with [AnyNode::with_name].
+
+use super::*;
+use node::{AllNode, AnyNode, Conflict, OneNode, PrlNode};
+use rule_set::RuleSet;
+
+/// Builds a [MarkovNode](crate::node::MarkovNode) from its children. See
+/// the [module](crate::macros) docs.
+#[macro_export]
+macro_rules! markov {
+    ($($child:expr),* $(,)?) => {
+        $crate::node::AnyNode::Markov($crate::node::MarkovNode {
+            name: None,
+            children: vec![$($child),*],
+        })
+    };
+}
+
+/// Builds a [SequenceNode](crate::node::SequenceNode) from its children.
+/// See the [module](crate::macros) docs.
+#[macro_export]
+macro_rules! seq {
+    ($($child:expr),* $(,)?) => {
+        $crate::node::AnyNode::Sequence($crate::node::SequenceNode {
+            name: None,
+            children: vec![$($child),*],
+        })
+    };
+}
+
+/// Builds a [OneNode](crate::node::OneNode) from its rules and options.
+/// See the [module](crate::macros) docs.
+#[macro_export]
+macro_rules! one {
+    ($($find:expr => $replace:expr),+ $(,)? $(; $($key:ident = $value:expr),* $(,)?)?) => {{
+        let options = $crate::macros::RuleOptions::default();
+        $($(let options = options.$key($value);)*)?
+        options.one(&[$(($find, $replace)),+])
+    }};
+}
+
+/// Builds an [AllNode](crate::node::AllNode) from its rules and options.
+/// See the [module](crate::macros) docs.
+#[macro_export]
+macro_rules! all {
+    ($($find:expr => $replace:expr),+ $(,)? $(; $($key:ident = $value:expr),* $(,)?)?) => {{
+        let options = $crate::macros::RuleOptions::default();
+        $($(let options = options.$key($value);)*)?
+        options.all(&[$(($find, $replace)),+])
+    }};
+}
+
+/// Builds a [PrlNode](crate::node::PrlNode) from its rules and options.
+/// See the [module](crate::macros) docs.
+#[macro_export]
+macro_rules! prl {
+    ($($find:expr => $replace:expr),+ $(,)? $(; $($key:ident = $value:expr),* $(,)?)?) => {{
+        let options = $crate::macros::RuleOptions::default();
+        $($(let options = options.$key($value);)*)?
+        options.prl(&[$(($find, $replace)),+])
+    }};
+}
+
+/// The options given to a rule node macro, each set by the method of the
+/// same name.
+#[doc(hidden)]
+#[derive(Clone, Debug, Default)]
+pub struct RuleOptions {
+    steps: Option<usize>,
+    symmetry: Symmetry,
+    conflict: Conflict,
+}
+
+impl RuleOptions {
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = Some(steps);
+        self
+    }
+
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    pub fn conflict(mut self, conflict: Conflict) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    fn rules(&self, rules: &[(&str, &str)]) -> RuleSet {
+        let rules = rules
+            .iter()
+            .map(|(find, replace)| Rule::from_strings(find, replace));
+        RuleSet::with_symmetry(rules, self.symmetry)
+    }
+
+    pub fn one(self, rules: &[(&str, &str)]) -> AnyNode {
+        AnyNode::One(OneNode {
+            name: None,
+            rules: self.rules(rules),
+            steps: self.steps,
+            sampler: Default::default(),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        })
+    }
+
+    pub fn all(self, rules: &[(&str, &str)]) -> AnyNode {
+        AnyNode::All(AllNode {
+            name: None,
+            rules: self.rules(rules),
+            steps: self.steps,
+            conflict: self.conflict,
+            fields: Vec::new(),
+            observations: Vec::new(),
+        })
+    }
+
+    pub fn prl(self, rules: &[(&str, &str)]) -> AnyNode {
+        AnyNode::Prl(PrlNode {
+            name: None,
+            rules: self.rules(rules),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::{MarkovNode, SequenceNode};
+    use runner::Runner;
+
+    #[test]
+    fn build_with_macros() {
+        let model = seq![
+            one!("B" => "W"; steps = 1),
+            markov![
+                all!("RB" => "RR", "WB" => "WW"; conflict = Conflict::Priority, steps = 2),
+                prl!("W" => "B"; symmetry = Symmetry::Anisotropic,),
+            ],
+        ];
+
+        let expected = AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: vec![
+                AnyNode::One(OneNode {
+                    name: None,
+                    rules: vec![Rule::from_strings("B", "W")].into(),
+                    steps: Some(1),
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
+                }),
+                AnyNode::Markov(MarkovNode {
+                    name: None,
+                    children: vec![
+                        AnyNode::All(AllNode {
+                            name: None,
+                            rules: [
+                                Rule::from_strings("RB", "RR").make_rotations(),
+                                Rule::from_strings("WB", "WW").make_rotations(),
+                            ]
+                            .concat()
+                            .into(),
+                            steps: Some(2),
+                            conflict: Conflict::Priority,
+                            fields: Vec::new(),
+                            observations: Vec::new(),
+                        }),
+                        AnyNode::Prl(PrlNode {
+                            name: None,
+                            rules: vec![Rule::from_strings("W", "B")].into(),
+                        }),
+                    ],
+                }),
+            ],
+        });
+
+        assert_eq!(model, expected);
+        assert_eq!(seq![], seq![].with_steps(3));
+        assert_eq!(one!("B" => "W").with_name("fill").name(), Some("fill"));
+    }
+
+    #[test]
+    fn river() {
+        let model = seq![
+            one!("B" => "W"; steps = 1),
+            one!("B" => "R"; steps = 1),
+            one!("RB" => "RR", "WB" => "WW"),
+            all!("RW" => "UU"),
+            all!("W" => "B", "R" => "B"),
+            all!("UB" => "UU"; steps = 1),
+            all!("BU/UB" => "U*/**"),
+            all!("UB" => "*G"),
+            one!("B" => "E"; steps = 13),
+            one!("EB" => "*E", "GB" => "*G"),
+        ];
+
+        // The river test in the node module, built by hand.
+        let one = |rules: RuleSet, steps| {
+            AnyNode::One(OneNode {
+                name: None,
+                rules,
+                steps,
+                sampler: Default::default(),
+                fields: Vec::new(),
+                observations: Vec::new(),
+            })
+        };
+
+        let all = |rules: RuleSet, steps| {
+            AnyNode::All(AllNode {
+                name: None,
+                rules,
+                steps,
+                conflict: Default::default(),
+                fields: Vec::new(),
+                observations: Vec::new(),
+            })
+        };
+
+        let expected = AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: vec![
+                one(vec![Rule::from_strings("B", "W")].into(), Some(1)),
+                one(vec![Rule::from_strings("B", "R")].into(), Some(1)),
+                one(
+                    [
+                        Rule::from_strings("RB", "RR").make_rotations(),
+                        Rule::from_strings("WB", "WW").make_rotations(),
+                    ]
+                    .concat()
+                    .into(),
+                    None,
+                ),
+                all(Rule::from_strings("RW", "UU").make_rotations().into(), None),
+                all(
+                    [
+                        Rule::from_strings("W", "B").make_rotations(),
+                        Rule::from_strings("R", "B").make_rotations(),
+                    ]
+                    .concat()
+                    .into(),
+                    None,
+                ),
+                all(
+                    Rule::from_strings("UB", "UU").make_rotations().into(),
+                    Some(1),
+                ),
+                all(
+                    Rule::from_strings("BU/UB", "U*/**").make_rotations().into(),
+                    None,
+                ),
+                all(Rule::from_strings("UB", "*G").make_rotations().into(), None),
+                one(vec![Rule::from_strings("B", "E")].into(), Some(13)),
+                one(
+                    [
+                        Rule::from_strings("EB", "*E").make_rotations(),
+                        Rule::from_strings("GB", "*G").make_rotations(),
+                    ]
+                    .concat()
+                    .into(),
+                    None,
+                ),
+            ],
+        });
+
+        assert_eq!(model, expected);
+
+        let run = |model: &AnyNode| {
+            let mut runner = Runner::new(model, Grid::new(32, 32), crate::tests::make_rng());
+            runner.run();
+            runner.grid
+        };
+
+        assert_eq!(run(&model), run(&expected));
+    }
+}
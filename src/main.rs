@@ -0,0 +1,1061 @@
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crabby_markov::catalogue::Catalogue;
+use crabby_markov::charset::Charset;
+use crabby_markov::isometric::IsometricStyle;
+use crabby_markov::model::{size_parameters, Model, Parameters};
+use crabby_markov::montage::Montage;
+use crabby_markov::node::AnyState;
+use crabby_markov::palette::Palette;
+use crabby_markov::recorder::{CapturePolicy, GifRecorder};
+use crabby_markov::replay::Replay;
+use crabby_markov::rng::DefaultRng;
+use crabby_markov::runner::{Budget, Outcome, Runner};
+use crabby_markov::schematic::Blocks;
+use crabby_markov::server::LiveServer;
+use crabby_markov::terminal::Theme;
+use crabby_markov::terrain::Heights;
+use crabby_markov::tiled;
+use crabby_markov::trace::Trace;
+use crabby_markov::tui::{self, Debugger};
+use crabby_markov::video::VideoRecorder;
+use crabby_markov::watch::ModelWatcher;
+use crabby_markov::{Grid, Symbol};
+
+#[derive(Parser)]
+#[command(version, about = "Pure Rust MarkovJunior port")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs a model file and writes its output.
+    Run(Box<RunArgs>),
+
+    /// Loads a model and steps through it interactively.
+    Repl(ReplArgs),
+
+    /// Re-renders a run recorded with `run --trace` as an animated GIF.
+    Replay(ReplayArgs),
+
+    /// Runs a model over many seeds and lays out the results in one PNG.
+    Montage(MontageArgs),
+
+    /// Draws every rule in a model, with its symmetric variants, in one PNG.
+    Rules(RulesArgs),
+
+    /// Runs a model while serving a live view of it to web browsers.
+    Serve(ServeArgs),
+
+    /// Steps through a model in a full-screen terminal debugger.
+    Tui(TuiArgs),
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    /// The XML model file to run.
+    model: PathBuf,
+
+    /// Grid size as `N` or `WIDTHxHEIGHT`.
+    #[arg(long, default_value = "64", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// Seed for the random number generator. Random if omitted.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Output file path.
+    #[arg(long, default_value = "out.gif")]
+    out: PathBuf,
+
+    /// Output format. Inferred from the output path if omitted.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Pixel size of each grid cell.
+    #[arg(long, default_value_t = 4)]
+    tile_size: u16,
+
+    /// Colors to draw symbols in.
+    #[arg(long, value_enum, default_value = "default")]
+    palette: Colors,
+
+    /// Capture an animation frame every this many steps.
+    #[arg(long, default_value_t = 64)]
+    frame_steps: usize,
+
+    /// Frames per second of video output.
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+
+    /// Also print the final grid to the terminal in this style.
+    #[arg(long, value_enum)]
+    preview: Option<Preview>,
+
+    /// Also write a trace of every rewrite to this file, for `replay`.
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Also write a JSON report of the run, with its seed, step counts,
+    /// per-rule counts, duration, and final histogram, to this file.
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Keep running, and rerun with the same seed whenever the model file
+    /// changes.
+    #[arg(long)]
+    watch: bool,
+
+    /// Wrap the grid around at its edges so the output tiles seamlessly.
+    #[arg(long)]
+    periodic: bool,
+
+    /// Set a model parameter, as `NAME=VALUE`. May be repeated.
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, i64)>,
+
+    /// Repeat the final grid this many times across and down in PNG output,
+    /// to check for seams.
+    #[arg(long, default_value = "1")]
+    tiles: NonZeroUsize,
+
+    /// Draw the output in isometric projection, as flat tiles or as cubes
+    /// stacked by layer.
+    #[arg(long, value_enum)]
+    isometric: Option<Isometric>,
+
+    /// Characters for text output, as `SYMBOL=CHAR` entries such as
+    /// `"B=. W=# R=+"`. Other symbols keep their own letters.
+    #[arg(long, default_value = "")]
+    charset: Charset,
+
+    /// Also write a 16-bit grayscale heightmap of the final grid here, with
+    /// RGBA splat maps of its symbols next to it.
+    #[arg(long)]
+    heightmap: Option<PathBuf>,
+
+    /// Heights for `--heightmap`, as `SYMBOL=HEIGHT` entries from 0 to
+    /// 65535, such as `"U=0 G=12000 W=40000"`. Other symbols are at 0.
+    #[arg(long, default_value = "")]
+    heights: Heights,
+
+    /// Minecraft blocks for schematic output, as `SYMBOL=BLOCK` entries
+    /// such as `"W=stone U=water"`. Black is air and other symbols are the
+    /// concrete closest to their color unless given.
+    #[arg(long, default_value = "")]
+    blocks: Blocks,
+}
+
+#[derive(clap::Args)]
+struct ReplayArgs {
+    /// The XML model file the trace was recorded from.
+    model: PathBuf,
+
+    /// The trace file written by `run --trace`.
+    trace: PathBuf,
+
+    /// Grid size as `N` or `WIDTHxHEIGHT`. Must match the traced run.
+    #[arg(long, default_value = "64", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// Output file path.
+    #[arg(long, default_value = "replay.gif")]
+    out: PathBuf,
+
+    /// Pixel size of each grid cell.
+    #[arg(long, default_value_t = 8)]
+    tile_size: u16,
+
+    /// Colors to draw symbols in.
+    #[arg(long, value_enum, default_value = "default")]
+    palette: Colors,
+
+    /// Capture an animation frame every this many steps.
+    #[arg(long, default_value_t = 64)]
+    frame_steps: usize,
+
+    /// Wrap the grid around at its edges. Must match the traced run.
+    #[arg(long)]
+    periodic: bool,
+
+    /// Set a model parameter, as `NAME=VALUE`. Must match the traced run.
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, i64)>,
+}
+
+#[derive(clap::Args)]
+struct MontageArgs {
+    /// The XML model file to run.
+    model: PathBuf,
+
+    /// Grid size as `N` or `WIDTHxHEIGHT`.
+    #[arg(long, default_value = "32", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// How many seeds to run.
+    #[arg(long, default_value_t = 16)]
+    count: u64,
+
+    /// The first seed to run. Seeds are consecutive from here.
+    #[arg(long, default_value_t = 0)]
+    first_seed: u64,
+
+    /// Grids per row. Roughly square if omitted.
+    #[arg(long)]
+    columns: Option<usize>,
+
+    /// Output file path.
+    #[arg(long, default_value = "montage.png")]
+    out: PathBuf,
+
+    /// Pixel size of each grid cell.
+    #[arg(long, default_value_t = 4)]
+    tile_size: u16,
+
+    /// Colors to draw symbols in.
+    #[arg(long, value_enum, default_value = "default")]
+    palette: Colors,
+
+    /// Leave out the seed labels.
+    #[arg(long)]
+    no_labels: bool,
+
+    /// Wrap the grid around at its edges.
+    #[arg(long)]
+    periodic: bool,
+
+    /// Set a model parameter, as `NAME=VALUE`. May be repeated.
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, i64)>,
+}
+
+#[derive(clap::Args)]
+struct RulesArgs {
+    /// The XML model file to draw.
+    model: PathBuf,
+
+    /// Grid size as `N` or `WIDTHxHEIGHT`, for evaluating parameters.
+    #[arg(long, default_value = "32", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// Output file path.
+    #[arg(long, default_value = "rules.png")]
+    out: PathBuf,
+
+    /// Pixel size of each pattern cell.
+    #[arg(long, default_value_t = 8)]
+    tile_size: u16,
+
+    /// Colors to draw symbols in.
+    #[arg(long, value_enum, default_value = "default")]
+    palette: Colors,
+
+    /// Set a model parameter, as `NAME=VALUE`. May be repeated.
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, i64)>,
+}
+
+#[derive(clap::Args)]
+struct ServeArgs {
+    /// The XML model file to run.
+    model: PathBuf,
+
+    /// Grid size as `N` or `WIDTHxHEIGHT`.
+    #[arg(long, default_value = "64", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// Seed for the random number generator. Random if omitted.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Address to serve the live view on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// Most frames sent to the browser per second.
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+
+    /// Wrap the grid around at its edges.
+    #[arg(long)]
+    periodic: bool,
+
+    /// Set a model parameter, as `NAME=VALUE`. May be repeated.
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, i64)>,
+}
+
+#[derive(clap::Args)]
+struct TuiArgs {
+    /// The XML model file to run.
+    model: PathBuf,
+
+    /// Grid size as `N` or `WIDTHxHEIGHT`.
+    #[arg(long, default_value = "32", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// Seed for the random number generator. Random if omitted.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Most frames drawn per second.
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+
+    /// Wrap the grid around at its edges.
+    #[arg(long)]
+    periodic: bool,
+
+    /// Set a model parameter, as `NAME=VALUE`. May be repeated.
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, i64)>,
+}
+
+#[derive(clap::Args)]
+struct ReplArgs {
+    /// The XML model file to load.
+    model: PathBuf,
+
+    /// Grid size as `N` or `WIDTHxHEIGHT`.
+    #[arg(long, default_value = "32", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// Seed for the random number generator. Random if omitted.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// How `print` draws the grid.
+    #[arg(long, value_enum, default_value = "halves")]
+    preview: Preview,
+
+    /// Set a model parameter, as `NAME=VALUE`. May be repeated.
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, i64)>,
+}
+
+impl ReplArgs {
+    fn parameters(&self) -> Parameters {
+        parameters(self.size, &self.params)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Animated GIF of the whole run.
+    Gif,
+    /// PNG of the final grid.
+    Png,
+    /// MP4 video of the whole run, encoded by ffmpeg.
+    Mp4,
+    /// WebM video of the whole run, encoded by ffmpeg.
+    Webm,
+    /// Text of the final grid, one character per cell.
+    Txt,
+    /// Wavefront OBJ mesh of the final grid, with an MTL file of its
+    /// materials next to it.
+    Obj,
+    /// Binary glTF mesh of the final grid, colored by per-symbol materials.
+    Glb,
+    /// Minecraft schematic of the final grid, with layers stacked upwards.
+    Schem,
+    /// Tiled map of the final grid, with a tileset of every symbol next to
+    /// it.
+    Tmx,
+    /// LDtk project with the final grid as IntGrid layers.
+    Ldtk,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Isometric {
+    /// Flat diamonds.
+    Tiles,
+    /// Cubes, with each layer stacked on the last.
+    Cubes,
+}
+
+impl From<Isometric> for IsometricStyle {
+    fn from(isometric: Isometric) -> Self {
+        match isometric {
+            Isometric::Tiles => IsometricStyle::Tiles,
+            Isometric::Cubes => IsometricStyle::Cubes,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Colors {
+    /// PICO-8's colors, with its secret colors for lowercase symbols.
+    Default,
+    /// PICO-8's sixteen standard colors only.
+    Pico8,
+    /// Shades of gray.
+    Grayscale,
+    /// Okabe-Ito hues, distinguishable with color blindness.
+    Colorblind,
+    /// Fully saturated colors.
+    HighContrast,
+}
+
+impl From<Colors> for Palette {
+    fn from(colors: Colors) -> Self {
+        match colors {
+            Colors::Default => Palette::Default,
+            Colors::Pico8 => Palette::Pico8,
+            Colors::Grayscale => Palette::Grayscale,
+            Colors::Colorblind => Palette::Colorblind,
+            Colors::HighContrast => Palette::HighContrast,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Preview {
+    /// Colored letters.
+    Letters,
+    /// Colored half blocks, two rows per line.
+    Halves,
+    /// Colored full blocks.
+    Blocks,
+    /// Colored square emoji.
+    Emoji,
+}
+
+impl From<Preview> for Theme {
+    fn from(preview: Preview) -> Self {
+        match preview {
+            Preview::Letters => Theme::Letters,
+            Preview::Halves => Theme::HalfBlocks,
+            Preview::Blocks => Theme::Blocks,
+            Preview::Emoji => Theme::Emoji,
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let parse = |n: &str| {
+        n.parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| format!("invalid grid size '{}'", size))
+    };
+
+    match size.split_once('x') {
+        Some((width, height)) => Ok((parse(width)?, parse(height)?)),
+        None => parse(size).map(|n| (n, n)),
+    }
+}
+
+fn parse_param(param: &str) -> Result<(String, i64), String> {
+    let invalid = || format!("invalid parameter '{}', expected NAME=VALUE", param);
+    let (name, value) = param.split_once('=').ok_or_else(invalid)?;
+    let value = value.trim().parse().map_err(|_| invalid())?;
+    Ok((name.trim().to_string(), value))
+}
+
+/// The parameters given on the command line, along with the grid size for
+/// expressions that use it.
+fn parameters((width, height): (usize, usize), params: &[(String, i64)]) -> Parameters {
+    let mut parameters = size_parameters(width, height);
+    parameters.extend(params.iter().cloned());
+    parameters
+}
+
+fn write_png(
+    path: &PathBuf,
+    grid: &Grid,
+    tile_size: u16,
+    palette: Palette,
+    seed: u64,
+) -> Result<(), png::EncodingError> {
+    let width = grid.width * tile_size as usize;
+    let height = grid.height * tile_size as usize;
+    let pixels = grid.render_indexed(tile_size);
+    let text = ("Seed", seed.to_string());
+    write_indexed_png(path, width, height, &pixels, palette, text)
+}
+
+fn write_indexed_png(
+    path: &PathBuf,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    palette: Palette,
+    (key, text): (&str, String),
+) -> Result<(), png::EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.colors());
+    encoder.add_text_chunk(key.to_string(), text)?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()
+}
+
+/// Writes the grid's heightmap to `path` and its splat maps next to it as
+/// `STEM-splatN.png`, printing which symbol each channel paints.
+fn write_terrain(
+    path: &PathBuf,
+    grid: &Grid,
+    heights: &Heights,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (width, height) = (grid.width as u32, grid.height as u32);
+    let heightmap = grid.heightmap(heights);
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    let pixels: Vec<u8> = heightmap
+        .grid
+        .iter()
+        .flat_map(|h| h.to_be_bytes())
+        .collect();
+    encoder.write_header()?.write_image_data(&pixels)?;
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    for (idx, map) in grid.splat_maps().iter().enumerate() {
+        let splat = path.with_file_name(format!("{}-splat{}.png", stem, idx));
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(&splat)?), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&map.pixels)?;
+
+        let channels: String = map.symbols.iter().map(Symbol::to_char).collect();
+        println!(
+            "wrote {} painting {} in RGBA order",
+            splat.display(),
+            channels
+        );
+    }
+
+    Ok(())
+}
+
+fn write_isometric_png(
+    path: &PathBuf,
+    grid: &Grid,
+    tile_size: u16,
+    style: IsometricStyle,
+    palette: Palette,
+    seed: u64,
+) -> Result<(), png::EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+    let (width, height) = grid.isometric_size(tile_size, style);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk("Seed".to_string(), seed.to_string())?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&grid.render_isometric_with(tile_size, style, palette))?;
+    writer.finish()
+}
+
+fn run(args: RunArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let parameters = parameters(args.size, &args.params);
+    let model = Model::load_with(&args.model, &parameters)?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+    run_model(&args, &model, seed)?;
+    if !args.watch {
+        return Ok(());
+    }
+
+    println!("watching {} for changes", args.model.display());
+    let mut watcher = ModelWatcher::new(&args.model).with_parameters(parameters);
+    loop {
+        std::thread::sleep(Duration::from_millis(250));
+        let result = match watcher.poll() {
+            Some(Ok(model)) => run_model(&args, &model, seed),
+            Some(Err(err)) => Err(err.into()),
+            None => continue,
+        };
+
+        if let Err(err) = result {
+            eprintln!("error: {}", err);
+        }
+    }
+}
+
+/// Runs a model once with the given seed and writes its output.
+fn run_model(args: &RunArgs, model: &Model, seed: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let (width, height) = args.size;
+    for diagnostic in model.validate(width, height) {
+        eprintln!("warning: {}", diagnostic);
+    }
+
+    let grid = model.make_grid(width, height);
+    let mut runner = Runner::<DefaultRng>::with_seed(&model.root, grid, seed);
+    runner.set_periodic(args.periodic);
+    if args.trace.is_some() {
+        runner.enable_trace();
+    }
+
+    if args.report.is_some() {
+        runner.enable_stats();
+    }
+
+    let format = match args.format {
+        Some(format) => format,
+        None => match args.out.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => Format::Png,
+            Some("mp4") => Format::Mp4,
+            Some("webm") => Format::Webm,
+            Some("txt") => Format::Txt,
+            Some("obj") => Format::Obj,
+            Some("glb") => Format::Glb,
+            Some("schem") => Format::Schem,
+            Some("tmx") => Format::Tmx,
+            Some("ldtk") => Format::Ldtk,
+            _ => Format::Gif,
+        },
+    };
+
+    let palette = args.palette.into();
+    match format {
+        Format::Gif => {
+            let file = BufWriter::new(File::create(&args.out)?);
+            let recorder = match args.isometric {
+                Some(style) => {
+                    GifRecorder::isometric(file, &runner.grid, args.tile_size, style.into())?
+                }
+                None => GifRecorder::new(file, width, height, args.tile_size)?,
+            };
+
+            let recorder = recorder
+                .with_policy(CapturePolicy::EverySteps(args.frame_steps))
+                .with_palette(palette);
+            runner.record(recorder)?;
+        }
+        Format::Png => {
+            runner.run();
+            let grid = runner.grid.tile(args.tiles.get(), args.tiles.get());
+            match args.isometric {
+                Some(style) => write_isometric_png(
+                    &args.out,
+                    &grid,
+                    args.tile_size,
+                    style.into(),
+                    palette,
+                    seed,
+                )?,
+                None => write_png(&args.out, &grid, args.tile_size, palette, seed)?,
+            }
+        }
+        Format::Mp4 | Format::Webm => {
+            let mut recorder = VideoRecorder::new(&args.out, width, height, args.tile_size)
+                .with_policy(CapturePolicy::EverySteps(args.frame_steps));
+            recorder.fps = args.fps;
+            recorder.palette = palette;
+            recorder.final_frames = args.fps as usize * 2;
+            runner.record_video(recorder)?;
+        }
+        Format::Txt => {
+            runner.run();
+            let text = runner
+                .grid
+                .to_text(&args.charset)
+                .ok_or("the charset leaves a symbol without a character")?;
+            std::fs::write(&args.out, text)?;
+        }
+        Format::Obj => {
+            runner.run();
+            let mesh = runner.grid.mesh();
+            let mtl = args.out.with_extension("mtl");
+            let mtllib = mtl.file_name().unwrap_or_default().to_string_lossy();
+            std::fs::write(&args.out, mesh.to_obj(&mtllib))?;
+            std::fs::write(&mtl, mesh.to_mtl())?;
+        }
+        Format::Glb => {
+            runner.run();
+            std::fs::write(&args.out, runner.grid.mesh().to_glb())?;
+        }
+        Format::Schem => {
+            runner.run();
+            std::fs::write(&args.out, runner.grid.to_schematic(&args.blocks)?)?;
+        }
+        Format::Tmx => {
+            runner.run();
+            let stem = args.out.file_stem().unwrap_or_default().to_string_lossy();
+            let (tsx, png) = (format!("{}-tiles.tsx", stem), format!("{}-tiles.png", stem));
+            let tiles = tiled::tileset_grid();
+            write_indexed_png(
+                &args.out.with_file_name(&png),
+                tiles.width * args.tile_size as usize,
+                tiles.height * args.tile_size as usize,
+                &tiles.render_indexed(args.tile_size),
+                palette,
+                ("Software", "crabby-markov".to_string()),
+            )?;
+            std::fs::write(
+                args.out.with_file_name(&tsx),
+                tiled::tileset_tsx(&png, args.tile_size),
+            )?;
+            std::fs::write(&args.out, runner.grid.to_tmx(&tsx, args.tile_size))?;
+        }
+        Format::Ldtk => {
+            runner.run();
+            std::fs::write(&args.out, runner.grid.to_ldtk(args.tile_size))?;
+        }
+    }
+
+    if let Some(path) = &args.heightmap {
+        write_terrain(path, &runner.grid, &args.heights)?;
+    }
+
+    if let Some(preview) = args.preview {
+        print!("{}", runner.grid.render_terminal(preview.into()));
+    }
+
+    if let (Some(path), Some(trace)) = (&args.trace, runner.trace()) {
+        std::fs::write(path, trace.to_string())?;
+    }
+
+    if let Some(path) = &args.report {
+        std::fs::write(path, runner.report().to_json())?;
+    }
+
+    println!(
+        "seed {}: {} steps, wrote {}",
+        seed,
+        runner.progress().steps,
+        args.out.display()
+    );
+
+    Ok(())
+}
+
+fn replay(args: ReplayArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let parameters = parameters(args.size, &args.params);
+    let model = Model::load_with(&args.model, &parameters)?;
+    let trace: Trace = std::fs::read_to_string(&args.trace)?.parse()?;
+    let (width, height) = args.size;
+    let mut grid = model.make_grid(width, height);
+    grid.periodic = args.periodic;
+    let mut replay = Replay::new(&trace, grid);
+
+    let file = BufWriter::new(File::create(&args.out)?);
+    let recorder = GifRecorder::new(file, width, height, args.tile_size)?
+        .with_policy(CapturePolicy::EverySteps(args.frame_steps))
+        .with_palette(args.palette.into());
+    replay.record(recorder)?;
+
+    println!(
+        "replayed {} steps, wrote {}",
+        replay.progress().steps,
+        args.out.display()
+    );
+
+    Ok(())
+}
+
+fn serve(args: ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let parameters = parameters(args.size, &args.params);
+    let model = Model::load_with(&args.model, &parameters)?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let (width, height) = args.size;
+    let mut grid = model.make_grid(width, height);
+    grid.periodic = args.periodic;
+
+    let interval = Duration::from_secs(1) / args.fps.max(1);
+    let mut server = LiveServer::bind(&args.addr)?.with_policy(CapturePolicy::Interval(interval));
+    println!("serving on http://{}", server.addr());
+
+    let mut runner = Runner::<DefaultRng>::with_seed(&model.root, grid, seed);
+    runner.serve(&mut server);
+    println!(
+        "seed {}: {} steps, still serving the final grid; press Ctrl-C to exit",
+        seed,
+        runner.progress().steps
+    );
+
+    loop {
+        std::thread::sleep(Duration::from_secs(60));
+    }
+}
+
+fn tui(args: TuiArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let parameters = parameters(args.size, &args.params);
+    let model = Model::load_with(&args.model, &parameters)?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let (width, height) = args.size;
+    let mut grid = model.make_grid(width, height);
+    grid.periodic = args.periodic;
+
+    tui::run(Debugger::new(&model.root, grid, seed), args.fps)?;
+    Ok(())
+}
+
+fn montage(args: MontageArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let parameters = parameters(args.size, &args.params);
+    let model = Model::load_with(&args.model, &parameters)?;
+    let (width, height) = args.size;
+    let mut grid = model.make_grid(width, height);
+    grid.periodic = args.periodic;
+
+    let seeds = args.first_seed..args.first_seed + args.count;
+    let batch = Runner::<DefaultRng>::run_batch(
+        &model.root,
+        &grid,
+        &Budget::default(),
+        seeds.clone(),
+        |seed, grid| (seed, grid.clone()),
+    );
+
+    let montage = Montage {
+        columns: args.columns.unwrap_or(0),
+        tile_size: args.tile_size,
+        labels: !args.no_labels,
+        ..Default::default()
+    };
+
+    let sheet = montage.render(&batch.outputs);
+    let seeds = format!("{}..{}", seeds.start, seeds.end);
+    write_indexed_png(
+        &args.out,
+        sheet.width,
+        sheet.height,
+        &sheet.pixels,
+        args.palette.into(),
+        ("Seeds", seeds.clone()),
+    )?;
+
+    println!("seeds {}: wrote {}", seeds, args.out.display());
+    Ok(())
+}
+
+fn rules(args: RulesArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let parameters = parameters(args.size, &args.params);
+    let model = Model::load_with(&args.model, &parameters)?;
+    let catalogue = Catalogue {
+        tile_size: args.tile_size,
+        ..Default::default()
+    };
+
+    let sheet = catalogue.render_model(&model.root);
+    write_indexed_png(
+        &args.out,
+        sheet.width,
+        sheet.height,
+        &sheet.pixels,
+        args.palette.into(),
+        ("Model", args.model.display().to_string()),
+    )?;
+
+    println!("wrote {}", args.out.display());
+    Ok(())
+}
+
+const REPL_HELP: &str = "\
+commands:
+  step [N]     take N steps (default 1)
+  run          step until the model finishes
+  back [N]     undo N steps (default 1)
+  print        draw the grid
+  state        show the node states and the active path
+  seed [N]     restart with seed N, or show the current seed
+  reset        restart with the same seed
+  reload       reload the model file and restart
+  help         show this message
+  quit         leave the REPL
+
+The model is also reloaded whenever its file changes.";
+
+/// The model and run being stepped through by the REPL.
+struct Session {
+    args: ReplArgs,
+    watcher: ModelWatcher,
+    model: Model,
+    seed: u64,
+    runner: Runner,
+}
+
+impl Session {
+    fn new(args: ReplArgs) -> Result<Self, Box<dyn std::error::Error>> {
+        let model = Model::load_with(&args.model, &args.parameters())?;
+        let seed = args.seed.unwrap_or_else(rand::random);
+        let runner = Self::start(&model, args.size, seed);
+        Ok(Self {
+            watcher: ModelWatcher::new(&args.model).with_parameters(args.parameters()),
+            args,
+            model,
+            seed,
+            runner,
+        })
+    }
+
+    fn start(model: &Model, (width, height): (usize, usize), seed: u64) -> Runner {
+        let mut runner = Runner::with_seed(&model.root, model.make_grid(width, height), seed);
+        runner.enable_history();
+        runner
+    }
+
+    fn restart(&mut self) {
+        self.runner = Self::start(&self.model, self.args.size, self.seed);
+    }
+
+    /// Restarts with the same seed if the model file has changed since it
+    /// was last loaded.
+    fn check_for_changes(&mut self) {
+        match self.watcher.poll() {
+            Some(Ok(model)) => {
+                self.model = model;
+                self.restart();
+                println!("model changed, restarted with seed {}", self.seed);
+            }
+            Some(Err(err)) => eprintln!("error: model changed but failed to load: {}", err),
+            None => {}
+        }
+    }
+
+    /// Executes one line of input. Returns false when the user quits.
+    fn execute(&mut self, line: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+
+        let count = |word: Option<&str>| -> Result<usize, String> {
+            word.map_or(Ok(1), |n| {
+                n.parse().map_err(|_| format!("invalid count '{}'", n))
+            })
+        };
+
+        match command {
+            "step" | "s" => {
+                let count = count(words.next())?;
+                let taken = (0..count).take_while(|_| self.runner.step()).count();
+                self.report(taken, taken < count);
+            }
+            "run" | "r" => {
+                let before = self.runner.progress().steps;
+                let outcome = self.runner.run();
+                self.report(
+                    self.runner.progress().steps - before,
+                    outcome == Outcome::Finished,
+                );
+            }
+            "back" | "b" => {
+                let undone = self.runner.rewind(count(words.next())?);
+                println!(
+                    "undid {} steps, at step {}",
+                    undone,
+                    self.runner.progress().steps
+                );
+            }
+            "print" | "p" => print!(
+                "{}",
+                self.runner.grid.render_terminal(self.args.preview.into())
+            ),
+            "state" => print_state(&self.runner.state, Some(&self.runner.progress().path), 0),
+            "seed" => match words.next() {
+                Some(seed) => {
+                    self.seed = seed
+                        .parse()
+                        .map_err(|_| format!("invalid seed '{}'", seed))?;
+                    self.restart();
+                    println!("restarted with seed {}", self.seed);
+                }
+                None => println!("seed {}", self.seed),
+            },
+            "reset" => {
+                self.restart();
+                println!("restarted with seed {}", self.seed);
+            }
+            "reload" => {
+                self.model = Model::load_with(&self.args.model, &self.args.parameters())?;
+                self.restart();
+                println!("reloaded {}", self.args.model.display());
+            }
+            "help" | "?" => println!("{}", REPL_HELP),
+            "quit" | "q" | "exit" => return Ok(false),
+            command => return Err(format!("unknown command '{}', try 'help'", command).into()),
+        }
+
+        Ok(true)
+    }
+
+    fn report(&self, taken: usize, finished: bool) {
+        let progress = self.runner.progress();
+        print!("took {} steps, at step {}", taken, progress.steps);
+        match finished {
+            true => println!(", model finished"),
+            false => println!(", active path {:?}", progress.path),
+        }
+    }
+}
+
+/// Prints a state tree, one node per line, marking the active path.
+fn print_state(state: &AnyState, path: Option<&[usize]>, depth: usize) {
+    println!(
+        "{:indent$}{} {} #{}: {} applications",
+        "",
+        if path.is_some() { "*" } else { " " },
+        state.kind(),
+        state.id(),
+        state.applications(),
+        indent = depth * 2
+    );
+
+    for (index, child) in state.children().iter().enumerate() {
+        let active = path.filter(|path| path.first() == Some(&index));
+        print_state(child, active.map(|path| &path[1..]), depth + 1);
+    }
+}
+
+fn repl(args: ReplArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut session = Session::new(args)?;
+    println!(
+        "loaded {} with seed {}, type 'help' for commands",
+        session.args.model.display(),
+        session.seed
+    );
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+
+        let Some(line) = lines.next() else {
+            println!();
+            return Ok(());
+        };
+
+        session.check_for_changes();
+        match session.execute(&line?) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(err) => eprintln!("error: {}", err),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Run(args) => run(*args),
+        Command::Repl(args) => repl(args),
+        Command::Replay(args) => replay(args),
+        Command::Montage(args) => montage(args),
+        Command::Rules(args) => rules(args),
+        Command::Serve(args) => serve(args),
+        Command::Tui(args) => tui(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
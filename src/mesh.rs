@@ -0,0 +1,271 @@
+//! Turning layered grids into triangle meshes.
+//!
+//! [Grid::mesh] treats a grid as a volume of unit cubes, one per cell, with
+//! the grid's layers stacked upwards as in [isometric](crate::isometric)
+//! renders. [Symbol::Black] cells are empty. Only the faces between a cube
+//! and empty space are kept, and neighboring faces of the same symbol that
+//! point the same way are merged into larger rectangles (greedy meshing), so
+//! large flat areas cost two triangles instead of two per cell.
+//!
+//! Meshes are Y-up: a cell at `(x, y)` on layer `l` spans `x..x + 1` along
+//! X, `l..l + 1` along Y, and `y..y + 1` along Z. Triangles wind counter-
+//! clockwise when seen from outside the volume.
+
+use std::ops::Range;
+
+use super::*;
+
+/// A corner of a face.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+
+    /// The unit normal of the face, pointing out of the volume.
+    pub normal: [f32; 3],
+
+    /// The symbol of the cell the face belongs to.
+    pub symbol: Symbol,
+}
+
+/// An indexed triangle mesh. See the [module](self) docs.
+///
+/// Every merged face is a quad of four vertices and two triangles, and the
+/// quads are ordered by symbol so that each symbol's faces form one run of
+/// [Mesh::indices].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+
+    /// Three indices into [Mesh::vertices] per triangle.
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    /// The number of merged faces.
+    pub fn quads(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    /// The indices of each symbol's triangles, in the order they appear.
+    pub fn symbol_ranges(&self) -> Vec<(Symbol, Range<usize>)> {
+        let mut ranges: Vec<(Symbol, Range<usize>)> = Vec::new();
+        for (quad, vertices) in self.vertices.chunks(4).enumerate() {
+            let indices = quad * 6..quad * 6 + 6;
+            match ranges.last_mut() {
+                Some((symbol, range)) if *symbol == vertices[0].symbol => range.end = indices.end,
+                _ => ranges.push((vertices[0].symbol, indices)),
+            }
+        }
+
+        ranges
+    }
+}
+
+/// A face found on one slice of the volume before merging.
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Face {
+    symbol: Symbol,
+
+    /// Whether the face points along the positive direction of its axis.
+    positive: bool,
+}
+
+/// A merged face: its corner at the lowest coordinates, its size along the
+/// two axes of its slice, and which way it points along the third.
+struct Quad {
+    face: Face,
+    axis: usize,
+    origin: [usize; 3],
+    size: [usize; 2],
+}
+
+impl Grid {
+    /// Meshes the grid's non-black cells. See the [module](self) docs.
+    pub fn mesh(&self) -> Mesh {
+        // The volume's size along X, Y (layers), and Z (rows).
+        let dims = [self.width, self.layers, self.height];
+        let solid = |at: [usize; 3]| {
+            let symbol = self[(Point { x: at[0], y: at[2] }, at[1])];
+            (symbol != Symbol::Black).then_some(symbol)
+        };
+
+        let mut quads = Vec::new();
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let mut mask = vec![None; dims[u] * dims[v]];
+
+            for slice in 0..=dims[axis] {
+                // Faces on the plane between the cells before and after
+                // `slice` along the axis.
+                for j in 0..dims[v] {
+                    for i in 0..dims[u] {
+                        let cell = |offset: usize| {
+                            let mut at = [0; 3];
+                            at[axis] = slice.checked_sub(offset).filter(|s| *s < dims[axis])?;
+                            at[u] = i;
+                            at[v] = j;
+                            solid(at)
+                        };
+
+                        mask[j * dims[u] + i] = match (cell(1), cell(0)) {
+                            (Some(symbol), None) => Some(Face {
+                                symbol,
+                                positive: true,
+                            }),
+                            (None, Some(symbol)) => Some(Face {
+                                symbol,
+                                positive: false,
+                            }),
+                            _ => None,
+                        };
+                    }
+                }
+
+                merge(&mut mask, dims[u], dims[v], |face, i, j, size| {
+                    let mut origin = [0; 3];
+                    origin[axis] = slice;
+                    origin[u] = i;
+                    origin[v] = j;
+                    quads.push(Quad {
+                        face,
+                        axis,
+                        origin,
+                        size,
+                    });
+                });
+            }
+        }
+
+        quads.sort_by_key(|quad| quad.face.symbol.palette_index());
+
+        let mut mesh = Mesh::default();
+        for quad in quads {
+            push_quad(&mut mesh, &quad);
+        }
+
+        mesh
+    }
+}
+
+/// Greedily covers the faces in a `width` by `height` mask with rectangles
+/// of equal faces, clearing the mask as it goes. Each rectangle is passed
+/// to `emit` with its lowest corner and its size.
+fn merge(
+    mask: &mut [Option<Face>],
+    width: usize,
+    height: usize,
+    mut emit: impl FnMut(Face, usize, usize, [usize; 2]),
+) {
+    for j in 0..height {
+        let mut i = 0;
+        while i < width {
+            let Some(face) = mask[j * width + i] else {
+                i += 1;
+                continue;
+            };
+
+            let run = (i..width)
+                .take_while(|x| mask[j * width + x] == Some(face))
+                .count();
+
+            let rows = (j..height)
+                .take_while(|y| (i..i + run).all(|x| mask[y * width + x] == Some(face)))
+                .count();
+
+            for y in j..j + rows {
+                mask[y * width + i..y * width + i + run].fill(None);
+            }
+
+            emit(face, i, j, [run, rows]);
+            i += run;
+        }
+    }
+}
+
+fn push_quad(mesh: &mut Mesh, quad: &Quad) {
+    let (u, v) = ((quad.axis + 1) % 3, (quad.axis + 2) % 3);
+    let corner = |du: usize, dv: usize| {
+        let mut position = quad.origin.map(|c| c as f32);
+        position[u] += du as f32;
+        position[v] += dv as f32;
+        position
+    };
+
+    let [width, height] = quad.size;
+    let mut corners = [
+        corner(0, 0),
+        corner(width, 0),
+        corner(width, height),
+        corner(0, height),
+    ];
+
+    // The slice's axes are a cyclic rotation of X, Y, Z, so these corners
+    // wind counter-clockwise around the positive direction of the axis.
+    let mut normal = [0.0; 3];
+    normal[quad.axis] = 1.0;
+    if !quad.face.positive {
+        corners.reverse();
+        normal[quad.axis] = -1.0;
+    }
+
+    let first = mesh.vertices.len() as u32;
+    mesh.vertices.extend(corners.map(|position| Vertex {
+        position,
+        normal,
+        symbol: quad.face.symbol,
+    }));
+
+    mesh.indices
+        .extend([0, 1, 2, 0, 2, 3].map(|index| first + index));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+
+    #[test]
+    fn greedy_mesh() {
+        let mut grid = Grid::with_layers(3, 2, 2);
+        assert_eq!(grid.mesh(), Mesh::default());
+
+        // A single cube.
+        grid[(Point { x: 1, y: 1 }, 1)] = Symbol::Red;
+        let mesh = grid.mesh();
+        assert_eq!((mesh.quads(), mesh.indices.len()), (6, 36));
+
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+            let edge = |to: Vertex| [0, 1, 2].map(|i| to.position[i] - a.position[i]);
+            assert_eq!(cross(edge(b), edge(c)), a.normal);
+
+            let center = [1.5, 1.5, 1.5];
+            let outwards: f32 = (0..3)
+                .map(|i| (a.position[i] - center[i]) * a.normal[i])
+                .sum();
+            assert!(outwards > 0.0);
+        }
+
+        // A 3x2 slab under the cube merges into one face per side, except
+        // for its top, which the cube splits into three.
+        for at in grid.enumerate_points().collect::<Vec<_>>() {
+            grid[at] = Symbol::Green;
+        }
+
+        let mesh = grid.mesh();
+        assert_eq!(mesh.quads(), 5 + 8);
+        let top = mesh.vertices.iter().filter(|v| v.normal == [0.0, 1.0, 0.0]);
+        assert_eq!(top.count(), 4 * 4);
+
+        let ranges = mesh.symbol_ranges();
+        assert_eq!(ranges, [(Symbol::Red, 0..30), (Symbol::Green, 30..78)]);
+        assert_eq!(mesh.indices.len(), 78);
+    }
+}
@@ -0,0 +1,1671 @@
+//! Loading of MarkovJunior-style XML model files.
+//!
+//! A model file's root element is itself a node. Composite nodes (`sequence`
+//! and `markov`) contain child nodes, while rule nodes (`one`, `all`, and
+//! `prl`) take their rules either from `in`/`out` attributes or from `rule`
+//! child elements. Rules are expanded into all four rotations unless they or
+//! their node set `symmetry` to `"none"`, `"all"` for every rotation and
+//! reflection, or one of MarkovJunior's `"()"`, `"(x)"`, `"(y)"`,
+//! `"(x)(y)"`, `"(xy+)"`, and `"(xy)"`.
+//!
+//! Rules can also require the center cell of their match to have a number
+//! of `neighbors` holding a symbol, e.g. `neighbors="W:5- R:-1"` for at
+//! least five `W` and at most one `R`, `neighbors="B:2"` for exactly two
+//! `B`, or `neighbors="B:1-3"` for between one and three. Only the four
+//! orthogonal neighbors are counted unless the rule sets `diagonals="True"`.
+//!
+//! `path` nodes draw the cheapest path `from` one set of symbols `to` another
+//! `on` a third in the given `color`. Stepping onto any `on` symbol costs 1
+//! unless `costs` says otherwise, e.g. `costs="U:5 B:2"`, and
+//! `heuristic="True"` enables an A* search.
+//!
+//! `all` nodes skip matches invalidated by earlier applications in the same
+//! step unless their `conflict` attribute says to `overwrite` regardless,
+//! apply rules in `priority` order, or `fail` the step on any overlap.
+//!
+//! `one` and `all` nodes can be steered by `field` child elements. Each
+//! gives the cells `for` a symbol a potential: the distance `to` the nearest
+//! target symbol moving `on` a substrate. Matches that move the symbol closer
+//! to its targets are preferred, or farther with `inversed="True"`. Fields are
+//! computed once unless `recompute="True"`, and an `essential="True"` field
+//! stops its node while no target is on the grid.
+//!
+//! They can also be given a goal with `observe` child elements. The cells
+//! holding an observed `value` when the node first steps are rewritten to
+//! its `from` symbol, if given, and must end up holding one of its `to`
+//! symbols. The node then only applies rules that keep the goal reachable,
+//! preferring those that move closest to it, and stops once it is reached.
+//!
+//! `repeat` nodes run their children in sequence `times` times, resetting them
+//! in between, or until a run does nothing if `times` is omitted.
+//!
+//! `if` nodes run their children in sequence only if the pattern given by
+//! their `present` attribute is found on the grid, or the one given by
+//! `absent` is not, when they are first reached.
+//!
+//! `until` nodes run their children in sequence until the grid holds at
+//! least `count` cells of their `symbol`, or at least `percent` percent.
+//!
+//! `walk` nodes paint a random walk of up to `length` moves `from` a random
+//! start cell `on` the given symbols in the given `color`. `persistence` is
+//! the percentage chance of continuing straight ahead.
+//!
+//! The root's `values` attribute lists the model's symbols, the first of
+//! which fills new grids. Setting `origin="True"` places the second value in
+//! the center, and `seed` elements directly under the root place symbols
+//! `at` the `center`, the `border`, `all` cells, or an `x,y` cell.
+//!
+//! Subtrees that a model repeats can be declared once with `define`
+//! elements directly under the root, each holding one or more nodes under a
+//! `name`, and instantiated with `<use name="..."/>` anywhere after it. A
+//! `use` may set `steps` to limit every `one`, `all`, and `walk` node in its
+//! copy to that many steps.
+//!
+//! Any node can be given a `name`, e.g. `<sequence name="erosion">`, to find
+//! it by in [breakpoints](crate::runner::Breakpoint). The copy a `use` makes
+//! is named after its definition unless the definition's node has a name of
+//! its own.
+//!
+//! Nodes can also be loaded from other files with `<include file="..."/>`,
+//! so that shared phases live in one place. The path is relative to the
+//! including file, or to the working directory for models parsed from a
+//! string. The included file's root node takes the `include`'s place, along
+//! with any `define`s under it; its `values`, `layers`, and seeds are
+//! ignored.
+//!
+//! Models can declare `param` elements directly under the root, each with a
+//! `name` and a default `value`. Any numeric attribute, such as `steps`,
+//! `times`, `count`, `percent`, `length`, `persistence`, `layers`, `layer`,
+//! or a seed's `at` coordinates, can then give an
+//! [expression](crate::expression) of parameters instead of a number, e.g.
+//! `steps="rooms * 2 + 1"`, as can each of a path's `costs` and each bound
+//! in `neighbors`. Since `-` separates a `neighbors` range, its bounds can't
+//! subtract. Callers can supply other values with [Model::load_with].
+//! Included files see the parameters of the file including them, and their
+//! own `param`s only fill in those that aren't already set.
+//!
+//! Callers that know the grid's size ahead of time can also supply `width`
+//! and `height`, which need not be declared, so that attributes like
+//! `steps="width * height / 40"` scale with the grid.
+//!
+//! Grids can be given several stacked layers with the root's `layers`
+//! attribute. Patterns separate their rows with `/` and their layers with
+//! spaces, bottom layer first. Rules address the layers starting from the
+//! bottom one unless they set `layer`, and seeds are always placed on the
+//! bottom layer.
+//!
+//! ```xml
+//! <sequence values="BWR">
+//!   <seed value="W" at="border"/>
+//!   <one in="B" out="R" steps="1"/>
+//!   <one in="RBB" out="WWR"/>
+//! </sequence>
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::{Path, PathBuf};
+
+use roxmltree::{Document, Node};
+
+use super::*;
+use condition::NeighborCount;
+use field::Field;
+use node::*;
+use observe::Observation;
+use rule_set::RuleSet;
+use seed::Seed;
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(std::io::Error),
+    Xml(roxmltree::Error),
+    UnknownNode(String),
+    MissingAttribute {
+        node: String,
+        attribute: &'static str,
+    },
+    InvalidAttribute {
+        attribute: String,
+        value: String,
+    },
+    UnknownSymbol(char),
+    InconsistentRows(String),
+
+    /// A pattern with an empty layer or row, such as `""` or `"B/"`.
+    EmptyPattern(String),
+    MismatchedRule {
+        find: String,
+        replace: String,
+    },
+    UnknownDefinition(String),
+    UnknownParameter(String),
+    IncludeCycle(PathBuf),
+    Include {
+        path: PathBuf,
+        error: Box<ModelError>,
+    },
+}
+
+impl Display for ModelError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ModelError::*;
+        match self {
+            Io(err) => write!(fmt, "failed to read model: {}", err),
+            Xml(err) => write!(fmt, "malformed model XML: {}", err),
+            UnknownNode(name) => write!(fmt, "unknown node <{}>", name),
+            MissingAttribute { node, attribute } => {
+                write!(fmt, "<{}> is missing attribute '{}'", node, attribute)
+            }
+            InvalidAttribute { attribute, value } => {
+                write!(
+                    fmt,
+                    "invalid value '{}' for attribute '{}'",
+                    value, attribute
+                )
+            }
+            UnknownSymbol(c) => write!(fmt, "unrecognized symbol '{}'", c),
+            InconsistentRows(pattern) => {
+                write!(fmt, "pattern '{}' has inconsistent row lengths", pattern)
+            }
+            EmptyPattern(pattern) => write!(fmt, "pattern '{}' has an empty row", pattern),
+            MismatchedRule { find, replace } => write!(
+                fmt,
+                "rule '{}' -> '{}' has mismatched dimensions",
+                find, replace
+            ),
+            UnknownDefinition(name) => write!(fmt, "no <define> named '{}'", name),
+            UnknownParameter(name) => write!(fmt, "model has no parameter '{}'", name),
+            IncludeCycle(path) => write!(fmt, "{} includes itself", path.display()),
+            Include { path, error } => write!(fmt, "in {}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl From<std::io::Error> for ModelError {
+    fn from(err: std::io::Error) -> Self {
+        ModelError::Io(err)
+    }
+}
+
+impl From<roxmltree::Error> for ModelError {
+    fn from(err: roxmltree::Error) -> Self {
+        ModelError::Xml(err)
+    }
+}
+
+pub type ModelResult<T> = Result<T, ModelError>;
+
+/// Values for a model's parameters, by name.
+pub type Parameters = HashMap<String, i64>;
+
+/// The parameters for the grid's size, which models needn't declare.
+const SIZE_PARAMETERS: [&str; 2] = ["width", "height"];
+
+/// Parameters giving only the grid's size.
+pub fn size_parameters(width: usize, height: usize) -> Parameters {
+    Parameters::from([
+        ("width".to_string(), width as i64),
+        ("height".to_string(), height as i64),
+    ])
+}
+
+/// A loaded model: its alphabet, initial seeds, and node tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Model {
+    /// The symbols declared by the root's `values` attribute, if it has one.
+    ///
+    /// The first value is used to fill new grids, or [Symbol::default] if
+    /// none were declared.
+    pub values: Option<Vec<Symbol>>,
+
+    /// Placements applied to new grids, in order.
+    pub seeds: Vec<Seed>,
+
+    /// The number of layers in new grids.
+    pub layers: usize,
+
+    /// The parameters the model declares, in order, with the values it was
+    /// loaded with.
+    pub parameters: Vec<(String, i64)>,
+
+    pub root: AnyNode,
+}
+
+impl Model {
+    /// Loads a model file, resolving its includes relative to the file.
+    pub fn load(path: impl AsRef<Path>) -> ModelResult<Self> {
+        Self::load_with(path, &Parameters::new())
+    }
+
+    /// Loads a model file with some of its parameters set. Every parameter
+    /// given must be declared by the model, except for `width` and `height`.
+    pub fn load_with(path: impl AsRef<Path>, parameters: &Parameters) -> ModelResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let scope = Scope::for_file(path, &[]);
+        Self::parse(&text, scope, parameters)
+    }
+
+    /// Parses a model, resolving its includes relative to the working
+    /// directory.
+    pub fn from_xml(text: &str) -> ModelResult<Self> {
+        Self::from_xml_with(text, &Parameters::new())
+    }
+
+    /// Parses a model with some of its parameters set, like
+    /// [Model::load_with].
+    pub fn from_xml_with(text: &str, parameters: &Parameters) -> ModelResult<Self> {
+        Self::parse(text, Scope::default(), parameters)
+    }
+
+    fn parse(text: &str, mut scope: Scope, supplied: &Parameters) -> ModelResult<Self> {
+        let document = Document::parse(text)?;
+        let root = document.root_element();
+
+        scope.parameters = supplied.clone();
+        let parameters = parse_parameters(root, &mut scope)?;
+        if let Some(name) = supplied
+            .keys()
+            .filter(|name| !SIZE_PARAMETERS.contains(&name.as_str()))
+            .find(|name| !parameters.iter().any(|(declared, _)| declared == *name))
+        {
+            return Err(ModelError::UnknownParameter(name.to_string()));
+        }
+
+        let values = root.attribute("values").map(parse_symbols).transpose()?;
+
+        let layers = parse_layers(root, &scope)?;
+        scope.layers = layers;
+        let mut seeds = Vec::new();
+
+        if let Some(origin) = root.attribute("origin") {
+            if parse_bool("origin", origin)? {
+                match values.as_ref().and_then(|values| values.get(1)) {
+                    Some(symbol) => seeds.push(Seed::Center(*symbol)),
+                    None => {
+                        return Err(ModelError::InvalidAttribute {
+                            attribute: "values".to_string(),
+                            value: root.attribute("values").unwrap_or_default().to_string(),
+                        })
+                    }
+                }
+            }
+        }
+
+        for child in root.children().filter(|child| is_seed(*child)) {
+            seeds.push(parse_seed(child, &scope)?);
+        }
+
+        parse_definitions(root, &mut scope)?;
+        Ok(Self {
+            values,
+            seeds,
+            layers,
+            parameters,
+            root: parse_node(root, &scope)?,
+        })
+    }
+
+    /// Creates a grid filled with the model's first value, then seeded.
+    pub fn make_grid(&self, width: usize, height: usize) -> Grid {
+        let fill = self
+            .values
+            .as_ref()
+            .and_then(|values| values.first())
+            .copied()
+            .unwrap_or_default();
+        let mut grid = Grid {
+            grid: vec![fill; width * height * self.layers],
+            width,
+            height,
+            layers: self.layers,
+            periodic: false,
+        };
+
+        for seed in self.seeds.iter() {
+            seed.apply(&mut grid);
+        }
+
+        grid
+    }
+}
+
+/// What the nodes of a model file can refer to while they are parsed.
+#[derive(Default)]
+struct Scope {
+    /// The subtrees declared so far by `define` elements, by name.
+    definitions: HashMap<String, AnyNode>,
+
+    /// The directory that `include` paths are relative to.
+    dir: PathBuf,
+
+    /// The files being parsed, outermost first, to catch include cycles.
+    files: Vec<PathBuf>,
+
+    /// The value of every parameter declared so far.
+    parameters: Parameters,
+
+    /// The model's layer count, which rules are checked against. Included
+    /// files share the including model's.
+    layers: usize,
+}
+
+impl Scope {
+    /// A fresh scope for the file at `path`, included from `files`.
+    fn for_file(path: &Path, files: &[PathBuf]) -> Self {
+        let mut files = files.to_vec();
+        files.push(std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        Self {
+            definitions: HashMap::new(),
+            dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            files,
+            parameters: Parameters::new(),
+            layers: 1,
+        }
+    }
+
+    /// Reads a numeric attribute, which may be an expression of parameters.
+    fn number<T: TryFrom<i64>>(&self, attribute: &str, value: &str) -> ModelResult<T> {
+        let invalid = || ModelError::InvalidAttribute {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+        };
+
+        let number = expression::evaluate(value, |name| self.parameters.get(name).copied())
+            .map_err(|_| invalid())?;
+        T::try_from(number).map_err(|_| invalid())
+    }
+}
+
+/// Reads the `param` elements under the root in order, keeping any values
+/// already set, and returns each with its value.
+fn parse_parameters(root: Node, scope: &mut Scope) -> ModelResult<Vec<(String, i64)>> {
+    let mut parameters = Vec::new();
+    for param in root.children().filter(|child| is_parameter(*child)) {
+        let attribute = |attribute| {
+            param
+                .attribute(attribute)
+                .ok_or(ModelError::MissingAttribute {
+                    node: "param".to_string(),
+                    attribute,
+                })
+        };
+
+        let name = attribute("name")?;
+        let value = match scope.parameters.get(name) {
+            Some(value) => *value,
+            None => scope.number("value", attribute("value")?)?,
+        };
+
+        scope.parameters.insert(name.to_string(), value);
+        parameters.push((name.to_string(), value));
+    }
+
+    Ok(parameters)
+}
+
+/// Reads the `define` elements under the root in order, so that each can
+/// use the ones before it.
+fn parse_definitions(root: Node, scope: &mut Scope) -> ModelResult<()> {
+    for define in root.children().filter(|child| is_definition(*child)) {
+        let name = define
+            .attribute("name")
+            .ok_or(ModelError::MissingAttribute {
+                node: "define".to_string(),
+                attribute: "name",
+            })?;
+
+        if scope.definitions.contains_key(name) {
+            return Err(ModelError::InvalidAttribute {
+                attribute: "name".to_string(),
+                value: name.to_string(),
+            });
+        }
+
+        let definition = *parse_child(define, scope)?;
+        scope.definitions.insert(name.to_string(), definition);
+    }
+
+    Ok(())
+}
+
+/// Parses the root node of the file an `include` element names.
+fn parse_include(node: Node, scope: &Scope) -> ModelResult<AnyNode> {
+    let file = node.attribute("file").ok_or(ModelError::MissingAttribute {
+        node: "include".to_string(),
+        attribute: "file",
+    })?;
+
+    let path = scope.dir.join(file);
+    let canonical = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+    if scope.files.contains(&canonical) {
+        return Err(ModelError::IncludeCycle(path));
+    }
+
+    let parse = || {
+        let text = std::fs::read_to_string(&path)?;
+        let document = Document::parse(&text)?;
+        let root = document.root_element();
+        let mut included = Scope::for_file(&path, &scope.files);
+        included.parameters = scope.parameters.clone();
+        included.layers = scope.layers;
+        parse_parameters(root, &mut included)?;
+        parse_definitions(root, &mut included)?;
+        parse_node(root, &included)
+    };
+
+    parse().map_err(|error| match error {
+        ModelError::IncludeCycle(_) => error,
+        error => ModelError::Include {
+            path: path.clone(),
+            error: Box::new(error),
+        },
+    })
+}
+
+fn parse_use(node: Node, scope: &Scope) -> ModelResult<AnyNode> {
+    let name = node.attribute("name").ok_or(ModelError::MissingAttribute {
+        node: "use".to_string(),
+        attribute: "name",
+    })?;
+
+    let definition = scope
+        .definitions
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ModelError::UnknownDefinition(name.to_string()))?;
+
+    Ok(match parse_steps(node, scope)? {
+        Some(steps) => definition.with_steps(steps),
+        None => definition,
+    })
+}
+
+fn parse_node(node: Node, scope: &Scope) -> ModelResult<AnyNode> {
+    let parsed = parse_unnamed_node(node, scope)?;
+
+    // A `use` names the definition it copies, which also names the copy
+    // unless the definition's root node has a name of its own.
+    Ok(match node.attribute("name") {
+        Some(_) if node.has_tag_name("use") && parsed.name().is_some() => parsed,
+        Some(name) => parsed.with_name(name),
+        None => parsed,
+    })
+}
+
+fn parse_unnamed_node(node: Node, scope: &Scope) -> ModelResult<AnyNode> {
+    let name = node.tag_name().name();
+    Ok(match name {
+        "sequence" => AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: parse_children(node, scope)?,
+        }),
+        "markov" => AnyNode::Markov(MarkovNode {
+            name: None,
+            children: parse_children(node, scope)?,
+        }),
+        "one" => AnyNode::One(OneNode {
+            name: None,
+            rules: parse_rules(node, scope)?,
+            steps: parse_steps(node, scope)?,
+            sampler: Default::default(),
+            fields: parse_fields(node)?,
+            observations: parse_observations(node)?,
+        }),
+        "all" => AnyNode::All(AllNode {
+            name: None,
+            rules: parse_rules(node, scope)?,
+            steps: parse_steps(node, scope)?,
+            conflict: parse_conflict(node)?,
+            fields: parse_fields(node)?,
+            observations: parse_observations(node)?,
+        }),
+        "prl" => AnyNode::Prl(PrlNode {
+            name: None,
+            rules: parse_rules(node, scope)?,
+        }),
+        "path" => AnyNode::Path(parse_path(node, scope)?),
+        "walk" => AnyNode::Walk(parse_walk(node, scope)?),
+        "repeat" => AnyNode::Repeat(parse_repeat(node, scope)?),
+        "if" => AnyNode::If(parse_if(node, scope)?),
+        "until" => AnyNode::Until(parse_until(node, scope)?),
+        "use" => parse_use(node, scope)?,
+        "include" => parse_include(node, scope)?,
+        _ => return Err(ModelError::UnknownNode(name.to_string())),
+    })
+}
+
+fn parse_children(node: Node, scope: &Scope) -> ModelResult<Vec<AnyNode>> {
+    node.children()
+        .filter(|child| is_node(*child))
+        .map(|child| parse_node(child, scope))
+        .collect()
+}
+
+/// Whether an element is a node, rather than a seed, definition, or
+/// parameter.
+fn is_node(node: Node) -> bool {
+    node.is_element() && !is_seed(node) && !is_definition(node) && !is_parameter(node)
+}
+
+/// Seeds are only allowed directly under the root node.
+fn is_seed(node: Node) -> bool {
+    node.has_tag_name("seed") && is_under_root(node)
+}
+
+/// Definitions are only allowed directly under the root node.
+fn is_definition(node: Node) -> bool {
+    node.has_tag_name("define") && is_under_root(node)
+}
+
+/// Parameters are only allowed directly under the root node.
+fn is_parameter(node: Node) -> bool {
+    node.has_tag_name("param") && is_under_root(node)
+}
+
+fn is_under_root(node: Node) -> bool {
+    node.parent_element()
+        .is_some_and(|parent| parent.parent_element().is_none())
+}
+
+fn parse_seed(node: Node, scope: &Scope) -> ModelResult<Seed> {
+    let value = node
+        .attribute("value")
+        .ok_or(ModelError::MissingAttribute {
+            node: "seed".to_string(),
+            attribute: "value",
+        })?;
+
+    let invalid = |attribute: &str, value: &str| ModelError::InvalidAttribute {
+        attribute: attribute.to_string(),
+        value: value.to_string(),
+    };
+
+    let symbol = parse_single_symbol("value", value)?;
+    let at = node.attribute("at").unwrap_or("center");
+    Ok(match at {
+        "center" => Seed::Center(symbol),
+        "border" => Seed::Border(symbol),
+        "all" => Seed::Fill(symbol),
+        _ => {
+            let (x, y) = at.split_once(',').ok_or_else(|| invalid("at", at))?;
+            let x = scope.number("at", x).map_err(|_| invalid("at", at))?;
+            let y = scope.number("at", y).map_err(|_| invalid("at", at))?;
+            Seed::At(Point { x, y }, symbol)
+        }
+    })
+}
+
+/// Reads the root's layer count, which must be at least one.
+fn parse_layers(root: Node, scope: &Scope) -> ModelResult<usize> {
+    match root.attribute("layers") {
+        Some(layers) => match scope.number("layers", layers) {
+            Ok(layers) if layers > 0 => Ok(layers),
+            _ => Err(ModelError::InvalidAttribute {
+                attribute: "layers".to_string(),
+                value: layers.to_string(),
+            }),
+        },
+        None => Ok(1),
+    }
+}
+
+fn parse_path(node: Node, scope: &Scope) -> ModelResult<PathNode> {
+    let attribute = |attribute| {
+        node.attribute(attribute)
+            .ok_or(ModelError::MissingAttribute {
+                node: "path".to_string(),
+                attribute,
+            })
+    };
+
+    let invalid = |value: &str| ModelError::InvalidAttribute {
+        attribute: "costs".to_string(),
+        value: value.to_string(),
+    };
+
+    let mut costs = Vec::new();
+    for cost in node
+        .attribute("costs")
+        .unwrap_or_default()
+        .split_whitespace()
+    {
+        let (symbol, cost) = cost.split_once(':').ok_or_else(|| invalid(cost))?;
+        let symbol = parse_single_symbol("costs", symbol)?;
+        let cost = scope.number("costs", cost).map_err(|_| invalid(cost))?;
+        costs.push((symbol, cost));
+    }
+
+    let on = parse_symbols(attribute("on")?)?
+        .into_iter()
+        .map(|symbol| {
+            let cost = costs.iter().find(|(s, _)| *s == symbol);
+            (symbol, cost.map_or(1, |(_, cost)| *cost))
+        })
+        .collect();
+
+    Ok(PathNode {
+        name: None,
+        from: parse_symbols(attribute("from")?)?,
+        to: parse_symbols(attribute("to")?)?,
+        on,
+        color: parse_single_symbol("color", attribute("color")?)?,
+        heuristic: match node.attribute("heuristic") {
+            Some(heuristic) => parse_bool("heuristic", heuristic)?,
+            None => false,
+        },
+    })
+}
+
+/// Parses a node's children as a single node, wrapping several in a sequence.
+fn parse_child(node: Node, scope: &Scope) -> ModelResult<Box<AnyNode>> {
+    let mut children = parse_children(node, scope)?;
+    Ok(Box::new(match children.len() {
+        1 => children.remove(0),
+        _ => AnyNode::Sequence(SequenceNode {
+            name: None,
+            children,
+        }),
+    }))
+}
+
+fn parse_if(node: Node, scope: &Scope) -> ModelResult<IfNode> {
+    let (guard, present) = match (node.attribute("present"), node.attribute("absent")) {
+        (Some(guard), None) => (guard, true),
+        (None, Some(guard)) => (guard, false),
+        _ => {
+            return Err(ModelError::MissingAttribute {
+                node: "if".to_string(),
+                attribute: "present",
+            })
+        }
+    };
+
+    Ok(IfNode {
+        name: None,
+        guard: parse_pattern(guard)?,
+        present,
+        child: parse_child(node, scope)?,
+    })
+}
+
+fn parse_until(node: Node, scope: &Scope) -> ModelResult<UntilNode> {
+    let symbol = node
+        .attribute("symbol")
+        .ok_or(ModelError::MissingAttribute {
+            node: "until".to_string(),
+            attribute: "symbol",
+        })?;
+
+    let threshold = match (node.attribute("count"), node.attribute("percent")) {
+        (Some(count), None) => Threshold::Count(scope.number("count", count)?),
+        (None, Some(percent)) => Threshold::Percent(scope.number("percent", percent)?),
+        _ => {
+            return Err(ModelError::MissingAttribute {
+                node: "until".to_string(),
+                attribute: "count",
+            })
+        }
+    };
+
+    Ok(UntilNode {
+        name: None,
+        symbol: parse_single_symbol("symbol", symbol)?,
+        threshold,
+        child: parse_child(node, scope)?,
+    })
+}
+
+fn parse_fields(node: Node) -> ModelResult<Vec<Field>> {
+    node.children()
+        .filter(|child| child.has_tag_name("field"))
+        .map(parse_field)
+        .collect()
+}
+
+fn parse_field(node: Node) -> ModelResult<Field> {
+    let attribute = |attribute| {
+        node.attribute(attribute)
+            .ok_or(ModelError::MissingAttribute {
+                node: "field".to_string(),
+                attribute,
+            })
+    };
+
+    let flag = |attribute| match node.attribute(attribute) {
+        Some(value) => parse_bool(attribute, value),
+        None => Ok(false),
+    };
+
+    Ok(Field {
+        symbol: parse_single_symbol("for", attribute("for")?)?,
+        to: parse_symbols(attribute("to")?)?,
+        on: parse_symbols(attribute("on")?)?,
+        repel: flag("inversed")?,
+        recompute: flag("recompute")?,
+        essential: flag("essential")?,
+    })
+}
+
+fn parse_observations(node: Node) -> ModelResult<Vec<Observation>> {
+    node.children()
+        .filter(|child| child.has_tag_name("observe"))
+        .map(parse_observation)
+        .collect()
+}
+
+fn parse_observation(node: Node) -> ModelResult<Observation> {
+    let attribute = |attribute| {
+        node.attribute(attribute)
+            .ok_or(ModelError::MissingAttribute {
+                node: "observe".to_string(),
+                attribute,
+            })
+    };
+
+    let from = match node.attribute("from") {
+        Some(from) => Some(parse_single_symbol("from", from)?),
+        None => None,
+    };
+
+    Ok(Observation {
+        value: parse_single_symbol("value", attribute("value")?)?,
+        from,
+        to: parse_symbols(attribute("to")?)?,
+    })
+}
+
+fn parse_repeat(node: Node, scope: &Scope) -> ModelResult<RepeatNode> {
+    let times = match node.attribute("times") {
+        Some(times) => Some(scope.number("times", times)?),
+        None => None,
+    };
+
+    Ok(RepeatNode {
+        name: None,
+        child: parse_child(node, scope)?,
+        times,
+    })
+}
+
+fn parse_walk(node: Node, scope: &Scope) -> ModelResult<WalkNode> {
+    let attribute = |attribute| {
+        node.attribute(attribute)
+            .ok_or(ModelError::MissingAttribute {
+                node: "walk".to_string(),
+                attribute,
+            })
+    };
+
+    let persistence = node.attribute("persistence").unwrap_or("0");
+    Ok(WalkNode {
+        name: None,
+        from: parse_symbols(attribute("from")?)?,
+        on: parse_symbols(attribute("on")?)?,
+        color: parse_single_symbol("color", attribute("color")?)?,
+        length: scope.number("length", attribute("length")?)?,
+        persistence: scope.number("persistence", persistence)?,
+        steps: parse_steps(node, scope)?,
+    })
+}
+
+fn parse_conflict(node: Node) -> ModelResult<Conflict> {
+    Ok(match node.attribute("conflict") {
+        None | Some("skip") => Conflict::Skip,
+        Some("overwrite") => Conflict::Overwrite,
+        Some("priority") => Conflict::Priority,
+        Some("fail") => Conflict::Fail,
+        Some(conflict) => {
+            return Err(ModelError::InvalidAttribute {
+                attribute: "conflict".to_string(),
+                value: conflict.to_string(),
+            })
+        }
+    })
+}
+
+fn parse_bool(attribute: &str, value: &str) -> ModelResult<bool> {
+    match value {
+        "True" | "true" => Ok(true),
+        "False" | "false" => Ok(false),
+        _ => Err(ModelError::InvalidAttribute {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn parse_steps(node: Node, scope: &Scope) -> ModelResult<Option<usize>> {
+    match node.attribute("steps") {
+        Some(steps) => Ok(Some(scope.number("steps", steps)?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_rules(node: Node, scope: &Scope) -> ModelResult<RuleSet> {
+    let mut rules = Vec::new();
+
+    if node.has_attribute("in") || node.has_attribute("out") {
+        rules.extend(parse_rule(node, scope)?);
+    }
+
+    for child in node.children().filter(|child| is_node(*child)) {
+        match child.tag_name().name() {
+            "rule" => rules.extend(parse_rule(child, scope)?),
+            "field" | "observe" if node.has_tag_name("one") || node.has_tag_name("all") => {}
+            name => return Err(ModelError::UnknownNode(name.to_string())),
+        }
+    }
+
+    if rules.is_empty() {
+        return Err(ModelError::MissingAttribute {
+            node: node.tag_name().name().to_string(),
+            attribute: "in",
+        });
+    }
+
+    Ok(RuleSet::new(rules))
+}
+
+fn parse_rule(node: Node, scope: &Scope) -> ModelResult<Vec<Rule>> {
+    let attribute = |attribute| {
+        node.attribute(attribute)
+            .ok_or_else(|| ModelError::MissingAttribute {
+                node: node.tag_name().name().to_string(),
+                attribute,
+            })
+    };
+
+    let find = attribute("in")?;
+    let replace = attribute("out")?;
+    let find_pattern = parse_pattern(find)?;
+    let rule = Rule {
+        conditions: parse_conditions(node, &find_pattern, scope)?,
+        find: find_pattern,
+        replace: parse_pattern(replace)?,
+    };
+
+    if rule.find.width != rule.replace.width
+        || rule.find.height != rule.replace.height
+        || rule.find.layers != rule.replace.layers
+    {
+        return Err(ModelError::MismatchedRule {
+            find: find.to_string(),
+            replace: replace.to_string(),
+        });
+    }
+
+    let layers = scope.layers;
+    if rule.find.layers > layers {
+        return Err(ModelError::InvalidAttribute {
+            attribute: "in".to_string(),
+            value: find.to_string(),
+        });
+    }
+
+    let rule = match node.attribute("layer") {
+        Some(layer) => match scope.number::<usize>("layer", layer) {
+            Ok(layer) if layer + rule.find.layers <= layers => rule.to_layer(layer, layers),
+            _ => {
+                return Err(ModelError::InvalidAttribute {
+                    attribute: "layer".to_string(),
+                    value: layer.to_string(),
+                })
+            }
+        },
+        None => rule,
+    };
+
+    Ok(parse_symmetry(node)?.expand(rule))
+}
+
+/// Reads a rule's `neighbors` conditions, such as `"W:3- R:-1 B:2"`, on
+/// the center cell of its find pattern.
+fn parse_conditions(node: Node, find: &Pattern, scope: &Scope) -> ModelResult<Vec<NeighborCount>> {
+    let Some(conditions) = node.attribute("neighbors") else {
+        return Ok(Vec::new());
+    };
+
+    let invalid = |value: &str| ModelError::InvalidAttribute {
+        attribute: "neighbors".to_string(),
+        value: value.to_string(),
+    };
+
+    let diagonals = match node.attribute("diagonals") {
+        Some(diagonals) => parse_bool("diagonals", diagonals)?,
+        None => false,
+    };
+
+    let at = Point {
+        x: find.width / 2,
+        y: find.height / 2,
+    };
+
+    conditions
+        .split_whitespace()
+        .map(|condition| {
+            let (symbol, range) = condition
+                .split_once(':')
+                .ok_or_else(|| invalid(condition))?;
+            let symbol = parse_single_symbol("neighbors", symbol)?;
+            if range.is_empty() || range == "-" {
+                return Err(invalid(condition));
+            }
+
+            let bound = |bound: &str, default| match bound {
+                "" => Ok(default),
+                bound => scope
+                    .number("neighbors", bound)
+                    .map_err(|_| invalid(condition)),
+            };
+
+            let (min, max) = match range.split_once('-') {
+                Some((min, max)) => (bound(min, 0)?, bound(max, usize::MAX)?),
+                None => {
+                    let count = bound(range, 0)?;
+                    (count, count)
+                }
+            };
+
+            Ok(NeighborCount {
+                at,
+                symbol,
+                min,
+                max,
+                diagonals,
+            })
+        })
+        .collect()
+}
+
+/// Reads a rule's symmetry, which `rule` elements inherit from their node.
+fn parse_symmetry(node: Node) -> ModelResult<Symmetry> {
+    let symmetry = node.attribute("symmetry").or_else(|| {
+        node.parent_element()
+            .filter(|_| node.has_tag_name("rule"))
+            .and_then(|parent| parent.attribute("symmetry"))
+    });
+
+    match symmetry {
+        None | Some("rotations" | "(xy+)") => Ok(Symmetry::Rotations),
+        Some("none" | "()") => Ok(Symmetry::Anisotropic),
+        Some("(x)") => Ok(Symmetry::MirrorX),
+        Some("(y)") => Ok(Symmetry::MirrorY),
+        Some("(x)(y)") => Ok(Symmetry::Mirrors),
+        Some("all" | "(xy)") => Ok(Symmetry::Square),
+        Some(symmetry) => Err(ModelError::InvalidAttribute {
+            attribute: "symmetry".to_string(),
+            value: symmetry.to_string(),
+        }),
+    }
+}
+
+fn parse_pattern(string: &str) -> ModelResult<Pattern> {
+    use ParsePatternError::*;
+    Pattern::parse(string).map_err(|err| match err {
+        EmptyRow => ModelError::EmptyPattern(string.to_string()),
+        UnknownSymbol(c) => ModelError::UnknownSymbol(c),
+        InconsistentRows | InconsistentLayers => ModelError::InconsistentRows(string.to_string()),
+    })
+}
+
+fn parse_symbols(string: &str) -> ModelResult<Vec<Symbol>> {
+    string.chars().map(parse_symbol).collect()
+}
+
+/// Parses an attribute holding exactly one symbol.
+fn parse_single_symbol(attribute: &str, value: &str) -> ModelResult<Symbol> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => parse_symbol(c),
+        _ => Err(ModelError::InvalidAttribute {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn parse_symbol(c: char) -> ModelResult<Symbol> {
+    Symbol::try_from_char(c).ok_or(ModelError::UnknownSymbol(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runner::Runner;
+
+    #[test]
+    fn load_sequence() {
+        let model = Model::from_xml(
+            r#"<sequence values="BRW">
+                <one in="B" out="R" steps="1"/>
+                <markov>
+                    <one in="RBB" out="WWR"/>
+                    <all>
+                        <rule in="RW" out="WW"/>
+                        <rule in="R/B" out="W/*"/>
+                    </all>
+                </markov>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            model.values,
+            Some(vec![Symbol::Black, Symbol::Red, Symbol::White])
+        );
+
+        let AnyNode::Sequence(sequence) = &model.root else {
+            panic!("expected a sequence");
+        };
+
+        assert_eq!(
+            sequence.children[0],
+            AnyNode::One(OneNode {
+                name: None,
+                rules: vec![Rule::from_strings("B", "R")].into(),
+                steps: Some(1),
+                sampler: Default::default(),
+                fields: Vec::new(),
+                observations: Vec::new(),
+            })
+        );
+
+        let AnyNode::Markov(markov) = &sequence.children[1] else {
+            panic!("expected a markov node");
+        };
+
+        let AnyNode::All(all) = &markov.children[1] else {
+            panic!("expected an all node");
+        };
+
+        assert_eq!(all.rules.len(), 8);
+        assert_eq!(all.rules[4], Rule::from_strings("R/B", "W/*"));
+    }
+
+    #[test]
+    fn load_seeds() {
+        let model = Model::from_xml(
+            r#"<one values="BRW" origin="True" in="RB" out="WR">
+                <seed value="W" at="border"/>
+                <seed value="R" at="1,2"/>
+            </one>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            model.seeds,
+            [
+                Seed::Center(Symbol::Red),
+                Seed::Border(Symbol::White),
+                Seed::At(Point { x: 1, y: 2 }, Symbol::Red),
+            ]
+        );
+
+        let grid = model.make_grid(5, 5);
+        assert_eq!(grid.to_string(), "WWWWW\nWBBBW\nWRRBW\nWBBBW\nWWWWW\n");
+
+        assert!(matches!(
+            Model::from_xml(r#"<one in="B" out="W"><seed value="W" at="left"/></one>"#),
+            Err(ModelError::InvalidAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_layers() {
+        let model = Model::from_xml(
+            r#"<sequence values="BG" layers="2">
+                <one in="B" out="G" steps="1"/>
+                <one in="B" out="G" layer="1" steps="1"/>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        assert_eq!(model.layers, 2);
+        assert_eq!(model.make_grid(3, 2).grid.len(), 12);
+
+        let AnyNode::Sequence(sequence) = &model.root else {
+            panic!("expected a sequence");
+        };
+
+        let AnyNode::One(one) = &sequence.children[1] else {
+            panic!("expected a one node");
+        };
+
+        assert_eq!(one.rules[0], Rule::from_strings("B", "G").to_layer(1, 2));
+
+        assert!(matches!(
+            Model::from_xml(r#"<one layers="2" layer="2" in="B" out="W"/>"#),
+            Err(ModelError::InvalidAttribute { .. })
+        ));
+
+        let model = Model::from_xml(r#"<one layers="3" layer="1" in="G B" out="* R"/>"#).unwrap();
+        let AnyNode::One(one) = &model.root else {
+            panic!("expected a one node");
+        };
+
+        assert_eq!(one.rules[0], Rule::from_strings("* G B", "* * R"));
+
+        let load = |xml| Model::from_xml(xml).unwrap_err();
+        assert!(matches!(
+            load(r#"<one layers="2" layer="1" in="G B" out="* R"/>"#),
+            ModelError::InvalidAttribute { .. }
+        ));
+        assert!(matches!(
+            load(r#"<one in="G B" out="* R"/>"#),
+            ModelError::InvalidAttribute { .. }
+        ));
+        assert!(matches!(
+            load(r#"<one layers="2" in="GG B" out="** R"/>"#),
+            ModelError::InconsistentRows(_)
+        ));
+        assert!(matches!(
+            load(r#"<one layers="2" in="G B" out="R"/>"#),
+            ModelError::MismatchedRule { .. }
+        ));
+        assert!(matches!(
+            Model::from_xml(r#"<one layers="0" in="B" out="W"/>"#),
+            Err(ModelError::InvalidAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_symmetry() {
+        let model = Model::from_xml(
+            r#"<sequence>
+                <all in="WB" out="BW" symmetry="none"/>
+                <one symmetry="()">
+                    <rule in="B/R" out="R/B"/>
+                    <rule in="BW" out="WB" symmetry="rotations"/>
+                </one>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        let AnyNode::Sequence(sequence) = &model.root else {
+            panic!("expected a sequence");
+        };
+
+        let AnyNode::All(all) = &sequence.children[0] else {
+            panic!("expected an all node");
+        };
+
+        assert_eq!(*all.rules, [Rule::from_strings("WB", "BW")]);
+        assert_eq!(all.conflict, Conflict::Skip);
+
+        let AnyNode::One(one) = &sequence.children[1] else {
+            panic!("expected a one node");
+        };
+
+        assert_eq!(one.rules.len(), 5);
+        assert_eq!(one.rules[0], Rule::from_strings("B/R", "R/B"));
+
+        assert!(matches!(
+            Model::from_xml(r#"<one in="B" out="W" symmetry="sideways"/>"#),
+            Err(ModelError::InvalidAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_path() {
+        let model = Model::from_xml(
+            r#"<path from="R" to="G" on="BU" color="W" costs="U:5" heuristic="True"/>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            model.root,
+            AnyNode::Path(PathNode {
+                name: None,
+                from: vec![Symbol::Red],
+                to: vec![Symbol::Green],
+                on: vec![(Symbol::Black, 1), (Symbol::Blue, 5)],
+                color: Symbol::White,
+                heuristic: true,
+            })
+        );
+
+        assert!(matches!(
+            Model::from_xml(r#"<path from="R" to="G" on="B" color="W" costs="B5"/>"#),
+            Err(ModelError::InvalidAttribute { .. })
+        ));
+        assert!(matches!(
+            Model::from_xml(r#"<path from="R" to="G" on="B"/>"#),
+            Err(ModelError::MissingAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_walk() {
+        let model =
+            Model::from_xml(r#"<walk from="R" on="B" color="W" length="40" persistence="75"/>"#)
+                .unwrap();
+
+        assert_eq!(
+            model.root,
+            AnyNode::Walk(WalkNode {
+                name: None,
+                from: vec![Symbol::Red],
+                on: vec![Symbol::Black],
+                color: Symbol::White,
+                length: 40,
+                persistence: 75,
+                steps: None,
+            })
+        );
+
+        assert!(matches!(
+            Model::from_xml(r#"<walk from="R" on="B" color="W"/>"#),
+            Err(ModelError::MissingAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_repeat() {
+        let model = Model::from_xml(
+            r#"<repeat times="3">
+                <one in="B" out="R" steps="1"/>
+                <all in="RB" out="RR" steps="1"/>
+            </repeat>"#,
+        )
+        .unwrap();
+
+        let AnyNode::Repeat(repeat) = &model.root else {
+            panic!("expected a repeat node");
+        };
+
+        assert_eq!(repeat.times, Some(3));
+        assert_eq!(repeat.child.children().len(), 2);
+        assert_eq!(model.root.find(2), Some(&repeat.child.children()[0]));
+    }
+
+    #[test]
+    fn load_definitions() {
+        let model = Model::from_xml(
+            r#"<sequence values="BWR">
+                <define name="grow">
+                    <one in="WB" out="WW"/>
+                </define>
+                <define name="cleanup">
+                    <all in="WBW" out="WWW"/>
+                    <use name="grow" steps="2"/>
+                </define>
+                <one name="seed" in="B" out="W" steps="1"/>
+                <use name="cleanup"/>
+                <use name="cleanup" steps="5"/>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            model.root.named_nodes(),
+            [
+                (1, "seed"),
+                (2, "cleanup"),
+                (4, "grow"),
+                (5, "cleanup"),
+                (7, "grow")
+            ]
+        );
+
+        // States carry their node's name, apart from its kind.
+        let state = model.root.make_state();
+        assert_eq!((state.kind(), state.name()), ("sequence", None));
+        let cleanup = &state.children()[1];
+        assert_eq!(
+            (cleanup.kind(), cleanup.name()),
+            ("sequence", Some("cleanup"))
+        );
+        assert_eq!(state.children()[0].name(), Some("seed"));
+
+        let children = model.root.children();
+        assert_eq!(children.len(), 3);
+
+        let steps = |node: &AnyNode| match node {
+            AnyNode::One(node) => node.steps,
+            AnyNode::All(node) => node.steps,
+            _ => panic!("expected a rule node"),
+        };
+
+        let cleanup = children[1].children();
+        assert_eq!(steps(&cleanup[0]), None);
+        assert_eq!(steps(&cleanup[1]), Some(2));
+        assert!(children[2]
+            .children()
+            .iter()
+            .all(|node| steps(node) == Some(5)));
+
+        assert!(matches!(
+            Model::from_xml(r#"<sequence><use name="missing"/></sequence>"#),
+            Err(ModelError::UnknownDefinition(_))
+        ));
+    }
+
+    #[test]
+    fn load_includes() {
+        let dir = std::env::temp_dir().join(format!("crabby-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("phases")).unwrap();
+        let write = |path: &str, xml: &str| std::fs::write(dir.join(path), xml).unwrap();
+
+        write(
+            "phases/grow.xml",
+            r#"<sequence values="BW">
+                <define name="fill">
+                    <include file="fill.xml"/>
+                </define>
+                <one in="WB" out="WW" steps="4"/>
+                <use name="fill"/>
+            </sequence>"#,
+        );
+        write("phases/fill.xml", r#"<all in="B" out="W"/>"#);
+        write(
+            "model.xml",
+            r#"<sequence values="BW">
+                <one in="B" out="W" steps="1"/>
+                <include file="phases/grow.xml"/>
+            </sequence>"#,
+        );
+        write("cycle.xml", r#"<include file="cycle.xml"/>"#);
+        write("broken.xml", r#"<include file="phases/missing.xml"/>"#);
+
+        // Included files' own `layers` are ignored in favor of the model's.
+        write(
+            "phases/top.xml",
+            r#"<one layers="3" in="B" out="W" layer="2"/>"#,
+        );
+        write(
+            "layered.xml",
+            r#"<sequence values="BW" layers="2"><include file="phases/top.xml"/></sequence>"#,
+        );
+        write(
+            "tall.xml",
+            r#"<sequence values="BW" layers="3"><include file="phases/top.xml"/></sequence>"#,
+        );
+
+        let model = Model::load(dir.join("model.xml")).unwrap();
+        let grow = &model.root.children()[1];
+        assert_eq!(grow.children().len(), 2);
+        assert!(matches!(grow.children()[1], AnyNode::All(_)));
+
+        assert!(matches!(
+            Model::load(dir.join("cycle.xml")),
+            Err(ModelError::IncludeCycle(_))
+        ));
+        assert!(matches!(
+            Model::load(dir.join("broken.xml")),
+            Err(ModelError::Include { .. })
+        ));
+
+        let tall = Model::load(dir.join("tall.xml")).unwrap();
+        let AnyNode::One(top) = &tall.root.children()[0] else {
+            panic!("expected a one node");
+        };
+
+        assert_eq!(top.rules[0], Rule::from_strings("B", "W").to_layer(2, 3));
+        assert!(matches!(
+            Model::load(dir.join("layered.xml")),
+            Err(ModelError::Include { error, .. })
+                if matches!(*error, ModelError::InvalidAttribute { .. })
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_parameters() {
+        let xml = r#"<sequence values="BWR">
+            <param name="rooms" value="4"/>
+            <param name="corner" value="2"/>
+            <seed value="R" at="corner, 1"/>
+            <one in="B" out="W" steps="rooms"/>
+            <repeat times="corner">
+                <walk from="R" on="B" color="W" length="rooms" persistence="50"/>
+            </repeat>
+        </sequence>"#;
+
+        let steps = |model: &Model| match &model.root.children()[0] {
+            AnyNode::One(node) => node.steps,
+            _ => panic!("expected a one node"),
+        };
+
+        let model = Model::from_xml(xml).unwrap();
+        assert_eq!(steps(&model), Some(4));
+        assert_eq!(
+            model.parameters,
+            [("rooms".to_string(), 4), ("corner".to_string(), 2)]
+        );
+        assert_eq!(model.seeds, [Seed::At(Point { x: 2, y: 1 }, Symbol::Red)]);
+
+        let parameters = Parameters::from([("rooms".to_string(), 9)]);
+        let model = Model::from_xml_with(xml, &parameters).unwrap();
+        assert_eq!(steps(&model), Some(9));
+
+        let AnyNode::Repeat(repeat) = &model.root.children()[1] else {
+            panic!("expected a repeat node");
+        };
+
+        assert_eq!(repeat.times, Some(2));
+        assert!(matches!(&*repeat.child, AnyNode::Walk(walk) if walk.length == 9));
+
+        let sized = r#"<sequence values="BW">
+            <param name="density" value="40"/>
+            <one in="B" out="W" steps="width * height / density"/>
+        </sequence>"#;
+        let model = Model::from_xml_with(sized, &size_parameters(20, 10)).unwrap();
+        assert_eq!(steps(&model), Some(5));
+        assert!(Model::from_xml(sized).is_err());
+
+        let unknown = Parameters::from([("doors".to_string(), 1)]);
+        assert!(matches!(
+            Model::from_xml_with(xml, &unknown),
+            Err(ModelError::UnknownParameter(_))
+        ));
+
+        let model = Model::from_xml(
+            r#"<sequence values="BWRU" layers="top + 1">
+                <param name="top" value="1"/>
+                <param name="wall" value="3"/>
+                <one in="B" out="W" layer="top" neighbors="R:wall-wall*2"/>
+                <path from="W" to="R" on="BU" color="W" costs="U:wall*2"/>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        assert_eq!(model.layers, 2);
+        let AnyNode::One(one) = &model.root.children()[0] else {
+            panic!("expected a one node");
+        };
+
+        let mut expected = Rule::from_strings("B", "W").to_layer(1, 2);
+        expected.conditions = vec![NeighborCount {
+            at: Point::ZERO,
+            symbol: Symbol::Red,
+            min: 3,
+            max: 6,
+            diagonals: false,
+        }];
+        assert_eq!(one.rules[0], expected);
+
+        let AnyNode::Path(path) = &model.root.children()[1] else {
+            panic!("expected a path node");
+        };
+
+        assert_eq!(path.on, [(Symbol::Black, 1), (Symbol::Blue, 6)]);
+    }
+
+    #[test]
+    fn load_if() {
+        let model = Model::from_xml(r#"<if absent="RW"><one in="B" out="R"/></if>"#).unwrap();
+        let AnyNode::If(node) = &model.root else {
+            panic!("expected an if node");
+        };
+
+        assert_eq!(node.guard, Pattern::from_string("RW"));
+        assert!(!node.present);
+
+        assert!(matches!(
+            Model::from_xml(r#"<if present="R" absent="W"><one in="B" out="R"/></if>"#),
+            Err(ModelError::MissingAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_until() {
+        let model = Model::from_xml(
+            r#"<until values="BE" symbol="E" percent="30"><one in="B" out="E"/></until>"#,
+        )
+        .unwrap();
+
+        let AnyNode::Until(node) = &model.root else {
+            panic!("expected an until node");
+        };
+
+        assert_eq!(node.symbol, Symbol::Emerald);
+        assert_eq!(node.threshold, Threshold::Percent(30));
+        assert!(matches!(
+            Model::from_xml(r#"<until symbol="E"><one in="B" out="E"/></until>"#),
+            Err(ModelError::MissingAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_fields() {
+        let model = Model::from_xml(
+            r#"<one values="BRWG" in="RB" out="WR" steps="13">
+                <seed value="R" at="1,1"/>
+                <seed value="G" at="8,8"/>
+                <field for="R" to="G" on="B" recompute="True"/>
+            </one>"#,
+        )
+        .unwrap();
+
+        let AnyNode::One(node) = &model.root else {
+            panic!("expected a one node");
+        };
+
+        assert_eq!(node.fields.len(), 1);
+        assert_eq!(node.fields[0].to, [Symbol::Green]);
+        assert!(node.fields[0].recompute && !node.fields[0].repel);
+
+        // The walker heads straight for the target, ending up next to it.
+        let mut runner: Runner = Runner::with_seed(&model.root, model.make_grid(12, 12), 0);
+        runner.run();
+        let end = [Point { x: 7, y: 8 }, Point { x: 8, y: 7 }];
+        assert!(end.iter().any(|at| runner.grid[*at] == Symbol::Red));
+
+        assert!(matches!(
+            Model::from_xml(r#"<prl in="B" out="W"><field for="W" to="R" on="B"/></prl>"#),
+            Err(ModelError::UnknownNode(_))
+        ));
+    }
+
+    #[test]
+    fn load_observations() {
+        let model = Model::from_xml(
+            r#"<one values="BRW" in="RB" out="RR">
+                <seed value="R" at="0,0"/>
+                <seed value="W" at="6,6"/>
+                <observe value="W" from="B" to="R"/>
+            </one>"#,
+        )
+        .unwrap();
+
+        let AnyNode::One(node) = &model.root else {
+            panic!("expected a one node");
+        };
+
+        assert_eq!(node.observations[0].from, Some(Symbol::Black));
+        let mut runner: Runner = Runner::with_seed(&model.root, model.make_grid(10, 10), 3);
+        runner.run();
+        assert_eq!(runner.grid[Point { x: 6, y: 6 }], Symbol::Red);
+        assert_eq!(runner.grid.count(Symbol::White), 0);
+
+        let unreachable = Model::from_xml(
+            r#"<one values="BRWG" in="RB" out="RR">
+                <seed value="W" at="3,3"/>
+                <observe value="W" to="G"/>
+            </one>"#,
+        )
+        .unwrap();
+
+        let mut runner: Runner = Runner::with_seed(&unreachable.root, model.make_grid(10, 10), 3);
+        runner.run();
+        assert_eq!(runner.progress().steps, 0);
+    }
+
+    #[test]
+    fn load_conditions() {
+        let model = Model::from_xml(
+            r#"<all values="WB" in="W" out="B" neighbors="B:2- W:1" diagonals="True"/>"#,
+        )
+        .unwrap();
+
+        let AnyNode::All(node) = &model.root else {
+            panic!("expected an all node");
+        };
+
+        let conditions = &node.rules[0].conditions;
+        assert_eq!((conditions[0].min, conditions[0].max), (2, usize::MAX));
+        assert_eq!((conditions[1].min, conditions[1].max), (1, 1));
+        assert!(conditions[0].diagonals);
+
+        let xml = r#"<one in="BB" out="WW" neighbors="W:-3"/>"#;
+        let model = Model::from_xml(xml).unwrap();
+        let AnyNode::One(node) = &model.root else {
+            panic!("expected a one node");
+        };
+
+        let sideways = &node.rules[0].conditions[0];
+        assert_eq!(sideways.at, Point { x: 1, y: 0 });
+        assert_eq!((sideways.min, sideways.max), (0, 3));
+
+        assert!(matches!(
+            Model::from_xml(r#"<one in="B" out="W" neighbors="W:3+"/>"#),
+            Err(ModelError::InvalidAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn load_examples() {
+        Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        Model::from_xml(include_str!("../models/nystrom-dungeon.xml")).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let load = |xml| Model::from_xml(xml).unwrap_err();
+
+        assert!(matches!(
+            load("<one/>"),
+            ModelError::MissingAttribute { .. }
+        ));
+        assert!(matches!(load("<wfc/>"), ModelError::UnknownNode(_)));
+        assert!(matches!(
+            load(r#"<one in="BX" out="BB"/>"#),
+            ModelError::UnknownSymbol('X')
+        ));
+        assert!(matches!(
+            load(r#"<one in="BB/B" out="BB/BB"/>"#),
+            ModelError::InconsistentRows(_)
+        ));
+        assert!(matches!(
+            load(r#"<one in="" out=""/>"#),
+            ModelError::EmptyPattern(_)
+        ));
+        assert!(matches!(
+            load(r#"<one in="B/" out="W/"/>"#),
+            ModelError::EmptyPattern(_)
+        ));
+        assert!(matches!(
+            load(r#"<one in="BB" out="B"/>"#),
+            ModelError::MismatchedRule { .. }
+        ));
+        assert!(matches!(
+            load(r#"<one in="B" out="W" steps="lots"/>"#),
+            ModelError::InvalidAttribute { .. }
+        ));
+    }
+}
@@ -0,0 +1,213 @@
+//! Contact sheets comparing a model's output across seeds.
+//!
+//! A [Montage] lays out many final grids, typically the outputs of
+//! [Runner::run_batch](crate::runner::Runner::run_batch), in rows on a single
+//! image with each grid's seed printed beneath it. Sheets are rendered as
+//! palette indices like [Grid::render_indexed], so they can be written with
+//! [Symbol::PALETTE] or the colors of any other [Palette].
+
+use super::*;
+use palette::Palette;
+
+/// How far label digits are scaled up from their 3x5 pixel glyphs.
+pub(crate) const LABEL_SCALE: usize = 2;
+
+/// The height in pixels of a label.
+pub(crate) const LABEL_HEIGHT: usize = 5 * LABEL_SCALE;
+
+/// Each digit's 3x5 glyph, row by row from the top, with the most
+/// significant bit of each row on the left.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b011, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// How a contact sheet is laid out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Montage {
+    /// Grids per row, or zero to lay the grids out in a roughly square
+    /// block.
+    pub columns: usize,
+
+    /// Pixel size of each grid cell.
+    pub tile_size: u16,
+
+    /// Pixels of background around and between grids.
+    pub gap: usize,
+
+    /// Print each grid's seed beneath it.
+    pub labels: bool,
+
+    pub background: Symbol,
+    pub text: Symbol,
+}
+
+impl Default for Montage {
+    fn default() -> Self {
+        Self {
+            columns: 0,
+            tile_size: 4,
+            gap: 4,
+            labels: true,
+            background: Symbol::Dark,
+            text: Symbol::White,
+        }
+    }
+}
+
+/// A rendered contact sheet of palette indices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sheet {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Sheet {
+    /// A sheet filled with `background`.
+    pub(crate) fn new(width: usize, height: usize, background: Symbol) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background.palette_index(); width * height],
+        }
+    }
+
+    /// The sheet as RGBA pixels.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        Palette::Default.to_rgba(&self.pixels)
+    }
+
+    /// Copies `width`-pixel rows of palette indices onto the sheet with their
+    /// top-left corner at (`left`, `top`).
+    pub(crate) fn paste(&mut self, left: usize, top: usize, pixels: &[u8], width: usize) {
+        for (y, row) in pixels.chunks(width.max(1)).enumerate() {
+            let start = (top + y) * self.width + left;
+            self.pixels[start..start + width].copy_from_slice(row);
+        }
+    }
+
+    /// Draws a 3x5 glyph, scaled up by [LABEL_SCALE], with its top-left
+    /// corner at (`left`, `top`), clipped to the sheet's edges.
+    pub(crate) fn glyph(&mut self, left: usize, top: usize, glyph: [u8; 5], color: Symbol) {
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in (0..3).filter(|column| bits & 0b100 >> column != 0) {
+                for dy in 0..LABEL_SCALE {
+                    for dx in 0..LABEL_SCALE {
+                        let x = left + column * LABEL_SCALE + dx;
+                        let y = top + row * LABEL_SCALE + dy;
+                        if x < self.width && y < self.height {
+                            self.pixels[y * self.width + x] = color.palette_index();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Prints `number` with its top-left corner at (`left`, `top`), clipped
+    /// to the sheet's edges.
+    pub(crate) fn label(&mut self, left: usize, top: usize, number: u64, color: Symbol) {
+        for (idx, digit) in number.to_string().bytes().enumerate() {
+            let glyph = DIGITS[(digit - b'0') as usize];
+            self.glyph(left + idx * 4 * LABEL_SCALE, top, glyph, color);
+        }
+    }
+}
+
+/// The width in pixels of `number`'s label.
+pub(crate) fn label_width(number: u64) -> usize {
+    number.to_string().len() * 4 * LABEL_SCALE - LABEL_SCALE
+}
+
+impl Montage {
+    /// Renders `grids`, given with their seeds, in order from left to right
+    /// and top to bottom. Every grid gets a slot as large as the largest
+    /// grid, with smaller grids placed in its top-left corner.
+    pub fn render(&self, grids: &[(u64, Grid)]) -> Sheet {
+        let tile = self.tile_size as usize;
+        let columns = match self.columns {
+            0 => (1..).find(|n| n * n >= grids.len()).unwrap_or(1),
+            columns => columns,
+        };
+
+        let rows = grids.len().div_ceil(columns);
+        let label_height = match self.labels {
+            true => LABEL_HEIGHT + self.gap,
+            false => 0,
+        };
+
+        let slot_width = grids.iter().map(|(_, grid)| grid.width).max().unwrap_or(0) * tile;
+        let slot_height = grids.iter().map(|(_, grid)| grid.height).max().unwrap_or(0) * tile;
+        let step_x = slot_width + self.gap;
+        let step_y = slot_height + label_height + self.gap;
+
+        let (width, height) = (columns * step_x + self.gap, rows * step_y + self.gap);
+        let mut sheet = Sheet::new(width, height, self.background);
+
+        for (idx, (seed, grid)) in grids.iter().enumerate() {
+            let left = self.gap + idx % columns * step_x;
+            let top = self.gap + idx / columns * step_y;
+            let pixels = grid.render_indexed(self.tile_size);
+            sheet.paste(left, top, &pixels, grid.width * tile);
+
+            if self.labels {
+                let top = top + grid.height * tile + self.gap;
+                sheet.label(left, top, *seed, self.text);
+            }
+        }
+
+        sheet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lay_out_sheet() {
+        let mut red = Grid::new(2, 2);
+        red[Point::ZERO] = Symbol::Red;
+        let grids: Vec<_> = (0..5).map(|seed| (seed, red.clone())).collect();
+
+        let montage = Montage {
+            tile_size: 2,
+            gap: 1,
+            labels: false,
+            ..Default::default()
+        };
+
+        let sheet = montage.render(&grids);
+        assert_eq!((sheet.width, sheet.height), (16, 11));
+        assert_eq!(sheet.pixels.len(), 16 * 11);
+
+        let at = |x: usize, y: usize| Symbol::from_palette_index(sheet.pixels[y * 16 + x]);
+        assert_eq!(at(0, 0), Some(Symbol::Dark));
+        assert_eq!(at(1, 1), Some(Symbol::Red));
+        assert_eq!(at(3, 3), Some(Symbol::Black));
+        assert_eq!(at(6, 6), Some(Symbol::Red));
+        assert_eq!(at(11, 6), Some(Symbol::Dark));
+
+        let labeled = Montage {
+            columns: 5,
+            labels: true,
+            ..montage
+        }
+        .render(&grids[..1]);
+        assert_eq!((labeled.width, labeled.height), (26, 17));
+        let white = Symbol::White.palette_index();
+        assert_eq!(
+            labeled.pixels.iter().filter(|p| **p == white).count(),
+            12 * 4
+        );
+    }
+}
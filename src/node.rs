@@ -1,12 +1,104 @@
-use rand::Rng;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use rand::{Rng, RngCore};
 
 use super::*;
+use field::{Field, Potentials};
+use observe::{Goal, Observation};
+use observer::{Application, Observer};
+use packed::PackedGrid;
+use path::PathQuery;
+use plugin::{DynNode, PluginState};
+use rule_set::RuleSet;
+use sampler::{AnySampler, Sampler};
 
+/// Something that can be stepped. Object-safe, so states can be driven as
+/// `Box<dyn Step>`.
 pub trait Step {
-    /// Performs a single step.
+    /// Performs a single step, reporting every pattern application to the
+    /// observer.
     ///
     /// Returns true if an operation was performed, i.e. this rule is not done.
-    fn step(&mut self, rng: &mut impl Rng, grid: &mut Grid) -> bool;
+    fn step(&mut self, rng: &mut dyn RngCore, grid: &mut Grid, observer: &mut dyn Observer)
+        -> bool;
+}
+
+pub(crate) fn apply(
+    grid: &mut Grid,
+    observer: &mut dyn Observer,
+    node: usize,
+    rule: usize,
+    replace: &Pattern,
+    at: Point,
+) {
+    let application = Application {
+        node,
+        rule,
+        at,
+        replace,
+    };
+
+    observer.before_apply(grid, &application);
+    grid.apply_pattern(replace, at);
+    observer.after_apply(grid, &application);
+}
+
+/// Sets up a rule node's goal the first time it steps, rewriting the observed
+/// cells as applications of rule index `rules.len()`.
+///
+/// Returns the goal and the number of cells rewritten.
+fn observe(
+    observations: &[Observation],
+    rules: &RuleSet,
+    grid: &mut Grid,
+    observer: &mut dyn Observer,
+    node: usize,
+) -> (Goal, usize) {
+    let mut goal = Goal::new(observations, rules, grid);
+    let rewrites = observe::rewrites(observations, grid);
+    let mut cell = Pattern::new(1, 1);
+    for (at, symbol) in rewrites.iter() {
+        cell.grid[0] = Some(*symbol);
+        apply(grid, observer, node, rules.len(), &cell, *at);
+    }
+
+    goal.estimate(rules, grid);
+    (goal, rewrites.len())
+}
+
+/// Finds every match of every rule, grouped by rule.
+///
+/// Packed matching can't wrap around, so periodic grids are scanned a cell
+/// at a time instead.
+pub(crate) fn find_all_matches(
+    grid: &Grid,
+    observer: &mut dyn Observer,
+    node: usize,
+    rules: &RuleSet,
+) -> Vec<Match> {
+    let mut matched = Vec::new();
+    let packed = PackedGrid::new(grid);
+
+    for idx in rules.fitting(grid) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("match", node, rule = idx).entered();
+
+        let mut found = match grid.periodic {
+            true => grid.find_matches(&rules[idx].find),
+            false => packed.find_matches(rules.packed_find(idx)),
+        };
+        if !rules[idx].conditions.is_empty() {
+            found.retain(|at| rules[idx].test_conditions(grid, *at));
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(matches = found.len());
+
+        observer.on_scan(node, idx, found.len());
+        matched.extend(found.into_iter().map(|at| Match::new(idx, at, grid.width)));
+    }
+
+    matched
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -16,59 +108,434 @@ pub enum AnyNode {
     One(OneNode),
     All(AllNode),
     Prl(PrlNode),
+    Path(PathNode),
+    Walk(WalkNode),
+    Repeat(RepeatNode),
+    If(IfNode),
+    Until(UntilNode),
+
+    /// A user-defined node. See the [plugin](crate::plugin) module.
+    Dyn(Box<dyn DynNode>),
 }
 
 impl AnyNode {
+    /// Creates a fresh state for this node.
+    ///
+    /// Every state is given the node's ID: its index in a pre-order
+    /// traversal of the tree, starting from zero at this node.
     pub fn make_state(&self) -> AnyState {
+        self.make_state_from(&mut 0)
+    }
+
+    fn make_state_from(&self, next_id: &mut usize) -> AnyState {
+        let id = *next_id;
+        *next_id += 1;
+
+        let mut children = |children: &[AnyNode]| {
+            children
+                .iter()
+                .map(|n| n.make_state_from(next_id))
+                .collect()
+        };
+
         use AnyNode::*;
         match self {
             Markov(node) => AnyState::Markov(MarkovState {
-                children: node.children.iter().map(|n| n.make_state()).collect(),
+                id,
+                name: node.name.clone(),
+                children: children(&node.children),
+                index: 0,
             }),
             Sequence(node) => AnyState::Sequence(SequenceState {
-                children: node.children.iter().map(|n| n.make_state()).collect(),
+                id,
+                name: node.name.clone(),
+                children: children(&node.children),
                 index: 0,
             }),
             One(node) => AnyState::One(OneState {
+                id,
                 node: node.to_owned(),
                 steps_taken: 0,
+                applications: 0,
+                potentials: Potentials::default(),
+                goal: None,
             }),
             All(node) => AnyState::All(AllState {
+                id,
                 node: node.to_owned(),
                 steps_taken: 0,
+                applications: 0,
+                potentials: Potentials::default(),
+                goal: None,
             }),
             Prl(node) => AnyState::Prl(PrlState {
+                id,
                 node: node.to_owned(),
+                applications: 0,
             }),
+            Path(node) => AnyState::Path(PathState {
+                id,
+                node: node.to_owned(),
+                applications: 0,
+            }),
+            Walk(node) => AnyState::Walk(WalkState {
+                id,
+                node: node.to_owned(),
+                steps_taken: 0,
+                applications: 0,
+            }),
+            Repeat(node) => {
+                let child = Box::new(node.child.make_state_from(next_id));
+                AnyState::Repeat(RepeatState {
+                    id,
+                    name: node.name.clone(),
+                    times: node.times,
+                    initial: child.clone(),
+                    child,
+                    iteration: 0,
+                    progressed: false,
+                    applications: 0,
+                })
+            }
+            If(node) => AnyState::If(IfState {
+                id,
+                name: node.name.clone(),
+                guard: node.guard.clone(),
+                present: node.present,
+                child: Box::new(node.child.make_state_from(next_id)),
+                entered: false,
+            }),
+            Until(node) => AnyState::Until(UntilState {
+                id,
+                name: node.name.clone(),
+                symbol: node.symbol,
+                threshold: node.threshold,
+                child: Box::new(node.child.make_state_from(next_id)),
+            }),
+            Dyn(node) => AnyState::Dyn(PluginState {
+                id,
+                kind: node.name(),
+                state: node.make_state(),
+            }),
+        }
+    }
+
+    /// Finds the node with the given pre-order ID.
+    pub fn find(&self, id: usize) -> Option<&AnyNode> {
+        self.find_from(id, &mut 0)
+    }
+
+    fn find_from(&self, id: usize, next_id: &mut usize) -> Option<&AnyNode> {
+        if *next_id == id {
+            return Some(self);
+        }
+
+        *next_id += 1;
+
+        self.children()
+            .iter()
+            .find_map(|child| child.find_from(id, next_id))
+    }
+
+    /// This node's direct children, if it has any.
+    pub fn children(&self) -> &[AnyNode] {
+        match self {
+            AnyNode::Markov(node) => &node.children,
+            AnyNode::Sequence(node) => &node.children,
+            AnyNode::Repeat(node) => std::slice::from_ref(&node.child),
+            AnyNode::If(node) => std::slice::from_ref(&node.child),
+            AnyNode::Until(node) => std::slice::from_ref(&node.child),
+            _ => &[],
+        }
+    }
+
+    /// This node's direct children, if it has any, mutably.
+    pub fn children_mut(&mut self) -> &mut [AnyNode] {
+        match self {
+            AnyNode::Markov(node) => &mut node.children,
+            AnyNode::Sequence(node) => &mut node.children,
+            AnyNode::Repeat(node) => std::slice::from_mut(&mut node.child),
+            AnyNode::If(node) => std::slice::from_mut(&mut node.child),
+            AnyNode::Until(node) => std::slice::from_mut(&mut node.child),
+            _ => &mut [],
+        }
+    }
+
+    /// Limits every `one`, `all`, and `walk` node in this subtree to
+    /// `steps` steps, for reusing the same subtree with different limits.
+    pub fn with_steps(mut self, steps: usize) -> Self {
+        self.set_steps(steps);
+        self
+    }
+
+    fn set_steps(&mut self, steps: usize) {
+        match self {
+            AnyNode::One(node) => node.steps = Some(steps),
+            AnyNode::All(node) => node.steps = Some(steps),
+            AnyNode::Walk(node) => node.steps = Some(steps),
+            _ => {}
+        }
+
+        for child in self.children_mut() {
+            child.set_steps(steps);
+        }
+    }
+
+    /// The name this node was given, if any. Plugin nodes have no name.
+    pub fn name(&self) -> Option<&str> {
+        use AnyNode::*;
+        let name = match self {
+            Markov(node) => &node.name,
+            Sequence(node) => &node.name,
+            One(node) => &node.name,
+            All(node) => &node.name,
+            Prl(node) => &node.name,
+            Path(node) => &node.name,
+            Walk(node) => &node.name,
+            Repeat(node) => &node.name,
+            If(node) => &node.name,
+            Until(node) => &node.name,
+            Dyn(_) => return None,
+        };
+
+        name.as_deref()
+    }
+
+    /// The name of this node's type, as used in model files.
+    pub fn kind(&self) -> &'static str {
+        use AnyNode::*;
+        match self {
+            Markov(_) => "markov",
+            Sequence(_) => "sequence",
+            One(_) => "one",
+            All(_) => "all",
+            Prl(_) => "prl",
+            Path(_) => "path",
+            Walk(_) => "walk",
+            Repeat(_) => "repeat",
+            If(_) => "if",
+            Until(_) => "until",
+            Dyn(node) => node.name(),
+        }
+    }
+
+    /// Names this node. Plugin nodes can't be named and are returned as is.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        use AnyNode::*;
+        let name = Some(name.into());
+        match &mut self {
+            Markov(node) => node.name = name,
+            Sequence(node) => node.name = name,
+            One(node) => node.name = name,
+            All(node) => node.name = name,
+            Prl(node) => node.name = name,
+            Path(node) => node.name = name,
+            Walk(node) => node.name = name,
+            Repeat(node) => node.name = name,
+            If(node) => node.name = name,
+            Until(node) => node.name = name,
+            Dyn(_) => {}
+        }
+
+        self
+    }
+
+    /// Every named node in this subtree with its ID, in pre-order. Several
+    /// nodes may share a name, e.g. copies of the same definition.
+    pub fn named_nodes(&self) -> Vec<(usize, &str)> {
+        let mut named = Vec::new();
+        self.named_nodes_from(&mut 0, &mut named);
+        named
+    }
+
+    fn named_nodes_from<'a>(&'a self, next_id: &mut usize, named: &mut Vec<(usize, &'a str)>) {
+        if let Some(name) = self.name() {
+            named.push((*next_id, name));
+        }
+
+        *next_id += 1;
+        for child in self.children() {
+            child.named_nodes_from(next_id, named);
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MarkovNode {
+    pub name: Option<String>,
     pub children: Vec<AnyNode>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SequenceNode {
+    pub name: Option<String>,
     pub children: Vec<AnyNode>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OneNode {
-    pub rules: Vec<Rule>,
+    pub name: Option<String>,
+    pub rules: RuleSet,
     pub steps: Option<usize>,
+
+    /// How the match to apply is chosen each step.
+    pub sampler: AnySampler,
+
+    /// Fields that narrow the matches offered to the sampler.
+    pub fields: Vec<Field>,
+
+    /// Observations defining a goal that narrows the matches offered to the
+    /// sampler.
+    pub observations: Vec<Observation>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AllNode {
-    pub rules: Vec<Rule>,
+    pub name: Option<String>,
+    pub rules: RuleSet,
     pub steps: Option<usize>,
+
+    /// What to do when matches overlap.
+    pub conflict: Conflict,
+
+    /// Fields that order the matches, best first.
+    pub fields: Vec<Field>,
+
+    /// Observations defining a goal that orders the matches, closest first.
+    pub observations: Vec<Observation>,
+}
+
+/// How an [AllNode] handles matches that overlap one another.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Conflict {
+    /// Matches are applied in random order, skipping any that an earlier
+    /// application has invalidated.
+    #[default]
+    Skip,
+
+    /// Every match is applied in random order, even if invalidated.
+    Overwrite,
+
+    /// Like [Conflict::Skip], but matches of earlier rules are applied first.
+    Priority,
+
+    /// The step fails without applying anything if any match writes to a
+    /// cell that another match reads or writes.
+    Fail,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PrlNode {
-    pub rules: Vec<Rule>,
+    pub name: Option<String>,
+    pub rules: RuleSet,
+}
+
+/// Draws the cheapest path between two kinds of cells each step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathNode {
+    pub name: Option<String>,
+    /// Symbols a path may start from.
+    pub from: Vec<Symbol>,
+
+    /// Symbols a path may end at.
+    pub to: Vec<Symbol>,
+
+    /// Symbols a path may cross, with the cost of stepping onto each.
+    pub on: Vec<(Symbol, u32)>,
+
+    /// The symbol drawn along the path, between its ends.
+    pub color: Symbol,
+
+    /// Whether to use an A* heuristic to speed up the search.
+    pub heuristic: bool,
+}
+
+/// Paints the trail of a random walk each step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalkNode {
+    pub name: Option<String>,
+    /// Symbols a walk may start from. The start cell is chosen uniformly.
+    pub from: Vec<Symbol>,
+
+    /// Symbols a walk may move onto.
+    pub on: Vec<Symbol>,
+
+    /// The symbol painted onto every cell the walk moves onto.
+    pub color: Symbol,
+
+    /// The maximum number of moves in a single walk.
+    pub length: usize,
+
+    /// The percentage chance of moving in the same direction as the last
+    /// move, when possible, rather than in a random direction.
+    pub persistence: u32,
+
+    pub steps: Option<usize>,
+}
+
+/// Runs its child to completion a number of times, resetting it in between.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepeatNode {
+    pub name: Option<String>,
+    pub child: Box<AnyNode>,
+
+    /// How many times to run the child. If `None`, the child is rerun until
+    /// a run does nothing.
+    pub times: Option<usize>,
+}
+
+/// Runs its child only if a guard pattern is present (or absent) anywhere on
+/// the grid when the node is first stepped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IfNode {
+    pub name: Option<String>,
+    pub guard: Pattern,
+
+    /// Whether the guard must be present, rather than absent.
+    pub present: bool,
+
+    pub child: Box<AnyNode>,
+}
+
+/// Runs its child until enough cells hold a given symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UntilNode {
+    pub name: Option<String>,
+    /// The symbol whose cells are counted, across every layer.
+    pub symbol: Symbol,
+
+    pub threshold: Threshold,
+    pub child: Box<AnyNode>,
+}
+
+/// How many cells an [UntilNode] waits for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Threshold {
+    /// At least this many cells.
+    Count(usize),
+
+    /// At least this percentage of the grid's cells.
+    Percent(u32),
+}
+
+impl Display for Threshold {
+    /// Writes the threshold as it's written in model files, e.g. `30%`.
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            Threshold::Count(count) => write!(fmt, "{}", count),
+            Threshold::Percent(percent) => write!(fmt, "{}%", percent),
+        }
+    }
+}
+
+impl Threshold {
+    /// Whether `count` of a grid's `cells` meets this threshold.
+    pub fn reached(&self, count: usize, cells: usize) -> bool {
+        match *self {
+            Threshold::Count(threshold) => count >= threshold,
+            Threshold::Percent(percent) => count * 100 >= percent as usize * cells,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -78,30 +545,312 @@ pub enum AnyState {
     One(OneState),
     All(AllState),
     Prl(PrlState),
+    Path(PathState),
+    Walk(WalkState),
+    Repeat(RepeatState),
+    If(IfState),
+    Until(UntilState),
+    Dyn(PluginState),
 }
 
 impl Step for AnyState {
-    fn step(&mut self, rng: &mut impl Rng, grid: &mut Grid) -> bool {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        use AnyState::*;
+        let id = self.id();
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::trace_span!("step", node = self.kind(), name = self.name(), id).entered();
+        observer.begin_step(id);
+
+        let stepped = match self {
+            Markov(s) => s.step(rng, grid, observer),
+            Sequence(s) => s.step(rng, grid, observer),
+            One(s) => s.step(rng, grid, observer),
+            All(s) => s.step(rng, grid, observer),
+            Prl(s) => s.step(rng, grid, observer),
+            Path(s) => s.step(rng, grid, observer),
+            Walk(s) => s.step(rng, grid, observer),
+            Repeat(s) => s.step(rng, grid, observer),
+            If(s) => s.step(rng, grid, observer),
+            Until(s) => s.step(rng, grid, observer),
+            Dyn(s) => s.state.step(s.id, rng, grid, observer),
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(stepped);
+
+        observer.end_step(id, stepped);
+        stepped
+    }
+}
+
+impl AnyState {
+    /// The name given to this state's node, if any, like [AnyNode::name].
+    pub fn name(&self) -> Option<&str> {
+        use AnyState::*;
+        let name = match self {
+            Markov(s) => &s.name,
+            Sequence(s) => &s.name,
+            One(s) => &s.node.name,
+            All(s) => &s.node.name,
+            Prl(s) => &s.node.name,
+            Path(s) => &s.node.name,
+            Walk(s) => &s.node.name,
+            Repeat(s) => &s.name,
+            If(s) => &s.name,
+            Until(s) => &s.name,
+            Dyn(_) => return None,
+        };
+
+        name.as_deref()
+    }
+
+    /// The name of this state's node type, as used in model files.
+    pub fn kind(&self) -> &'static str {
         use AnyState::*;
         match self {
-            Markov(s) => s.step(rng, grid),
-            Sequence(s) => s.step(rng, grid),
-            One(s) => s.step(rng, grid),
-            All(s) => s.step(rng, grid),
-            Prl(s) => s.step(rng, grid),
+            Markov(_) => "markov",
+            Sequence(_) => "sequence",
+            One(_) => "one",
+            All(_) => "all",
+            Prl(_) => "prl",
+            Path(_) => "path",
+            Walk(_) => "walk",
+            Repeat(_) => "repeat",
+            If(_) => "if",
+            Until(_) => "until",
+            Dyn(s) => s.kind,
         }
     }
+
+    /// The ID of the node this state was made from.
+    pub fn id(&self) -> usize {
+        use AnyState::*;
+        match self {
+            Markov(s) => s.id,
+            Sequence(s) => s.id,
+            One(s) => s.id,
+            All(s) => s.id,
+            Prl(s) => s.id,
+            Path(s) => s.id,
+            Walk(s) => s.id,
+            Repeat(s) => s.id,
+            If(s) => s.id,
+            Until(s) => s.id,
+            Dyn(s) => s.id,
+        }
+    }
+
+    /// The total number of patterns applied by this state and its children.
+    pub fn applications(&self) -> usize {
+        use AnyState::*;
+        match self {
+            Markov(s) => s.children.iter().map(|c| c.applications()).sum(),
+            Sequence(s) => s.children.iter().map(|c| c.applications()).sum(),
+            One(s) => s.applications,
+            All(s) => s.applications,
+            Prl(s) => s.applications,
+            Path(s) => s.applications,
+            Walk(s) => s.applications,
+            Repeat(s) => s.applications + s.child.applications(),
+            If(s) => s.child.applications(),
+            Until(s) => s.child.applications(),
+            Dyn(s) => s.state.applications(),
+        }
+    }
+
+    /// Appends this state's progress counters to `counters`, in pre-order.
+    pub fn save_counters(&self, counters: &mut Vec<usize>) {
+        use AnyState::*;
+        match self {
+            Markov(s) => {
+                counters.push(s.index);
+                s.children.iter().for_each(|c| c.save_counters(counters));
+            }
+            Sequence(s) => {
+                counters.push(s.index);
+                s.children.iter().for_each(|c| c.save_counters(counters));
+            }
+            One(s) => {
+                counters.extend([s.steps_taken, s.applications]);
+                s.node.sampler.save_counters(counters);
+            }
+            All(s) => counters.extend([s.steps_taken, s.applications]),
+            Prl(s) => counters.push(s.applications),
+            Path(s) => counters.push(s.applications),
+            Walk(s) => counters.extend([s.steps_taken, s.applications]),
+            Repeat(s) => {
+                counters.extend([s.iteration, s.progressed as usize, s.applications]);
+                s.child.save_counters(counters);
+            }
+            If(s) => {
+                counters.push(s.entered as usize);
+                s.child.save_counters(counters);
+            }
+            Until(s) => s.child.save_counters(counters),
+            Dyn(s) => s.state.save_counters(counters),
+        }
+    }
+
+    /// Restores progress counters saved by [AnyState::save_counters].
+    pub fn load_counters(&mut self, counters: &mut impl Iterator<Item = usize>) {
+        use AnyState::*;
+        let mut next = || counters.next().expect("too few counters");
+        match self {
+            Markov(s) => {
+                s.index = next();
+                s.children
+                    .iter_mut()
+                    .for_each(|c| c.load_counters(counters));
+            }
+            Sequence(s) => {
+                s.index = next();
+                s.children
+                    .iter_mut()
+                    .for_each(|c| c.load_counters(counters));
+            }
+            One(s) => {
+                s.steps_taken = next();
+                s.applications = next();
+                s.node.sampler.load_counters(counters);
+            }
+            All(s) => {
+                s.steps_taken = next();
+                s.applications = next();
+            }
+            Prl(s) => s.applications = next(),
+            Path(s) => s.applications = next(),
+            Walk(s) => {
+                s.steps_taken = next();
+                s.applications = next();
+            }
+            Repeat(s) => {
+                s.iteration = next();
+                s.progressed = next() != 0;
+                s.applications = next();
+                s.child.load_counters(counters);
+            }
+            If(s) => {
+                s.entered = next() != 0;
+                s.child.load_counters(counters);
+            }
+            Until(s) => s.child.load_counters(counters),
+            Dyn(s) => s.state.load_counters(counters),
+        }
+    }
+
+    /// Appends the goals and field potentials that this state's rule nodes
+    /// have cached to `words`, in pre-order.
+    pub fn save_caches(&self, words: &mut Vec<usize>) {
+        let save = |potentials: &Potentials, goal: &Option<Goal>, words: &mut Vec<usize>| {
+            potentials.save(words);
+            match goal {
+                Some(goal) => {
+                    words.push(1);
+                    goal.save(words);
+                }
+                None => words.push(0),
+            }
+        };
+
+        use AnyState::*;
+        match self {
+            One(s) => save(&s.potentials, &s.goal, words),
+            All(s) => save(&s.potentials, &s.goal, words),
+            _ => self.children().iter().for_each(|c| c.save_caches(words)),
+        }
+    }
+
+    /// Restores caches saved by [AnyState::save_caches], returning `None` if
+    /// they don't fit this state.
+    pub fn load_caches(&mut self, words: &mut impl Iterator<Item = usize>) -> Option<()> {
+        fn load(words: &mut impl Iterator<Item = usize>) -> Option<(Potentials, Option<Goal>)> {
+            let potentials = Potentials::load(words)?;
+            let goal = match words.next()? {
+                0 => None,
+                _ => Some(Goal::load(words)?),
+            };
+
+            Some((potentials, goal))
+        }
+
+        use AnyState::*;
+        match self {
+            One(s) => (s.potentials, s.goal) = load(words)?,
+            All(s) => (s.potentials, s.goal) = load(words)?,
+            Markov(MarkovState { children, .. }) | Sequence(SequenceState { children, .. }) => {
+                for child in children {
+                    child.load_caches(words)?;
+                }
+            }
+            Repeat(RepeatState { child, .. })
+            | If(IfState { child, .. })
+            | Until(UntilState { child, .. }) => child.load_caches(words)?,
+            Prl(_) | Path(_) | Walk(_) | Dyn(_) => {}
+        }
+
+        Some(())
+    }
+
+    /// This state's direct children, if it has any.
+    pub fn children(&self) -> &[AnyState] {
+        match self {
+            AnyState::Markov(s) => &s.children,
+            AnyState::Sequence(s) => &s.children,
+            AnyState::Repeat(s) => std::slice::from_ref(&s.child),
+            AnyState::If(s) => std::slice::from_ref(&s.child),
+            AnyState::Until(s) => std::slice::from_ref(&s.child),
+            _ => &[],
+        }
+    }
+
+    /// The child indices leading from this state to the currently active node.
+    pub fn active_path(&self) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut state = self;
+        loop {
+            let index = match state {
+                AnyState::Markov(s) => s.index,
+                AnyState::Sequence(s) => s.index,
+                AnyState::Repeat(_) | AnyState::If(_) | AnyState::Until(_) => 0,
+                _ => break,
+            };
+
+            path.push(index);
+
+            match state.children().get(index) {
+                Some(child) => state = child,
+                None => break,
+            }
+        }
+
+        path
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MarkovState {
+    pub id: usize,
+    pub name: Option<String>,
     pub children: Vec<AnyState>,
+    pub index: usize,
 }
 
 impl Step for MarkovState {
-    fn step(&mut self, rng: &mut impl Rng, grid: &mut Grid) -> bool {
-        for child in self.children.iter_mut() {
-            if child.step(rng, grid) {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        for (index, child) in self.children.iter_mut().enumerate() {
+            if child.step(rng, grid, observer) {
+                self.index = index;
                 return true;
             }
         }
@@ -112,32 +861,67 @@ impl Step for MarkovState {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SequenceState {
+    pub id: usize,
+    pub name: Option<String>,
     pub children: Vec<AnyState>,
     pub index: usize,
 }
 
 impl Step for SequenceState {
-    fn step(&mut self, rng: &mut impl Rng, grid: &mut Grid) -> bool {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
         while let Some(child) = self.children.get_mut(self.index) {
-            if child.step(rng, grid) {
+            if child.step(rng, grid, observer) {
                 return true;
             } else {
                 self.index += 1;
             }
         }
 
-        return false;
+        false
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OneState {
+    pub id: usize,
     pub node: OneNode,
     pub steps_taken: usize,
+    pub applications: usize,
+    pub potentials: Potentials,
+
+    /// The goal set by the node's observations, once it has first stepped.
+    pub goal: Option<Goal>,
 }
 
 impl Step for OneState {
-    fn step(&mut self, rng: &mut impl Rng, grid: &mut Grid) -> bool {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        if self.goal.is_none() && !self.node.observations.is_empty() {
+            let observations = &self.node.observations;
+            let (goal, rewritten) =
+                observe(observations, &self.node.rules, grid, observer, self.id);
+            self.applications += rewritten;
+            self.goal = Some(goal);
+            if rewritten > 0 {
+                return true;
+            }
+        }
+
+        if let Some(goal) = &self.goal {
+            if !goal.reachable() || goal.reached(grid) {
+                return false;
+            }
+        }
+
         if let Some(limit) = self.node.steps {
             if self.steps_taken >= limit {
                 return false;
@@ -146,16 +930,28 @@ impl Step for OneState {
             }
         }
 
-        let mut matched = Vec::new();
+        let mut matched = find_all_matches(grid, observer, self.id, &self.node.rules);
+        if !self.node.fields.is_empty() {
+            matched = self
+                .potentials
+                .best(&self.node.fields, grid, &self.node.rules, matched);
+        }
 
-        for (idx, rule) in self.node.rules.iter().enumerate() {
-            for at in grid.find_matches(&rule.find) {
-                matched.push((idx, at));
-            }
+        if let Some(goal) = &self.goal {
+            matched = goal.best(grid, &self.node.rules, matched);
         }
 
-        if let Some((idx, at)) = matched.choose(rng) {
-            grid.apply_pattern(&self.node.rules[*idx].replace, *at);
+        if let Some(chosen) = self.node.sampler.choose(rng, grid, &matched) {
+            let (idx, at) = matched[chosen].unpack(grid.width);
+            apply(
+                grid,
+                observer,
+                self.id,
+                idx,
+                &self.node.rules[idx].replace,
+                at,
+            );
+            self.applications += 1;
             true
         } else {
             false
@@ -165,12 +961,53 @@ impl Step for OneState {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AllState {
+    pub id: usize,
     pub node: AllNode,
     pub steps_taken: usize,
+    pub applications: usize,
+    pub potentials: Potentials,
+
+    /// The goal set by the node's observations, once it has first stepped.
+    pub goal: Option<Goal>,
 }
 
 impl Step for AllState {
-    fn step(&mut self, rng: &mut impl Rng, grid: &mut Grid) -> bool {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        self.step_with(rng, grid, observer, find_all_matches)
+    }
+}
+
+impl AllState {
+    /// Performs a step using `scan` in place of [find_all_matches].
+    pub(crate) fn step_with(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+        scan: impl FnOnce(&Grid, &mut dyn Observer, usize, &RuleSet) -> Vec<Match>,
+    ) -> bool {
+        if self.goal.is_none() && !self.node.observations.is_empty() {
+            let observations = &self.node.observations;
+            let (goal, rewritten) =
+                observe(observations, &self.node.rules, grid, observer, self.id);
+            self.applications += rewritten;
+            self.goal = Some(goal);
+            if rewritten > 0 {
+                return true;
+            }
+        }
+
+        if let Some(goal) = &self.goal {
+            if !goal.reachable() || goal.reached(grid) {
+                return false;
+            }
+        }
+
         if let Some(limit) = self.node.steps {
             if self.steps_taken >= limit {
                 return false;
@@ -179,24 +1016,40 @@ impl Step for AllState {
             }
         }
 
-        let mut matched = Vec::new();
+        let mut matched = scan(grid, observer, self.id, &self.node.rules);
 
-        for (idx, rule) in self.node.rules.iter().enumerate() {
-            for at in grid.find_matches(&rule.find) {
-                matched.push((idx, at));
-            }
+        if matched.is_empty() {
+            return false;
+        }
+
+        matched.shuffle(rng);
+
+        if !self.node.fields.is_empty() {
+            self.potentials
+                .sort(&self.node.fields, grid, &self.node.rules, &mut matched);
+        }
+
+        if let Some(goal) = &self.goal {
+            goal.sort(grid, &self.node.rules, &mut matched);
         }
 
         if matched.is_empty() {
             return false;
         }
 
-        matched.shuffle(rng);
+        match self.node.conflict {
+            Conflict::Priority => matched.sort_by_key(|matched| matched.rule),
+            Conflict::Fail if has_conflicts(grid, &self.node.rules, &matched) => return false,
+            _ => {}
+        }
 
-        for (idx, at) in matched {
+        let overwrite = self.node.conflict == Conflict::Overwrite;
+        let width = grid.width;
+        for (idx, at) in matched.iter().map(|matched| matched.unpack(width)) {
             let rule = &self.node.rules[idx];
-            if grid.test_match(&rule.find, at) {
-                grid.apply_pattern(&rule.replace, at);
+            if overwrite || rule.test_match(grid, at) {
+                apply(grid, observer, self.id, idx, &rule.replace, at);
+                self.applications += 1;
             }
         }
 
@@ -206,18 +1059,32 @@ impl Step for AllState {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PrlState {
+    pub id: usize,
     pub node: PrlNode,
+    pub applications: usize,
 }
 
 impl Step for PrlState {
-    fn step(&mut self, rng: &mut impl Rng, grid: &mut Grid) -> bool {
-        let mut matched = Vec::new();
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        self.step_with(rng, grid, observer, find_all_matches)
+    }
+}
 
-        for (idx, rule) in self.node.rules.iter().enumerate() {
-            for at in grid.find_matches(&rule.find) {
-                matched.push((idx, at));
-            }
-        }
+impl PrlState {
+    /// Performs a step using `scan` in place of [find_all_matches].
+    pub(crate) fn step_with(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+        scan: impl FnOnce(&Grid, &mut dyn Observer, usize, &RuleSet) -> Vec<Match>,
+    ) -> bool {
+        let mut matched = scan(grid, observer, self.id, &self.node.rules);
 
         if matched.is_empty() {
             return false;
@@ -225,191 +1092,740 @@ impl Step for PrlState {
 
         matched.shuffle(rng);
 
-        for (idx, at) in matched {
-            grid.apply_pattern(&self.node.rules[idx].replace, at);
+        let width = grid.width;
+        for (idx, at) in matched.iter().map(|matched| matched.unpack(width)) {
+            apply(
+                grid,
+                observer,
+                self.id,
+                idx,
+                &self.node.rules[idx].replace,
+                at,
+            );
+            self.applications += 1;
+        }
+
+        true
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathState {
+    pub id: usize,
+    pub node: PathNode,
+    pub applications: usize,
+}
+
+impl Step for PathState {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        let query = PathQuery {
+            from: &self.node.from,
+            to: &self.node.to,
+            on: &self.node.on,
+            heuristic: self.node.heuristic,
+        };
+
+        let Some(path) = grid.find_path(rng, &query) else {
+            return false;
+        };
+
+        if path.len() <= 2 {
+            return false;
+        }
+
+        let mut color = Pattern::new(1, 1);
+        color.grid[0] = Some(self.node.color);
+
+        for at in path[1..(path.len() - 1)].iter() {
+            apply(grid, observer, self.id, 0, &color, *at);
+            self.applications += 1;
         }
 
         true
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalkState {
+    pub id: usize,
+    pub node: WalkNode,
+    pub steps_taken: usize,
+    pub applications: usize,
+}
+
+impl Step for WalkState {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        if let Some(limit) = self.node.steps {
+            if self.steps_taken >= limit {
+                return false;
+            } else {
+                self.steps_taken += 1;
+            }
+        }
+
+        let starts: Vec<_> = (0..grid.height)
+            .flat_map(|y| (0..grid.width).map(move |x| Point { x, y }))
+            .filter(|at| self.node.from.contains(&grid[*at]))
+            .collect();
+
+        let Some(mut at) = starts.choose(rng).copied() else {
+            return false;
+        };
+
+        let mut color = Pattern::new(1, 1);
+        color.grid[0] = Some(self.node.color);
+
+        let mut direction = None;
+        let mut moved = false;
+        for _ in 0..self.node.length {
+            let free: Vec<_> = grid
+                .neighbors(at)
+                .filter(|next| self.node.on.contains(&grid[*next]))
+                .collect();
+
+            let keep = direction
+                .and_then(|direction| grid.offset(at, direction))
+                .filter(|next| free.contains(next))
+                .filter(|_| rng.gen_range(0..100) < self.node.persistence);
+
+            let Some(next) = keep.or_else(|| free.choose(rng).copied()) else {
+                break;
+            };
+
+            direction = Some(next - at);
+            apply(grid, observer, self.id, 0, &color, next);
+            self.applications += 1;
+            moved = true;
+            at = next;
+        }
+
+        moved
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepeatState {
+    pub id: usize,
+    pub name: Option<String>,
+    pub times: Option<usize>,
+
+    /// A fresh copy of the child's state, restored before each run.
+    pub initial: Box<AnyState>,
+    pub child: Box<AnyState>,
+
+    /// The number of completed runs of the child.
+    pub iteration: usize,
+
+    /// Whether the current run of the child has taken a step yet.
+    pub progressed: bool,
+
+    /// The number of patterns applied by completed runs.
+    pub applications: usize,
+}
+
+impl Step for RepeatState {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        loop {
+            if self.times.is_some_and(|times| self.iteration >= times) {
+                return false;
+            }
+
+            if self.child.step(rng, grid, observer) {
+                self.progressed = true;
+                return true;
+            }
+
+            if self.times.is_none() && !self.progressed {
+                return false;
+            }
+
+            self.applications += self.child.applications();
+            self.child = self.initial.clone();
+            self.iteration += 1;
+            self.progressed = false;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IfState {
+    pub id: usize,
+    pub name: Option<String>,
+    pub guard: Pattern,
+    pub present: bool,
+    pub child: Box<AnyState>,
+
+    /// Whether the guard passed and the child has started running.
+    pub entered: bool,
+}
+
+impl Step for IfState {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        if !self.entered {
+            // A guard bigger than the grid is never found, like a rule that
+            // doesn't fit.
+            let guard = &self.guard;
+            let fits = placements(grid.width, guard.width) > 0
+                && placements(grid.height, guard.height) > 0
+                && guard.layers <= grid.layers;
+            let found = fits && !grid.find_matches(guard).is_empty();
+            if found != self.present {
+                return false;
+            }
+
+            self.entered = true;
+        }
+
+        self.child.step(rng, grid, observer)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UntilState {
+    pub id: usize,
+    pub name: Option<String>,
+    pub symbol: Symbol,
+    pub threshold: Threshold,
+    pub child: Box<AnyState>,
+}
+
+impl Step for UntilState {
+    fn step(
+        &mut self,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        let count = grid.count(self.symbol);
+        if self.threshold.reached(count, grid.grid.len()) {
+            return false;
+        }
+
+        self.child.step(rng, grid, observer)
+    }
+}
+
+/// Checks whether any match writes to a cell that another match reads or
+/// writes.
+fn has_conflicts(grid: &Grid, rules: &[Rule], matched: &[Match]) -> bool {
+    let mut writers = vec![0; grid.grid.len()];
+    for (idx, at) in matched.iter().map(|matched| matched.unpack(grid.width)) {
+        for offset in cell_offsets(grid, &rules[idx].replace, at) {
+            writers[offset] += 1;
+        }
+    }
+
+    matched.iter().any(|matched| {
+        let (idx, at) = matched.unpack(grid.width);
+        let writes = cell_offsets(grid, &rules[idx].replace, at);
+        let reads = cell_offsets(grid, &rules[idx].find, at);
+        let written_by_others =
+            |offset: &usize| writers[*offset] > usize::from(writes.contains(offset));
+
+        writes.iter().any(|offset| writers[*offset] > 1) || reads.iter().any(written_by_others)
+    })
+}
+
+/// The grid offsets of a pattern's non-wildcard cells when placed at `at`.
+fn cell_offsets(grid: &Grid, pattern: &Pattern, at: Point) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    for layer in 0..pattern.layers {
+        for y in 0..pattern.height {
+            for x in 0..pattern.width {
+                let pt = Point { x, y };
+                if pattern[(pt, layer)].is_some() {
+                    offsets.push(grid.find_layer_offset(pt + at, layer));
+                }
+            }
+        }
+    }
+
+    offsets
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::recorder::GifRecorder;
+    use crate::runner::Runner;
 
-    pub fn render_gif(filename: &str, mut grid: Grid, mut state: AnyState) {
-        let mut rng = crate::tests::make_rng();
-        let tile_size = 4;
-        let width = grid.width as u16 * tile_size;
-        let height = grid.height as u16 * tile_size;
-        let mut file = std::fs::File::create(filename).unwrap();
-        let mut encoder = gif::Encoder::new(&mut file, width, height, Symbol::PALETTE).unwrap();
-        encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+    pub fn render_gif(filename: &str, grid: Grid, model: &AnyNode) {
+        let file = std::fs::File::create(filename).unwrap();
+        let recorder = GifRecorder::new(file, grid.width, grid.height, 4).unwrap();
+        let mut runner = Runner::new(model, grid, crate::tests::make_rng());
+        runner.record(recorder).unwrap();
+        println!("{}", runner.grid);
+    }
+
+    #[test]
+    fn periodic_matches() {
+        let mut grid = Grid::from_display_str("RBBB\nBBBB\nBBRW").unwrap();
+        let rules = RuleSet::new([Rule::from_strings("RW", "WR"), Rule::from_strings("B", "B")]);
+        let flat = find_all_matches(&grid, &mut (), 0, &rules);
+
+        // Neither rule has a match that wraps, so wrapping finds no more.
+        grid.periodic = true;
+        let wrapped = find_all_matches(&grid, &mut (), 0, &rules);
+        assert_eq!(flat, wrapped);
+        assert_eq!(flat[0], Match::new(0, Point { x: 2, y: 2 }, grid.width));
+        assert_eq!(flat.len(), 1 + 9);
+    }
+
+    #[test]
+    fn all_conflicts() {
+        let run = |conflict, rules: &[(&str, &str)]| {
+            let rules = rules
+                .iter()
+                .map(|(f, r)| Rule::from_strings(f, r))
+                .collect();
+            let mut state = AnyNode::All(AllNode {
+                name: None,
+                rules,
+                steps: Some(1),
+                conflict,
+                fields: Vec::new(),
+                observations: Vec::new(),
+            })
+            .make_state();
+
+            let mut grid = Grid::new(8, 3);
+            let stepped = state.step(&mut crate::tests::make_rng(), &mut grid, &mut ());
+            (stepped, state.applications(), grid)
+        };
+
+        let (stepped, applications, grid) = run(Conflict::Skip, &[("BB", "RR")]);
+        assert!(stepped);
+        // Each row of eight cells fits three or four dominoes that don't
+        // overlap and leave no room for another.
+        assert!((9..=12).contains(&applications));
+        let red = grid.grid.iter().filter(|s| **s == Symbol::Red).count();
+        assert_eq!(red, applications * 2);
+
+        let (_, applications, _) = run(Conflict::Overwrite, &[("BB", "RR")]);
+        assert_eq!(applications, 7 * 3);
 
-        let mut frames = Vec::new();
-        let mut counter = 0;
-        while state.step(&mut rng, &mut grid) {
-            println!("Stepping...");
+        let (stepped, applications, grid) = run(Conflict::Fail, &[("BB", "RR")]);
+        assert!(!stepped);
+        assert_eq!(applications, 0);
+        assert_eq!(grid, Grid::new(8, 3));
 
-            counter += 1;
-            if counter >= 64 {
-                counter = 0;
-                let mut frame = grid.render_gif_frame(tile_size);
-                frame.delay = 2;
-                frames.push(frame);
+        let (_, _, grid) = run(Conflict::Priority, &[("BB", "RR"), ("BB", "GG")]);
+        assert!(grid.grid.contains(&Symbol::Red));
+        assert!(!grid.grid.contains(&Symbol::Green));
+    }
+
+    #[test]
+    fn repeat_child() {
+        let child = AnyNode::One(OneNode {
+            name: None,
+            rules: vec![Rule::from_strings("B", "R")].into(),
+            steps: Some(2),
+            sampler: Default::default(),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let run = |times| {
+            let mut grid = Grid::new(8, 8);
+            let mut state = AnyNode::Repeat(RepeatNode {
+                name: None,
+                child: Box::new(child.clone()),
+                times,
+            })
+            .make_state();
+
+            let mut rng = crate::tests::make_rng();
+            let mut steps = 0;
+            while state.step(&mut rng, &mut grid, &mut ()) {
+                steps += 1;
+                assert_eq!(state.applications(), steps);
             }
-        }
 
-        let mut frame = grid.render_gif_frame(tile_size);
-        frame.delay = 1000;
-        frames.push(frame);
+            steps
+        };
+
+        assert_eq!(run(Some(3)), 6);
+        assert_eq!(run(Some(0)), 0);
+        assert_eq!(run(None), 64);
+    }
+
+    #[test]
+    fn if_guard() {
+        let run = |size, guard, present| {
+            let mut grid = Grid::new(size, size);
+            grid[Point { x: 1, y: 1 }] = Symbol::Red;
+            let mut state = AnyNode::If(IfNode {
+                name: None,
+                guard: Pattern::from_string(guard),
+                present,
+                child: Box::new(AnyNode::All(AllNode {
+                    name: None,
+                    rules: vec![Rule::from_strings("B", "W")].into(),
+                    steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
+                })),
+            })
+            .make_state();
 
-        for frame in frames {
-            encoder.write_frame(&frame).unwrap();
+            while state.step(&mut crate::tests::make_rng(), &mut grid, &mut ()) {}
+            grid[Point::ZERO]
+        };
+
+        assert_eq!(run(8, "R", true), Symbol::White);
+        assert_eq!(run(8, "R", false), Symbol::Black);
+
+        // A guard bigger than the grid is never found.
+        assert_eq!(run(2, "RRR", true), Symbol::Black);
+        assert_eq!(run(2, "RRR", false), Symbol::White);
+    }
+
+    #[test]
+    fn until_threshold() {
+        let run = |threshold| {
+            let mut grid = Grid::new(10, 10);
+            let mut state = AnyNode::Until(UntilNode {
+                name: None,
+                symbol: Symbol::Emerald,
+                threshold,
+                child: Box::new(AnyNode::One(OneNode {
+                    name: None,
+                    rules: vec![Rule::from_strings("B", "E")].into(),
+                    steps: None,
+                    sampler: AnySampler::Uniform,
+                    fields: Vec::new(),
+                    observations: Vec::new(),
+                })),
+            })
+            .make_state();
+
+            while state.step(&mut crate::tests::make_rng(), &mut grid, &mut ()) {}
+            grid.count(Symbol::Emerald)
+        };
+
+        assert_eq!(run(Threshold::Count(12)), 12);
+        assert_eq!(run(Threshold::Percent(30)), 30);
+
+        assert_eq!(run(Threshold::Percent(100)), 100);
+    }
+
+    #[test]
+    fn boxed_states() {
+        let fill = |color: &str| {
+            AnyNode::One(OneNode {
+                name: None,
+                rules: vec![Rule::from_strings("B", color)].into(),
+                steps: Some(4),
+                sampler: AnySampler::Uniform,
+                fields: Vec::new(),
+                observations: Vec::new(),
+            })
+        };
+
+        let mut states: Vec<Box<dyn Step>> = vec![
+            Box::new(fill("W").make_state()),
+            Box::new(fill("R").make_state()),
+        ];
+
+        let mut grid = Grid::new(8, 8);
+        let mut rng = crate::tests::make_rng();
+        let mut steps = 0;
+        for state in states.iter_mut() {
+            while state.step(&mut rng, &mut grid, &mut ()) {
+                steps += 1;
+            }
         }
 
-        println!("{}", grid);
+        assert_eq!(steps, 8);
+        assert!(grid.grid.contains(&Symbol::White));
+        assert!(grid.grid.contains(&Symbol::Red));
+    }
+
+    #[test]
+    fn walk_trail() {
+        let mut grid = Grid::new(16, 16);
+        grid[Point { x: 8, y: 8 }] = Symbol::Red;
+
+        let mut state = AnyNode::Walk(WalkNode {
+            name: None,
+            from: vec![Symbol::Red],
+            on: vec![Symbol::Black],
+            color: Symbol::White,
+            length: 20,
+            persistence: 90,
+            steps: Some(3),
+        })
+        .make_state();
+
+        let mut rng = crate::tests::make_rng();
+        while state.step(&mut rng, &mut grid, &mut ()) {}
+
+        let painted = grid.grid.iter().filter(|s| **s == Symbol::White).count();
+        assert_eq!(painted, state.applications());
+        assert!(painted > 20 && painted <= 60);
+        assert_eq!(grid[Point { x: 8, y: 8 }], Symbol::Red);
     }
 
     #[test]
     fn nystrom_dungeon() {
         use AnyNode::*;
         let model = Sequence(SequenceNode {
+            name: None,
             children: vec![
                 One(OneNode {
-                    rules: vec![Rule::from_strings("B", "P")],
+                    name: None,
+                    rules: vec![Rule::from_strings("B", "P")].into(),
                     steps: Some(1),
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: Rule::from_strings("PBB", "**P").make_rotations(),
+                    name: None,
+                    rules: Rule::from_strings("PBB", "**P").make_rotations().into(),
                     steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 One(OneNode {
+                    name: None,
                     rules: Rule::from_strings(
                         "PBPBPBPBP/BBBBBBBBB/PBPBPBPBP/BBBBBBBBB/PBPBPBPBP/BBBBBBBBB/PBPBPBPBP",
                         "WWWWWWWWW/WWWWWWWWW/WWWWWWWWW/WWWWWWWWW/WWWWWWWWW/WWWWWWWWW/WWWWWWWWW",
                     )
-                    .make_rotations(),
+                    .make_rotations()
+                    .into(),
                     steps: None,
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 Markov(MarkovNode {
+                    name: None,
                     children: vec![
                         One(OneNode {
-                            rules: Rule::from_strings("RBP", "GGR").make_rotations(),
+                            name: None,
+                            rules: Rule::from_strings("RBP", "GGR").make_rotations().into(),
                             steps: None,
+                            sampler: Default::default(),
+                            fields: Vec::new(),
+                            observations: Vec::new(),
                         }),
                         One(OneNode {
-                            rules: Rule::from_strings("GGR", "RWW").make_rotations(),
+                            name: None,
+                            rules: Rule::from_strings("GGR", "RWW").make_rotations().into(),
                             steps: None,
+                            sampler: Default::default(),
+                            fields: Vec::new(),
+                            observations: Vec::new(),
                         }),
                         One(OneNode {
-                            rules: vec![Rule::from_strings("P", "R")],
+                            name: None,
+                            rules: vec![Rule::from_strings("P", "R")].into(),
                             steps: None,
+                            sampler: Default::default(),
+                            fields: Vec::new(),
+                            observations: Vec::new(),
                         }),
                     ],
                 }),
                 One(OneNode {
-                    rules: vec![Rule::from_strings("R", "G")],
+                    name: None,
+                    rules: vec![Rule::from_strings("R", "G")].into(),
                     steps: Some(1),
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: vec![Rule::from_strings("R", "W")],
+                    name: None,
+                    rules: vec![Rule::from_strings("R", "W")].into(),
                     steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 Markov(MarkovNode {
+                    name: None,
                     children: vec![
                         All(AllNode {
-                            rules: Rule::from_strings("GWW", "**G").make_rotations(),
+                            name: None,
+                            rules: Rule::from_strings("GWW", "**G").make_rotations().into(),
                             steps: None,
+                            conflict: Default::default(),
+                            fields: Vec::new(),
+                            observations: Vec::new(),
                         }),
                         One(OneNode {
-                            rules: Rule::from_strings("GBW", "*WG").make_rotations(),
+                            name: None,
+                            rules: Rule::from_strings("GBW", "*WG").make_rotations().into(),
                             steps: None,
+                            sampler: Default::default(),
+                            fields: Vec::new(),
+                            observations: Vec::new(),
                         }),
                     ],
                 }),
                 One(OneNode {
-                    rules: Rule::from_strings("GBG", "*W*").make_rotations(),
+                    name: None,
+                    rules: Rule::from_strings("GBG", "*W*").make_rotations().into(),
                     steps: Some(5),
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 One(OneNode {
-                    rules: vec![Rule::from_strings("G", "W")],
+                    name: None,
+                    rules: vec![Rule::from_strings("G", "W")].into(),
                     steps: None,
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: Rule::from_strings("BBB/BWB", "BBB/BBB").make_rotations(),
+                    name: None,
+                    rules: Rule::from_strings("BBB/BWB", "BBB/BBB")
+                        .make_rotations()
+                        .into(),
                     steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
             ],
         });
 
         let grid = Grid::new(64, 64);
-        let state = model.make_state();
-        render_gif("nystrom-dungeon.gif", grid, state);
+        render_gif("nystrom-dungeon.gif", grid, &model);
     }
 
     #[test]
     fn river() {
         use AnyNode::*;
         let model = Sequence(SequenceNode {
+            name: None,
             children: vec![
                 One(OneNode {
-                    rules: vec![Rule::from_strings("B", "W")],
+                    name: None,
+                    rules: vec![Rule::from_strings("B", "W")].into(),
                     steps: Some(1),
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 One(OneNode {
-                    rules: vec![Rule::from_strings("B", "R")],
+                    name: None,
+                    rules: vec![Rule::from_strings("B", "R")].into(),
                     steps: Some(1),
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 One(OneNode {
-                    rules: vec![
+                    name: None,
+                    rules: [
                         Rule::from_strings("RB", "RR").make_rotations(),
                         Rule::from_strings("WB", "WW").make_rotations(),
                     ]
-                    .concat(),
+                    .concat()
+                    .into(),
                     steps: None,
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: Rule::from_strings("RW", "UU").make_rotations(),
+                    name: None,
+                    rules: Rule::from_strings("RW", "UU").make_rotations().into(),
                     steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: vec![
+                    name: None,
+                    rules: [
                         Rule::from_strings("W", "B").make_rotations(),
                         Rule::from_strings("R", "B").make_rotations(),
                     ]
-                    .concat(),
+                    .concat()
+                    .into(),
                     steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: Rule::from_strings("UB", "UU").make_rotations(),
+                    name: None,
+                    rules: Rule::from_strings("UB", "UU").make_rotations().into(),
                     steps: Some(1),
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: Rule::from_strings("BU/UB", "U*/**").make_rotations(),
+                    name: None,
+                    rules: Rule::from_strings("BU/UB", "U*/**").make_rotations().into(),
                     steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 All(AllNode {
-                    rules: Rule::from_strings("UB", "*G").make_rotations(),
+                    name: None,
+                    rules: Rule::from_strings("UB", "*G").make_rotations().into(),
                     steps: None,
+                    conflict: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 One(OneNode {
-                    rules: vec![Rule::from_strings("B", "E")],
+                    name: None,
+                    rules: vec![Rule::from_strings("B", "E")].into(),
                     steps: Some(13),
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
                 One(OneNode {
-                    rules: vec![
+                    name: None,
+                    rules: [
                         Rule::from_strings("EB", "*E").make_rotations(),
                         Rule::from_strings("GB", "*G").make_rotations(),
                     ]
-                    .concat(),
+                    .concat()
+                    .into(),
                     steps: None,
+                    sampler: Default::default(),
+                    fields: Vec::new(),
+                    observations: Vec::new(),
                 }),
             ],
         });
 
         let grid = Grid::new(128, 128);
-        let state = model.make_state();
-        render_gif("river.gif", grid, state);
+        render_gif("river.gif", grid, &model);
     }
 }
@@ -0,0 +1,106 @@
+//! Exporting meshes as Wavefront OBJ files.
+//!
+//! OBJ keeps colors out of the geometry, in a separate material library
+//! (`.mtl`) file that the OBJ file names. [Mesh::to_obj] writes each merged
+//! face as a quad that uses the material of its symbol, and [Mesh::to_mtl]
+//! writes the matching library, with each material's diffuse color taken
+//! from the [palette](Symbol::PALETTE). Materials are named after their
+//! symbols in lowercase, e.g. `red`.
+//!
+//! Single-layer grids mesh as their non-black cells raised one unit off the
+//! ground, so 2D outputs come out as extrusions of their floor plans.
+
+use std::fmt::Write;
+
+use super::*;
+use mesh::Mesh;
+
+/// The name of `symbol`'s material.
+pub fn material_name(symbol: Symbol) -> String {
+    format!("{:?}", symbol).to_lowercase()
+}
+
+impl Mesh {
+    /// Writes the mesh as an OBJ file that takes its materials from the
+    /// library at `mtllib`, a path relative to the OBJ file.
+    pub fn to_obj(&self, mtllib: &str) -> String {
+        let mut obj = String::from("# crabby-markov\n");
+        writeln!(obj, "mtllib {}", mtllib).unwrap();
+
+        for vertex in self.vertices.iter() {
+            let [x, y, z] = vertex.position;
+            writeln!(obj, "v {} {} {}", x, y, z).unwrap();
+        }
+
+        for vertex in self.vertices.iter() {
+            let [x, y, z] = vertex.normal;
+            writeln!(obj, "vn {} {} {}", x, y, z).unwrap();
+        }
+
+        for (symbol, range) in self.symbol_ranges() {
+            writeln!(obj, "usemtl {}", material_name(symbol)).unwrap();
+
+            // Every quad's six indices start with its four vertices in
+            // order. OBJ indices count from one.
+            for quad in range.step_by(6).map(|index| index / 6) {
+                obj.push('f');
+                for vertex in quad * 4 + 1..quad * 4 + 5 {
+                    write!(obj, " {}//{}", vertex, vertex).unwrap();
+                }
+
+                obj.push('\n');
+            }
+        }
+
+        obj
+    }
+
+    /// Writes a material library with a material for each symbol in the
+    /// mesh.
+    pub fn to_mtl(&self) -> String {
+        let mut mtl = String::from("# crabby-markov\n");
+        for (symbol, _) in self.symbol_ranges() {
+            let rgb = &Symbol::PALETTE[symbol.palette_index() as usize * 3..][..3];
+            let [r, g, b] = [0, 1, 2].map(|i| rgb[i] as f32 / 255.0);
+            writeln!(mtl, "\nnewmtl {}", material_name(symbol)).unwrap();
+            writeln!(mtl, "Kd {:.4} {:.4} {:.4}", r, g, b).unwrap();
+        }
+
+        mtl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_obj() {
+        let mut grid = Grid::new(3, 1);
+        grid[Point { x: 0, y: 0 }] = Symbol::Red;
+        grid[Point { x: 2, y: 0 }] = Symbol::White;
+        let mesh = grid.mesh();
+
+        let obj = mesh.to_obj("out.mtl");
+        let lines: Vec<_> = obj.lines().collect();
+        assert_eq!(lines[1], "mtllib out.mtl");
+        assert_eq!(lines.iter().filter(|l| l.starts_with("v ")).count(), 48);
+        assert_eq!(lines.iter().filter(|l| l.starts_with("vn ")).count(), 48);
+        assert!(lines.contains(&"v 3 1 1"));
+
+        let materials: Vec<_> = lines.iter().filter(|l| l.starts_with("usemtl")).collect();
+        assert_eq!(materials, [&"usemtl white", &"usemtl red"]);
+        let faces: Vec<_> = lines.iter().filter(|l| l.starts_with("f ")).collect();
+        assert_eq!(faces.len(), 12);
+        assert_eq!(*faces[0], "f 1//1 2//2 3//3 4//4");
+        assert_eq!(*faces[11], "f 45//45 46//46 47//47 48//48");
+
+        let mtl = mesh.to_mtl();
+        assert!(
+            mtl.contains("newmtl white\nKd 1.0000 0.9451 0.9098\n"),
+            "{}",
+            mtl
+        );
+        assert!(mtl.contains("newmtl red\n"));
+    }
+}
@@ -0,0 +1,334 @@
+//! Steering rule nodes toward a goal state, as in MarkovJunior's `<observe>`
+//! elements.
+//!
+//! Each [Observation] says that the cells holding a symbol when a node first
+//! steps must end up holding one of a set of symbols, optionally rewriting
+//! them to another symbol in the meantime. From these a node builds a [Goal]:
+//! it propagates potentials backward from the goal through its rules to learn
+//! how many applications each symbol needs at each cell to reach the goal,
+//! and forward from the grid to estimate whether the goal can be reached at
+//! all.
+//!
+//! Applications that would write a symbol from which the goal can't be
+//! reached are rejected, and of the rest only those that move closest to the
+//! goal are offered to the node's sampler. Goals only consider the bottom
+//! layer, and ignore rules that span several layers.
+
+use super::*;
+use rule_set::RuleSet;
+
+const SYMBOLS: usize = Symbol::ALL.len();
+
+/// A constraint on the cells holding `value` when a rule node first steps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Observation {
+    pub value: Symbol,
+
+    /// A symbol to rewrite the observed cells to before the node applies any
+    /// rules.
+    pub from: Option<Symbol>,
+
+    /// The symbols the observed cells may hold once the goal is reached.
+    pub to: Vec<Symbol>,
+}
+
+/// Lists the cells `observations` rewrite when their node first steps, with
+/// the symbols they are rewritten to.
+pub fn rewrites(observations: &[Observation], grid: &Grid) -> Vec<(Point, Symbol)> {
+    grid.enumerate_points()
+        .filter_map(|at| {
+            let observation = find(observations, grid[at])?;
+            let from = observation.from.filter(|from| *from != grid[at])?;
+            Some((at, from))
+        })
+        .collect()
+}
+
+fn find(observations: &[Observation], value: Symbol) -> Option<&Observation> {
+    observations
+        .iter()
+        .find(|observation| observation.value == value)
+}
+
+/// The state a rule node's observations steer it toward.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Goal {
+    /// The symbols each cell may hold in the goal, as bit masks indexed by
+    /// palette index.
+    targets: GenericGrid<u32>,
+
+    /// The number of applications needed to reach the goal from each symbol
+    /// at each cell, indexed by palette index, or `None` if it can't be.
+    potentials: Vec<GenericGrid<Option<u32>>>,
+
+    reachable: bool,
+}
+
+impl Goal {
+    /// Reads the goal from the cells `observations` apply to, before they are
+    /// rewritten, and propagates potentials backward from it through `rules`.
+    pub fn new(observations: &[Observation], rules: &RuleSet, grid: &Grid) -> Self {
+        let mut targets = cells_like(grid);
+        for at in grid.enumerate_points() {
+            targets[at] = match find(observations, grid[at]) {
+                Some(observation) => mask(&observation.to),
+                None => u32::MAX,
+            };
+        }
+
+        let mut potentials = vec![cells_like(grid); SYMBOLS];
+        for at in grid.enumerate_points() {
+            for (symbol, potential) in potentials.iter_mut().enumerate() {
+                if targets[at] & 1 << symbol != 0 {
+                    potential[at] = Some(0);
+                }
+            }
+        }
+
+        propagate(&mut potentials, rules, |rule| (&rule.replace, &rule.find));
+        Self {
+            targets,
+            potentials,
+            reachable: true,
+        }
+    }
+
+    /// Estimates whether the goal can be reached from `grid` by propagating
+    /// potentials forward from it through `rules`.
+    ///
+    /// The estimate checks each cell on its own, so it may miss goals that
+    /// are unreachable because of how cells interact.
+    pub fn estimate(&mut self, rules: &RuleSet, grid: &Grid) {
+        let mut potentials = vec![cells_like(grid); SYMBOLS];
+        for at in grid.enumerate_points() {
+            potentials[grid[at].palette_index() as usize][at] = Some(0);
+        }
+
+        propagate(&mut potentials, rules, |rule| (&rule.find, &rule.replace));
+        self.reachable = grid.enumerate_points().all(|at| {
+            (0..SYMBOLS).any(|symbol| {
+                self.targets[at] & 1 << symbol != 0 && potentials[symbol][at].is_some()
+            })
+        });
+    }
+
+    /// Appends this goal to `words`, for [checkpoints](crate::checkpoint).
+    pub fn save(&self, words: &mut Vec<usize>) {
+        checkpoint::save_grid(&self.targets, words, |mask| *mask as usize);
+        words.push(self.potentials.len());
+        for potential in &self.potentials {
+            checkpoint::save_grid(potential, words, checkpoint::potential_word);
+        }
+
+        words.push(self.reachable as usize);
+    }
+
+    /// Reads a goal written by [Goal::save].
+    pub fn load(words: &mut impl Iterator<Item = usize>) -> Option<Self> {
+        let targets = checkpoint::load_grid(words, |word| u32::try_from(word).ok())?;
+        let potentials = (0..words.next()?)
+            .map(|_| checkpoint::load_grid(words, checkpoint::word_potential))
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            targets,
+            potentials,
+            reachable: words.next()? != 0,
+        })
+    }
+
+    /// Whether the last [Goal::estimate] found the goal reachable.
+    pub fn reachable(&self) -> bool {
+        self.reachable
+    }
+
+    /// Whether every cell holds one of its goal symbols.
+    pub fn reached(&self, grid: &Grid) -> bool {
+        grid.enumerate_points()
+            .all(|at| self.targets[at] & 1 << grid[at].palette_index() != 0)
+    }
+
+    /// Narrows `matches` to those that keep the goal reachable and move
+    /// closest to it.
+    pub fn best(&self, grid: &Grid, rules: &RuleSet, matches: Vec<Match>) -> Vec<Match> {
+        let scores: Vec<_> = matches
+            .iter()
+            .map(|matched| self.score(grid, rules, matched))
+            .collect();
+
+        let Some(best) = scores.iter().flatten().min().copied() else {
+            return Vec::new();
+        };
+
+        matches
+            .into_iter()
+            .zip(scores)
+            .filter(|(_, score)| *score == Some(best))
+            .map(|(matched, _)| matched)
+            .collect()
+    }
+
+    /// Sorts `matches` closest to the goal first, keeping the existing order
+    /// between equal scores, and drops those that make it unreachable.
+    pub fn sort(&self, grid: &Grid, rules: &RuleSet, matches: &mut Vec<Match>) {
+        let mut scored: Vec<_> = matches
+            .drain(..)
+            .filter_map(|matched| Some((self.score(grid, rules, &matched)?, matched)))
+            .collect();
+
+        scored.sort_by_key(|(score, _)| *score);
+        matches.extend(scored.into_iter().map(|(_, matched)| matched));
+    }
+
+    /// Scores a match by the change in potential of the cells it writes,
+    /// lower being better, or `None` if it writes a symbol the goal can't
+    /// be reached from.
+    fn score(&self, grid: &Grid, rules: &RuleSet, matched: &Match) -> Option<i64> {
+        let (rule, at) = matched.unpack(grid.width);
+        let replace = &rules[rule].replace;
+        let mut score = 0;
+        for (offset, new) in cells(replace) {
+            let at = at + offset;
+            let old = grid[at];
+            if new == old {
+                continue;
+            }
+
+            score += self.potentials[new.palette_index() as usize][at]? as i64;
+            if let Some(old) = self.potentials[old.palette_index() as usize][at] {
+                score -= old as i64;
+            }
+        }
+
+        Some(score)
+    }
+}
+
+/// A grid of default values the same size as `grid`, wrapping if it does.
+fn cells_like<T: Clone + Default>(grid: &Grid) -> GenericGrid<T> {
+    let mut cells = GenericGrid::new(grid.width, grid.height);
+    cells.periodic = grid.periodic;
+    cells
+}
+
+fn mask(symbols: &[Symbol]) -> u32 {
+    symbols
+        .iter()
+        .fold(0, |mask, symbol| mask | 1 << symbol.palette_index())
+}
+
+/// The set cells of a pattern's bottom layer, with their offsets.
+fn cells(pattern: &Pattern) -> impl Iterator<Item = (Point, Symbol)> + '_ {
+    pattern.grid[..pattern.width * pattern.height]
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, cell)| {
+            let offset = Point {
+                x: idx % pattern.width,
+                y: idx / pattern.width,
+            };
+
+            Some((offset, (*cell)?))
+        })
+}
+
+/// Spreads potentials through `rules` one application at a time.
+///
+/// `sides` picks which of a rule's patterns must already be reachable (the
+/// source) and which gains potentials (the destination). A rule placed where
+/// every source cell is reachable gives each destination cell it changes a
+/// potential one greater than the highest source potential.
+fn propagate(
+    potentials: &mut [GenericGrid<Option<u32>>],
+    rules: &RuleSet,
+    sides: impl Fn(&Rule) -> (&Pattern, &Pattern),
+) {
+    let (width, height) = (potentials[0].width, potentials[0].height);
+    let periodic = potentials[0].periodic;
+    let rules: Vec<_> = rules
+        .iter()
+        .filter(|rule| rule.find.layers == 1)
+        .filter(|rule| rule.find.width <= width && rule.find.height <= height)
+        .map(&sides)
+        .collect();
+
+    for t in 0.. {
+        let mut changed = false;
+        for (source, destination) in rules.iter() {
+            let (rows, columns) = match periodic {
+                true => (height, width),
+                false => (height - source.height + 1, width - source.width + 1),
+            };
+
+            for y in 0..rows {
+                for x in 0..columns {
+                    let at = Point { x, y };
+                    let mut highest = 0;
+                    let reachable = cells(source).all(|(offset, symbol)| {
+                        let potential = potentials[symbol.palette_index() as usize][at + offset];
+                        highest = highest.max(potential.unwrap_or(u32::MAX));
+                        potential.is_some_and(|potential| potential <= t)
+                    });
+
+                    if !reachable || highest != t {
+                        continue;
+                    }
+
+                    for (offset, symbol) in cells(destination) {
+                        let unchanged = source[offset] == Some(symbol);
+                        let potential =
+                            &mut potentials[symbol.palette_index() as usize][at + offset];
+                        if !unchanged && potential.is_none() {
+                            *potential = Some(t + 1);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reach_goal() {
+        let grid = Grid::from_display_str("RBBBBW").unwrap();
+        let observations = [Observation {
+            value: Symbol::White,
+            from: Some(Symbol::Black),
+            to: vec![Symbol::Red],
+        }];
+
+        let rules: RuleSet = Rule::with_symmetry("RB", "RR", Symmetry::Rotations).into();
+        assert_eq!(
+            rewrites(&observations, &grid),
+            [(Point { x: 5, y: 0 }, Symbol::Black)]
+        );
+
+        let mut goal = Goal::new(&observations, &rules, &grid);
+        let mut grid = Grid::from_display_str("RBBBBB").unwrap();
+        goal.estimate(&rules, &grid);
+        assert!(goal.reachable());
+        assert!(!goal.reached(&grid));
+
+        let matches = vec![Match::new(0, Point::ZERO, grid.width)];
+        assert_eq!(goal.best(&grid, &rules, matches.clone()), matches);
+
+        for x in 1..6 {
+            grid[Point { x, y: 0 }] = Symbol::Red;
+        }
+
+        assert!(goal.reached(&grid));
+
+        let stuck = Grid::from_display_str("BBBBBB").unwrap();
+        goal.estimate(&rules, &stuck);
+        assert!(!goal.reachable());
+    }
+}
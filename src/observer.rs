@@ -0,0 +1,117 @@
+use super::*;
+
+/// A single application of a rule's replacement pattern.
+#[derive(Copy, Clone, Debug)]
+pub struct Application<'a> {
+    /// The ID of the applying node. See [AnyNode::make_state](crate::node::AnyNode::make_state).
+    pub node: usize,
+
+    /// The index of the applied rule within its node.
+    pub rule: usize,
+
+    /// Where the replacement pattern's top-left corner is placed.
+    pub at: Point,
+
+    pub replace: &'a Pattern,
+}
+
+impl Application<'_> {
+    /// Reads the grid's symbols at every cell the replacement writes to.
+    ///
+    /// Called from [Observer::before_apply] this gives the overwritten
+    /// cells, and from [Observer::after_apply] the newly written ones.
+    pub fn read_cells(&self, grid: &Grid) -> Pattern {
+        let replace = self.replace;
+        let mut cells = Pattern::with_layers(replace.width, replace.height, replace.layers);
+        for layer in 0..replace.layers {
+            for x in 0..replace.width {
+                for y in 0..replace.height {
+                    let pt = Point { x, y };
+                    if replace[(pt, layer)].is_some() {
+                        cells[(pt, layer)] = Some(grid[(pt + self.at, layer)]);
+                    }
+                }
+            }
+        }
+
+        cells
+    }
+}
+
+/// Receives notifications as node states step and apply patterns.
+///
+/// Nodes are identified by their IDs. See
+/// [AnyNode::make_state](crate::node::AnyNode::make_state).
+pub trait Observer {
+    /// Called when a node begins an attempted step.
+    fn begin_step(&mut self, _node: usize) {}
+
+    /// Called after a rule node searches the grid for one of its rules.
+    fn on_scan(&mut self, _node: usize, _rule: usize, _matches: usize) {}
+
+    /// Called just before a pattern is applied.
+    fn before_apply(&mut self, _grid: &Grid, _application: &Application) {}
+
+    /// Called just after a pattern is applied.
+    fn after_apply(&mut self, _grid: &Grid, _application: &Application) {}
+
+    /// Called when a node's attempted step ends, with whether it did
+    /// anything.
+    fn end_step(&mut self, _node: usize, _stepped: bool) {}
+}
+
+/// Ignores every notification.
+impl Observer for () {}
+
+impl<T: Observer + ?Sized> Observer for &mut T {
+    fn begin_step(&mut self, node: usize) {
+        (**self).begin_step(node);
+    }
+
+    fn on_scan(&mut self, node: usize, rule: usize, matches: usize) {
+        (**self).on_scan(node, rule, matches);
+    }
+
+    fn before_apply(&mut self, grid: &Grid, application: &Application) {
+        (**self).before_apply(grid, application);
+    }
+
+    fn after_apply(&mut self, grid: &Grid, application: &Application) {
+        (**self).after_apply(grid, application);
+    }
+
+    fn end_step(&mut self, node: usize, stepped: bool) {
+        (**self).end_step(node, stepped);
+    }
+}
+
+impl<T: Observer + ?Sized> Observer for Box<T> {
+    fn begin_step(&mut self, node: usize) {
+        (**self).begin_step(node);
+    }
+
+    fn on_scan(&mut self, node: usize, rule: usize, matches: usize) {
+        (**self).on_scan(node, rule, matches);
+    }
+
+    fn before_apply(&mut self, grid: &Grid, application: &Application) {
+        (**self).before_apply(grid, application);
+    }
+
+    fn after_apply(&mut self, grid: &Grid, application: &Application) {
+        (**self).after_apply(grid, application);
+    }
+
+    fn end_step(&mut self, node: usize, stepped: bool) {
+        (**self).end_step(node, stepped);
+    }
+}
+
+/// Calls a closure after every application.
+pub struct OnApply<F>(pub F);
+
+impl<F: FnMut(&Grid, &Application)> Observer for OnApply<F> {
+    fn after_apply(&mut self, grid: &Grid, application: &Application) {
+        (self.0)(grid, application);
+    }
+}
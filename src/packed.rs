@@ -0,0 +1,174 @@
+//! Pattern matching eight cells at a time.
+//!
+//! A [PackedGrid] stores one byte per cell, and a [PackedPattern] stores
+//! each row as 64-bit words of expected bytes plus a mask that clears
+//! wildcards. Testing a row is then a XOR and an AND per eight cells
+//! instead of a branch per cell.
+
+use super::*;
+
+/// One word of a packed pattern row.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Word {
+    value: u64,
+    mask: u64,
+}
+
+/// A pattern packed for fast matching against a [PackedGrid].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedPattern {
+    pub width: usize,
+    pub height: usize,
+    pub layers: usize,
+    words: Vec<Word>,
+}
+
+impl PackedPattern {
+    pub fn new(pattern: &Pattern) -> Self {
+        let rows = pattern.height * pattern.layers;
+        let mut words = Vec::with_capacity(pattern.width.div_ceil(8) * rows);
+
+        // Empty patterns have no rows to pack, and can't be chunked into them.
+        let pattern_rows = match pattern.width {
+            0 => [].chunks(1),
+            width => pattern.grid.chunks(width),
+        };
+
+        for row in pattern_rows {
+            for chunk in row.chunks(8) {
+                let mut word = Word { value: 0, mask: 0 };
+                for (idx, cell) in chunk.iter().enumerate() {
+                    if let Some(symbol) = cell {
+                        word.value |= (*symbol as u64) << (idx * 8);
+                        word.mask |= 0xff << (idx * 8);
+                    }
+                }
+
+                words.push(word);
+            }
+        }
+
+        Self {
+            width: pattern.width,
+            height: pattern.height,
+            layers: pattern.layers,
+            words,
+        }
+    }
+}
+
+/// A snapshot of a grid with one byte per cell.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedGrid {
+    pub width: usize,
+    pub height: usize,
+    pub layers: usize,
+    cells: Vec<u8>,
+}
+
+impl PackedGrid {
+    pub fn new(grid: &Grid) -> Self {
+        Self {
+            width: grid.width,
+            height: grid.height,
+            layers: grid.layers,
+            cells: grid.grid.iter().map(|symbol| *symbol as u8).collect(),
+        }
+    }
+
+    /// Loads eight cells starting at `offset` into a word, padding with
+    /// zeros past the end of the grid. Cells past the end of a pattern row
+    /// are cleared by its mask.
+    fn load(&self, offset: usize) -> u64 {
+        match self.cells.get(offset..(offset + 8)) {
+            Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+            None => {
+                let rest = &self.cells[offset..];
+                let mut bytes = [0; 8];
+                bytes[..rest.len()].copy_from_slice(rest);
+                u64::from_le_bytes(bytes)
+            }
+        }
+    }
+
+    /// Tests a packed pattern against the grid, like [Grid::test_match].
+    ///
+    /// The pattern must fit inside the grid at `at`.
+    pub fn test_match(&self, pattern: &PackedPattern, at: Point) -> bool {
+        let mut words = pattern.words.iter();
+        for layer in 0..pattern.layers {
+            for y in 0..pattern.height {
+                let row = (layer * self.height + at.y + y) * self.width + at.x;
+                for start in (0..pattern.width).step_by(8) {
+                    let word = words.next().unwrap();
+                    if (self.load(row + start) ^ word.value) & word.mask != 0 {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Finds every match of a packed pattern, scanning the same positions
+    /// in the same order as [Grid::find_matches].
+    pub fn find_matches(&self, pattern: &PackedPattern) -> Vec<Point> {
+        let mut found = Vec::new();
+        let free_width = placements(self.width, pattern.width);
+        let free_height = placements(self.height, pattern.height);
+
+        for x in 0..free_width {
+            for y in 0..free_height {
+                let test_pt = Point { x, y };
+                if self.test_match(pattern, test_pt) {
+                    found.push(test_pt);
+                }
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_matches() {
+        use rand::Rng;
+
+        let mut rng = crate::tests::make_rng();
+        let mut grid = Grid::with_layers(24, 20, 2);
+        for cell in grid.grid.iter_mut() {
+            *cell = Symbol::from_palette_index(rng.gen_range(0..3)).unwrap();
+        }
+
+        let packed = PackedGrid::new(&grid);
+        for pattern in [
+            "B",
+            "WR",
+            "B*W/*RB",
+            "BBBBBBBBB*",
+            "R* *W",
+            "*********W/BBBBBBBBBB",
+        ] {
+            let pattern = Pattern::from_string(pattern);
+            assert_eq!(
+                packed.find_matches(&PackedPattern::new(&pattern)),
+                grid.find_matches(&pattern),
+            );
+        }
+
+        let empty = PackedPattern::new(&Pattern::new(0, 0));
+        assert_eq!(empty.words, []);
+
+        // Matches may start in the last column and row.
+        let cell = PackedPattern::new(&Pattern::from_string("B"));
+        assert_eq!(
+            PackedGrid::new(&Grid::new(3, 3)).find_matches(&cell).len(),
+            9
+        );
+    }
+}
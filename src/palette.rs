@@ -0,0 +1,156 @@
+//! Alternative colors for rendering symbols.
+//!
+//! Symbols are colored by [Symbol::PALETTE] unless a renderer is given
+//! another [Palette]. Palettes only change how a grid is drawn, so a model's
+//! outputs look different without any of its symbols changing, and indexed
+//! renders such as [Grid::render_indexed] are the same under every palette.
+//!
+//! Every palette has a color for all 32 symbols, so some share colors or
+//! are told apart only by lightness where a palette has fewer distinct hues.
+
+use super::*;
+
+/// A set of colors for every symbol.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Palette {
+    /// [Symbol::PALETTE]: PICO-8's sixteen colors for the uppercase symbols
+    /// and its sixteen secret colors for the lowercase ones.
+    #[default]
+    Default,
+
+    /// Only PICO-8's sixteen standard colors, with each lowercase symbol
+    /// drawn in the standard color nearest to its own.
+    Pico8,
+
+    /// Each symbol's [Palette::Default] color reduced to its luma.
+    Grayscale,
+
+    /// Hues from the Okabe-Ito palette, which stay distinguishable under the
+    /// common forms of color blindness. Lowercase symbols are darker
+    /// versions of their uppercase counterparts.
+    Colorblind,
+
+    /// Fully saturated colors against black, for projectors and low-vision
+    /// use. Lowercase symbols are darker versions of their uppercase
+    /// counterparts.
+    HighContrast,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 5] = [
+        Palette::Default,
+        Palette::Pico8,
+        Palette::Grayscale,
+        Palette::Colorblind,
+        Palette::HighContrast,
+    ];
+
+    /// Flat RGB triples for every symbol, laid out like [Symbol::PALETTE].
+    pub fn colors(&self) -> &'static [u8] {
+        use Palette::*;
+        match self {
+            Default => Symbol::PALETTE,
+            Pico8 => &PICO8,
+            Grayscale => &GRAYSCALE,
+            Colorblind => &COLORBLIND,
+            HighContrast => &HIGH_CONTRAST,
+        }
+    }
+
+    pub fn rgb(&self, symbol: Symbol) -> [u8; 3] {
+        let rgb = &self.colors()[symbol.palette_index() as usize * 3..][..3];
+        [rgb[0], rgb[1], rgb[2]]
+    }
+
+    /// Converts palette indices, as rendered by [Grid::render_indexed], to
+    /// RGBA pixels.
+    pub fn to_rgba(&self, indices: &[u8]) -> Vec<u8> {
+        let colors = self.colors();
+        indices
+            .iter()
+            .flat_map(|index| {
+                let rgb = &colors[*index as usize * 3..][..3];
+                [rgb[0], rgb[1], rgb[2], 0xff]
+            })
+            .collect()
+    }
+}
+
+/// Lays out `0xRRGGBB` colors as flat RGB triples.
+const fn flatten(colors: [u32; 32]) -> [u8; 96] {
+    let mut flat = [0; 96];
+    let mut idx = 0;
+    while idx < colors.len() {
+        let [_, r, g, b] = colors[idx].to_be_bytes();
+        flat[idx * 3] = r;
+        flat[idx * 3 + 1] = g;
+        flat[idx * 3 + 2] = b;
+        idx += 1;
+    }
+
+    flat
+}
+
+// Each table is in palette order, eight symbols to a line.
+
+const PICO8: [u8; 96] = flatten([
+    0x000000, 0xfff1e8, 0xff004d, 0x00e436, 0x29adff, 0x008751, 0x7e2553, 0x1d2b53, //
+    0xab5236, 0x5f574f, 0xc2c3c7, 0xffa300, 0xffec27, 0x83769c, 0xff77a8, 0xffccaa, //
+    0x000000, 0x1d2b53, 0x1d2b53, 0x1d2b53, 0x7e2553, 0x5f574f, 0x83769c, 0xffccaa, //
+    0x7e2553, 0xffa300, 0xffec27, 0x008751, 0x008751, 0x5f574f, 0xff77a8, 0xff77a8, //
+]);
+
+const GRAYSCALE: [u8; 96] = flatten([
+    0x000000, 0xf4f4f4, 0x555555, 0x8c8c8c, 0x8f8f8f, 0x585858, 0x454545, 0x2b2b2b, //
+    0x696969, 0x585858, 0xc3c3c3, 0xacacac, 0xdbdbdb, 0x7e7e7e, 0xa5a5a5, 0xd7d7d7, //
+    0x1d1d1d, 0x1c1c1c, 0x2d2d2d, 0x404040, 0x434343, 0x3a3a3a, 0x8e8e8e, 0xe3e3e3, //
+    0x4c4c4c, 0x909090, 0xbfbfbf, 0x727272, 0x4b4b4b, 0x585858, 0x979797, 0xb7b7b7, //
+]);
+
+const COLORBLIND: [u8; 96] = flatten([
+    0x000000, 0xffffff, 0xd55e00, 0x009e73, 0x56b4e9, 0x00604a, 0xcc79a7, 0x0072b2, //
+    0x8f3f00, 0x595959, 0xbbbbbb, 0xe69f00, 0xf0e442, 0x7a87b8, 0xf2b8d2, 0xf5d9a0, //
+    0x333333, 0x999999, 0x803800, 0x005f45, 0x346c8c, 0x003a2c, 0x7a4964, 0x00446b, //
+    0x562600, 0x353535, 0x707070, 0x8a5f00, 0x908928, 0x49516e, 0x916e7e, 0x938260, //
+]);
+
+const HIGH_CONTRAST: [u8; 96] = flatten([
+    0x000000, 0xffffff, 0xff0000, 0x00ff00, 0x0080ff, 0x00ffc0, 0xc000ff, 0x4040ff, //
+    0xa05000, 0x606060, 0xb0b0b0, 0xff8000, 0xffff00, 0x8080c0, 0xff60c0, 0xffd090, //
+    0x404040, 0x999999, 0x990000, 0x009900, 0x004d99, 0x009973, 0x730099, 0x262699, //
+    0x603000, 0x3a3a3a, 0x6a6a6a, 0x994d00, 0x999900, 0x4d4d73, 0x993a73, 0x997d56, //
+]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes() {
+        for palette in Palette::ALL {
+            assert_eq!(palette.colors().len(), Symbol::PALETTE.len());
+            assert_eq!(palette.rgb(Symbol::Black), [0, 0, 0]);
+        }
+
+        assert_eq!(Palette::Default.rgb(Symbol::Red), [0xff, 0x00, 0x4d]);
+        assert_eq!(
+            Palette::Pico8.rgb(Symbol::Lime),
+            Palette::Pico8.rgb(Symbol::Yellow)
+        );
+        assert_eq!(Palette::HighContrast.rgb(Symbol::Red), [0xff, 0, 0]);
+        assert!(Palette::Grayscale
+            .colors()
+            .chunks(3)
+            .all(|rgb| rgb[0] == rgb[1] && rgb[1] == rgb[2]));
+
+        let grid = Grid::from_display_str("BR").unwrap();
+        assert_eq!(
+            grid.render_rgba_with(1, Palette::Colorblind),
+            [0, 0, 0, 0xff, 0xd5, 0x5e, 0x00, 0xff]
+        );
+        assert_eq!(
+            grid.render_rgba(1),
+            Palette::Default.to_rgba(&grid.render_indexed(1))
+        );
+    }
+}
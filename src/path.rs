@@ -0,0 +1,171 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use rand::{Rng, RngCore};
+
+use super::*;
+
+/// What a path may start from, end at, and travel through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathQuery<'a> {
+    /// Symbols a path may start from.
+    pub from: &'a [Symbol],
+
+    /// Symbols a path may end at.
+    pub to: &'a [Symbol],
+
+    /// Symbols a path may travel through, with the cost of stepping onto
+    /// each. Stepping onto the final cell is free.
+    pub on: &'a [(Symbol, u32)],
+
+    /// Whether to guide the search towards the nearest end cell. This finds
+    /// equally cheap paths while usually exploring far fewer cells.
+    pub heuristic: bool,
+}
+
+impl Grid {
+    /// Finds the cheapest path from any `from` cell to any `to` cell,
+    /// moving orthogonally through `on` cells, with ties between equally
+    /// cheap paths broken randomly.
+    ///
+    /// Returns every cell along the path, including both ends. Only the
+    /// bottom layer is considered.
+    pub fn find_path(&self, rng: &mut dyn RngCore, query: &PathQuery) -> Option<Vec<Point>> {
+        let point = |offset: usize| Point {
+            x: offset % self.width,
+            y: offset / self.width,
+        };
+
+        let cost = |symbol: Symbol| {
+            query
+                .on
+                .iter()
+                .find(|(on, _)| *on == symbol)
+                .map(|(_, cost)| *cost)
+        };
+
+        let goals: Vec<_> = (0..self.width * self.height)
+            .map(point)
+            .filter(|at| query.to.contains(&self[*at]))
+            .collect();
+
+        // Paths may cross the edges of periodic grids, so the distance along
+        // each axis is the shorter of the two ways around.
+        let axis = |a: usize, b: usize, size: usize| match self.periodic {
+            true => a.abs_diff(b).min(size - a.abs_diff(b)),
+            false => a.abs_diff(b),
+        };
+
+        let min_cost = query.on.iter().map(|(_, cost)| *cost).min().unwrap_or(0);
+        let estimate = |at: Point| {
+            let nearest = goals
+                .iter()
+                .map(|goal| axis(at.x, goal.x, self.width) + axis(at.y, goal.y, self.height))
+                .min()
+                .unwrap_or(0);
+
+            nearest.saturating_sub(1) as u32 * min_cost
+        };
+
+        let mut best: Vec<Option<u32>> = vec![None; self.width * self.height];
+        let mut came_from = vec![None; self.width * self.height];
+        let mut open = BinaryHeap::new();
+
+        for (offset, at) in (0..self.width * self.height).map(|o| (o, point(o))) {
+            if query.from.contains(&self[at]) {
+                best[offset] = Some(0);
+                open.push((Reverse(0), rng.gen::<u32>(), offset));
+            }
+        }
+
+        while let Some((_, _, offset)) = open.pop() {
+            let at = point(offset);
+            let cost_so_far = best[offset].unwrap();
+
+            for neighbor in self.neighbors(at) {
+                let next_offset = self.find_offset(neighbor);
+                let symbol = self[neighbor];
+
+                if query.to.contains(&symbol) && best[next_offset].is_none() {
+                    let mut path = vec![neighbor, at];
+                    let mut current = offset;
+                    while let Some(previous) = came_from[current] {
+                        path.push(point(previous));
+                        current = previous;
+                    }
+
+                    path.reverse();
+                    return Some(path);
+                }
+
+                let Some(step) = cost(symbol) else {
+                    continue;
+                };
+
+                let next_cost = cost_so_far + step;
+                if best[next_offset].is_some_and(|best| best <= next_cost) {
+                    continue;
+                }
+
+                best[next_offset] = Some(next_cost);
+                came_from[next_offset] = Some(offset);
+
+                let priority = match query.heuristic {
+                    true => next_cost + estimate(neighbor),
+                    false => next_cost,
+                };
+
+                open.push((Reverse(priority), rng.gen(), next_offset));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &str) -> Grid {
+        let pattern = Pattern::from_string(rows);
+        Grid {
+            width: pattern.width,
+            height: pattern.height,
+            layers: 1,
+            grid: pattern.grid.into_iter().flatten().collect(),
+            periodic: false,
+        }
+    }
+
+    #[test]
+    fn weighted_paths() {
+        let mut rng = crate::tests::make_rng();
+        let grid = grid("RBBBG/UUUUU/UUUUU");
+
+        let on = [(Symbol::Black, 10), (Symbol::Blue, 1)];
+        for heuristic in [false, true] {
+            let query = PathQuery {
+                from: &[Symbol::Red],
+                to: &[Symbol::Green],
+                on: &on,
+                heuristic,
+            };
+
+            let path = grid.find_path(&mut rng, &query).unwrap();
+            assert_eq!(path.first(), Some(&Point { x: 0, y: 0 }));
+            assert_eq!(path.last(), Some(&Point { x: 4, y: 0 }));
+            assert_eq!(path.len(), 7);
+            assert!(path[1..6].iter().all(|at| grid[*at] == Symbol::Blue));
+        }
+
+        let query = PathQuery {
+            from: &[Symbol::Red],
+            to: &[Symbol::Green],
+            on: &[(Symbol::White, 1)],
+            heuristic: false,
+        };
+
+        assert_eq!(grid.find_path(&mut rng, &query), None);
+    }
+}
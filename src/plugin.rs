@@ -0,0 +1,233 @@
+//! Extension points for node types defined outside this crate.
+//!
+//! A custom node implements [DynNode] and is placed in a tree as
+//! [AnyNode::Dyn], and its state implements [DynState]. Both get their
+//! cloning and comparison from [Clone] and [PartialEq] implementations.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use rand::RngCore;
+
+use super::*;
+use node::AnyNode;
+use observer::Observer;
+
+/// A user-defined node type.
+pub trait DynNode: DynNodeBase + Debug + Send + Sync {
+    /// The node type's name, used for tracing and debugging.
+    fn name(&self) -> &'static str;
+
+    /// Creates a fresh state for this node.
+    fn make_state(&self) -> Box<dyn DynState>;
+}
+
+/// A user-defined node state. See [Step](crate::node::Step).
+pub trait DynState: DynStateBase + Debug + Send + Sync {
+    /// Performs a single step, reporting every pattern application to the
+    /// observer with the given node ID.
+    ///
+    /// Returns true if an operation was performed.
+    fn step(
+        &mut self,
+        id: usize,
+        rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool;
+
+    /// The total number of patterns applied by this state.
+    fn applications(&self) -> usize {
+        0
+    }
+
+    /// Appends the counters needed to rewind this state. States that don't
+    /// save any are not restored by [Runner::rewind](crate::runner::Runner::rewind).
+    fn save_counters(&self, _counters: &mut Vec<usize>) {}
+
+    /// Restores counters saved by [DynState::save_counters].
+    fn load_counters(&mut self, _counters: &mut dyn Iterator<Item = usize>) {}
+}
+
+/// Object-safe cloning and comparison for [DynNode], implemented for every
+/// node type that is [Clone] and [PartialEq].
+pub trait DynNodeBase {
+    fn clone_node(&self) -> Box<dyn DynNode>;
+    fn eq_node(&self, other: &dyn Any) -> bool;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: DynNode + Clone + PartialEq + 'static> DynNodeBase for T {
+    fn clone_node(&self) -> Box<dyn DynNode> {
+        Box::new(self.clone())
+    }
+
+    fn eq_node(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Object-safe cloning and comparison for [DynState], implemented for every
+/// state type that is [Clone] and [PartialEq].
+pub trait DynStateBase {
+    fn clone_state(&self) -> Box<dyn DynState>;
+    fn eq_state(&self, other: &dyn Any) -> bool;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: DynState + Clone + PartialEq + 'static> DynStateBase for T {
+    fn clone_state(&self) -> Box<dyn DynState> {
+        Box::new(self.clone())
+    }
+
+    fn eq_state(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Clone for Box<dyn DynNode> {
+    fn clone(&self) -> Self {
+        self.clone_node()
+    }
+}
+
+impl PartialEq for dyn DynNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_node(DynNodeBase::as_any(other))
+    }
+}
+
+impl Eq for dyn DynNode {}
+
+impl Clone for Box<dyn DynState> {
+    fn clone(&self) -> Self {
+        self.clone_state()
+    }
+}
+
+impl PartialEq for dyn DynState {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_state(DynStateBase::as_any(other))
+    }
+}
+
+impl Eq for dyn DynState {}
+
+/// The state of an [AnyNode::Dyn] node.
+#[derive(Clone, Debug)]
+pub struct PluginState {
+    pub id: usize,
+    pub kind: &'static str,
+    pub state: Box<dyn DynState>,
+}
+
+// Deriving these runs into rust-lang/rust#31740 with the boxed trait object.
+impl PartialEq for PluginState {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.kind == other.kind && *self.state == *other.state
+    }
+}
+
+impl Eq for PluginState {}
+
+impl AnyNode {
+    /// Wraps a user-defined node.
+    pub fn from_dyn(node: impl DynNode + 'static) -> Self {
+        AnyNode::Dyn(Box::new(node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::{AnyState, SequenceNode, Step};
+    use runner::Runner;
+
+    /// Flips one random cell to white per step, a fixed number of times.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Sprinkle(usize);
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct SprinkleState {
+        remaining: usize,
+        applications: usize,
+    }
+
+    impl DynNode for Sprinkle {
+        fn name(&self) -> &'static str {
+            "sprinkle"
+        }
+
+        fn make_state(&self) -> Box<dyn DynState> {
+            Box::new(SprinkleState {
+                remaining: self.0,
+                applications: 0,
+            })
+        }
+    }
+
+    impl DynState for SprinkleState {
+        fn step(
+            &mut self,
+            _id: usize,
+            rng: &mut dyn RngCore,
+            grid: &mut Grid,
+            _observer: &mut dyn Observer,
+        ) -> bool {
+            if self.remaining == 0 {
+                return false;
+            }
+
+            let offset = rng.next_u32() as usize % grid.grid.len();
+            grid.grid[offset] = Symbol::White;
+            self.remaining -= 1;
+            self.applications += 1;
+            true
+        }
+
+        fn applications(&self) -> usize {
+            self.applications
+        }
+    }
+
+    #[test]
+    fn custom_node() {
+        let model = AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: vec![AnyNode::from_dyn(Sprinkle(5))],
+        });
+
+        assert_eq!(model.clone(), model);
+        assert_ne!(
+            AnyNode::from_dyn(Sprinkle(4)),
+            AnyNode::from_dyn(Sprinkle(5))
+        );
+
+        let state = model.make_state();
+        let AnyState::Sequence(sequence) = &state else {
+            panic!("expected a sequence");
+        };
+
+        assert_eq!(sequence.children[0].kind(), "sprinkle");
+        assert_eq!(sequence.children[0].name(), None);
+        assert_eq!(sequence.children[0].id(), 1);
+
+        let mut runner = Runner::new(&model, Grid::new(4, 4), crate::tests::make_rng());
+        assert_eq!(runner.steps().count(), 5);
+        assert_eq!(runner.progress().applications, 5);
+        assert!(runner.grid.grid.contains(&Symbol::White));
+        assert!(!runner.state.clone().step(
+            &mut crate::tests::make_rng(),
+            &mut runner.grid,
+            &mut ()
+        ));
+    }
+}
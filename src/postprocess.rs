@@ -0,0 +1,369 @@
+//! Cleanup passes for finished grids.
+//!
+//! Rewrite rules tend to leave a few rough edges: jagged region borders,
+//! lone cells of one symbol inside another, corridors a single cell wide,
+//! and rooms that run into the edge of the map. Each [Pass] fixes one of
+//! these, and can be run directly on a grid through its [Grid] method or
+//! added to a model as a [PassNode] that runs once the rewrite phases
+//! before it are done:
+//!
+//! ```ignore
+//! use crabby_markov::{postprocess::{Pass, PassNode}, Symbol::*};
+//!
+//! let cleanup = vec![
+//!     AnyNode::from_dyn(PassNode::new(Pass::Smooth { symbols: vec![Black, White] }, 3)),
+//!     AnyNode::from_dyn(PassNode::new(Pass::SealBorder { wall: Black }, 1)),
+//! ];
+//! ```
+//!
+//! Like [Grid::components], which despeckling is built on, passes only
+//! change the bottom layer, and their neighborhoods wrap around the edges
+//! of periodic grids.
+
+use rand::RngCore;
+
+use super::*;
+use node::apply;
+use observer::Observer;
+use plugin::{DynNode, DynState};
+
+const SYMBOLS: usize = Symbol::ALL.len();
+
+/// The eight cells around a cell, as counted by [Grid::smooth].
+const SURROUNDING: [Offset; 8] = [
+    Offset { dx: -1, dy: -1 },
+    Offset { dx: 0, dy: -1 },
+    Offset { dx: 1, dy: -1 },
+    Offset { dx: -1, dy: 0 },
+    Offset { dx: 1, dy: 0 },
+    Offset { dx: -1, dy: 1 },
+    Offset { dx: 0, dy: 1 },
+    Offset { dx: 1, dy: 1 },
+];
+
+/// A single cleanup operation. See the [module](self) docs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pass {
+    /// See [Grid::smooth].
+    Smooth { symbols: Vec<Symbol> },
+
+    /// See [Grid::despeckle].
+    Despeckle {
+        symbols: Vec<Symbol>,
+        min_size: usize,
+    },
+
+    /// See [Grid::widen].
+    Widen { corridor: Symbol, over: Vec<Symbol> },
+
+    /// See [Grid::seal_border].
+    SealBorder { wall: Symbol },
+}
+
+impl Pass {
+    /// Runs the pass on `grid`, returning how many cells changed.
+    pub fn apply(&self, grid: &mut Grid) -> usize {
+        use Pass::*;
+        match self {
+            Smooth { symbols } => grid.smooth(symbols),
+            Despeckle { symbols, min_size } => grid.despeckle(symbols, *min_size),
+            Widen { corridor, over } => grid.widen(*corridor, over),
+            SealBorder { wall } => grid.seal_border(*wall),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        use Pass::*;
+        match self {
+            Smooth { .. } => "smooth",
+            Despeckle { .. } => "despeckle",
+            Widen { .. } => "widen",
+            SealBorder { .. } => "seal border",
+        }
+    }
+}
+
+/// The symbol with the most votes, preferring the earliest in palette
+/// order on ties, or `None` without any votes.
+fn most_voted(votes: &[usize; SYMBOLS]) -> Option<Symbol> {
+    let most = *votes.iter().max()?;
+    let index = votes.iter().position(|count| *count == most)?;
+    (most > 0).then(|| Symbol::ALL[index])
+}
+
+impl Grid {
+    /// Replaces each cell of `symbols` with the symbol held by more than
+    /// half of the cells around it, diagonals included, counting only
+    /// neighbors that are also in `symbols`. Cells without such a majority
+    /// are left alone, as are cells of other symbols, so that doors and
+    /// other features survive smoothing the walls around them.
+    ///
+    /// Every cell votes on the grid as it was before the pass. Returns how
+    /// many cells changed.
+    pub fn smooth(&mut self, symbols: &[Symbol]) -> usize {
+        let before = self.clone();
+        let mut changed = 0;
+        for at in before.enumerate_points() {
+            let here = before[at];
+            if !symbols.contains(&here) {
+                continue;
+            }
+
+            let mut votes = [0; SYMBOLS];
+            let mut neighbors = 0;
+            for offset in SURROUNDING {
+                let Some(near) = before.offset(at, offset) else {
+                    continue;
+                };
+
+                neighbors += 1;
+                if symbols.contains(&before[near]) {
+                    votes[before[near].palette_index() as usize] += 1;
+                }
+            }
+
+            let Some(winner) = most_voted(&votes) else {
+                continue;
+            };
+
+            if winner != here && votes[winner.palette_index() as usize] * 2 > neighbors {
+                self[at] = winner;
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+
+    /// Fills every connected region of a single symbol from `symbols` that
+    /// has fewer than `min_size` cells with the symbol most often found
+    /// orthogonally next to it. Regions that nothing borders, i.e. ones
+    /// that fill the whole grid, are left alone.
+    ///
+    /// Returns how many cells changed.
+    pub fn despeckle(&mut self, symbols: &[Symbol], min_size: usize) -> usize {
+        let before = self.clone();
+        let mut changed = 0;
+        for symbol in symbols {
+            let components = before.components(&[*symbol]);
+            let mut regions = vec![Vec::new(); components.len()];
+            for (at, label) in components.labels.iter() {
+                if let Some(label) = label {
+                    regions[*label as usize].push(at);
+                }
+            }
+
+            for (label, region) in regions.iter().enumerate() {
+                if region.len() >= min_size {
+                    continue;
+                }
+
+                let mut votes = [0; SYMBOLS];
+                for at in region {
+                    for near in before.neighbors(*at) {
+                        if components.labels[near] != Some(label as u32) {
+                            votes[before[near].palette_index() as usize] += 1;
+                        }
+                    }
+                }
+
+                let Some(fill) = most_voted(&votes) else {
+                    continue;
+                };
+
+                for at in region {
+                    self[*at] = fill;
+                }
+
+                changed += region.len();
+            }
+        }
+
+        changed
+    }
+
+    /// Turns every cell of `over` orthogonally next to a `corridor` cell
+    /// into `corridor`, widening corridors by a cell on each side.
+    ///
+    /// Returns how many cells changed.
+    pub fn widen(&mut self, corridor: Symbol, over: &[Symbol]) -> usize {
+        let before = self.clone();
+        let mut changed = 0;
+        for at in before.enumerate_points() {
+            if !over.contains(&before[at]) || before[at] == corridor {
+                continue;
+            }
+
+            if before.neighbors(at).any(|near| before[near] == corridor) {
+                self[at] = corridor;
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+
+    /// Sets every cell along the edges of the grid to `wall`, closing off
+    /// any region that runs into them.
+    ///
+    /// Returns how many cells changed.
+    pub fn seal_border(&mut self, wall: Symbol) -> usize {
+        let (width, height) = (self.width, self.height);
+        let mut changed = 0;
+        for (at, cell) in self.iter_mut() {
+            let edge = at.x == 0 || at.y == 0 || at.x + 1 == width || at.y + 1 == height;
+            if edge && *cell != wall {
+                *cell = wall;
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+}
+
+/// A node that runs a [Pass] up to `repeat` times, one run per step,
+/// stopping early once a run changes nothing.
+///
+/// Every changed cell is reported to observers as an application of a
+/// single-cell pattern with rule index 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassNode {
+    pub pass: Pass,
+    pub repeat: usize,
+}
+
+impl PassNode {
+    pub fn new(pass: Pass, repeat: usize) -> Self {
+        Self { pass, repeat }
+    }
+}
+
+impl DynNode for PassNode {
+    fn name(&self) -> &'static str {
+        self.pass.name()
+    }
+
+    fn make_state(&self) -> Box<dyn DynState> {
+        Box::new(PassState {
+            pass: self.pass.clone(),
+            remaining: self.repeat,
+            applications: 0,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PassState {
+    pass: Pass,
+    remaining: usize,
+    applications: usize,
+}
+
+impl DynState for PassState {
+    fn step(
+        &mut self,
+        id: usize,
+        _rng: &mut dyn RngCore,
+        grid: &mut Grid,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        if self.remaining == 0 {
+            return false;
+        }
+
+        let mut after = grid.clone();
+        if self.pass.apply(&mut after) == 0 {
+            self.remaining = 0;
+            return false;
+        }
+
+        let mut cell = Pattern::new(1, 1);
+        for (at, symbol) in after.iter() {
+            if grid[at] != *symbol {
+                cell.grid[0] = Some(*symbol);
+                apply(grid, observer, id, 0, &cell, at);
+                self.applications += 1;
+            }
+        }
+
+        self.remaining -= 1;
+        true
+    }
+
+    fn applications(&self) -> usize {
+        self.applications
+    }
+
+    fn save_counters(&self, counters: &mut Vec<usize>) {
+        counters.extend([self.remaining, self.applications]);
+    }
+
+    fn load_counters(&mut self, counters: &mut dyn Iterator<Item = usize>) {
+        self.remaining = counters.next().unwrap_or_default();
+        self.applications = counters.next().unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::{AnyNode, SequenceNode};
+    use runner::Runner;
+    use Symbol::*;
+
+    #[test]
+    fn cleanup_passes() {
+        let mut grid = Grid::from_display_str(
+            "
+            WWWWWW
+            WWBWWW
+            WWWWBB
+            BBRBBB
+            BBBBBB
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(grid.smooth(&[Black, White]), 1);
+        assert_eq!(grid.to_string(), "WWWWWW\nWWWWWW\nWWWWBB\nBBRBBB\nBBBBBB\n");
+        assert_eq!(grid.smooth(&[Black, White]), 0);
+
+        let mut grid = Grid::from_display_str("WWWR\nWBWW\nWWWW\nRRWW").unwrap();
+        assert_eq!(grid.despeckle(&[Black, Red], 2), 2);
+        assert_eq!(grid.to_string(), "WWWW\nWWWW\nWWWW\nRRWW\n");
+        assert_eq!(Grid::new(2, 2).despeckle(&[Black], 10), 0);
+
+        let mut grid = Grid::from_display_str("BBBBB\nBBBBB\nWWWWW\nBBBBB\nRRRRR").unwrap();
+        assert_eq!(grid.widen(White, &[Black]), 10);
+        assert_eq!(grid.to_string(), "BBBBB\nWWWWW\nWWWWW\nWWWWW\nRRRRR\n");
+
+        let mut grid = Grid::from_display_str("WWW\nWWW\nWBW\nWWW").unwrap();
+        assert_eq!(grid.seal_border(Black), 10);
+        assert_eq!(grid.to_string(), "BBB\nBWB\nBBB\nBBB\n");
+
+        let model = AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: vec![
+                AnyNode::from_dyn(PassNode::new(
+                    Pass::Widen {
+                        corridor: White,
+                        over: vec![Black],
+                    },
+                    8,
+                )),
+                AnyNode::from_dyn(PassNode::new(Pass::SealBorder { wall: Red }, 3)),
+            ],
+        });
+
+        let mut grid = Grid::new(7, 7);
+        grid[Point { x: 3, y: 3 }] = White;
+        let mut runner = Runner::new(&model, grid, crate::tests::make_rng());
+        assert_eq!(runner.steps().count(), 7);
+        assert_eq!(runner.progress().applications, 48 + 24);
+        assert_eq!(
+            runner.grid.to_string(),
+            "RRRRRRR\nRWWWWWR\nRWWWWWR\nRWWWWWR\nRWWWWWR\nRWWWWWR\nRRRRRRR\n"
+        );
+    }
+}
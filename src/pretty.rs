@@ -0,0 +1,177 @@
+//! Readable, indented printing of node and state trees.
+//!
+//! The [Display] impls of [AnyNode] and [AnyState] write one line per node,
+//! indented two spaces per level, in place of their derived [Debug] output,
+//! which buries the tree's shape under every rule's patterns:
+//!
+//! ```text
+//! sequence #0
+//!   repeat #1: times: 3
+//!     one #2: 4 rules, steps: 10
+//!   all #3: 1 rule
+//! ```
+//!
+//! Nodes show their limits, the same as [AnyNode::to_dot]. States show how
+//! far they've gotten instead, along with the patterns they've applied:
+//!
+//! ```text
+//! sequence #0: index: 0/2, applications: 3
+//!   repeat #1: iteration: 1/3, applications: 3
+//!     one #2: steps: 2/10, applications: 2
+//!   all #3: steps: 0, applications: 0
+//! ```
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::*;
+use dot::details;
+use node::{AnyNode, AnyState};
+
+impl Display for AnyNode {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write_node(fmt, self, &mut 0, 0)
+    }
+}
+
+fn write_node(fmt: &mut Formatter, node: &AnyNode, next_id: &mut usize, depth: usize) -> FmtResult {
+    write!(
+        fmt,
+        "{:indent$}{} #{}",
+        "",
+        node.kind(),
+        next_id,
+        indent = depth * 2
+    )?;
+    *next_id += 1;
+
+    if let Some(name) = node.name() {
+        write!(fmt, " {:?}", name)?;
+    }
+
+    let details = details(node);
+    if !details.is_empty() {
+        write!(fmt, ": {}", details.join(", "))?;
+    }
+
+    writeln!(fmt)?;
+    for child in node.children() {
+        write_node(fmt, child, next_id, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+impl Display for AnyState {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write_state(fmt, self, 0)
+    }
+}
+
+fn write_state(fmt: &mut Formatter, state: &AnyState, depth: usize) -> FmtResult {
+    write!(
+        fmt,
+        "{:indent$}{} #{}: ",
+        "",
+        state.kind(),
+        state.id(),
+        indent = depth * 2
+    )?;
+
+    let limited = |name: &str, count: usize, limit: Option<usize>| match limit {
+        Some(limit) => format!("{}: {}/{}", name, count, limit),
+        None => format!("{}: {}", name, count),
+    };
+
+    use AnyState::*;
+    let progress = match state {
+        Markov(s) => Some(limited("index", s.index, Some(s.children.len()))),
+        Sequence(s) => Some(limited("index", s.index, Some(s.children.len()))),
+        One(s) => Some(limited("steps", s.steps_taken, s.node.steps)),
+        All(s) => Some(limited("steps", s.steps_taken, s.node.steps)),
+        Walk(s) => Some(limited("steps", s.steps_taken, s.node.steps)),
+        Repeat(s) => Some(limited("iteration", s.iteration, s.times)),
+        If(s) => Some(match s.entered {
+            true => "entered".to_string(),
+            false => "not entered".to_string(),
+        }),
+        Until(s) => Some(format!("{} reaches {}", s.symbol.to_char(), s.threshold)),
+        Prl(_) | Path(_) | Dyn(_) => None,
+    };
+
+    if let Some(progress) = progress {
+        write!(fmt, "{}, ", progress)?;
+    }
+
+    writeln!(fmt, "applications: {}", state.applications())?;
+    for child in state.children() {
+        write_state(fmt, child, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node::{AllNode, OneNode, RepeatNode, SequenceNode, Step};
+    use rule_set::RuleSet;
+
+    #[test]
+    fn pretty_print() {
+        let one = AnyNode::One(OneNode {
+            name: None,
+            rules: RuleSet::with_symmetry([Rule::from_strings("WB", "WW")], Symmetry::Rotations),
+            steps: Some(10),
+            sampler: Default::default(),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let all = AnyNode::All(AllNode {
+            name: Some("fill".to_string()),
+            rules: RuleSet::new([Rule::from_strings("B", "R")]),
+            steps: None,
+            conflict: Default::default(),
+            fields: Vec::new(),
+            observations: Vec::new(),
+        });
+
+        let model = AnyNode::Sequence(SequenceNode {
+            name: None,
+            children: vec![
+                AnyNode::Repeat(RepeatNode {
+                    name: None,
+                    child: Box::new(one),
+                    times: Some(3),
+                }),
+                all,
+            ],
+        });
+
+        assert_eq!(
+            model.to_string(),
+            "sequence #0
+  repeat #1: times: 3
+    one #2: 4 rules, steps: 10
+  all #3 \"fill\": 1 rule
+"
+        );
+
+        let mut grid = Grid::new(4, 4);
+        grid[Point::ZERO] = Symbol::White;
+        let mut state = model.make_state();
+        let mut rng = crate::tests::make_rng();
+        for _ in 0..2 {
+            state.step(&mut rng, &mut grid, &mut ());
+        }
+
+        assert_eq!(
+            state.to_string(),
+            "sequence #0: index: 0/2, applications: 2
+  repeat #1: iteration: 0/3, applications: 2
+    one #2: steps: 2/10, applications: 2
+  all #3: steps: 0, applications: 0
+"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use gif::{AnyExtension, DisposalMethod, Encoder, EncodingError, Extension, Repeat};
+
+use super::*;
+use atlas::Atlas;
+use isometric::IsometricStyle;
+use palette::Palette;
+use runner::Progress;
+
+/// When a recorder captures intermediate frames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CapturePolicy {
+    /// Every N successful steps.
+    EverySteps(usize),
+
+    /// Every N pattern applications.
+    EveryApplications(usize),
+
+    /// Whenever a different node becomes active.
+    OnTransition,
+
+    /// Never; only the final frame is written.
+    OnlyFinal,
+
+    /// Whenever at least this much wall-clock time has passed.
+    Interval(Duration),
+}
+
+impl Default for CapturePolicy {
+    fn default() -> Self {
+        CapturePolicy::EverySteps(64)
+    }
+}
+
+/// Tracks a [CapturePolicy] against a run's progress.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    pub policy: CapturePolicy,
+    last: Progress,
+    last_time: Instant,
+}
+
+impl Capture {
+    pub fn new(policy: CapturePolicy) -> Self {
+        Self {
+            policy,
+            last: Progress::default(),
+            last_time: Instant::now(),
+        }
+    }
+
+    /// Returns true if a frame should be captured at this point.
+    pub fn update(&mut self, progress: &Progress) -> bool {
+        use CapturePolicy::*;
+        let capture = match &self.policy {
+            EverySteps(n) => progress.steps >= self.last.steps + n,
+            EveryApplications(n) => progress.applications >= self.last.applications + n,
+            OnTransition => progress.path != self.last.path,
+            OnlyFinal => false,
+            Interval(interval) => self.last_time.elapsed() >= *interval,
+        };
+
+        if capture || matches!(self.policy, OnTransition) {
+            self.last = progress.clone();
+        }
+
+        if capture {
+            self.last_time = Instant::now();
+        }
+
+        capture
+    }
+}
+
+/// Streams grid frames into a GIF encoder as they are captured.
+///
+/// Frames are encoded and written immediately, so memory use does not grow
+/// with the length of the run. Each frame after the first only covers the
+/// pixels that changed since the frame before it, drawn over the previous
+/// frame, so that runs which only change a few cells at a time stay small.
+///
+/// GIF frames can't make a pixel transparent again once something has been
+/// drawn over it, so sprites and isometric renders are flattened onto black
+/// before they are encoded.
+///
+/// The GIF's header, which holds the palette, is written along with the
+/// first frame or comment, so [GifRecorder::with_palette] must be called
+/// before either.
+pub struct GifRecorder<W: Write> {
+    /// The output, until the header is written to it.
+    writer: Option<W>,
+    encoder: Option<Encoder<W>>,
+    palette: Palette,
+    width: u16,
+    height: u16,
+    tile_size: u16,
+    atlas: Option<Atlas>,
+    isometric: Option<IsometricStyle>,
+    capture: Capture,
+
+    /// The pixels of the last frame written, to find what changed since.
+    previous: Option<Vec<u8>>,
+
+    pub frame_delay: u16,
+    pub final_delay: u16,
+
+    /// Write only the changed part of each frame. When disabled every frame
+    /// covers the whole image.
+    pub delta_frames: bool,
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(
+        writer: W,
+        width: usize,
+        height: usize,
+        tile_size: u16,
+    ) -> Result<Self, EncodingError> {
+        let width = width.saturating_mul(tile_size as usize);
+        let height = height.saturating_mul(tile_size as usize);
+        Self::with_canvas(writer, width, height, tile_size)
+    }
+
+    /// Creates a recorder that draws grids shaped like `grid` in isometric
+    /// projection.
+    pub fn isometric(
+        writer: W,
+        grid: &Grid,
+        tile_size: u16,
+        style: IsometricStyle,
+    ) -> Result<Self, EncodingError> {
+        let (width, height) = grid.isometric_size(tile_size, style);
+        let mut recorder = Self::with_canvas(writer, width, height, tile_size)?;
+        recorder.isometric = Some(style);
+        Ok(recorder)
+    }
+
+    /// Fails if the canvas is too large for a GIF.
+    fn with_canvas(
+        writer: W,
+        width: usize,
+        height: usize,
+        tile_size: u16,
+    ) -> Result<Self, EncodingError> {
+        let (width, height) = gif_size(width, height)?;
+
+        Ok(Self {
+            writer: Some(writer),
+            encoder: None,
+            palette: Palette::Default,
+            width,
+            height,
+            tile_size,
+            atlas: None,
+            isometric: None,
+            capture: Capture::new(Default::default()),
+            previous: None,
+            frame_delay: 2,
+            final_delay: 1000,
+            delta_frames: true,
+        })
+    }
+
+    /// Creates a recorder that draws cells with an atlas's sprites, using
+    /// its tile size.
+    pub fn with_atlas(
+        writer: W,
+        width: usize,
+        height: usize,
+        atlas: Atlas,
+    ) -> Result<Self, EncodingError> {
+        let mut recorder = Self::new(writer, width, height, atlas.tile_size())?;
+        recorder.atlas = Some(atlas);
+        Ok(recorder)
+    }
+
+    pub fn with_policy(mut self, policy: CapturePolicy) -> Self {
+        self.capture = Capture::new(policy);
+        self
+    }
+
+    /// Colors frames with `palette` instead of [Symbol::PALETTE].
+    ///
+    /// Panics if a frame or comment has already been written.
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        if self.writer.is_none() {
+            panic!("the palette must be set before the GIF's header is written");
+        }
+
+        self.palette = palette;
+        self
+    }
+
+    /// The encoder, writing the GIF's header first if it hasn't been yet.
+    fn encoder(&mut self) -> Result<&mut Encoder<W>, EncodingError> {
+        if let Some(writer) = self.writer.take() {
+            let colors = self.palette.colors();
+            let mut encoder = Encoder::new(writer, self.width, self.height, colors)?;
+            encoder.set_repeat(Repeat::Infinite)?;
+            self.encoder = Some(encoder);
+        }
+
+        Ok(self.encoder.as_mut().unwrap())
+    }
+
+    /// Records a frame if the capture policy calls for one.
+    pub fn update(&mut self, progress: &Progress, grid: &Grid) -> Result<(), EncodingError> {
+        if self.capture.update(progress) {
+            self.record(grid)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a comment extension, e.g. to record the seed of a run.
+    pub fn comment(&mut self, text: &str) -> Result<(), EncodingError> {
+        let comment = AnyExtension(Extension::Comment as u8);
+        Ok(self
+            .encoder()?
+            .write_raw_extension(comment, &[text.as_bytes()])?)
+    }
+
+    /// Renders and writes a single intermediate frame.
+    pub fn record(&mut self, grid: &Grid) -> Result<(), EncodingError> {
+        self.write(grid, self.frame_delay)
+    }
+
+    /// Writes the final frame and returns the underlying writer.
+    pub fn finish(mut self, grid: &Grid) -> Result<W, EncodingError> {
+        self.write(grid, self.final_delay)?;
+        Ok(self.encoder.take().unwrap().into_inner()?)
+    }
+
+    fn write(&mut self, grid: &Grid, delay: u16) -> Result<(), EncodingError> {
+        let (pixels, depth) = self.render(grid);
+        let width = self.width as usize;
+        let full = Rect::new(Point::ZERO, width, self.height as usize);
+        let rect = match (&self.previous, self.delta_frames) {
+            // Unchanged frames still need writing for their delay, so they
+            // redraw a single pixel.
+            (Some(previous), true) => {
+                dirty(previous, &pixels, width, depth).unwrap_or(Rect::new(Point::ZERO, 1, 1))
+            }
+            _ => full,
+        };
+
+        let mut cropped: Vec<u8> = (rect.y..rect.y + rect.height)
+            .flat_map(|y| &pixels[(y * width + rect.x) * depth..][..rect.width * depth])
+            .copied()
+            .collect();
+
+        let (frame_width, frame_height) = (rect.width as u16, rect.height as u16);
+        let mut frame = match depth {
+            1 => gif::Frame::from_indexed_pixels(frame_width, frame_height, &cropped, None),
+            _ => gif::Frame::from_rgba_speed(frame_width, frame_height, &mut cropped, 10),
+        };
+
+        frame.left = rect.x as u16;
+        frame.top = rect.y as u16;
+        frame.delay = delay;
+        frame.dispose = DisposalMethod::Keep;
+        self.encoder()?.write_frame(&frame)?;
+        self.previous = Some(pixels);
+        Ok(())
+    }
+
+    /// Renders a frame's pixels, along with how many bytes each takes: one
+    /// for palette indices or four for RGBA.
+    fn render(&self, grid: &Grid) -> (Vec<u8>, usize) {
+        let mut pixels = match (&self.atlas, self.isometric) {
+            (_, Some(style)) => grid.render_isometric_with(self.tile_size, style, self.palette),
+            (Some(atlas), None) => grid.render_atlas(atlas),
+            (None, None) => return (grid.render_indexed(self.tile_size), 1),
+        };
+
+        for pixel in pixels.chunks_mut(4) {
+            let alpha = pixel[3] as u16;
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as u16 * alpha / 0xff) as u8;
+            }
+
+            pixel[3] = 0xff;
+        }
+
+        (pixels, 4)
+    }
+}
+
+/// The bounding box of the pixels that differ between two frames, or `None`
+/// if they are the same.
+fn dirty(previous: &[u8], pixels: &[u8], width: usize, depth: usize) -> Option<Rect> {
+    let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+    let changed = previous
+        .chunks(depth)
+        .zip(pixels.chunks(depth))
+        .enumerate()
+        .filter(|(_, (before, after))| before != after);
+
+    for (idx, _) in changed {
+        let (x, y) = (idx % width, idx / width);
+        left = left.min(x);
+        top = top.min(y);
+        right = right.max(x + 1);
+        bottom = bottom.max(y + 1);
+    }
+
+    (right > 0).then(|| Rect::new(Point { x: left, y: top }, right - left, bottom - top))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_frames() {
+        let mut grid = Grid::new(8, 8);
+        let mut recorder = GifRecorder::new(Vec::new(), grid.width, grid.height, 2).unwrap();
+        recorder.record(&grid).unwrap();
+        let header_len = recorder.encoder.as_ref().unwrap().get_ref().len();
+
+        grid[Point { x: 3, y: 3 }] = Symbol::Red;
+        recorder.record(&grid).unwrap();
+        assert!(recorder.encoder.as_ref().unwrap().get_ref().len() > header_len);
+
+        let bytes = recorder.finish(&grid).unwrap();
+        assert_eq!(bytes.last(), Some(&0x3b));
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(bytes.as_slice())
+            .unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.left, frame.top, frame.width, frame.height));
+        }
+
+        assert_eq!(frames, [(0, 0, 16, 16), (6, 6, 2, 2), (0, 0, 1, 1)]);
+
+        assert!(GifRecorder::new(Vec::new(), 200, 10, 400).is_err());
+        assert!(GifRecorder::new(Vec::new(), 65536, 1, 1).is_err());
+        assert!(GifRecorder::new(Vec::new(), 65535, 1, 1).is_ok());
+
+        let recorder = GifRecorder::new(Vec::new(), 1, 1, 1)
+            .unwrap()
+            .with_palette(Palette::HighContrast);
+        let bytes = recorder.finish(&Grid::new(1, 1)).unwrap();
+        let decoder = gif::DecodeOptions::new()
+            .read_info(bytes.as_slice())
+            .unwrap();
+        assert_eq!(
+            decoder.global_palette(),
+            Some(Palette::HighContrast.colors())
+        );
+    }
+
+    #[test]
+    fn capture_policies() {
+        let at = |steps, applications, path: &[usize]| Progress {
+            steps,
+            applications,
+            path: path.to_vec(),
+        };
+
+        let mut capture = Capture::new(CapturePolicy::EverySteps(2));
+        let captured: Vec<_> = (1..=6).map(|n| capture.update(&at(n, 0, &[]))).collect();
+        assert_eq!(captured, [false, true, false, true, false, true]);
+
+        let mut capture = Capture::new(CapturePolicy::EveryApplications(10));
+        assert!(!capture.update(&at(1, 4, &[])));
+        assert!(capture.update(&at(2, 12, &[])));
+        assert!(!capture.update(&at(3, 21, &[])));
+        assert!(capture.update(&at(4, 22, &[])));
+
+        let mut capture = Capture::new(CapturePolicy::OnTransition);
+        assert!(capture.update(&at(1, 1, &[0])));
+        assert!(!capture.update(&at(2, 2, &[0])));
+        assert!(capture.update(&at(3, 3, &[1, 0])));
+
+        let mut capture = Capture::new(CapturePolicy::OnlyFinal);
+        assert!(!capture.update(&at(1000, 1000, &[5])));
+    }
+}
@@ -0,0 +1,194 @@
+//! Finding and filling connected regions of cells.
+//!
+//! Cells are connected to their four orthogonal neighbors. Only the bottom
+//! layer is considered, as when indexing a grid by a bare [Point].
+
+use super::*;
+
+impl<T: Clone + PartialEq> GenericGrid<T> {
+    /// Replaces every `target` cell connected to `start` with `replacement`,
+    /// returning how many cells were filled.
+    ///
+    /// Nothing is filled if `start` isn't a `target` cell or if `target` and
+    /// `replacement` are equal. Rows are filled a span at a time, so large
+    /// regions don't need deep recursion or a queue entry per cell.
+    pub fn flood_fill(&mut self, start: Point, target: T, replacement: T) -> usize {
+        if target == replacement || self[start] != target {
+            return 0;
+        }
+
+        let mut filled = 0;
+        let mut seeds = vec![start];
+        while let Some(Point { x, y }) = seeds.pop() {
+            if self[Point { x, y }] != target {
+                continue;
+            }
+
+            let mut left = x;
+            while left > 0 && self[Point { x: left - 1, y }] == target {
+                left -= 1;
+            }
+
+            let mut right = x;
+            while right + 1 < self.width && self[Point { x: right + 1, y }] == target {
+                right += 1;
+            }
+
+            for x in left..=right {
+                self[Point { x, y }] = replacement.clone();
+            }
+
+            filled += right - left + 1;
+
+            let rows = [y.checked_sub(1), Some(y + 1).filter(|y| *y < self.height)];
+            for y in rows.into_iter().flatten() {
+                // Seed the start of each run of target cells along the span.
+                let mut in_run = false;
+                for x in left..=right {
+                    let matches = self[Point { x, y }] == target;
+                    if matches && !in_run {
+                        seeds.push(Point { x, y });
+                    }
+
+                    in_run = matches;
+                }
+            }
+        }
+
+        filled
+    }
+}
+
+/// The connected regions of a grid, as found by [Grid::components].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Components {
+    /// Each cell's component, numbered in the order their first cells
+    /// appear row by row, or `None` for cells outside every component.
+    pub labels: GenericGrid<Option<u32>>,
+
+    /// The number of cells in each component.
+    pub sizes: Vec<usize>,
+}
+
+impl Components {
+    /// The number of components.
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// The component with the most cells, preferring the first on ties.
+    pub fn largest(&self) -> Option<u32> {
+        let largest = self.sizes.iter().max()?;
+        let index = self.sizes.iter().position(|size| size == largest)?;
+        Some(index as u32)
+    }
+
+    /// The cells of a component, row by row.
+    pub fn points(&self, component: u32) -> impl Iterator<Item = Point> + '_ {
+        self.labels
+            .iter()
+            .filter(move |(_, label)| **label == Some(component))
+            .map(|(at, _)| at)
+    }
+}
+
+impl Grid {
+    /// Labels the connected regions made of any of `symbols`.
+    ///
+    /// Neighboring cells belong to the same component whenever both are in
+    /// `symbols`, even if their symbols differ.
+    pub fn components(&self, symbols: &[Symbol]) -> Components {
+        let mut labels = GenericGrid::<Option<u32>>::new(self.width, self.height);
+        let mut sizes = Vec::new();
+        let mut stack = Vec::new();
+
+        for start in self.enumerate_points() {
+            if labels[start].is_some() || !symbols.contains(&self[start]) {
+                continue;
+            }
+
+            let label = sizes.len() as u32;
+            let mut size = 0;
+            labels[start] = Some(label);
+            stack.push(start);
+            while let Some(at) = stack.pop() {
+                size += 1;
+                for next in self.neighbors(at) {
+                    if labels[next].is_none() && symbols.contains(&self[next]) {
+                        labels[next] = Some(label);
+                        stack.push(next);
+                    }
+                }
+            }
+
+            sizes.push(size);
+        }
+
+        Components { labels, sizes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flood_fill() {
+        let mut grid = Grid::from_display_str(
+            "
+            BBWBB
+            BWBWB
+            BBBWB
+            WWWBB
+            BBWBW
+            ",
+        )
+        .unwrap();
+
+        let filled = grid.flood_fill(Point { x: 0, y: 0 }, Symbol::Black, Symbol::Red);
+        assert_eq!(filled, 7);
+        assert_eq!(grid.to_string(), "RRWBB\nRWRWB\nRRRWB\nWWWBB\nBBWBW\n");
+
+        let filled = grid.flood_fill(Point { x: 4, y: 0 }, Symbol::Black, Symbol::Red);
+        assert_eq!(filled, 7);
+        assert_eq!(
+            grid.flood_fill(Point { x: 0, y: 4 }, Symbol::Red, Symbol::Blue),
+            0
+        );
+        assert_eq!(
+            grid.flood_fill(Point { x: 0, y: 4 }, Symbol::Black, Symbol::Black),
+            0
+        );
+
+        let mut large = Grid::new(300, 300);
+        let filled = large.flood_fill(Point { x: 150, y: 150 }, Symbol::Black, Symbol::White);
+        assert_eq!(filled, 300 * 300);
+    }
+
+    #[test]
+    fn components() {
+        let grid = Grid::from_display_str(
+            "
+            WWBRB
+            BWBRB
+            BBBBW
+            RBBWW
+            ",
+        )
+        .unwrap();
+
+        let components = grid.components(&[Symbol::White, Symbol::Red]);
+        assert_eq!(components.sizes, [3, 2, 3, 1]);
+        assert_eq!(components.largest(), Some(0));
+        assert_eq!(components.labels[Point { x: 4, y: 2 }], Some(2));
+        assert_eq!(components.labels[Point { x: 0, y: 2 }], None);
+
+        let debris: Vec<_> = components.points(3).collect();
+        assert_eq!(debris, [Point { x: 0, y: 3 }]);
+        assert!(grid.components(&[Symbol::Blue]).is_empty());
+    }
+}
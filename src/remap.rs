@@ -0,0 +1,183 @@
+//! Translating grids and rules from one set of symbols to another.
+//!
+//! A model written for one alphabet can be reused with another by remapping
+//! its rules instead of editing every rule string, and a finished grid can
+//! have symbols merged or swapped before it's rendered or exported:
+//!
+//! ```ignore
+//! let remap: Remap = "E=G Y=W".parse()?;
+//! let rules: Vec<Rule> = rules.iter().map(|rule| rule.remap(&remap)).collect();
+//! let grid = grid.remap(&remap);
+//! ```
+//!
+//! Several symbols may map to the same one, collapsing them together.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use super::*;
+
+/// The symbol each symbol becomes. Symbols map to themselves unless given
+/// another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Remap {
+    symbols: [Symbol; Symbol::ALL.len()],
+}
+
+impl Default for Remap {
+    fn default() -> Self {
+        Self {
+            symbols: Symbol::ALL,
+        }
+    }
+}
+
+impl Remap {
+    /// Maps `from` to `to`.
+    pub fn with(mut self, from: Symbol, to: Symbol) -> Self {
+        self.symbols[from.palette_index() as usize] = to;
+        self
+    }
+
+    pub fn get(&self, symbol: Symbol) -> Symbol {
+        self.symbols[symbol.palette_index() as usize]
+    }
+
+    /// Whether every symbol maps to itself.
+    pub fn is_identity(&self) -> bool {
+        self.symbols == Symbol::ALL
+    }
+}
+
+/// Why a remapping couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseRemapError {
+    /// An entry wasn't two symbol characters separated by `=`.
+    InvalidEntry(String),
+    UnknownSymbol(char),
+}
+
+impl Display for ParseRemapError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ParseRemapError::*;
+        match self {
+            InvalidEntry(entry) => write!(fmt, "invalid remap entry '{}', expected FROM=TO", entry),
+            UnknownSymbol(c) => write!(fmt, "unrecognized symbol '{}'", c),
+        }
+    }
+}
+
+impl std::error::Error for ParseRemapError {}
+
+impl FromStr for Remap {
+    type Err = ParseRemapError;
+
+    /// Parses whitespace- or comma-separated `FROM=TO` entries, such as
+    /// `"E=G Y=W"`.
+    fn from_str(string: &str) -> Result<Self, ParseRemapError> {
+        let mut remap = Self::default();
+        let entries = string
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty());
+
+        for entry in entries {
+            let invalid = || ParseRemapError::InvalidEntry(entry.to_string());
+            let mut chars = entry.chars();
+            let (Some(from), Some('='), Some(to), None) =
+                (chars.next(), chars.next(), chars.next(), chars.next())
+            else {
+                return Err(invalid());
+            };
+
+            let symbol = |c| Symbol::try_from_char(c).ok_or(ParseRemapError::UnknownSymbol(c));
+            remap = remap.with(symbol(from)?, symbol(to)?);
+        }
+
+        Ok(remap)
+    }
+}
+
+impl Pattern {
+    /// Remaps every cell of the pattern, leaving wildcards as they are.
+    pub fn remap(&self, remap: &Remap) -> Self {
+        Self {
+            grid: self
+                .grid
+                .iter()
+                .map(|cell| cell.map(|symbol| remap.get(symbol)))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Grid {
+    /// Remaps every cell, on every layer.
+    pub fn remap(&self, remap: &Remap) -> Self {
+        Self {
+            grid: self.grid.iter().map(|symbol| remap.get(*symbol)).collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Rule {
+    /// Remaps both patterns and the symbols counted by the conditions.
+    pub fn remap(&self, remap: &Remap) -> Self {
+        Self {
+            find: self.find.remap(remap),
+            replace: self.replace.remap(remap),
+            conditions: self
+                .conditions
+                .iter()
+                .map(|condition| condition::NeighborCount {
+                    symbol: remap.get(condition.symbol),
+                    ..condition.clone()
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Symbol::*;
+
+    #[test]
+    fn remap_symbols() {
+        let remap: Remap = "E=G, Y=W R=Y".parse().unwrap();
+        assert_eq!(remap.get(Emerald), Green);
+        assert_eq!(remap.get(Red), Yellow);
+        assert_eq!(remap.get(Blue), Blue);
+        assert!(!remap.is_identity());
+        assert!(Remap::default().with(Red, Red).is_identity());
+
+        let grid = Grid::from_display_str("EGY\nRBW").unwrap();
+        assert_eq!(grid.remap(&remap).to_string(), "GGW\nYBW\n");
+
+        let mut rule = Rule::from_strings("RB*", "EY*");
+        rule.conditions.push(condition::NeighborCount {
+            at: Point::ZERO,
+            symbol: Emerald,
+            min: 1,
+            max: 4,
+            diagonals: false,
+        });
+
+        let remapped = rule.remap(&remap);
+        assert_eq!(remapped.find, Pattern::from_string("YB*"));
+        assert_eq!(remapped.replace, Pattern::from_string("GW*"));
+        assert_eq!(remapped.conditions[0].symbol, Green);
+        assert_eq!(remapped.conditions[0].max, 4);
+
+        assert_eq!(
+            "E=".parse::<Remap>(),
+            Err(ParseRemapError::InvalidEntry("E=".to_string()))
+        );
+        assert_eq!(
+            "E=Q".parse::<Remap>(),
+            Err(ParseRemapError::UnknownSymbol('Q'))
+        );
+    }
+}
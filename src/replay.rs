@@ -0,0 +1,114 @@
+//! Re-applying a recorded [Trace] without rerunning its model.
+//!
+//! Replays need neither the model nor an RNG, only the trace and the grid the
+//! run started from. Since rendering is independent of the run, a replay can
+//! be recorded with a larger tile size or an [Atlas](crate::atlas::Atlas)
+//! than the original run used.
+
+use std::io::Write;
+
+use gif::EncodingError;
+
+use super::*;
+use recorder::GifRecorder;
+use runner::Progress;
+use trace::{Trace, TraceEvent};
+
+/// Steps through a trace's events, applying them to a grid.
+pub struct Replay<'a> {
+    pub grid: Grid,
+    events: &'a [TraceEvent],
+    progress: Progress,
+}
+
+impl<'a> Replay<'a> {
+    /// Starts replaying `trace` over `grid`, which should be the grid the
+    /// traced run started from.
+    pub fn new(trace: &'a Trace, grid: Grid) -> Self {
+        Self {
+            grid,
+            events: &trace.events,
+            progress: Progress::default(),
+        }
+    }
+
+    /// How far the replay has progressed, counting steps as the traced run
+    /// did. The path is that of the node which made the last application.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Applies every event from the next recorded step.
+    ///
+    /// Returns false once the trace has been fully replayed.
+    pub fn step(&mut self) -> bool {
+        let Some(first) = self.events.first() else {
+            return false;
+        };
+
+        let len = self
+            .events
+            .iter()
+            .take_while(|event| event.step == first.step)
+            .count();
+
+        let (step, rest) = self.events.split_at(len);
+        for event in step {
+            self.grid.apply_pattern(&event.replace, event.at);
+        }
+
+        self.events = rest;
+        self.progress.steps = first.step + 1;
+        self.progress.applications += len;
+        self.progress.path.clone_from(&step[len - 1].path);
+        true
+    }
+
+    /// Replays the rest of the trace.
+    pub fn run(&mut self) {
+        while self.step() {}
+    }
+
+    /// Replays the rest of the trace into a GIF recorder, then finishes it.
+    pub fn record<W: Write>(&mut self, mut recorder: GifRecorder<W>) -> Result<W, EncodingError> {
+        while self.step() {
+            recorder.update(&self.progress, &self.grid)?;
+        }
+
+        recorder.finish(&self.grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use runner::Runner;
+
+    #[test]
+    fn replay_trace() {
+        let model = Model::from_xml(include_str!("../models/nystrom-dungeon.xml")).unwrap();
+        let grid = model.make_grid(21, 21);
+        let mut runner: Runner = Runner::with_seed(&model.root, grid.clone(), 9);
+        runner.enable_trace();
+        let halfway: Vec<_> = runner.snapshots().take(20).collect();
+        runner.run();
+
+        let trace: Trace = runner.trace().unwrap().to_string().parse().unwrap();
+        let mut replay = Replay::new(&trace, grid.clone());
+        for snapshot in halfway {
+            assert!(replay.step());
+            assert_eq!(replay.progress().steps, snapshot.progress.steps);
+            assert_eq!(replay.grid, snapshot.grid);
+        }
+
+        replay.run();
+        assert_eq!(replay.grid, runner.grid);
+        assert_eq!(replay.progress().steps, runner.progress().steps);
+        assert!(!replay.step());
+
+        let recorder = GifRecorder::new(Vec::new(), grid.width, grid.height, 8).unwrap();
+        let gif = Replay::new(&trace, grid).record(recorder).unwrap();
+        assert_eq!(&gif[..6], b"GIF89a");
+    }
+}
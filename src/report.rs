@@ -0,0 +1,217 @@
+//! Machine-readable summaries of finished runs.
+//!
+//! A [Report] gathers what a pipeline generating many grids needs to know
+//! about each one: the seed to reproduce it, a hash identifying the model,
+//! how long the run went on for, and what the final grid holds. Reports are
+//! written as a single JSON object:
+//!
+//! ```json
+//! {"seed":7,"model_hash":"9c1f0d5e2a7b3468","steps":42,"applications":42,
+//! "duration_secs":0.0031,"histogram":{"B":900,"W":124},
+//! "rules":[{"node":1,"rule":0,"scans":43,"matches":512,"applications":42}]}
+//! ```
+//!
+//! Seeds are written as plain numbers, which some JSON parsers round once
+//! they exceed 2^53. The hash is written as a hex string for the same
+//! reason. `seed` is `null` for runners created without one, and `rules` is
+//! only present when [statistics](runner::Runner::enable_stats) were
+//! collected.
+
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::*;
+use analysis::Histogram;
+use node::AnyNode;
+use runner::Runner;
+
+/// How often a single rule was searched for and applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleReport {
+    /// The ID of the rule's node.
+    pub node: usize,
+
+    /// The index of the rule within its node.
+    pub rule: usize,
+
+    pub scans: usize,
+    pub matches: usize,
+    pub applications: usize,
+}
+
+/// A summary of a run. See [Runner::report].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub seed: Option<u64>,
+
+    /// Identifies the model's node tree. See [model_hash].
+    pub model_hash: u64,
+
+    pub steps: usize,
+    pub applications: usize,
+
+    /// Wall-clock time spent stepping.
+    pub duration: Duration,
+
+    /// The final grid's visible symbols.
+    pub histogram: Histogram,
+
+    /// Every rule that was searched for, by node and then rule, if the
+    /// runner collected statistics.
+    pub rules: Option<Vec<RuleReport>>,
+}
+
+impl Report {
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        let seed = match self.seed {
+            Some(seed) => seed.to_string(),
+            None => "null".to_string(),
+        };
+
+        write!(
+            json,
+            "\"seed\":{},\"model_hash\":\"{:016x}\",\"steps\":{},\"applications\":{},\"duration_secs\":{}",
+            seed,
+            self.model_hash,
+            self.steps,
+            self.applications,
+            self.duration.as_secs_f64()
+        )
+        .unwrap();
+
+        json.push_str(",\"histogram\":{");
+        for (idx, (symbol, count)) in self.histogram.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+
+            write!(json, "\"{}\":{}", symbol.to_char(), count).unwrap();
+        }
+
+        json.push('}');
+
+        if let Some(rules) = &self.rules {
+            json.push_str(",\"rules\":[");
+            for (idx, rule) in rules.iter().enumerate() {
+                if idx > 0 {
+                    json.push(',');
+                }
+
+                write!(
+                    json,
+                    "{{\"node\":{},\"rule\":{},\"scans\":{},\"matches\":{},\"applications\":{}}}",
+                    rule.node, rule.rule, rule.scans, rule.matches, rule.applications
+                )
+                .unwrap();
+            }
+
+            json.push(']');
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+/// Hashes a model's node tree, so that reports from the same model can be
+/// grouped. The hash is stable for a given build of this crate, but may
+/// change between versions.
+pub fn model_hash(model: &AnyNode) -> u64 {
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    write!(hasher, "{:?}", model).unwrap();
+    hasher.0
+}
+
+/// A 64-bit FNV-1a hash of everything written to it.
+struct Fnv(u64);
+
+impl fmt::Write for Fnv {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        for byte in string.bytes() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Rng> Runner<R> {
+    /// Summarizes the run so far.
+    pub fn report(&self) -> Report {
+        let rules = self.stats().map(|stats| {
+            stats
+                .nodes
+                .iter()
+                .flat_map(|(node, stats)| {
+                    stats
+                        .rules
+                        .iter()
+                        .enumerate()
+                        .map(|(rule, stats)| RuleReport {
+                            node: *node,
+                            rule,
+                            scans: stats.scans,
+                            matches: stats.matches,
+                            applications: stats.applications,
+                        })
+                })
+                .collect()
+        });
+
+        Report {
+            seed: self.seed(),
+            model_hash: self.model_hash(),
+            steps: self.progress().steps,
+            applications: self.progress().applications,
+            duration: self.elapsed(),
+            histogram: self.grid.histogram(),
+            rules,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    fn report_json() {
+        let model = Model::from_xml(r#"<one values="BW" in="B" out="W" steps="20"/>"#).unwrap();
+        let grid = model.make_grid(8, 4);
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, grid, 7);
+        runner.enable_stats();
+        runner.run();
+
+        let report = runner.report();
+        assert_eq!(report.seed, Some(7));
+        assert_eq!(report.model_hash, model_hash(&model.root));
+        assert_eq!(report.histogram.total(), 32);
+
+        let rules = report.rules.as_ref().unwrap();
+        let applied: usize = rules.iter().map(|rule| rule.applications).sum();
+        assert_eq!(applied, report.applications);
+
+        let json = report.to_json();
+        let prefix = format!(
+            "{{\"seed\":7,\"model_hash\":\"{:016x}\",\"steps\":{},",
+            report.model_hash, report.steps
+        );
+        assert!(json.starts_with(&prefix), "{}", json);
+        let histogram = format!("\"histogram\":{{\"B\":{},\"W\":{}}}", 32 - applied, applied);
+        assert!(json.contains(&histogram), "{}", json);
+        assert!(
+            json.contains("\"rules\":[{\"node\":0,\"rule\":0,"),
+            "{}",
+            json
+        );
+        assert!(json.ends_with("]}"));
+
+        let other = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        assert_ne!(model_hash(&other.root), report.model_hash);
+    }
+}
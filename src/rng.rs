@@ -0,0 +1,142 @@
+//! The crate's own random number generator.
+//!
+//! [SmallRng](rand::rngs::SmallRng) may change algorithm between `rand`
+//! releases and platforms, which silently changes what a shared seed
+//! generates. [Pcg32] is implemented here instead, so its output for a seed
+//! only changes along with [Pcg32::VERSION].
+
+use rand::{Error, RngCore, SeedableRng};
+
+use super::*;
+use checkpoint::RngState;
+
+/// The RNG used when a runner's isn't specified.
+pub type DefaultRng = Pcg32;
+
+/// The PCG-XSH-RR generator with 64-bit state and 32-bit output, as in the
+/// reference `pcg32_random_r`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    /// Bumped whenever the sequence generated from a seed changes.
+    pub const VERSION: u32 = 1;
+
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    /// The stream used by [SeedableRng::seed_from_u64].
+    const DEFAULT_STREAM: u64 = 54;
+
+    /// Creates a generator like the reference `pcg32_srandom_r`. Different
+    /// streams give independent sequences for the same state.
+    pub fn new(state: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+
+        rng.advance();
+        rng.state = rng.state.wrapping_add(state);
+        rng.advance();
+        rng
+    }
+
+    fn advance(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.advance();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Pcg32 {
+    /// The initial state followed by the stream, both little-endian.
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let (state, stream) = seed.split_at(8);
+        Self::new(
+            u64::from_le_bytes(state.try_into().unwrap()),
+            u64::from_le_bytes(stream.try_into().unwrap()),
+        )
+    }
+
+    /// Uses `seed` as the initial state directly, rather than `rand`'s
+    /// seed expansion, so that seeds stay stable across `rand` releases.
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::new(seed, Self::DEFAULT_STREAM)
+    }
+}
+
+impl RngState for Pcg32 {
+    fn save_state(&self) -> Vec<u8> {
+        [self.state, self.increment]
+            .into_iter()
+            .flat_map(u64::to_le_bytes)
+            .collect()
+    }
+
+    fn load_state(bytes: &[u8]) -> Option<Self> {
+        let (state, increment) = bytes.split_at_checked(8)?;
+        let increment = u64::from_le_bytes(increment.try_into().ok()?);
+        if increment & 1 == 0 {
+            return None;
+        }
+
+        Some(Self {
+            state: u64::from_le_bytes(state.try_into().ok()?),
+            increment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_output() {
+        // From the reference implementation's pcg32-demo.
+        let mut rng = Pcg32::new(42, 54);
+        let outputs: Vec<_> = (0..6).map(|_| rng.next_u32()).collect();
+        assert_eq!(
+            outputs,
+            [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e]
+        );
+
+        let saved = rng.save_state();
+        let mut loaded = Pcg32::load_state(&saved).unwrap();
+        assert_eq!(loaded.next_u64(), rng.next_u64());
+        assert_eq!(Pcg32::load_state(&saved[1..]), None);
+    }
+}
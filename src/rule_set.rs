@@ -0,0 +1,188 @@
+//! Rule lists that are compiled once and shared between nodes.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::*;
+use packed::PackedPattern;
+
+/// The space a rule covers, taking the larger of its find and replace
+/// patterns in each dimension.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Extent {
+    pub width: usize,
+    pub height: usize,
+    pub layers: usize,
+}
+
+impl Extent {
+    fn of(rule: &Rule) -> Self {
+        Self {
+            width: rule.find.width.max(rule.replace.width),
+            height: rule.find.height.max(rule.replace.height),
+            layers: rule.find.layers.max(rule.replace.layers),
+        }
+    }
+
+    /// Whether a rule with this extent can be placed anywhere on `grid`.
+    pub fn fits(&self, grid: &Grid) -> bool {
+        placements(grid.width, self.width) > 0
+            && placements(grid.height, self.height) > 0
+            && self.layers <= grid.layers
+    }
+}
+
+impl Display for Extent {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{}x{}", self.width, self.height)?;
+        if self.layers > 1 {
+            write!(fmt, "x{}", self.layers)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An immutable list of rules with their symmetric variants expanded and
+/// duplicates removed.
+///
+/// Cloning a rule set is cheap, so several nodes can share one.
+#[derive(Clone, Debug)]
+pub struct RuleSet(Arc<Compiled>);
+
+#[derive(Debug)]
+struct Compiled {
+    rules: Vec<Rule>,
+    extents: Vec<Extent>,
+    finds: Vec<PackedPattern>,
+}
+
+impl RuleSet {
+    /// Compiles a list of rules that have already been expanded, keeping
+    /// only the first copy of each duplicate.
+    pub fn new(rules: impl IntoIterator<Item = Rule>) -> Self {
+        let mut unique: Vec<Rule> = Vec::new();
+        for rule in rules {
+            if !unique.contains(&rule) {
+                unique.push(rule);
+            }
+        }
+
+        let extents = unique.iter().map(Extent::of).collect();
+        let finds = unique
+            .iter()
+            .map(|rule| PackedPattern::new(&rule.find))
+            .collect();
+
+        Self(Arc::new(Compiled {
+            rules: unique,
+            extents,
+            finds,
+        }))
+    }
+
+    /// Expands every rule into its symmetric variants and compiles them.
+    pub fn with_symmetry(rules: impl IntoIterator<Item = Rule>, symmetry: Symmetry) -> Self {
+        Self::new(rules.into_iter().flat_map(|rule| symmetry.expand(rule)))
+    }
+
+    /// Joins several rule sets into one, in order, dropping rules repeated
+    /// across sets. The sets' rules have already been expanded, so they are
+    /// not expanded again.
+    pub fn concat<'a>(sets: impl IntoIterator<Item = &'a RuleSet>) -> Self {
+        Self::new(sets.into_iter().flat_map(|set| set.iter().cloned()))
+    }
+
+    /// The [inverse](Rule::inverse) of every rule, for undoing this set's
+    /// applications.
+    pub fn inverse(&self) -> Self {
+        self.iter().map(Rule::inverse).collect()
+    }
+
+    /// The extent of the rule at `idx`.
+    pub fn extent(&self, idx: usize) -> Extent {
+        self.0.extents[idx]
+    }
+
+    /// The find pattern of the rule at `idx`, packed for fast matching.
+    pub fn packed_find(&self, idx: usize) -> &PackedPattern {
+        &self.0.finds[idx]
+    }
+
+    /// The indices of the rules that fit on `grid`. The others can never
+    /// match and are skipped when scanning.
+    pub fn fitting<'a>(&'a self, grid: &'a Grid) -> impl Iterator<Item = usize> + 'a {
+        (0..self.len()).filter(|idx| self.extent(*idx).fits(grid))
+    }
+}
+
+impl Deref for RuleSet {
+    type Target = [Rule];
+
+    fn deref(&self) -> &[Rule] {
+        &self.0.rules
+    }
+}
+
+impl PartialEq for RuleSet {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0.rules == other.0.rules
+    }
+}
+
+impl Eq for RuleSet {}
+
+impl From<Vec<Rule>> for RuleSet {
+    fn from(rules: Vec<Rule>) -> Self {
+        Self::new(rules)
+    }
+}
+
+impl FromIterator<Rule> for RuleSet {
+    fn from_iter<I: IntoIterator<Item = Rule>>(rules: I) -> Self {
+        Self::new(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_rules() {
+        let rules = RuleSet::with_symmetry(
+            [
+                Rule::from_strings("BWB", "BBB"),
+                Rule::from_strings("R/B", "R/R"),
+            ],
+            Symmetry::Rotations,
+        );
+
+        // "BWB" only has two distinct rotations.
+        assert_eq!(rules.len(), 6);
+        assert_eq!(rules[0], Rule::from_strings("BWB", "BBB"));
+        assert_eq!(
+            rules.extent(1),
+            Extent {
+                width: 1,
+                height: 3,
+                layers: 1
+            }
+        );
+
+        let shared = rules.clone();
+        assert_eq!(shared, rules);
+        assert_eq!(shared, RuleSet::from(rules.to_vec()));
+
+        let grid = Grid::new(2, 4);
+        let fitting: Vec<_> = rules.fitting(&grid).collect();
+        assert_eq!(fitting, [1, 2, 3, 4, 5]);
+
+        // A rule exactly the grid's size fits, and is scanned where it fits.
+        let whole = RuleSet::new([Rule::from_strings("BB/BB/BB/BB", "RR/RR/RR/RR")]);
+        assert_eq!(whole.fitting(&grid).collect::<Vec<_>>(), [0]);
+        let matches = node::find_all_matches(&grid, &mut (), 0, &whole);
+        assert_eq!(matches, [Match::new(0, Point::ZERO, grid.width)]);
+    }
+}
@@ -0,0 +1,1065 @@
+use std::io::Write;
+use std::iter::FusedIterator;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use gif::EncodingError;
+use rand::{Rng, SeedableRng};
+
+use super::*;
+use checkpoint::{Checkpoint, CheckpointError};
+use history::History;
+use node::{AnyNode, AnyState, Step};
+use observer::{Application, Observer};
+use recorder::GifRecorder;
+use report::model_hash;
+use rng::DefaultRng;
+use stats::Stats;
+use trace::Trace;
+use video::{VideoError, VideoRecorder};
+
+/// How far a run has progressed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of successful steps taken.
+    pub steps: usize,
+
+    /// The number of patterns applied to the grid.
+    pub applications: usize,
+
+    /// The child indices leading to the currently active node.
+    pub path: Vec<usize>,
+}
+
+/// Why a run stopped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The model has no more steps to take.
+    Finished,
+
+    /// A user-supplied predicate on the grid was satisfied.
+    Predicate,
+
+    /// The runner's application budget was used up.
+    StepLimit,
+
+    /// The runner's time budget ran out.
+    Timeout,
+
+    /// One of the runner's breakpoints was hit. See
+    /// [Runner::breakpoint_hit].
+    Breakpoint,
+
+    /// The runner was [paused](Runner::pause).
+    Paused,
+}
+
+/// What a call to [Runner::step_for] did.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Slice {
+    /// The number of successful steps taken.
+    pub steps: usize,
+
+    /// The number of patterns applied to the grid.
+    pub applications: usize,
+
+    /// Whether the model has finished.
+    pub finished: bool,
+}
+
+/// Pauses and resumes a [Runner], possibly from another thread.
+///
+/// Every handle to a runner shares its state, so a UI can keep a handle
+/// while the runner itself is busy in [Runner::run] elsewhere.
+#[derive(Clone, Debug, Default)]
+pub struct PauseHandle {
+    paused: Arc<AtomicBool>,
+}
+
+impl PauseHandle {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+/// A point in a run at which [Runner::run] and [Runner::run_until] stop, so
+/// that the grid can be inspected before carrying on.
+///
+/// Breakpoints refer to nodes by [name](AnyNode::name) and apply to every
+/// node with that name. They are checked as each step goes on, but the run
+/// always stops between steps.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// The node takes its first step that does something, or its first
+    /// since it last finished.
+    Start(String),
+
+    /// The node tries to step and does nothing, after it had started.
+    Finish(String),
+
+    /// The node and its children have made this many applications in
+    /// total. The run stops after the step that made the last of them, so
+    /// to stop just before a `one` node's `n`th application, which is a
+    /// step of its own, break after `n - 1`.
+    Applications(String, usize),
+}
+
+impl Breakpoint {
+    /// The name of the node this breakpoint watches.
+    pub fn name(&self) -> &str {
+        match self {
+            Breakpoint::Start(name) => name,
+            Breakpoint::Finish(name) => name,
+            Breakpoint::Applications(name, _) => name,
+        }
+    }
+}
+
+/// Limits on how long a single call to [Runner::run] may go on for.
+///
+/// Models whose nodes never stop matching would otherwise run forever.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    /// The maximum total number of pattern applications over the whole run.
+    pub applications: Option<usize>,
+
+    /// The maximum wall-clock time spent in each call to [Runner::run].
+    pub duration: Option<Duration>,
+}
+
+/// Drives a model's state over a grid.
+pub struct Runner<R = DefaultRng> {
+    pub grid: Grid,
+    pub state: AnyState,
+    pub rng: R,
+    progress: Progress,
+    seed: Option<u64>,
+    model_hash: u64,
+    elapsed: Duration,
+    budget: Budget,
+    names: Vec<(usize, String)>,
+    breakpoints: Breakpoints,
+    pause: PauseHandle,
+    history: Option<History>,
+    stats: Option<Stats>,
+    trace: Option<Trace>,
+    observers: Vec<Box<dyn Observer + Send + Sync>>,
+}
+
+impl<R: SeedableRng + Rng> Runner<R> {
+    /// Creates a runner with an RNG seeded from `seed`.
+    ///
+    /// The same seed, model, and initial grid always produce the same run.
+    pub fn with_seed(model: &AnyNode, grid: Grid, seed: u64) -> Self {
+        let mut runner = Self::new(model, grid, R::seed_from_u64(seed));
+        runner.seed = Some(seed);
+        runner
+    }
+}
+
+impl<R: Rng> Runner<R> {
+    pub fn new(model: &AnyNode, grid: Grid, rng: R) -> Self {
+        let state = model.make_state();
+        let progress = Progress {
+            path: state.active_path(),
+            ..Default::default()
+        };
+
+        Self {
+            grid,
+            state,
+            rng,
+            progress,
+            seed: None,
+            model_hash: model_hash(model),
+            elapsed: Duration::ZERO,
+            budget: Budget::default(),
+            names: model
+                .named_nodes()
+                .into_iter()
+                .map(|(id, name)| (id, name.to_string()))
+                .collect(),
+            breakpoints: Breakpoints::default(),
+            pause: PauseHandle::default(),
+            history: None,
+            stats: None,
+            trace: None,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// The seed this runner's RNG was created from, if it was seeded.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Identifies the model this runner was created for. See
+    /// [model_hash].
+    pub fn model_hash(&self) -> u64 {
+        self.model_hash
+    }
+
+    /// The total wall-clock time spent stepping.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    /// Limits how long [Runner::run] and [Runner::run_until] may go on for.
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = budget;
+    }
+
+    /// Whether the run wraps around the grid's edges. See
+    /// [Runner::set_periodic].
+    pub fn periodic(&self) -> bool {
+        self.grid.periodic
+    }
+
+    /// Makes the run wrap around the grid's edges, so that its output tiles
+    /// seamlessly. Rules match across the edges, and fields, paths, walks,
+    /// and neighbor counts all treat opposite edges as adjacent.
+    ///
+    /// The flag is kept on the grid, where every node can see it.
+    pub fn set_periodic(&mut self, periodic: bool) {
+        self.grid.periodic = periodic;
+    }
+
+    /// Makes [Runner::run] and [Runner::run_until] stop before their next
+    /// step, and return [Outcome::Paused] without stepping until the runner
+    /// is resumed. Single steps with [Runner::step] are still taken, so a
+    /// paused run can be stepped through.
+    pub fn pause(&self) {
+        self.pause.pause();
+    }
+
+    pub fn resume(&self) {
+        self.pause.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    /// A handle for pausing and resuming this runner from other threads.
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.pause.clone()
+    }
+
+    /// Performs a single step of the model.
+    ///
+    /// Returns false once the model has finished.
+    pub fn step(&mut self) -> bool {
+        self.step_with(&mut ())
+    }
+
+    /// Performs a single step, notifying `observer` of every application in
+    /// addition to the registered observers.
+    ///
+    /// Returns false once the model has finished.
+    pub fn step_with(&mut self, observer: &mut dyn Observer) -> bool {
+        self.breakpoints.hit = None;
+        if let Some(history) = &mut self.history {
+            let mut counters = Vec::new();
+            self.state.save_counters(&mut counters);
+            history.begin_step(counters, self.progress.clone());
+        }
+
+        let mut observers = Observers {
+            breakpoints: &mut self.breakpoints,
+            history: &mut self.history,
+            stats: &mut self.stats,
+            trace: &mut self.trace,
+            registered: &mut self.observers,
+            extra: observer,
+        };
+
+        let started = Instant::now();
+        let stepped = self
+            .state
+            .step(&mut self.rng, &mut self.grid, &mut observers);
+        self.elapsed += started.elapsed();
+
+        if !stepped {
+            if let Some(history) = &mut self.history {
+                history.cancel_step();
+            }
+
+            return false;
+        }
+
+        self.progress.steps += 1;
+        self.progress.applications = self.state.applications();
+        self.progress.path = self.state.active_path();
+        true
+    }
+
+    /// Registers an observer to be notified of every pattern application.
+    pub fn add_observer(&mut self, observer: impl Observer + Send + Sync + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Stops [Runner::run] and [Runner::run_until] at `breakpoint`.
+    ///
+    /// Returns false, without adding the breakpoint, if no node of the
+    /// model has its name.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let nodes: Vec<usize> = self
+            .names
+            .iter()
+            .filter(|(_, name)| name == breakpoint.name())
+            .map(|(id, _)| *id)
+            .collect();
+
+        if nodes.is_empty() {
+            return false;
+        }
+
+        let counts = vec![0; nodes.len()];
+        self.breakpoints.watched.push(Watch {
+            breakpoint,
+            nodes,
+            counts,
+        });
+
+        true
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints = Breakpoints::default();
+    }
+
+    /// The breakpoint hit by the last step, if any.
+    pub fn breakpoint_hit(&self) -> Option<&Breakpoint> {
+        let hit = self.breakpoints.hit?;
+        Some(&self.breakpoints.watched[hit].breakpoint)
+    }
+
+    /// Starts recording every step so that it can be rewound.
+    pub fn enable_history(&mut self) {
+        self.history.get_or_insert_with(History::new);
+    }
+
+    /// Stops recording steps and forgets the recorded history.
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Starts collecting per-node and per-rule statistics.
+    pub fn enable_stats(&mut self) {
+        self.stats.get_or_insert_with(Stats::new);
+    }
+
+    /// Stops collecting statistics and forgets those collected.
+    pub fn disable_stats(&mut self) {
+        self.stats = None;
+    }
+
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Starts logging every application from here on. Rewinding doesn't
+    /// remove events from the log.
+    pub fn enable_trace(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(Trace::new(&self.state, self.progress.steps));
+        }
+    }
+
+    /// Stops logging applications and returns the log so far.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Undoes up to `steps` recorded steps, restoring both the grid and the
+    /// node states. The RNG is not rewound, so stepping again explores a
+    /// different branch.
+    ///
+    /// Returns how many steps were undone.
+    pub fn rewind(&mut self, steps: usize) -> usize {
+        let Some(history) = &mut self.history else {
+            return 0;
+        };
+
+        let mut undone = 0;
+        while undone < steps {
+            let Some((counters, progress)) = history.undo_step(&mut self.grid) else {
+                break;
+            };
+
+            self.state.load_counters(&mut counters.into_iter());
+            self.progress = progress;
+            undone += 1;
+        }
+
+        undone
+    }
+
+    /// Rewinds to the point just after the given step was taken.
+    ///
+    /// Returns false if that step is not in the recorded history.
+    pub fn rewind_to(&mut self, step: usize) -> bool {
+        let back = self.progress.steps.saturating_sub(step);
+        let recorded = self.history.as_ref().map_or(0, History::len);
+        if step > self.progress.steps || back > recorded {
+            return false;
+        }
+
+        self.rewind(back);
+        true
+    }
+
+    /// Snapshots the grid, node states, and RNG so that the run can later be
+    /// resumed with [Runner::restore].
+    pub fn checkpoint(&self) -> Checkpoint<R>
+    where
+        R: Clone,
+    {
+        let mut counters = Vec::new();
+        self.state.save_counters(&mut counters);
+        let mut caches = Vec::new();
+        self.state.save_caches(&mut caches);
+        Checkpoint {
+            grid: self.grid.clone(),
+            model_hash: self.model_hash,
+            counters,
+            caches,
+            steps: self.progress.steps,
+            seed: self.seed,
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Resumes from a checkpoint taken by a runner for the same model. Any
+    /// recorded history is forgotten, since it can't be rewound past.
+    pub fn restore(&mut self, checkpoint: &Checkpoint<R>) -> Result<(), CheckpointError>
+    where
+        R: Clone,
+    {
+        let mut expected = Vec::new();
+        self.state.save_counters(&mut expected);
+        if checkpoint.model_hash != self.model_hash || expected.len() != checkpoint.counters.len() {
+            return Err(CheckpointError::ModelMismatch);
+        }
+
+        let mut state = self.state.clone();
+        state.load_counters(&mut checkpoint.counters.iter().copied());
+        let mut caches = checkpoint.caches.iter().copied();
+        if state.load_caches(&mut caches).is_none() || caches.next().is_some() {
+            return Err(CheckpointError::ModelMismatch);
+        }
+
+        self.state = state;
+        let periodic = self.grid.periodic;
+        self.grid = checkpoint.grid.clone();
+        self.grid.periodic = periodic;
+        self.rng = checkpoint.rng.clone();
+        self.seed = checkpoint.seed;
+        self.progress = Progress {
+            steps: checkpoint.steps,
+            applications: self.state.applications(),
+            path: self.state.active_path(),
+        };
+
+        if let Some(history) = &mut self.history {
+            *history = History::new();
+        }
+
+        Ok(())
+    }
+
+    /// Steps the model until it finishes or its budget runs out.
+    pub fn run(&mut self) -> Outcome {
+        self.run_until(|_| false)
+    }
+
+    /// Steps the model until `predicate` returns true for the grid, the
+    /// model finishes, or the budget runs out, whichever comes first.
+    ///
+    /// The predicate is checked before the first step, so a grid that
+    /// already satisfies it is left untouched.
+    pub fn run_until(&mut self, mut predicate: impl FnMut(&Grid) -> bool) -> Outcome {
+        let deadline = self
+            .budget
+            .duration
+            .map(|duration| Instant::now() + duration);
+
+        loop {
+            if self.pause.is_paused() {
+                return Outcome::Paused;
+            }
+
+            if predicate(&self.grid) {
+                return Outcome::Predicate;
+            }
+
+            if let Some(limit) = self.budget.applications {
+                if self.progress.applications >= limit {
+                    return Outcome::StepLimit;
+                }
+            }
+
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    return Outcome::Timeout;
+                }
+            }
+
+            if !self.step() {
+                return Outcome::Finished;
+            }
+
+            if self.breakpoints.hit.is_some() {
+                return Outcome::Breakpoint;
+            }
+        }
+    }
+
+    /// Steps the model until it has applied `applications` patterns, for
+    /// hosts that spread a run over many frames.
+    ///
+    /// Steps aren't split, so a step that applies many patterns at once may
+    /// take the total past `applications`. Stops early if the model
+    /// finishes, a breakpoint is hit, or the runner is paused, and ignores
+    /// the budget.
+    pub fn step_for(&mut self, applications: usize) -> Slice {
+        let start = self.progress.clone();
+        let mut finished = false;
+        while self.progress.applications - start.applications < applications
+            && !self.pause.is_paused()
+        {
+            if !self.step() {
+                finished = true;
+                break;
+            }
+
+            if self.breakpoints.hit.is_some() {
+                break;
+            }
+        }
+
+        Slice {
+            steps: self.progress.steps - start.steps,
+            applications: self.progress.applications - start.applications,
+            finished,
+        }
+    }
+
+    /// Returns an iterator that steps the model, yielding the progress after
+    /// each step.
+    pub fn steps(&mut self) -> Steps<'_, R> {
+        Steps { runner: self }
+    }
+
+    /// Returns an iterator that steps the model, yielding a copy of the grid
+    /// after each step.
+    pub fn snapshots(&mut self) -> Snapshots<'_, R> {
+        Snapshots { runner: self }
+    }
+
+    /// Steps the model until it finishes, capturing frames into a recorder.
+    ///
+    /// The seed, if known, is written as a comment. Returns the recorder's
+    /// writer after the final frame is written.
+    pub fn record<W: Write>(&mut self, mut recorder: GifRecorder<W>) -> Result<W, EncodingError> {
+        if let Some(seed) = self.seed {
+            recorder.comment(&format!("seed {}", seed))?;
+        }
+
+        while self.step() {
+            recorder.update(&self.progress, &self.grid)?;
+        }
+
+        recorder.finish(&self.grid)
+    }
+
+    /// Steps the model until it finishes, capturing frames into a video.
+    pub fn record_video(&mut self, mut recorder: VideoRecorder) -> Result<(), VideoError> {
+        while self.step() {
+            recorder.update(&self.progress, &self.grid)?;
+        }
+
+        recorder.finish(&self.grid)
+    }
+}
+
+/// An iterator over a run's steps. See [Runner::steps].
+pub struct Steps<'a, R> {
+    runner: &'a mut Runner<R>,
+}
+
+impl<'a, R: Rng> Iterator for Steps<'a, R> {
+    type Item = Progress;
+
+    fn next(&mut self) -> Option<Progress> {
+        if self.runner.step() {
+            Some(self.runner.progress.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, R: Rng> FusedIterator for Steps<'a, R> {}
+
+/// The state of a run's grid after a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub progress: Progress,
+    pub grid: Grid,
+}
+
+/// An iterator over snapshots of a run. See [Runner::snapshots].
+pub struct Snapshots<'a, R> {
+    runner: &'a mut Runner<R>,
+}
+
+impl<'a, R: Rng> Iterator for Snapshots<'a, R> {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Snapshot> {
+        if self.runner.step() {
+            Some(Snapshot {
+                progress: self.runner.progress.clone(),
+                grid: self.runner.grid.clone(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, R: Rng> FusedIterator for Snapshots<'a, R> {}
+
+/// A breakpoint and the nodes it watches, with how many applications each
+/// has made.
+struct Watch {
+    breakpoint: Breakpoint,
+    nodes: Vec<usize>,
+    counts: Vec<usize>,
+}
+
+/// Watches a run for its breakpoints as an [Observer].
+#[derive(Default)]
+struct Breakpoints {
+    watched: Vec<Watch>,
+
+    /// The watched nodes that have started and not finished since.
+    running: Vec<usize>,
+
+    /// The nodes currently stepping, innermost last.
+    active: Vec<usize>,
+
+    /// The index of the first breakpoint hit during the current step.
+    hit: Option<usize>,
+}
+
+impl Breakpoints {
+    fn hit(&mut self, matches: impl Fn(&Watch) -> bool) {
+        if self.hit.is_none() {
+            self.hit = self.watched.iter().position(matches);
+        }
+    }
+}
+
+impl Observer for Breakpoints {
+    fn begin_step(&mut self, node: usize) {
+        if !self.watched.is_empty() {
+            self.active.push(node);
+        }
+    }
+
+    fn after_apply(&mut self, _grid: &Grid, _application: &Application) {
+        for (idx, watch) in self.watched.iter_mut().enumerate() {
+            let Breakpoint::Applications(_, limit) = watch.breakpoint else {
+                continue;
+            };
+
+            for (node, count) in watch.nodes.iter().zip(watch.counts.iter_mut()) {
+                if self.active.contains(node) {
+                    *count += 1;
+                    if *count == limit && self.hit.is_none() {
+                        self.hit = Some(idx);
+                    }
+                }
+            }
+        }
+    }
+
+    fn end_step(&mut self, node: usize, stepped: bool) {
+        if self.watched.is_empty() {
+            return;
+        }
+
+        self.active.pop();
+        let running = self.running.iter().position(|other| *other == node);
+        match (stepped, running) {
+            (true, None) => {
+                self.running.push(node);
+                self.hit(|watch| {
+                    matches!(watch.breakpoint, Breakpoint::Start(_)) && watch.nodes.contains(&node)
+                });
+            }
+            (false, Some(running)) => {
+                self.running.swap_remove(running);
+                self.hit(|watch| {
+                    matches!(watch.breakpoint, Breakpoint::Finish(_)) && watch.nodes.contains(&node)
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Every observer notified during a single [Runner] step.
+struct Observers<'a> {
+    breakpoints: &'a mut Breakpoints,
+    history: &'a mut Option<History>,
+    stats: &'a mut Option<Stats>,
+    trace: &'a mut Option<Trace>,
+    registered: &'a mut [Box<dyn Observer + Send + Sync>],
+    extra: &'a mut dyn Observer,
+}
+
+impl<'a> Observers<'a> {
+    fn for_each(&mut self, mut f: impl FnMut(&mut dyn Observer)) {
+        f(self.breakpoints);
+
+        if let Some(history) = self.history {
+            f(history);
+        }
+
+        if let Some(stats) = self.stats {
+            f(stats);
+        }
+
+        if let Some(trace) = self.trace {
+            f(trace);
+        }
+
+        for observer in self.registered.iter_mut() {
+            f(observer.as_mut());
+        }
+
+        f(self.extra);
+    }
+}
+
+impl<'a> Observer for Observers<'a> {
+    fn begin_step(&mut self, node: usize) {
+        self.for_each(|observer| observer.begin_step(node));
+    }
+
+    fn on_scan(&mut self, node: usize, rule: usize, matches: usize) {
+        self.for_each(|observer| observer.on_scan(node, rule, matches));
+    }
+
+    fn before_apply(&mut self, grid: &Grid, application: &Application) {
+        self.for_each(|observer| observer.before_apply(grid, application));
+    }
+
+    fn after_apply(&mut self, grid: &Grid, application: &Application) {
+        self.for_each(|observer| observer.after_apply(grid, application));
+    }
+
+    fn end_step(&mut self, node: usize, stepped: bool) {
+        self.for_each(|observer| observer.end_step(node, stepped));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use rand::rngs::SmallRng;
+
+    fn run_seed(model: &Model, seed: u64) -> Grid {
+        let grid = model.make_grid(48, 48);
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, grid, seed);
+        assert_eq!(runner.seed(), Some(seed));
+        runner.run();
+        runner.grid
+    }
+
+    #[test]
+    fn iterate_steps() {
+        let model = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        let grid = model.make_grid(32, 32);
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, grid, 7);
+
+        let steps: Vec<_> = runner.steps().take(3).map(|p| p.steps).collect();
+        assert_eq!(steps, [1, 2, 3]);
+
+        let snapshot = runner.snapshots().step_by(10).nth(2).unwrap();
+        assert_eq!(snapshot.progress.steps, 24);
+        assert_eq!(snapshot.grid, runner.grid);
+
+        let remaining = runner.steps().count();
+        assert!(remaining > 0);
+        assert_eq!(runner.steps().next(), None);
+        assert_eq!(runner.progress().steps, 24 + remaining);
+    }
+
+    #[test]
+    fn run_until_predicate() {
+        let model = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        let grid = model.make_grid(32, 32);
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, grid, 5);
+
+        let count = |grid: &Grid, symbol| grid.grid.iter().filter(|s| **s == symbol).count();
+        let outcome = runner.run_until(|grid| count(grid, Symbol::Red) >= 20);
+        assert_eq!(outcome, Outcome::Predicate);
+        assert_eq!(count(&runner.grid, Symbol::Red), 20);
+
+        let steps = runner.progress().steps;
+        assert_eq!(runner.run_until(|_| true), Outcome::Predicate);
+        assert_eq!(runner.progress().steps, steps);
+
+        assert_eq!(runner.run_until(|_| false), Outcome::Finished);
+        assert!(runner.progress().steps > steps);
+    }
+
+    #[test]
+    fn budgets() {
+        let model = Model::from_xml(r#"<one in="B" out="W"/>"#).unwrap();
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+
+        runner.set_budget(Budget {
+            applications: Some(10),
+            ..Default::default()
+        });
+
+        assert_eq!(runner.run(), Outcome::StepLimit);
+        assert_eq!(runner.progress().applications, 10);
+
+        runner.set_budget(Budget {
+            duration: Some(Duration::ZERO),
+            ..Default::default()
+        });
+
+        assert_eq!(runner.run(), Outcome::Timeout);
+        assert_eq!(runner.progress().applications, 10);
+
+        runner.set_budget(Budget::default());
+        assert_eq!(runner.run(), Outcome::Finished);
+    }
+
+    #[test]
+    fn step_in_slices() {
+        let model = Model::from_xml(
+            r#"<sequence values="BW">
+                <one in="B" out="W" steps="4"/>
+                <all in="B" out="W"/>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        let slice = runner.step_for(3);
+        assert_eq!(
+            slice,
+            Slice {
+                steps: 3,
+                applications: 3,
+                finished: false
+            }
+        );
+
+        assert_eq!(runner.step_for(0), Slice::default());
+
+        let slice = runner.step_for(2);
+        assert_eq!((slice.steps, slice.finished), (2, false));
+        assert!(slice.applications > 2);
+
+        let slice = runner.step_for(100);
+        assert_eq!((slice.steps, slice.applications), (0, 0));
+        assert!(slice.finished);
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let model = Model::from_xml(r#"<one in="B" out="W"/>"#).unwrap();
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        let handle = runner.pause_handle();
+
+        runner.pause();
+        assert!(handle.is_paused());
+        assert_eq!(runner.run(), Outcome::Paused);
+        assert_eq!(runner.progress().steps, 0);
+        assert!(runner.step());
+        assert_eq!(runner.progress().steps, 1);
+
+        runner.resume();
+        let pauser = handle.clone();
+        let outcome = runner.run_until(move |grid| {
+            if grid.histogram().get(Symbol::White) == 3 {
+                pauser.pause();
+            }
+
+            false
+        });
+
+        // The predicate is checked just before the step that makes it pause.
+        assert_eq!(outcome, Outcome::Paused);
+        assert_eq!(runner.progress().steps, 4);
+
+        handle.resume();
+        assert!(!runner.is_paused());
+        assert_eq!(runner.run(), Outcome::Finished);
+
+        let handle = runner.pause_handle();
+        std::thread::spawn(move || handle.pause()).join().unwrap();
+        assert!(runner.is_paused());
+    }
+
+    #[test]
+    fn breakpoints() {
+        let model = Model::from_xml(
+            r#"<sequence values="BWR">
+                <one name="grow" in="B" out="W" steps="5"/>
+                <all name="paint" in="W" out="R"/>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        assert!(!runner.add_breakpoint(Breakpoint::Start("missing".to_string())));
+        assert!(runner.add_breakpoint(Breakpoint::Applications("grow".to_string(), 3)));
+        assert!(runner.add_breakpoint(Breakpoint::Finish("grow".to_string())));
+        assert!(runner.add_breakpoint(Breakpoint::Start("paint".to_string())));
+
+        let hit = |runner: &Runner<SmallRng>| runner.breakpoint_hit().cloned();
+        assert_eq!(runner.run(), Outcome::Breakpoint);
+        assert_eq!(runner.progress().applications, 3);
+        assert_eq!(
+            hit(&runner),
+            Some(Breakpoint::Applications("grow".to_string(), 3))
+        );
+
+        assert_eq!(runner.run(), Outcome::Breakpoint);
+        assert_eq!(hit(&runner), Some(Breakpoint::Finish("grow".to_string())));
+        assert_eq!(runner.grid.histogram().get(Symbol::Red), 5);
+
+        runner.clear_breakpoints();
+        assert_eq!(runner.run(), Outcome::Finished);
+        assert_eq!(hit(&runner), None);
+
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        assert!(runner.add_breakpoint(Breakpoint::Start("paint".to_string())));
+        assert_eq!(runner.run(), Outcome::Breakpoint);
+        assert_eq!(hit(&runner), Some(Breakpoint::Start("paint".to_string())));
+        assert_eq!(runner.progress().applications, 10);
+    }
+
+    #[test]
+    fn rewind_history() {
+        let model = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        let grid = model.make_grid(32, 32);
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, grid, 3);
+        runner.steps().nth(9);
+        runner.enable_history();
+
+        runner.steps().nth(99);
+        let grid = runner.grid.clone();
+        let state = runner.state.clone();
+        let progress = runner.progress().clone();
+
+        runner.run();
+        assert_ne!(runner.grid, grid);
+        assert!(!runner.rewind_to(5));
+        assert!(runner.rewind_to(110));
+        assert_eq!(runner.grid, grid);
+        assert_eq!(runner.state, state);
+        assert_eq!(runner.progress(), &progress);
+
+        assert_eq!(runner.rewind(1000), 100);
+        assert_eq!(runner.progress().steps, 10);
+        assert_eq!(runner.history().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn observe_applications() {
+        use observer::{Application, OnApply};
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Default)]
+        struct Changes(Vec<(usize, usize, Pattern, Pattern)>, Option<Pattern>);
+
+        impl Observer for Changes {
+            fn before_apply(&mut self, grid: &Grid, application: &Application) {
+                self.1 = Some(application.read_cells(grid));
+            }
+
+            fn after_apply(&mut self, grid: &Grid, application: &Application) {
+                let before = self.1.take().unwrap();
+                let after = application.read_cells(grid);
+                self.0
+                    .push((application.node, application.rule, before, after));
+            }
+        }
+
+        let model = Model::from_xml(
+            r#"<sequence values="BRW">
+                <one in="B" out="R" steps="1"/>
+                <one in="RB" out="WR" steps="3"/>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        let count = Arc::new(Mutex::new(0));
+        let counter = count.clone();
+        runner.add_observer(OnApply(move |_: &Grid, _: &Application| {
+            *counter.lock().unwrap() += 1;
+        }));
+
+        let mut changes = Changes::default();
+        while runner.step_with(&mut changes) {}
+
+        assert_eq!(*count.lock().unwrap(), 4);
+        assert_eq!(changes.0.len(), 4);
+
+        let (node, rule, before, after) = &changes.0[0];
+        assert_eq!((*node, *rule), (1, 0));
+        assert_eq!(before, &Pattern::from_string("B"));
+        assert_eq!(after, &Pattern::from_string("R"));
+
+        for (node, _, before, after) in &changes.0[1..] {
+            assert_eq!(*node, 2);
+            assert!(before.grid.contains(&Some(Symbol::Black)));
+            assert!(after.grid.contains(&Some(Symbol::White)));
+        }
+    }
+
+    #[test]
+    fn seeds_reproduce() {
+        let model = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        assert_eq!(run_seed(&model, 12345), run_seed(&model, 12345));
+        assert_ne!(run_seed(&model, 12345), run_seed(&model, 54321));
+    }
+}
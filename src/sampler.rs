@@ -0,0 +1,189 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{Rng, RngCore};
+
+use super::*;
+
+/// Chooses which match a [OneNode](crate::node::OneNode) applies.
+pub trait Sampler {
+    /// Chooses one of `matches`, found on `grid`, and returns its index
+    /// within `matches`.
+    ///
+    /// Returns `None` if no match should be applied.
+    fn choose(&mut self, rng: &mut dyn RngCore, grid: &Grid, matches: &[Match]) -> Option<usize>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AnySampler {
+    /// Every match is equally likely.
+    #[default]
+    Uniform,
+
+    /// Matches are weighted by their rule, indexed like the node's rules.
+    /// Rules without a weight count as 1.
+    Weighted(Vec<u32>),
+
+    /// The match closest to a point, with ties broken randomly.
+    Nearest(Point),
+
+    /// The match farthest from the previous application, with ties broken
+    /// randomly. The first match is chosen uniformly.
+    FarthestFromLast(Option<Point>),
+
+    /// The match nearest to the previous application, with ties broken
+    /// randomly. The first match is chosen uniformly.
+    ///
+    /// This grows structures depth-first from their newest cells, where
+    /// [AnySampler::Uniform] grows them breadth-first.
+    NearestToLast(Option<Point>),
+
+    /// Matches are weighted by the field's value at their position. Matches
+    /// on zero-weight cells are never chosen.
+    Field(GenericGrid<u32>),
+}
+
+impl Sampler for AnySampler {
+    fn choose(&mut self, rng: &mut dyn RngCore, grid: &Grid, matches: &[Match]) -> Option<usize> {
+        use AnySampler::*;
+
+        let width = grid.width;
+
+        if matches.is_empty() {
+            return None;
+        }
+
+        let chosen = match self {
+            Uniform => Some(rng.gen_range(0..matches.len())),
+            Weighted(weights) => choose_weighted(rng, matches, |matched| {
+                weights.get(matched.rule()).copied().unwrap_or(1)
+            }),
+            Nearest(target) => {
+                let target = *target;
+                choose_best(rng, matches, width, |at| {
+                    usize::MAX - distance_squared(at, target)
+                })
+            }
+            FarthestFromLast(None) | NearestToLast(None) => Some(rng.gen_range(0..matches.len())),
+            FarthestFromLast(Some(last)) => {
+                let last = *last;
+                choose_best(rng, matches, width, |at| distance_squared(at, last))
+            }
+            NearestToLast(Some(last)) => {
+                let last = *last;
+                choose_best(rng, matches, width, |at| {
+                    usize::MAX - distance_squared(at, last)
+                })
+            }
+            Field(field) => choose_weighted(rng, matches, |matched| field[matched.at(width)]),
+        };
+
+        if let (FarthestFromLast(last) | NearestToLast(last), Some(idx)) = (self, chosen) {
+            *last = Some(matches[idx].at(width));
+        }
+
+        chosen
+    }
+}
+
+impl AnySampler {
+    /// Appends any state this sampler carries between steps, always using
+    /// the same number of counters for the same kind of sampler.
+    pub fn save_counters(&self, counters: &mut Vec<usize>) {
+        if let AnySampler::FarthestFromLast(last) | AnySampler::NearestToLast(last) = self {
+            match last {
+                Some(at) => counters.extend([1, at.x, at.y]),
+                None => counters.extend([0, 0, 0]),
+            }
+        }
+    }
+
+    /// Restores state saved by [AnySampler::save_counters].
+    pub fn load_counters(&mut self, counters: &mut impl Iterator<Item = usize>) {
+        let mut next = || counters.next().expect("too few counters");
+        if let AnySampler::FarthestFromLast(last) | AnySampler::NearestToLast(last) = self {
+            let (some, x, y) = (next(), next(), next());
+            *last = (some != 0).then_some(Point { x, y });
+        }
+    }
+}
+
+fn distance_squared(a: Point, b: Point) -> usize {
+    let dx = a.x.abs_diff(b.x);
+    let dy = a.y.abs_diff(b.y);
+    dx * dx + dy * dy
+}
+
+fn choose_weighted(
+    rng: &mut dyn RngCore,
+    matches: &[Match],
+    weight: impl Fn(&Match) -> u32,
+) -> Option<usize> {
+    let weights = WeightedIndex::new(matches.iter().map(weight)).ok()?;
+    Some(weights.sample(rng))
+}
+
+/// Chooses randomly among the matches with the highest score.
+fn choose_best(
+    rng: &mut dyn RngCore,
+    matches: &[Match],
+    width: usize,
+    score: impl Fn(Point) -> usize,
+) -> Option<usize> {
+    let best = matches.iter().map(|m| score(m.at(width))).max()?;
+    let tied: Vec<_> = (0..matches.len())
+        .filter(|idx| score(matches[*idx].at(width)) == best)
+        .collect();
+
+    tied.choose(rng).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches() -> Vec<Match> {
+        [(0, 0, 0), (1, 5, 5), (0, 2, 1), (1, 9, 9)]
+            .into_iter()
+            .map(|(rule, x, y)| Match::new(rule, Point { x, y }, 10))
+            .collect()
+    }
+
+    #[test]
+    fn built_in_samplers() {
+        let mut rng = crate::tests::make_rng();
+        let grid = Grid::new(10, 10);
+        let matches = matches();
+
+        let mut nearest = AnySampler::Nearest(Point { x: 3, y: 1 });
+        assert_eq!(nearest.choose(&mut rng, &grid, &matches), Some(2));
+
+        let mut farthest = AnySampler::FarthestFromLast(Some(Point::ZERO));
+        assert_eq!(farthest.choose(&mut rng, &grid, &matches), Some(3));
+        assert_eq!(
+            farthest,
+            AnySampler::FarthestFromLast(Some(Point { x: 9, y: 9 }))
+        );
+        assert_eq!(farthest.choose(&mut rng, &grid, &matches), Some(0));
+
+        let mut recent = AnySampler::NearestToLast(Some(Point { x: 8, y: 8 }));
+        assert_eq!(recent.choose(&mut rng, &grid, &matches), Some(3));
+        assert_eq!(recent.choose(&mut rng, &grid, &matches), Some(3));
+        assert_eq!(
+            recent,
+            AnySampler::NearestToLast(Some(Point { x: 9, y: 9 }))
+        );
+
+        let mut weighted = AnySampler::Weighted(vec![0, 1]);
+        for _ in 0..16 {
+            let idx = weighted.choose(&mut rng, &grid, &matches).unwrap();
+            assert_eq!(matches[idx].rule(), 1);
+        }
+
+        let mut field = GenericGrid::new(10, 10);
+        field[Point { x: 5, y: 5 }] = 3;
+        let mut field = AnySampler::Field(field);
+        assert_eq!(field.choose(&mut rng, &grid, &matches), Some(1));
+
+        let mut uniform = AnySampler::Uniform;
+        assert_eq!(uniform.choose(&mut rng, &grid, &[]), None);
+    }
+}
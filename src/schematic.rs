@@ -0,0 +1,309 @@
+//! Exporting grids as Minecraft schematics.
+//!
+//! [Grid::to_schematic] writes a Sponge schematic (version 2), the `.schem`
+//! format read by WorldEdit, FAWE, and most other Minecraft building tools.
+//! Each cell becomes a block: a grid's X and Y run along Minecraft's X and
+//! Z, and its layers stack upwards along Y, as in [Grid::mesh].
+//!
+//! [Blocks] maps each symbol to a block ID. By default [Symbol::Black] is
+//! air and every other symbol is the concrete closest to its palette color,
+//! so outputs can be pasted in straight away and retextured later.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use super::*;
+
+/// The Minecraft data version written to schematics, that of 1.20.1.
+/// Block IDs are interpreted as of this version.
+pub const DATA_VERSION: i32 = 3465;
+
+/// The sixteen concretes and their approximate colors.
+const CONCRETES: [(&str, [u8; 3]); 16] = [
+    ("white", [207, 213, 214]),
+    ("orange", [224, 97, 1]),
+    ("magenta", [169, 48, 159]),
+    ("light_blue", [36, 137, 199]),
+    ("yellow", [241, 175, 21]),
+    ("lime", [94, 169, 24]),
+    ("pink", [214, 101, 143]),
+    ("gray", [55, 58, 62]),
+    ("light_gray", [125, 125, 115]),
+    ("cyan", [21, 119, 136]),
+    ("purple", [100, 32, 156]),
+    ("blue", [45, 47, 143]),
+    ("brown", [96, 60, 32]),
+    ("green", [73, 91, 36]),
+    ("red", [142, 33, 33]),
+    ("black", [8, 10, 15]),
+];
+
+/// The block ID of each symbol. See the [module](self) docs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Blocks {
+    blocks: Vec<String>,
+}
+
+impl Default for Blocks {
+    fn default() -> Self {
+        let blocks = Symbol::ALL.map(|symbol| {
+            if symbol == Symbol::Black {
+                return "minecraft:air".to_string();
+            }
+
+            let rgb = &Symbol::PALETTE[symbol.palette_index() as usize * 3..][..3];
+            let distance = |color: &[u8; 3]| -> i32 {
+                (0..3)
+                    .map(|i| (rgb[i] as i32 - color[i] as i32).pow(2))
+                    .sum()
+            };
+
+            let (name, _) = CONCRETES
+                .iter()
+                .min_by_key(|(_, color)| distance(color))
+                .unwrap();
+            format!("minecraft:{}_concrete", name)
+        });
+
+        Self {
+            blocks: blocks.to_vec(),
+        }
+    }
+}
+
+impl Blocks {
+    /// Maps `symbol` to the block with ID `block`, such as
+    /// `"minecraft:stone"` or `"minecraft:oak_stairs[facing=north]"`.
+    pub fn with(mut self, symbol: Symbol, block: impl Into<String>) -> Self {
+        self.blocks[symbol.palette_index() as usize] = block.into();
+        self
+    }
+
+    pub fn block(&self, symbol: Symbol) -> &str {
+        &self.blocks[symbol.palette_index() as usize]
+    }
+}
+
+/// Why a block mapping couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseBlocksError {
+    /// An entry wasn't a symbol character, `=`, and a block ID.
+    InvalidEntry(String),
+    UnknownSymbol(char),
+}
+
+impl Display for ParseBlocksError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ParseBlocksError::*;
+        match self {
+            InvalidEntry(entry) => write!(
+                fmt,
+                "invalid block entry '{}', expected SYMBOL=BLOCK",
+                entry
+            ),
+            UnknownSymbol(c) => write!(fmt, "unrecognized symbol '{}'", c),
+        }
+    }
+}
+
+impl std::error::Error for ParseBlocksError {}
+
+impl FromStr for Blocks {
+    type Err = ParseBlocksError;
+
+    /// Parses whitespace-separated `SYMBOL=BLOCK` entries, such as
+    /// `"W=minecraft:stone U=minecraft:water"`, on top of the
+    /// [default](Blocks::default) blocks. Block IDs without a namespace are
+    /// in `minecraft:`. Commas are left to block states, as in
+    /// `minecraft:oak_stairs[facing=north,half=top]`.
+    fn from_str(string: &str) -> Result<Self, ParseBlocksError> {
+        let mut blocks = Self::default();
+        for entry in string.split_whitespace() {
+            let invalid = || ParseBlocksError::InvalidEntry(entry.to_string());
+            let (symbol, block) = entry.split_once('=').ok_or_else(invalid)?;
+            let mut chars = symbol.chars();
+            let (Some(symbol), None) = (chars.next(), chars.next()) else {
+                return Err(invalid());
+            };
+
+            if block.is_empty() {
+                return Err(invalid());
+            }
+
+            let symbol =
+                Symbol::try_from_char(symbol).ok_or(ParseBlocksError::UnknownSymbol(symbol))?;
+            blocks = match block.split('[').next().unwrap_or_default().contains(':') {
+                true => blocks.with(symbol, block),
+                false => blocks.with(symbol, format!("minecraft:{}", block)),
+            };
+        }
+
+        Ok(blocks)
+    }
+}
+
+impl Grid {
+    /// Writes the grid as a gzipped Sponge schematic. See the
+    /// [module](self) docs.
+    ///
+    /// Fails only if compression does, or if the grid is over 65535 cells
+    /// along any axis, which schematics can't hold.
+    pub fn to_schematic(&self, blocks: &Blocks) -> io::Result<Vec<u8>> {
+        let too_large = [self.width, self.height, self.layers]
+            .iter()
+            .any(|size| *size > u16::MAX as usize);
+        if too_large {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "grids over 65535 cells along an axis don't fit in a schematic",
+            ));
+        }
+
+        // Block IDs in order of first use, and each block's palette index
+        // as a varint in Minecraft's Y, Z, X order.
+        let mut palette: Vec<&str> = Vec::new();
+        let mut data = Vec::with_capacity(self.grid.len());
+        for layer in 0..self.layers {
+            for at in self.enumerate_points() {
+                let block = blocks.block(self[(at, layer)]);
+                let index = match palette.iter().position(|other| *other == block) {
+                    Some(index) => index,
+                    None => {
+                        palette.push(block);
+                        palette.len() - 1
+                    }
+                };
+
+                write_varint(&mut data, index);
+            }
+        }
+
+        let mut nbt = Vec::new();
+        tag(&mut nbt, COMPOUND, "Schematic");
+        tag(&mut nbt, INT, "Version");
+        nbt.extend(2i32.to_be_bytes());
+        tag(&mut nbt, INT, "DataVersion");
+        nbt.extend(DATA_VERSION.to_be_bytes());
+
+        for (name, size) in [
+            ("Width", self.width),
+            ("Height", self.layers),
+            ("Length", self.height),
+        ] {
+            tag(&mut nbt, SHORT, name);
+            nbt.extend((size as u16).to_be_bytes());
+        }
+
+        tag(&mut nbt, INT_ARRAY, "Offset");
+        nbt.extend(3i32.to_be_bytes());
+        nbt.extend([0; 12]);
+
+        tag(&mut nbt, INT, "PaletteMax");
+        nbt.extend((palette.len() as i32).to_be_bytes());
+        tag(&mut nbt, COMPOUND, "Palette");
+        for (index, block) in palette.iter().enumerate() {
+            tag(&mut nbt, INT, block);
+            nbt.extend((index as i32).to_be_bytes());
+        }
+
+        nbt.push(END);
+
+        tag(&mut nbt, BYTE_ARRAY, "BlockData");
+        nbt.extend((data.len() as i32).to_be_bytes());
+        nbt.extend(data);
+        nbt.push(END);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&nbt)?;
+        encoder.finish()
+    }
+}
+
+const END: u8 = 0;
+const SHORT: u8 = 2;
+const INT: u8 = 3;
+const BYTE_ARRAY: u8 = 7;
+const COMPOUND: u8 = 10;
+const INT_ARRAY: u8 = 11;
+
+/// Starts a named NBT tag of type `kind`.
+fn tag(nbt: &mut Vec<u8>, kind: u8, name: &str) {
+    nbt.push(kind);
+    nbt.extend((name.len() as u16).to_be_bytes());
+    nbt.extend(name.as_bytes());
+}
+
+/// Writes `value` seven bits at a time, lowest first, with the top bit of
+/// each byte set if more follow.
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn write_schematic() {
+        let blocks: Blocks =
+            "W=stone U=minecraft:water[level=0] R=oak_stairs[facing=north,half=top]"
+                .parse()
+                .unwrap();
+        assert_eq!(blocks.block(Symbol::Black), "minecraft:air");
+        assert_eq!(blocks.block(Symbol::White), "minecraft:stone");
+        assert_eq!(blocks.block(Symbol::Blue), "minecraft:water[level=0]");
+        assert_eq!(
+            blocks.block(Symbol::Red),
+            "minecraft:oak_stairs[facing=north,half=top]"
+        );
+        assert_eq!(blocks.block(Symbol::Green), "minecraft:lime_concrete");
+        assert_eq!(
+            "W".parse::<Blocks>(),
+            Err(ParseBlocksError::InvalidEntry("W".to_string()))
+        );
+
+        let mut grid = Grid::with_layers(3, 2, 2);
+        grid[(Point { x: 1, y: 0 }, 0)] = Symbol::White;
+        grid[(Point { x: 2, y: 1 }, 1)] = Symbol::Blue;
+
+        let mut nbt = Vec::new();
+        let schematic = grid.to_schematic(&blocks).unwrap();
+        GzDecoder::new(schematic.as_slice())
+            .read_to_end(&mut nbt)
+            .unwrap();
+
+        assert_eq!(nbt[..12], *b"\x0a\x00\x09Schematic");
+        assert!(contains(&nbt, b"\x02\x00\x05Width\x00\x03"));
+        assert!(contains(&nbt, b"\x02\x00\x06Height\x00\x02"));
+        assert!(contains(&nbt, b"\x02\x00\x06Length\x00\x02"));
+        assert!(contains(&nbt, b"\x03\x00\x0aPaletteMax\x00\x00\x00\x03"));
+        assert!(contains(
+            &nbt,
+            b"\x03\x00\x0fminecraft:stone\x00\x00\x00\x01"
+        ));
+
+        let data = b"\x07\x00\x09BlockData\x00\x00\x00\x0c\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00";
+        assert!(nbt.ends_with(data));
+
+        let mut varint = Vec::new();
+        write_varint(&mut varint, 300);
+        assert_eq!(varint, [0xac, 0x02]);
+    }
+}
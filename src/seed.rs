@@ -0,0 +1,61 @@
+use super::*;
+
+/// A declarative placement of symbols applied to a grid before stepping.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Seed {
+    /// Fills every cell.
+    Fill(Symbol),
+
+    /// Places a symbol in the center cell.
+    Center(Symbol),
+
+    /// Places a symbol at a specific cell.
+    At(Point, Symbol),
+
+    /// Fills the outermost ring of cells.
+    Border(Symbol),
+}
+
+impl Seed {
+    pub fn apply(&self, grid: &mut Grid) {
+        match *self {
+            Seed::Fill(symbol) => grid.grid.fill(symbol),
+            Seed::Center(symbol) => {
+                let at = Point {
+                    x: grid.width / 2,
+                    y: grid.height / 2,
+                };
+
+                grid[at] = symbol;
+            }
+            Seed::At(at, symbol) => grid[at] = symbol,
+            Seed::Border(symbol) => {
+                let (right, bottom) = (grid.width - 1, grid.height - 1);
+                for x in 0..grid.width {
+                    grid[Point { x, y: 0 }] = symbol;
+                    grid[Point { x, y: bottom }] = symbol;
+                }
+
+                for y in 0..grid.height {
+                    grid[Point { x: 0, y }] = symbol;
+                    grid[Point { x: right, y }] = symbol;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_seeds() {
+        let mut grid = Grid::new(5, 4);
+        Seed::Fill(Symbol::Blue).apply(&mut grid);
+        Seed::Border(Symbol::White).apply(&mut grid);
+        Seed::Center(Symbol::Red).apply(&mut grid);
+        Seed::At(Point { x: 1, y: 1 }, Symbol::Green).apply(&mut grid);
+        assert_eq!(grid.to_string(), "WWWWW\nWGUUW\nWURUW\nWWWWW\n");
+    }
+}
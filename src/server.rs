@@ -0,0 +1,340 @@
+//! Watching runs live from a browser.
+//!
+//! A [LiveServer] listens for HTTP connections and serves a small page that
+//! draws the grid on a canvas. The page connects back over a WebSocket at
+//! `/ws`, and every frame the server [sends](LiveServer::send) is pushed to
+//! each connected page, so long runs on a headless machine can be watched
+//! from anywhere that can reach it. Pages that connect mid-run are sent the
+//! latest frame straight away.
+//!
+//! Frames are binary WebSocket messages: the grid's width and height as
+//! little-endian `u16`s, the number of steps taken as a little-endian `u32`,
+//! then the [palette index](Symbol::palette_index) of each cell's
+//! [visible](Grid::visible) symbol in row order.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use base64::Engine;
+use rand::Rng;
+use sha1::{Digest, Sha1};
+
+use super::*;
+use recorder::{Capture, CapturePolicy};
+use runner::{Progress, Runner};
+
+/// Appended to a client's key to accept a WebSocket handshake.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How long a client may take to send its request or accept a frame before
+/// it is dropped.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>crabby-markov</title>
+<style>
+body { background: #111; color: #ccc; font-family: monospace; margin: 1em; }
+canvas { image-rendering: pixelated; width: min(90vw, 90vh); }
+</style>
+</head>
+<body>
+<div id="status">connecting</div>
+<canvas id="grid"></canvas>
+<script>
+const palette = PALETTE;
+const canvas = document.getElementById("grid");
+const status = document.getElementById("status");
+const context = canvas.getContext("2d");
+const socket = new WebSocket(`ws://${location.host}/ws`);
+socket.binaryType = "arraybuffer";
+socket.onclose = () => status.textContent += " (disconnected)";
+socket.onmessage = (event) => {
+  const view = new DataView(event.data);
+  const width = view.getUint16(0, true);
+  const height = view.getUint16(2, true);
+  const steps = view.getUint32(4, true);
+  const cells = new Uint8Array(event.data, 8);
+  canvas.width = width;
+  canvas.height = height;
+  const image = context.createImageData(width, height);
+  cells.forEach((index, cell) => {
+    image.data.set([...palette.slice(index * 3, index * 3 + 3), 255], cell * 4);
+  });
+  context.putImageData(image, 0, 0);
+  status.textContent = `${width}x${height}, ${steps} steps`;
+};
+</script>
+</body>
+</html>
+"#;
+
+/// The connected pages and the frame most recently sent to them.
+#[derive(Default)]
+struct Clients {
+    sockets: Vec<TcpStream>,
+    latest: Option<Vec<u8>>,
+}
+
+/// Serves a live view of a grid. See the [module](self) docs.
+///
+/// Connections are accepted on a background thread until the server is
+/// dropped.
+pub struct LiveServer {
+    addr: SocketAddr,
+    clients: Arc<Mutex<Clients>>,
+    closed: Arc<AtomicBool>,
+    capture: Capture,
+}
+
+impl LiveServer {
+    /// Starts listening on `addr`. Port 0 picks any free port, which
+    /// [LiveServer::addr] then gives.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(Clients::default()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let (accepted, stopped) = (clients.clone(), closed.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    return;
+                }
+
+                if let Ok(stream) = stream {
+                    // A misbehaving client only loses its own connection.
+                    let _ = accept(stream, &accepted);
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            clients,
+            closed,
+            capture: Capture::new(Default::default()),
+        })
+    }
+
+    pub fn with_policy(mut self, policy: CapturePolicy) -> Self {
+        self.capture = Capture::new(policy);
+        self
+    }
+
+    /// The address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The number of pages currently connected.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().sockets.len()
+    }
+
+    /// Sends a frame if the capture policy calls for one.
+    pub fn update(&mut self, progress: &Progress, grid: &Grid) {
+        if self.capture.update(progress) {
+            self.send(progress, grid);
+        }
+    }
+
+    /// Sends a frame of the grid to every connected page, dropping any that
+    /// can't keep up.
+    pub fn send(&self, progress: &Progress, grid: &Grid) {
+        let message = websocket_frame(&encode_frame(progress, grid));
+        let mut clients = self.clients.lock().unwrap();
+        clients
+            .sockets
+            .retain_mut(|socket| socket.write_all(&message).is_ok());
+        clients.latest = Some(message);
+    }
+}
+
+impl Drop for LiveServer {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::SeqCst);
+
+        // Wakes the accepting thread so that it sees the server is closed.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+impl<R: Rng> Runner<R> {
+    /// Steps the model until it finishes, streaming frames to `server`'s
+    /// pages as its capture policy calls for them and after the final step.
+    pub fn serve(&mut self, server: &mut LiveServer) {
+        while self.step() {
+            server.update(self.progress(), &self.grid);
+        }
+
+        server.send(self.progress(), &self.grid);
+    }
+}
+
+/// Serves the page or upgrades a connection to a WebSocket.
+fn accept(mut stream: TcpStream, clients: &Mutex<Clients>) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let mut key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    match (path, key) {
+        ("/ws", Some(key)) => {
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            )?;
+
+            let mut clients = clients.lock().unwrap();
+            if let Some(latest) = &clients.latest {
+                stream.write_all(latest)?;
+            }
+
+            clients.sockets.push(stream);
+        }
+        ("/", _) => {
+            let palette: Vec<_> = Symbol::PALETTE.iter().map(u8::to_string).collect();
+            let page = PAGE.replace("PALETTE", &format!("[{}]", palette.join(",")));
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                page.len(),
+                page
+            )?;
+        }
+        _ => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?,
+    }
+
+    Ok(())
+}
+
+/// The `Sec-WebSocket-Accept` header answering a client's key.
+fn accept_key(key: &str) -> String {
+    let digest = Sha1::digest(format!("{}{}", key, WEBSOCKET_GUID));
+    base64::engine::general_purpose::STANDARD.encode(digest)
+}
+
+fn encode_frame(progress: &Progress, grid: &Grid) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(8 + grid.width * grid.height);
+    frame.extend((grid.width as u16).to_le_bytes());
+    frame.extend((grid.height as u16).to_le_bytes());
+    frame.extend((progress.steps as u32).to_le_bytes());
+    frame.extend(
+        grid.enumerate_points()
+            .map(|at| grid.visible(at).palette_index()),
+    );
+
+    frame
+}
+
+/// Wraps `payload` in an unmasked binary WebSocket frame, as servers send.
+fn websocket_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x82];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend(payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn serve_frames() {
+        let server = LiveServer::bind("127.0.0.1:0").unwrap();
+        let connect = || {
+            let stream = TcpStream::connect(server.addr()).unwrap();
+            stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+            stream
+        };
+
+        let mut page = connect();
+        page.write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        page.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("<canvas"));
+        assert!(!response.contains("PALETTE"));
+
+        let mut socket = connect();
+        socket
+            .write_all(b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(socket.try_clone().unwrap());
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+
+            lines.push(line.trim().to_string());
+        }
+
+        assert_eq!(lines[0], "HTTP/1.1 101 Switching Protocols");
+        assert!(lines.contains(&"Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string()));
+
+        while server.clients() == 0 {
+            thread::yield_now();
+        }
+
+        let mut grid = Grid::new(3, 2);
+        grid[Point { x: 1, y: 1 }] = Symbol::Red;
+        let progress = Progress {
+            steps: 7,
+            ..Default::default()
+        };
+
+        server.send(&progress, &grid);
+        let mut frame = [0; 16];
+        reader.read_exact(&mut frame).unwrap();
+        assert_eq!(frame[..2], [0x82, 14]);
+        assert_eq!(frame[2..10], [3, 0, 2, 0, 7, 0, 0, 0]);
+        assert_eq!(frame[10..], [0, 0, 0, 0, Symbol::Red.palette_index(), 0]);
+
+        assert_eq!(websocket_frame(&[0; 300])[..4], [0x82, 126, 1, 44]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Running a model on a background thread.
+//!
+//! [Runner::spawn] moves a runner onto a thread of its own and sends
+//! snapshots of its grid over a [crossbeam_channel] as it goes, so that a
+//! GUI can keep drawing while the model runs. The returned [RunHandle]
+//! receives the snapshots, pauses and resumes the run, and hands the runner
+//! back once it is done.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use rand::Rng;
+
+use super::*;
+use recorder::{Capture, CapturePolicy};
+use runner::{PauseHandle, Runner, Snapshot};
+
+/// What a spawned run does when its receiver falls behind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Queue up to this many snapshots, then wait for the receiver to take
+    /// some.
+    Block(usize),
+
+    /// Queue up to this many snapshots, then drop new ones until the
+    /// receiver takes some. The final snapshot is never dropped.
+    Drop(usize),
+
+    /// Queue every snapshot without ever waiting.
+    Unbounded,
+}
+
+/// How a spawned run reports its progress.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpawnOptions {
+    /// When to send snapshots, besides the final one.
+    pub policy: CapturePolicy,
+    pub backpressure: Backpressure,
+}
+
+impl Default for SpawnOptions {
+    fn default() -> Self {
+        Self {
+            policy: CapturePolicy::default(),
+            backpressure: Backpressure::Drop(1),
+        }
+    }
+}
+
+/// A model running on a background thread. See [Runner::spawn].
+pub struct RunHandle<R> {
+    receiver: Receiver<Snapshot>,
+    pause: PauseHandle,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Runner<R>>,
+}
+
+impl<R> RunHandle<R> {
+    /// Receives the run's snapshots. The channel is disconnected after the
+    /// final snapshot.
+    pub fn receiver(&self) -> &Receiver<Snapshot> {
+        &self.receiver
+    }
+
+    /// Makes the run wait before its next step until it is resumed.
+    pub fn pause(&self) {
+        self.pause.pause();
+    }
+
+    pub fn resume(&self) {
+        self.pause.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    /// Asks the run to stop before its next step, sending the grid as it
+    /// stands as the final snapshot.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the run has finished or stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the run to end and returns the runner.
+    ///
+    /// Panics if the run panicked.
+    pub fn join(self) -> Runner<R> {
+        self.thread.join().expect("the spawned run panicked")
+    }
+}
+
+impl<R: Rng + Send + 'static> Runner<R> {
+    /// Runs the model to completion on a new thread, sending a snapshot
+    /// whenever `options.policy` calls for one and after the final step.
+    ///
+    /// The run ignores the budget and breakpoints, but can be paused or
+    /// stopped through the returned handle, and also stops if the receiver
+    /// is dropped.
+    pub fn spawn(mut self, options: SpawnOptions) -> RunHandle<R> {
+        let (sender, receiver) = match options.backpressure {
+            Backpressure::Block(capacity) | Backpressure::Drop(capacity) => {
+                crossbeam_channel::bounded(capacity)
+            }
+            Backpressure::Unbounded => crossbeam_channel::unbounded(),
+        };
+
+        let pause = self.pause_handle();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut capture = Capture::new(options.policy);
+            loop {
+                while self.is_paused() && !stopped.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_millis(5));
+                }
+
+                let finished = stopped.load(Ordering::SeqCst) || !self.step();
+                if !finished && !capture.update(self.progress()) {
+                    continue;
+                }
+
+                let snapshot = Snapshot {
+                    progress: self.progress().clone(),
+                    grid: self.grid.clone(),
+                };
+
+                let drop = matches!(options.backpressure, Backpressure::Drop(_));
+                if !send(&sender, snapshot, drop && !finished) || finished {
+                    return self;
+                }
+            }
+        });
+
+        RunHandle {
+            receiver,
+            pause,
+            stop,
+            thread,
+        }
+    }
+}
+
+/// Sends a snapshot, dropping it instead of waiting if `drop` is set.
+///
+/// Returns false if the receiver was dropped.
+fn send(sender: &Sender<Snapshot>, snapshot: Snapshot, drop: bool) -> bool {
+    match drop {
+        true => !matches!(
+            sender.try_send(snapshot),
+            Err(TrySendError::Disconnected(_))
+        ),
+        false => sender.send(snapshot).is_ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    fn spawn_runs() {
+        let model = Model::from_xml(r#"<one values="BW" in="B" out="W"/>"#).unwrap();
+        let runner = || Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+
+        let handle = runner().spawn(SpawnOptions {
+            policy: CapturePolicy::EverySteps(10),
+            backpressure: Backpressure::Block(1),
+        });
+
+        let steps: Vec<_> = handle
+            .receiver()
+            .iter()
+            .map(|snapshot| snapshot.progress.steps)
+            .collect();
+        assert_eq!(steps, [10, 20, 30, 40, 50, 60, 64]);
+
+        let mut expected = runner();
+        expected.run();
+        assert_eq!(handle.join().grid, expected.grid);
+
+        let handle = runner().spawn(SpawnOptions {
+            policy: CapturePolicy::EverySteps(1),
+            backpressure: Backpressure::Drop(1),
+        });
+
+        let snapshots: Vec<_> = handle.receiver().iter().collect();
+        assert!(snapshots.len() <= 64);
+        assert_eq!(snapshots.last().unwrap().grid, expected.grid);
+        assert_eq!(handle.join().progress().steps, 64);
+
+        let paused = runner();
+        paused.pause();
+        let handle = paused.spawn(SpawnOptions::default());
+        assert!(handle.is_paused());
+        handle.stop();
+        let snapshots: Vec<_> = handle.receiver().iter().collect();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(handle.join().progress().steps, 0);
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use super::*;
+use observer::{Application, Observer};
+
+/// Statistics about a single rule within a node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    /// How many times the grid was searched for this rule.
+    pub scans: usize,
+
+    /// How many of those searches found no matches.
+    pub failed_scans: usize,
+
+    /// The total number of matches found.
+    pub matches: usize,
+
+    /// How many times this rule was applied.
+    pub applications: usize,
+}
+
+/// Statistics about a single node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    /// How many steps were attempted.
+    pub steps: usize,
+
+    /// How many attempted steps did nothing.
+    pub failed_steps: usize,
+
+    /// The total number of patterns applied, including by children.
+    pub applications: usize,
+
+    /// Wall-clock time spent stepping, including children.
+    pub time: Duration,
+
+    /// Per-rule statistics, indexed by rule. Empty for non-rule nodes.
+    pub rules: Vec<RuleStats>,
+}
+
+impl NodeStats {
+    /// The total number of matches found across all rules.
+    pub fn matches(&self) -> usize {
+        self.rules.iter().map(|rule| rule.matches).sum()
+    }
+}
+
+/// Collects per-node and per-rule statistics as an [Observer].
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Statistics keyed by node ID.
+    pub nodes: BTreeMap<usize, NodeStats>,
+
+    /// The nodes currently stepping, innermost last, with their start times.
+    active: Vec<(usize, Instant)>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn node(&self, id: usize) -> Option<&NodeStats> {
+        self.nodes.get(&id)
+    }
+
+    fn rule(&mut self, node: usize, rule: usize) -> &mut RuleStats {
+        let rules = &mut self.nodes.entry(node).or_default().rules;
+        if rules.len() <= rule {
+            rules.resize(rule + 1, RuleStats::default());
+        }
+
+        &mut rules[rule]
+    }
+}
+
+impl Observer for Stats {
+    fn begin_step(&mut self, node: usize) {
+        self.active.push((node, Instant::now()));
+    }
+
+    fn on_scan(&mut self, node: usize, rule: usize, matches: usize) {
+        let rule = self.rule(node, rule);
+        rule.scans += 1;
+        rule.matches += matches;
+        if matches == 0 {
+            rule.failed_scans += 1;
+        }
+    }
+
+    fn after_apply(&mut self, _grid: &Grid, application: &Application) {
+        self.rule(application.node, application.rule).applications += 1;
+        for (node, _) in self.active.iter() {
+            self.nodes.entry(*node).or_default().applications += 1;
+        }
+    }
+
+    fn end_step(&mut self, node: usize, stepped: bool) {
+        let started = match self.active.pop() {
+            Some((active, started)) if active == node => started,
+            _ => panic!("mismatched end of step for node {}", node),
+        };
+
+        let stats = self.nodes.entry(node).or_default();
+        stats.steps += 1;
+        stats.time += started.elapsed();
+        if !stepped {
+            stats.failed_steps += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use rand::rngs::SmallRng;
+    use runner::Runner;
+
+    #[test]
+    fn collect_stats() {
+        let model = Model::from_xml(
+            r#"<sequence values="BRW">
+                <one in="B" out="R" steps="1"/>
+                <one in="RB" out="WR" steps="3"/>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        let mut runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        runner.enable_stats();
+        runner.run();
+
+        let stats = runner.stats().unwrap();
+        let root = stats.node(0).unwrap();
+        assert_eq!(root.applications, 4);
+        assert!(root.rules.is_empty());
+        assert_eq!(root.steps, root.failed_steps + 4);
+
+        let first = stats.node(1).unwrap();
+        assert_eq!(first.applications, 1);
+        assert_eq!(first.rules[0].scans, 1);
+        assert!(first.rules[0].matches > 1);
+
+        let second = stats.node(2).unwrap();
+        assert_eq!(second.applications, 3);
+        assert_eq!(second.steps - second.failed_steps, 3);
+        let applied: usize = second.rules.iter().map(|rule| rule.applications).sum();
+        assert_eq!(applied, 3);
+        assert!(second.matches() >= 3);
+        assert!(root.time >= second.time);
+    }
+}
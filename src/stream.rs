@@ -0,0 +1,136 @@
+//! Streaming snapshots of a run to async code under tokio.
+//!
+//! [Runner::into_stream] moves a runner onto tokio's blocking thread pool
+//! with [spawn_blocking], where it steps the model to completion and sends
+//! snapshots of the grid back over a bounded [mpsc] channel. The
+//! [SnapshotStream] on the other end can be awaited directly or used as a
+//! [futures_core::Stream].
+//!
+//! Snapshots are queued up to the stream's capacity, after which the run
+//! waits for the consumer to catch up. Dropping the stream cancels the run
+//! before its next step.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use rand::Rng;
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+
+use super::*;
+use recorder::{Capture, CapturePolicy};
+use runner::{Runner, Snapshot};
+
+/// An async stream of snapshots from a run. See the [module](self) docs.
+pub struct SnapshotStream {
+    receiver: mpsc::Receiver<Snapshot>,
+}
+
+impl SnapshotStream {
+    /// The next snapshot, or `None` once the final one has been taken.
+    pub async fn next(&mut self) -> Option<Snapshot> {
+        self.receiver.recv().await
+    }
+}
+
+impl futures_core::Stream for SnapshotStream {
+    type Item = Snapshot;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Snapshot>> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
+
+impl<R: Rng + Send + 'static> Runner<R> {
+    /// Runs the model on tokio's blocking thread pool, streaming a snapshot
+    /// whenever `policy` calls for one and after the final step.
+    ///
+    /// At most `capacity` snapshots are queued before the run waits for the
+    /// stream to take some. The run ignores the budget, breakpoints, and
+    /// pausing.
+    ///
+    /// Panics if `capacity` is zero, or if called outside of a tokio
+    /// runtime.
+    pub fn into_stream(mut self, policy: CapturePolicy, capacity: usize) -> SnapshotStream {
+        assert!(capacity > 0, "a snapshot stream needs room for a snapshot");
+
+        let (sender, receiver) = mpsc::channel(capacity);
+        spawn_blocking(move || {
+            let mut capture = Capture::new(policy);
+            while !sender.is_closed() {
+                let finished = !self.step();
+                if !finished && !capture.update(self.progress()) {
+                    continue;
+                }
+
+                let snapshot = Snapshot {
+                    progress: self.progress().clone(),
+                    grid: self.grid.clone(),
+                };
+
+                // Fails once the stream is dropped.
+                if sender.blocking_send(snapshot).is_err() || finished {
+                    return;
+                }
+            }
+        });
+
+        SnapshotStream { receiver }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use rand::rngs::SmallRng;
+    use std::time::{Duration, Instant};
+    use tokio::runtime::{Builder, Runtime};
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread().build().unwrap()
+    }
+
+    #[test]
+    fn stream_snapshots() {
+        let model = Model::from_xml(r#"<one values="BW" in="B" out="W"/>"#).unwrap();
+        let runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        let mut expected = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+        expected.run();
+
+        let snapshots = runtime().block_on(async {
+            let mut stream = runner.into_stream(CapturePolicy::EverySteps(10), 1);
+            let mut snapshots = Vec::new();
+            while let Some(snapshot) = stream.next().await {
+                snapshots.push(snapshot);
+            }
+
+            snapshots
+        });
+
+        let steps: Vec<_> = snapshots.iter().map(|s| s.progress.steps).collect();
+        assert_eq!(steps, [10, 20, 30, 40, 50, 60, 64]);
+        assert_eq!(snapshots.last().unwrap().grid, expected.grid);
+    }
+
+    #[test]
+    fn drop_cancels_run() {
+        // Flips the grid between two colors forever.
+        let model = Model::from_xml(
+            r#"<markov values="BW"><all in="B" out="W"/><all in="W" out="B"/></markov>"#,
+        )
+        .unwrap();
+
+        let runtime = runtime();
+        runtime.block_on(async {
+            let runner = Runner::<SmallRng>::with_seed(&model.root, model.make_grid(8, 8), 1);
+            drop(runner.into_stream(CapturePolicy::OnlyFinal, 1));
+        });
+
+        // Shutting down waits on the blocking pool, so a run that outlived
+        // its stream would use up the whole timeout.
+        let start = Instant::now();
+        runtime.shutdown_timeout(Duration::from_secs(10));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,143 @@
+//! Previewing grids in a terminal with 24-bit ANSI colors.
+
+use std::fmt::Write;
+
+use super::*;
+
+/// How [Grid::render_terminal] draws each cell.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    /// The symbol's letter, as in the [Display] output, in its color.
+    Letters,
+
+    /// Two cells per character using upper half blocks, so that cells come
+    /// out roughly square and twice as many rows fit on screen.
+    #[default]
+    HalfBlocks,
+
+    /// Two full blocks per cell.
+    Blocks,
+
+    /// A colored square emoji per cell. Needs no color support, but only
+    /// suits terminals and fonts that render emoji two columns wide.
+    /// Symbols without a matching emoji are drawn as colored blocks.
+    Emoji,
+}
+
+const RESET: &str = "\x1b[0m";
+
+pub(crate) fn rgb(symbol: Symbol) -> (u8, u8, u8) {
+    let rgb = &Symbol::PALETTE[symbol.palette_index() as usize * 3..][..3];
+    (rgb[0], rgb[1], rgb[2])
+}
+
+fn foreground(out: &mut String, symbol: Symbol) {
+    let (r, g, b) = rgb(symbol);
+    write!(out, "\x1b[38;2;{};{};{}m", r, g, b).unwrap();
+}
+
+fn background(out: &mut String, symbol: Symbol) {
+    let (r, g, b) = rgb(symbol);
+    write!(out, "\x1b[48;2;{};{};{}m", r, g, b).unwrap();
+}
+
+fn emoji(symbol: Symbol) -> Option<char> {
+    use Symbol::*;
+    Some(match symbol {
+        Black => '⬛',
+        White => '⬜',
+        Red => '🟥',
+        Green => '🟩',
+        Blue => '🟦',
+        Emerald => '🟢',
+        Purple => '🟪',
+        Brown => '🟫',
+        Orange => '🟧',
+        Yellow => '🟨',
+        _ => return None,
+    })
+}
+
+impl Grid {
+    /// Renders the visible cells as lines of text for a terminal.
+    pub fn render_terminal(&self, theme: Theme) -> String {
+        let mut out = String::new();
+        let visible = |x, y| self.visible(Point { x, y });
+
+        if theme == Theme::HalfBlocks {
+            for y in (0..self.height).step_by(2) {
+                for x in 0..self.width {
+                    foreground(&mut out, visible(x, y));
+                    if y + 1 < self.height {
+                        background(&mut out, visible(x, y + 1));
+                    }
+
+                    out.push('▀');
+                }
+
+                out.push_str(RESET);
+                out.push('\n');
+            }
+
+            return out;
+        }
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let symbol = visible(x, y);
+                match theme {
+                    Theme::Letters => {
+                        foreground(&mut out, symbol);
+                        out.push(symbol.to_char());
+                    }
+                    Theme::Blocks => {
+                        foreground(&mut out, symbol);
+                        out.push_str("██");
+                    }
+                    Theme::Emoji => match emoji(symbol) {
+                        Some(emoji) => out.push(emoji),
+                        None => {
+                            foreground(&mut out, symbol);
+                            out.push_str("██");
+                            out.push_str(RESET);
+                        }
+                    },
+                    Theme::HalfBlocks => unreachable!(),
+                }
+            }
+
+            if theme != Theme::Emoji {
+                out.push_str(RESET);
+            }
+
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes() {
+        let mut grid = Grid::new(2, 3);
+        grid[Point { x: 1, y: 0 }] = Symbol::Red;
+        grid[Point { x: 1, y: 1 }] = Symbol::White;
+
+        let emoji = grid.render_terminal(Theme::Emoji);
+        assert_eq!(emoji, "⬛🟥\n⬛⬜\n⬛⬛\n");
+
+        let halves = grid.render_terminal(Theme::HalfBlocks);
+        assert_eq!(halves.lines().count(), 2);
+        assert_eq!(halves.matches('▀').count(), 4);
+        assert!(halves.starts_with("\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m▀"));
+        assert!(halves.contains("\x1b[38;2;255;0;77m\x1b[48;2;255;241;232m▀"));
+
+        let letters = grid.render_terminal(Theme::Letters);
+        assert!(letters.contains("\x1b[38;2;255;0;77mR"));
+        assert_eq!(grid.render_terminal(Theme::Blocks).matches('█').count(), 12);
+    }
+}
@@ -0,0 +1,177 @@
+//! Reading grids as terrain for game engines.
+//!
+//! Engine terrains are usually driven by two kinds of image: a heightmap,
+//! giving the ground's height at each point, and splat maps, giving how
+//! strongly each ground texture is painted there. [Grid::heightmap] looks
+//! up the height of each cell's [visible](Grid::visible) symbol in a
+//! [Heights] table, and [Grid::splat_maps] paints a channel per symbol.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use super::*;
+
+/// The height of each symbol's terrain, from 0 to [u16::MAX]. Symbols
+/// default to 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heights {
+    heights: [u16; Symbol::ALL.len()],
+}
+
+impl Default for Heights {
+    fn default() -> Self {
+        Self {
+            heights: [0; Symbol::ALL.len()],
+        }
+    }
+}
+
+impl Heights {
+    pub fn with(mut self, symbol: Symbol, height: u16) -> Self {
+        self.heights[symbol.palette_index() as usize] = height;
+        self
+    }
+
+    pub fn height(&self, symbol: Symbol) -> u16 {
+        self.heights[symbol.palette_index() as usize]
+    }
+}
+
+/// Why a heights description couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseHeightsError {
+    /// An entry wasn't a symbol character, `=`, and a height.
+    InvalidEntry(String),
+    UnknownSymbol(char),
+}
+
+impl Display for ParseHeightsError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use ParseHeightsError::*;
+        match self {
+            InvalidEntry(entry) => write!(
+                fmt,
+                "invalid height entry '{}', expected SYMBOL=HEIGHT with a height from 0 to {}",
+                entry,
+                u16::MAX
+            ),
+            UnknownSymbol(c) => write!(fmt, "unrecognized symbol '{}'", c),
+        }
+    }
+}
+
+impl std::error::Error for ParseHeightsError {}
+
+impl FromStr for Heights {
+    type Err = ParseHeightsError;
+
+    /// Parses whitespace- or comma-separated `SYMBOL=HEIGHT` entries, such
+    /// as `"U=0 Y=9000 G=12000 W=40000"`.
+    fn from_str(string: &str) -> Result<Self, ParseHeightsError> {
+        let mut heights = Self::default();
+        let entries = string
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty());
+
+        for entry in entries {
+            let invalid = || ParseHeightsError::InvalidEntry(entry.to_string());
+            let (symbol, height) = entry.split_once('=').ok_or_else(invalid)?;
+            let mut chars = symbol.chars();
+            let (Some(symbol), None) = (chars.next(), chars.next()) else {
+                return Err(invalid());
+            };
+
+            let symbol =
+                Symbol::try_from_char(symbol).ok_or(ParseHeightsError::UnknownSymbol(symbol))?;
+            let height = height.parse().map_err(|_| invalid())?;
+            heights = heights.with(symbol, height);
+        }
+
+        Ok(heights)
+    }
+}
+
+/// An RGBA image painting up to four symbols, one per channel. See
+/// [Grid::splat_maps].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplatMap {
+    /// The symbol painted by each channel, in RGBA order. The last map's
+    /// unused channels are always zero.
+    pub symbols: Vec<Symbol>,
+
+    /// Four bytes per cell in row order: 255 in the channel of the cell's
+    /// symbol and 0 in the others.
+    pub pixels: Vec<u8>,
+}
+
+impl Grid {
+    /// The height of each cell's visible symbol, as a single-layer grid.
+    pub fn heightmap(&self, heights: &Heights) -> GenericGrid<u16> {
+        let mut heightmap = GenericGrid::new(self.width, self.height);
+        for at in self.enumerate_points() {
+            heightmap[at] = heights.height(self.visible(at));
+        }
+
+        heightmap
+    }
+
+    /// Splat maps painting every visible symbol in the grid, in palette
+    /// order, four symbols to a map.
+    pub fn splat_maps(&self) -> Vec<SplatMap> {
+        let symbols: Vec<_> = self.histogram().iter().map(|(symbol, _)| symbol).collect();
+        symbols
+            .chunks(4)
+            .map(|symbols| {
+                let mut pixels = Vec::with_capacity(self.width * self.height * 4);
+                for at in self.enumerate_points() {
+                    let symbol = self.visible(at);
+                    pixels.extend([0, 1, 2, 3].map(|channel| match symbols.get(channel) {
+                        Some(painted) if *painted == symbol => 255,
+                        _ => 0,
+                    }));
+                }
+
+                SplatMap {
+                    symbols: symbols.to_vec(),
+                    pixels,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terrain_maps() {
+        let heights: Heights = "U=0, G=1000 W=65535".parse().unwrap();
+        assert_eq!(heights.height(Symbol::White), u16::MAX);
+        assert_eq!(heights.height(Symbol::Red), 0);
+
+        let grid = Grid::from_display_str("UGW\nRGB").unwrap();
+        let heightmap = grid.heightmap(&heights);
+        assert_eq!(heightmap.grid, [0, 1000, 65535, 0, 1000, 0]);
+
+        let maps = grid.splat_maps();
+        assert_eq!(maps.len(), 2);
+        assert_eq!(
+            maps[0].symbols,
+            [Symbol::Black, Symbol::White, Symbol::Red, Symbol::Green]
+        );
+        assert_eq!(maps[1].symbols, [Symbol::Blue]);
+        assert_eq!(maps[0].pixels[4..8], [0, 0, 0, 255]);
+        assert_eq!(maps[1].pixels[..4], [255, 0, 0, 0]);
+        assert_eq!(maps[1].pixels[20..], [0, 0, 0, 0]);
+
+        assert_eq!(
+            "W=70000".parse::<Heights>(),
+            Err(ParseHeightsError::InvalidEntry("W=70000".to_string()))
+        );
+        assert_eq!(
+            "Q=1".parse::<Heights>(),
+            Err(ParseHeightsError::UnknownSymbol('Q'))
+        );
+    }
+}
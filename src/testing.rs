@@ -0,0 +1,124 @@
+//! Helpers for golden tests of models.
+//!
+//! Runs are deterministic for a given model, grid size, and seed, so a
+//! model's behavior can be pinned down by checking its final grid against a
+//! fixture written in the text format of [Grid::from_display_str]:
+//!
+//! ```ignore
+//! use crabby_markov::{model::Model, testing::assert_golden};
+//!
+//! let model = Model::load("models/river.xml").unwrap();
+//! assert_golden(&model, 16, 16, 42, include_str!("river-42.txt"));
+//! ```
+//!
+//! On a mismatch the panic message lays the expected and actual rows side by
+//! side, marks the rows that differ, and prints the actual grid in full so
+//! that it can be pasted in as the new fixture once the change is intended.
+
+use std::fmt::Write;
+
+use super::*;
+use model::Model;
+use rng::DefaultRng;
+use runner::Runner;
+
+/// Runs `model` to completion on a new `width` by `height` grid with the
+/// runner's default RNG seeded from `seed`, and returns the final grid.
+pub fn run_seeded(model: &Model, width: usize, height: usize, seed: u64) -> Grid {
+    let grid = model.make_grid(width, height);
+    let mut runner = Runner::<DefaultRng>::with_seed(&model.root, grid, seed);
+    runner.run();
+    runner.grid
+}
+
+/// Runs `model` like [run_seeded] and asserts that the final grid matches
+/// `expected`. See [assert_grid_matches].
+#[track_caller]
+pub fn assert_golden(model: &Model, width: usize, height: usize, seed: u64, expected: &str) {
+    let grid = run_seeded(model, width, height, seed);
+    assert_grid_matches(&grid, expected);
+}
+
+/// Asserts that `actual` holds exactly the cells written in `expected`,
+/// ignoring whether it is periodic.
+///
+/// Panics if `expected` can't be parsed, or with a diff of the two grids if
+/// they don't match.
+#[track_caller]
+pub fn assert_grid_matches(actual: &Grid, expected: &str) {
+    let expected = match Grid::from_display_str(expected) {
+        Ok(expected) => expected,
+        Err(err) => panic!("invalid grid fixture: {}", err),
+    };
+
+    let size = |grid: &Grid| (grid.width, grid.height, grid.layers);
+    if size(actual) != size(&expected) {
+        panic!(
+            "expected a {}x{} grid with {} layers, got {}x{} with {}:\n{}",
+            expected.width,
+            expected.height,
+            expected.layers,
+            actual.width,
+            actual.height,
+            actual.layers,
+            actual
+        );
+    }
+
+    if actual.grid != expected.grid {
+        panic!("{}", describe_mismatch(actual, &expected));
+    }
+}
+
+/// Lays out the rows of two equally sized grids side by side, marking those
+/// that differ, followed by the actual grid.
+fn describe_mismatch(actual: &Grid, expected: &Grid) -> String {
+    let cells = actual
+        .grid
+        .iter()
+        .zip(expected.grid.iter())
+        .filter(|(actual, expected)| actual != expected)
+        .count();
+
+    let mut message = format!("grid differs from the fixture in {} cells\n", cells);
+    let column = expected.width.max("expected".len());
+    writeln!(message, "  {:column$}  actual", "expected").unwrap();
+
+    let (expected, actual) = (expected.to_string(), actual.to_string());
+    for (expected, actual) in expected.lines().zip(actual.lines()) {
+        let marker = if expected == actual { ' ' } else { '>' };
+        writeln!(message, "{} {:column$}  {}", marker, expected, actual).unwrap();
+    }
+
+    write!(message, "\nactual grid:\n{}", actual).unwrap();
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_grids() {
+        let model = Model::from_xml(r#"<one values="BW" in="B" out="W" steps="3"/>"#).unwrap();
+        let grid = run_seeded(&model, 4, 3, 1);
+        assert_eq!(grid, run_seeded(&model, 4, 3, 1));
+
+        let fixture = grid.to_string();
+        assert_golden(&model, 4, 3, 1, &fixture);
+        assert_grid_matches(&grid, &format!("\n    {}", fixture.replace('\n', "\n    ")));
+
+        let mut changed = grid.clone();
+        changed[Point { x: 3, y: 2 }] = Symbol::Red;
+        let message = describe_mismatch(&changed, &grid);
+        let rows: Vec<_> = message.lines().collect();
+        assert_eq!(rows[0], "grid differs from the fixture in 1 cells");
+        assert_eq!(rows[1], "  expected  actual");
+        assert_eq!(rows[2], format!("  {:8}  {}", &fixture[..4], &fixture[..4]));
+        assert!(rows[4].starts_with('>') && rows[4].ends_with('R'));
+        assert!(message.ends_with(&changed.to_string()));
+
+        let result = std::panic::catch_unwind(|| assert_grid_matches(&changed, &fixture));
+        assert!(result.is_err());
+    }
+}
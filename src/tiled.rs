@@ -0,0 +1,167 @@
+//! Exporting grids as [Tiled](https://www.mapeditor.org) maps.
+//!
+//! A Tiled map (`.tmx`) refers to a tileset (`.tsx`), which in turn refers
+//! to an image of its tiles. Every symbol is a tile, and a symbol's tile ID
+//! is its [palette index](Symbol::palette_index), so maps of different
+//! models can share one tileset. [tileset_grid] lays the symbols out as the
+//! tileset's image, to be rendered with [Grid::render_indexed] at the same
+//! tile size that [tileset_tsx] and [Grid::to_tmx] are given.
+//!
+//! Each tile has a `symbol` property holding its symbol's character and a
+//! class named after the symbol, e.g. `red`, so that scripts and automapping
+//! rules in Tiled can tell symbols apart without relying on tile IDs. Each
+//! layer of the grid becomes a tile layer, from the bottom up.
+
+use std::fmt::Write;
+
+use super::*;
+use obj::material_name;
+
+/// How many tiles wide the tileset's image is.
+pub const TILESET_COLUMNS: usize = 8;
+
+/// The Tiled version whose format is written.
+const VERSION: &str = "1.10";
+
+/// Every symbol, [TILESET_COLUMNS] to a row, as drawn in the tileset image.
+pub fn tileset_grid() -> Grid {
+    let mut grid = Grid::new(TILESET_COLUMNS, Symbol::ALL.len() / TILESET_COLUMNS);
+    grid.grid.copy_from_slice(&Symbol::ALL);
+    grid
+}
+
+/// Writes a tileset of every symbol, drawn from the image at `image`, a
+/// path relative to the tileset.
+pub fn tileset_tsx(image: &str, tile_size: u16) -> String {
+    let tiles = tileset_grid();
+    let mut tsx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        tsx,
+        r#"<tileset version="{VERSION}" name="crabby-markov" tilewidth="{tile_size}" tileheight="{tile_size}" tilecount="{}" columns="{TILESET_COLUMNS}">"#,
+        Symbol::ALL.len()
+    )
+    .unwrap();
+
+    writeln!(
+        tsx,
+        r#" <image source="{}" width="{}" height="{}"/>"#,
+        escape(image),
+        tiles.width * tile_size as usize,
+        tiles.height * tile_size as usize
+    )
+    .unwrap();
+
+    for symbol in Symbol::ALL {
+        writeln!(
+            tsx,
+            r#" <tile id="{}" type="{}">"#,
+            symbol.palette_index(),
+            material_name(symbol)
+        )
+        .unwrap();
+        writeln!(tsx, "  <properties>").unwrap();
+        writeln!(
+            tsx,
+            r#"   <property name="symbol" value="{}"/>"#,
+            symbol.to_char()
+        )
+        .unwrap();
+        writeln!(tsx, "  </properties>\n </tile>").unwrap();
+    }
+
+    tsx.push_str("</tileset>\n");
+    tsx
+}
+
+impl Grid {
+    /// Writes the grid as a map using the tileset at `tileset`, a path
+    /// relative to the map, whose tiles are `tile_size` pixels square.
+    pub fn to_tmx(&self, tileset: &str, tile_size: u16) -> String {
+        let mut tmx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            tmx,
+            r#"<map version="{VERSION}" orientation="orthogonal" renderorder="right-down" width="{}" height="{}" tilewidth="{tile_size}" tileheight="{tile_size}" infinite="0" nextlayerid="{}" nextobjectid="1">"#,
+            self.width,
+            self.height,
+            self.layers + 1
+        )
+        .unwrap();
+        writeln!(
+            tmx,
+            r#" <tileset firstgid="1" source="{}"/>"#,
+            escape(tileset)
+        )
+        .unwrap();
+
+        for layer in 0..self.layers {
+            writeln!(
+                tmx,
+                r#" <layer id="{}" name="layer {}" width="{}" height="{}">"#,
+                layer + 1,
+                layer,
+                self.width,
+                self.height
+            )
+            .unwrap();
+            writeln!(tmx, r#"  <data encoding="csv">"#).unwrap();
+
+            // Global tile IDs count from the tileset's `firstgid`, since 0
+            // means no tile.
+            for y in 0..self.height {
+                let row: Vec<_> = (0..self.width)
+                    .map(|x| (self[(Point { x, y }, layer)].palette_index() + 1).to_string())
+                    .collect();
+                let separator = if y + 1 < self.height { "," } else { "" };
+                writeln!(tmx, "{}{}", row.join(","), separator).unwrap();
+            }
+
+            writeln!(tmx, "  </data>\n </layer>").unwrap();
+        }
+
+        tmx.push_str("</map>\n");
+        tmx
+    }
+}
+
+/// Escapes a string for use in an XML attribute.
+fn escape(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_tiled() {
+        let tiles = tileset_grid();
+        assert_eq!(tiles[Point { x: 2, y: 0 }], Symbol::Red);
+        assert_eq!(tiles[Point { x: 0, y: 1 }], Symbol::Brown);
+
+        let tsx = tileset_tsx("tiles & more.png", 16);
+        assert!(tsx.contains(r#"tilewidth="16" tileheight="16" tilecount="32" columns="8""#));
+        assert!(tsx.contains(r#"<image source="tiles &amp; more.png" width="128" height="64"/>"#));
+        assert!(tsx.contains(
+            " <tile id=\"2\" type=\"red\">\n  <properties>\n   <property name=\"symbol\" value=\"R\"/>"
+        ));
+
+        let mut grid = Grid::from_display_str("BWR\nRWB").unwrap();
+        grid = grid.to_layer(0, 2);
+        grid[(Point { x: 1, y: 1 }, 1)] = Symbol::White;
+
+        let tmx = grid.to_tmx("tiles.tsx", 16);
+        assert!(tmx.contains(
+            r#"width="3" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3""#
+        ));
+        assert!(tmx.contains(r#"<tileset firstgid="1" source="tiles.tsx"/>"#));
+        assert!(tmx.contains(
+            "<layer id=\"1\" name=\"layer 0\" width=\"3\" height=\"2\">\n  <data encoding=\"csv\">\n1,2,3,\n3,2,1\n  </data>"
+        ));
+        assert!(tmx.contains("<data encoding=\"csv\">\n1,1,1,\n1,2,1\n  </data>"));
+        assert!(tmx.ends_with("</map>\n"));
+    }
+}
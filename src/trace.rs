@@ -0,0 +1,291 @@
+//! Logging every rewrite a run makes.
+//!
+//! A [Trace] observes a run and records each pattern application in order,
+//! along with where in the node tree it came from and which step made it.
+//! Traces can be written out as text, one event per line:
+//!
+//! ```text
+//! step node path rule x,y replace
+//! 12 3 0.2 1 5,7 WW/B*
+//! ```
+//!
+//! The path lists the child indices leading from the root to the applying
+//! node, separated by dots, or `-` for the root itself.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use super::*;
+use node::AnyState;
+use observer::{Application, Observer};
+
+/// A single recorded pattern application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// The number of steps the run had completed before this one.
+    pub step: usize,
+
+    /// The ID of the applying node.
+    pub node: usize,
+
+    /// The child indices leading from the root to the applying node.
+    pub path: Vec<usize>,
+
+    /// The index of the applied rule within its node.
+    pub rule: usize,
+
+    /// Where the replacement pattern's top-left corner was placed.
+    pub at: Point,
+
+    pub replace: Pattern,
+}
+
+/// An ordered log of every application in a run, as an [Observer].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+
+    /// Each node's path, indexed by node ID.
+    paths: Vec<Vec<usize>>,
+
+    /// The number of completed steps.
+    step: usize,
+
+    /// How many nodes are currently stepping.
+    depth: usize,
+}
+
+impl Trace {
+    /// Creates an empty trace for a run of the given state tree, starting
+    /// after `step` completed steps.
+    pub fn new(state: &AnyState, step: usize) -> Self {
+        let mut paths = Vec::new();
+        collect_paths(state, &mut Vec::new(), &mut paths);
+        Self {
+            paths,
+            step,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Records the paths of a state and its descendants in pre-order, which is
+/// the order of their IDs.
+fn collect_paths(state: &AnyState, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+    paths.push(path.clone());
+    for (index, child) in state.children().iter().enumerate() {
+        path.push(index);
+        collect_paths(child, path, paths);
+        path.pop();
+    }
+}
+
+impl Observer for Trace {
+    fn begin_step(&mut self, _node: usize) {
+        self.depth += 1;
+    }
+
+    fn after_apply(&mut self, _grid: &Grid, application: &Application) {
+        self.events.push(TraceEvent {
+            step: self.step,
+            node: application.node,
+            path: self
+                .paths
+                .get(application.node)
+                .cloned()
+                .unwrap_or_default(),
+            rule: application.rule,
+            at: application.at,
+            replace: application.replace.clone(),
+        });
+    }
+
+    fn end_step(&mut self, _node: usize, stepped: bool) {
+        self.depth -= 1;
+        if self.depth == 0 && stepped {
+            self.step += 1;
+        }
+    }
+}
+
+impl Display for TraceEvent {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{} {} ", self.step, self.node)?;
+        match self.path.is_empty() {
+            true => write!(fmt, "-")?,
+            false => {
+                let path: Vec<_> = self.path.iter().map(usize::to_string).collect();
+                write!(fmt, "{}", path.join("."))?;
+            }
+        }
+
+        write!(fmt, " {} {},{} ", self.rule, self.at.x, self.at.y)?;
+        for (idx, row) in self.replace.grid.chunks(self.replace.width).enumerate() {
+            if idx > 0 {
+                let separator = match idx % self.replace.height {
+                    0 => ' ',
+                    _ => '/',
+                };
+
+                write!(fmt, "{}", separator)?;
+            }
+
+            for cell in row {
+                write!(fmt, "{}", cell.map_or('*', |symbol| symbol.to_char()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        for event in self.events.iter() {
+            writeln!(fmt, "{}", event)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A line of a trace's text that couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseTraceError {
+    /// The 1-based line number.
+    pub line: usize,
+}
+
+impl Display for ParseTraceError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "invalid trace event on line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseTraceError {}
+
+impl FromStr for TraceEvent {
+    type Err = ();
+
+    fn from_str(line: &str) -> Result<Self, ()> {
+        let mut fields = line.split(' ');
+        let mut next = || fields.next().ok_or(());
+        let number = |field: &str| field.parse::<usize>().map_err(|_| ());
+
+        let step = number(next()?)?;
+        let node = number(next()?)?;
+        let path = match next()? {
+            "-" => Vec::new(),
+            path => path.split('.').map(number).collect::<Result<_, _>>()?,
+        };
+
+        let rule = number(next()?)?;
+        let (x, y) = next()?.split_once(',').ok_or(())?;
+        let at = Point {
+            x: number(x)?,
+            y: number(y)?,
+        };
+
+        let replace: Vec<_> = fields.collect();
+        Ok(Self {
+            step,
+            node,
+            path,
+            rule,
+            at,
+            replace: parse_pattern(&replace.join(" ")).ok_or(())?,
+        })
+    }
+}
+
+/// Parses a pattern written by [TraceEvent]'s [Display] impl, checking it
+/// before [Pattern::from_string] would panic.
+fn parse_pattern(string: &str) -> Option<Pattern> {
+    let layers: Vec<Vec<&str>> = string
+        .split(' ')
+        .map(|layer| layer.split('/').collect())
+        .collect();
+
+    let width = layers[0][0].chars().count();
+    let consistent = layers.iter().all(|layer| {
+        layer.len() == layers[0].len() && layer.iter().all(|row| row.chars().count() == width)
+    });
+
+    let symbols = string
+        .chars()
+        .all(|c| matches!(c, '*' | '/' | ' ') || Symbol::try_from_char(c).is_some());
+
+    (width > 0 && consistent && symbols).then(|| Pattern::from_string(string))
+}
+
+impl FromStr for Trace {
+    type Err = ParseTraceError;
+
+    /// Parses a trace's events from its text. The parsed trace has no node
+    /// paths to record further events with.
+    fn from_str(text: &str) -> Result<Self, ParseTraceError> {
+        let events = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                line.trim()
+                    .parse()
+                    .map_err(|_| ParseTraceError { line: idx + 1 })
+            })
+            .collect::<Result<Vec<TraceEvent>, _>>()?;
+
+        Ok(Self {
+            events,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use runner::Runner;
+
+    #[test]
+    fn record_and_export() {
+        let model = Model::from_xml(include_str!("../models/river.xml")).unwrap();
+        let mut runner: Runner = Runner::with_seed(&model.root, model.make_grid(24, 24), 5);
+        runner.enable_trace();
+        runner.run();
+
+        let trace = runner.trace().unwrap();
+        assert_eq!(trace.len(), runner.state.applications());
+        assert_eq!(trace.events[0].step, 0);
+        assert_eq!(trace.events[0].path, [0]);
+        assert_eq!(
+            trace.events.last().unwrap().step,
+            runner.progress().steps - 1
+        );
+        assert!(trace
+            .events
+            .windows(2)
+            .all(|pair| pair[0].step <= pair[1].step));
+
+        let text = trace.to_string();
+        let parsed: Trace = text.parse().unwrap();
+        assert_eq!(parsed.events, trace.events);
+        assert_eq!(
+            "0 1 0 0 1,2 WW/B*".parse::<TraceEvent>().unwrap().replace,
+            Pattern::from_string("WW/B*")
+        );
+
+        let broken = text.replacen(" ", " x", 1);
+        assert_eq!(broken.parse::<Trace>(), Err(ParseTraceError { line: 1 }));
+        assert!("0 1 0 0 1,2 WW/B".parse::<TraceEvent>().is_err());
+    }
+}
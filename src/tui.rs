@@ -0,0 +1,377 @@
+//! A full-screen terminal debugger for models.
+//!
+//! A [Debugger] steps a model a few steps per frame and draws the live grid
+//! next to the node tree, with the path to the active node in bold and the
+//! active node itself highlighted, above counters for the run so far. [run]
+//! drives it from the keyboard:
+//!
+//! | Key       | Action                                 |
+//! |-----------|----------------------------------------|
+//! | space     | pause or resume                        |
+//! | `n`       | take a single step while paused        |
+//! | `+` / `-` | double or halve the steps per frame    |
+//! | `r`       | restart the run with the same seed     |
+//! | `q`       | quit                                   |
+//!
+//! The screen is drawn with [ratatui] on top of [crossterm], which takes
+//! care of raw mode and the alternate screen on every platform it supports.
+
+use std::fmt::Write as _;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Paragraph, Widget};
+use ratatui::{DefaultTerminal, Frame};
+
+use super::*;
+use node::{AnyNode, AnyState};
+use rng::DefaultRng;
+use runner::Runner;
+
+/// The most steps a [Debugger] takes per frame.
+const MAX_SPEED: usize = 1 << 16;
+
+const HELP: &str = "space pause  n step  +/- speed  r restart  q quit";
+
+/// The state of a debugging session. See the [module](self) docs.
+pub struct Debugger {
+    model: AnyNode,
+    initial: Grid,
+    seed: u64,
+    runner: Runner,
+    speed: usize,
+    finished: bool,
+    quit: bool,
+}
+
+impl Debugger {
+    /// Starts a paused run of `model` over `grid`, seeded from `seed`.
+    pub fn new(model: &AnyNode, grid: Grid, seed: u64) -> Self {
+        let runner = Runner::<DefaultRng>::with_seed(model, grid.clone(), seed);
+        runner.pause();
+
+        Self {
+            model: model.clone(),
+            initial: grid,
+            seed,
+            runner,
+            speed: 1,
+            finished: false,
+            quit: false,
+        }
+    }
+
+    pub fn runner(&self) -> &Runner {
+        &self.runner
+    }
+
+    /// The number of steps taken per frame while running.
+    pub fn speed(&self) -> usize {
+        self.speed
+    }
+
+    /// Whether the model has finished.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Whether the user asked to quit.
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Handles a key press. Unknown keys are ignored.
+    pub fn key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            // Raw mode turns Ctrl-C into a plain key press.
+            if key.code == KeyCode::Char('c') {
+                self.quit = true;
+            }
+
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char(' ') if self.runner.is_paused() => self.runner.resume(),
+            KeyCode::Char(' ') => self.runner.pause(),
+            KeyCode::Char('n') if self.runner.is_paused() => self.step(1),
+            KeyCode::Char('+' | '=') => self.speed = (self.speed * 2).min(MAX_SPEED),
+            KeyCode::Char('-') => self.speed = (self.speed / 2).max(1),
+            KeyCode::Char('r') => self.restart(),
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            _ => {}
+        }
+    }
+
+    /// Advances the run by a frame's worth of steps, unless it is paused.
+    pub fn tick(&mut self) {
+        if !self.runner.is_paused() {
+            self.step(self.speed);
+        }
+    }
+
+    fn step(&mut self, steps: usize) {
+        for _ in 0..steps {
+            if self.finished || !self.runner.step() {
+                self.finished = true;
+                return;
+            }
+        }
+    }
+
+    fn restart(&mut self) {
+        let paused = self.runner.is_paused();
+        self.runner = Runner::with_seed(&self.model, self.initial.clone(), self.seed);
+        if paused {
+            self.runner.pause();
+        }
+
+        self.finished = false;
+    }
+
+    /// Draws the grid in the left of `frame` and the panel beside it.
+    pub fn draw(&self, frame: &mut Frame) {
+        let grid = &self.runner.grid;
+        let width = u16::try_from(grid.width).unwrap_or(u16::MAX);
+        let [grid_area, _, panel_area] = Layout::horizontal([
+            Constraint::Length(width),
+            Constraint::Length(2),
+            Constraint::Min(0),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(GridView(grid), grid_area);
+        frame.render_widget(Paragraph::new(self.panel()), panel_area);
+    }
+
+    /// The counters, node tree, and key help drawn beside the grid.
+    fn panel(&self) -> Vec<Line<'static>> {
+        let progress = self.runner.progress();
+        let status = match (self.finished, self.runner.is_paused()) {
+            (true, _) => "finished",
+            (false, true) => "paused",
+            (false, false) => "running",
+        };
+
+        let mut panel = vec![
+            Line::from(format!("seed {}  {}", self.seed, status)),
+            Line::from(format!(
+                "{} steps  {} applications",
+                progress.steps, progress.applications
+            )),
+            Line::from(format!("{} steps per frame", self.speed)),
+            Line::default(),
+        ];
+
+        let path = (!self.finished).then_some(progress.path.as_slice());
+        self.tree(&self.runner.state, path, 0, &mut panel);
+        panel.push(Line::default());
+        panel.push(Line::from(HELP));
+        panel
+    }
+
+    /// Lists `state` and its children, marking those on the remaining
+    /// `path` to the active node.
+    fn tree(
+        &self,
+        state: &AnyState,
+        path: Option<&[usize]>,
+        depth: usize,
+        out: &mut Vec<Line<'static>>,
+    ) {
+        let mut line = format!(
+            "{:indent$}{} #{}",
+            "",
+            state.kind(),
+            state.id(),
+            indent = depth * 2
+        );
+        if let Some(name) = state.name() {
+            write!(line, " \"{}\"", name).unwrap();
+        }
+
+        write!(line, ": {}", state.applications()).unwrap();
+
+        // The active node is the last one on the path whose children aren't
+        // on it too.
+        let active = path.is_some_and(|path| {
+            path.first()
+                .is_none_or(|index| *index >= state.children().len())
+        });
+
+        let style = match (active, path.is_some()) {
+            (true, _) => Style::new().add_modifier(Modifier::REVERSED),
+            (false, true) => Style::new().add_modifier(Modifier::BOLD),
+            (false, false) => Style::new(),
+        };
+
+        out.push(Line::styled(line, style));
+
+        for (index, child) in state.children().iter().enumerate() {
+            let on_path = path.filter(|path| path.first() == Some(&index));
+            self.tree(child, on_path.map(|path| &path[1..]), depth + 1, out);
+        }
+    }
+}
+
+/// Draws a grid with two cells per character using upper half blocks, like
+/// [Theme::HalfBlocks](terminal::Theme::HalfBlocks).
+struct GridView<'a>(&'a Grid);
+
+impl Widget for GridView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let color = |x, y| {
+            let (r, g, b) = terminal::rgb(self.0.visible(Point { x, y }));
+            Color::Rgb(r, g, b)
+        };
+
+        let rows = self.0.height.div_ceil(2);
+        for (row, y) in (area.top()..area.bottom()).zip(0..rows) {
+            for (column, x) in (area.left()..area.right()).zip(0..self.0.width) {
+                let mut style = Style::new().fg(color(x, y * 2));
+                if y * 2 + 1 < self.0.height {
+                    style = style.bg(color(x, y * 2 + 1));
+                }
+
+                buf[(column, row)].set_char('▀').set_style(style);
+            }
+        }
+    }
+}
+
+/// Runs `debugger` full-screen until the user quits, drawing at most `fps`
+/// frames per second.
+///
+/// The terminal is restored afterwards, including when drawing fails or the
+/// debugger panics.
+pub fn run(mut debugger: Debugger, fps: u32) -> io::Result<()> {
+    let mut terminal = ratatui::try_init().inspect_err(|_| ratatui::restore())?;
+    let result = run_loop(&mut terminal, &mut debugger, fps);
+    ratatui::try_restore()?;
+    result
+}
+
+fn run_loop(terminal: &mut DefaultTerminal, debugger: &mut Debugger, fps: u32) -> io::Result<()> {
+    let interval = Duration::from_secs(1) / fps.max(1);
+    while !debugger.should_quit() {
+        let frame = Instant::now();
+        terminal.draw(|frame| debugger.draw(frame))?;
+
+        // Handle every key that arrives before the next frame is due.
+        while let Some(timeout) = interval.checked_sub(frame.elapsed()) {
+            if !event::poll(timeout)? {
+                break;
+            }
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    debugger.key(key);
+                }
+            }
+        }
+
+        debugger.tick();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Model;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    /// Draws a frame of `debugger` and returns its lines along with the
+    /// style of each line's last character.
+    fn draw(debugger: &Debugger, width: u16, height: u16) -> Vec<(String, Style)> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| debugger.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                let line: String = (0..width).map(|x| buffer[(x, y)].symbol()).collect();
+                let last = line.trim_end().chars().count().saturating_sub(1) as u16;
+                (line.trim_end().to_string(), buffer[(last, y)].style())
+            })
+            .collect()
+    }
+
+    fn has_line(frame: &[(String, Style)], text: &str, modifier: Modifier) -> bool {
+        frame
+            .iter()
+            .any(|(line, style)| line.ends_with(text) && style.add_modifier == modifier)
+    }
+
+    fn press(debugger: &mut Debugger, key: char) {
+        debugger.key(KeyCode::Char(key).into());
+    }
+
+    #[test]
+    fn debug_session() {
+        let model = Model::from_xml(
+            r#"<sequence values="BW"><one name="paint" in="B" out="W" steps="3"/><one in="W" out="B"/></sequence>"#,
+        )
+        .unwrap();
+        let mut debugger = Debugger::new(&model.root, model.make_grid(8, 8), 1);
+
+        debugger.tick();
+        assert_eq!(debugger.runner().progress().steps, 0);
+        press(&mut debugger, 'n');
+        assert_eq!(debugger.runner().progress().steps, 1);
+
+        let frame = draw(&debugger, 60, 12);
+        assert!(frame[0].0.starts_with(&"▀".repeat(8)));
+        assert!(frame[4].0.starts_with(&" ".repeat(10)));
+        assert!(has_line(&frame, "seed 1  paused", Modifier::empty()));
+        assert!(has_line(
+            &frame,
+            "1 steps  1 applications",
+            Modifier::empty()
+        ));
+        assert!(has_line(&frame, "sequence #0: 1", Modifier::BOLD));
+        assert!(has_line(
+            &frame,
+            "  one #1 \"paint\": 1",
+            Modifier::REVERSED
+        ));
+        assert!(has_line(&frame, HELP, Modifier::empty()));
+        assert_eq!(draw(&debugger, 20, 2).len(), 2);
+
+        press(&mut debugger, '+');
+        press(&mut debugger, '+');
+        assert_eq!(debugger.speed(), 4);
+        press(&mut debugger, ' ');
+        debugger.tick();
+        assert_eq!(debugger.runner().progress().steps, 5);
+        assert!(has_line(
+            &draw(&debugger, 60, 12),
+            "  one #2: 2",
+            Modifier::REVERSED
+        ));
+
+        while !debugger.is_finished() {
+            debugger.tick();
+        }
+
+        assert!(has_line(
+            &draw(&debugger, 60, 12),
+            "seed 1  finished",
+            Modifier::empty()
+        ));
+
+        press(&mut debugger, 'r');
+        assert_eq!(debugger.runner().progress().steps, 0);
+        assert!(!debugger.is_finished() && !debugger.runner().is_paused());
+
+        debugger.key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert!(debugger.should_quit());
+    }
+}
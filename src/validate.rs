@@ -0,0 +1,351 @@
+//! Acceptance checks for generated grids.
+//!
+//! Models are random, so a level generator that usually works can still
+//! produce the odd unplayable level. [Grid::validate] runs a list of
+//! [Check]s against a finished grid and reports which passed, with a
+//! [Failure] explaining each one that didn't, so that bad outputs can be
+//! rejected or rerolled before they ship:
+//!
+//! ```ignore
+//! use crabby_markov::{validate::Check, Symbol::*};
+//!
+//! let validation = grid.validate(&[
+//!     Check::Connected { symbols: vec![White] },
+//!     Check::Path { from: Red, to: Green, through: vec![White] },
+//!     Check::Rooms { symbols: vec![White], count: 4, min_size: 9 },
+//! ]);
+//!
+//! if !validation.passed() {
+//!     eprintln!("{}", validation);
+//! }
+//! ```
+//!
+//! Like [Grid::components] and [Grid::distance_field], which they are built
+//! on, checks look at the bottom layer only and wrap around the edges of
+//! periodic grids.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::*;
+
+/// A property a grid must have.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// The cells of `symbols` form a single connected region.
+    Connected { symbols: Vec<Symbol> },
+
+    /// A cell of `from` can reach a cell of `to` by orthogonal steps through
+    /// cells of `through`.
+    Path {
+        from: Symbol,
+        to: Symbol,
+        through: Vec<Symbol>,
+    },
+
+    /// At least `count` connected regions of `symbols` have `min_size` cells
+    /// or more.
+    Rooms {
+        symbols: Vec<Symbol>,
+        count: usize,
+        min_size: usize,
+    },
+}
+
+/// Why a grid failed a [Check].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The grid has no cells of the symbols checked.
+    NoCells(Vec<Symbol>),
+
+    /// The cells are split into several regions. `stray` is the first cell,
+    /// row by row, outside the largest one.
+    Disconnected { sizes: Vec<usize>, stray: Point },
+
+    /// No cell of `from` can reach a cell of `to`.
+    NoPath { from: Symbol, to: Symbol },
+
+    /// Too few regions were large enough. `sizes` holds every region's
+    /// size, largest first.
+    TooFewRooms { found: usize, sizes: Vec<usize> },
+}
+
+impl Display for Check {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use Check::*;
+        match self {
+            Connected { symbols } => write!(fmt, "{} is connected", chars(symbols)),
+            Path { from, to, through } => write!(
+                fmt,
+                "{} reaches {} through {}",
+                from.to_char(),
+                to.to_char(),
+                chars(through)
+            ),
+            Rooms {
+                symbols,
+                count,
+                min_size,
+            } => write!(
+                fmt,
+                "at least {} regions of {} with {} or more cells",
+                count,
+                chars(symbols),
+                min_size
+            ),
+        }
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use Failure::*;
+        match self {
+            NoCells(symbols) => write!(fmt, "no cells of {}", chars(symbols)),
+            Disconnected { sizes, stray } => write!(
+                fmt,
+                "split into {} regions of sizes {:?}, e.g. at {} outside the largest",
+                sizes.len(),
+                sizes,
+                stray
+            ),
+            NoPath { from, to } => write!(
+                fmt,
+                "no cell of {} can reach a cell of {}",
+                from.to_char(),
+                to.to_char()
+            ),
+            TooFewRooms { found, sizes } => write!(
+                fmt,
+                "only {} large enough, region sizes are {:?}",
+                found, sizes
+            ),
+        }
+    }
+}
+
+/// Writes symbols as their characters, e.g. `WR`.
+fn chars(symbols: &[Symbol]) -> String {
+    symbols.iter().map(Symbol::to_char).collect()
+}
+
+impl Check {
+    /// Runs the check against `grid`.
+    pub fn run(&self, grid: &Grid) -> Result<(), Failure> {
+        use Check::*;
+        match self {
+            Connected { symbols } => {
+                let components = grid.components(symbols);
+                let Some(largest) = components.largest() else {
+                    return Err(Failure::NoCells(symbols.clone()));
+                };
+
+                if components.len() == 1 {
+                    return Ok(());
+                }
+
+                let stray = components
+                    .labels
+                    .iter()
+                    .find(|(_, label)| label.is_some_and(|label| label != largest))
+                    .map(|(at, _)| at)
+                    .unwrap();
+
+                Err(Failure::Disconnected {
+                    sizes: components.sizes,
+                    stray,
+                })
+            }
+            Path { from, to, through } => {
+                for symbol in [from, to] {
+                    if !grid.enumerate_points().any(|at| grid[at] == *symbol) {
+                        return Err(Failure::NoCells(vec![*symbol]));
+                    }
+                }
+
+                // Searching from `to` ends on `from` cells, so they must be
+                // passable too.
+                let mut passable = through.clone();
+                passable.push(*from);
+                let distances = grid.distance_field(&[*to], &passable);
+                let reached = grid
+                    .enumerate_points()
+                    .any(|at| grid[at] == *from && distances[at].is_some());
+
+                match reached {
+                    true => Ok(()),
+                    false => Err(Failure::NoPath {
+                        from: *from,
+                        to: *to,
+                    }),
+                }
+            }
+            Rooms {
+                symbols,
+                count,
+                min_size,
+            } => {
+                let mut sizes = grid.components(symbols).sizes;
+                sizes.sort_unstable_by(|a, b| b.cmp(a));
+                let found = sizes.iter().filter(|size| **size >= *min_size).count();
+                match found >= *count {
+                    true => Ok(()),
+                    false => Err(Failure::TooFewRooms { found, sizes }),
+                }
+            }
+        }
+    }
+}
+
+/// The results of [Grid::validate], in the order the checks were given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Validation {
+    pub results: Vec<(Check, Result<(), Failure>)>,
+}
+
+impl Validation {
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+
+    /// The checks that failed, with why.
+    pub fn failures(&self) -> impl Iterator<Item = (&Check, &Failure)> {
+        self.results
+            .iter()
+            .filter_map(|(check, result)| Some((check, result.as_ref().err()?)))
+    }
+}
+
+impl Display for Validation {
+    /// Writes a line per check, e.g. `FAIL W is connected: split into ...`.
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        for (check, result) in self.results.iter() {
+            match result {
+                Ok(()) => writeln!(fmt, "pass {}", check)?,
+                Err(failure) => writeln!(fmt, "FAIL {}: {}", check, failure)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Grid {
+    /// Runs every check against the grid. See the [module](self) docs.
+    pub fn validate(&self, checks: &[Check]) -> Validation {
+        Validation {
+            results: checks
+                .iter()
+                .map(|check| (check.clone(), check.run(self)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Symbol::*;
+
+    #[test]
+    fn validate_levels() {
+        let grid = Grid::from_display_str(
+            "
+            RWWBWWW
+            BWWBWWW
+            BBBBBWG
+            WWBBBBB
+            ",
+        )
+        .unwrap();
+
+        let validation = grid.validate(&[
+            Check::Connected {
+                symbols: vec![White],
+            },
+            Check::Connected {
+                symbols: vec![White, Red],
+            },
+            Check::Path {
+                from: Red,
+                to: Green,
+                through: vec![White],
+            },
+            Check::Path {
+                from: Green,
+                to: Green,
+                through: vec![],
+            },
+            Check::Path {
+                from: Red,
+                to: Blue,
+                through: vec![White, Black],
+            },
+            Check::Rooms {
+                symbols: vec![White],
+                count: 2,
+                min_size: 4,
+            },
+            Check::Rooms {
+                symbols: vec![White],
+                count: 3,
+                min_size: 2,
+            },
+        ]);
+
+        let results: Vec<_> = validation.results.iter().map(|(_, r)| r.clone()).collect();
+        assert_eq!(
+            results,
+            [
+                Err(Failure::Disconnected {
+                    sizes: vec![4, 7, 2],
+                    stray: Point { x: 1, y: 0 }
+                }),
+                Err(Failure::Disconnected {
+                    sizes: vec![5, 7, 2],
+                    stray: Point { x: 0, y: 0 }
+                }),
+                Err(Failure::NoPath {
+                    from: Red,
+                    to: Green
+                }),
+                Ok(()),
+                Err(Failure::NoCells(vec![Blue])),
+                Ok(()),
+                Ok(()),
+            ]
+        );
+
+        assert!(!validation.passed());
+        assert_eq!(validation.failures().count(), 4);
+        let report = validation.to_string();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(
+            lines[2],
+            "FAIL R reaches G through W: no cell of R can reach a cell of G"
+        );
+        assert_eq!(
+            lines[5],
+            "pass at least 2 regions of W with 4 or more cells"
+        );
+
+        let rooms = Check::Rooms {
+            symbols: vec![White],
+            count: 3,
+            min_size: 4,
+        };
+        assert_eq!(
+            rooms.run(&grid),
+            Err(Failure::TooFewRooms {
+                found: 2,
+                sizes: vec![7, 4, 2]
+            })
+        );
+
+        let connected = Check::Connected {
+            symbols: vec![Blue],
+        };
+        assert_eq!(connected.run(&grid), Err(Failure::NoCells(vec![Blue])));
+        assert!(Grid::new(2, 2).validate(&[]).passed());
+    }
+}
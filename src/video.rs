@@ -0,0 +1,223 @@
+//! Recording runs as MP4 or WebM videos by piping frames to `ffmpeg`.
+//!
+//! GIFs are limited to 256 colors per frame and to frame delays in
+//! hundredths of a second, and long runs at large tile sizes make for huge
+//! files. A [VideoRecorder] instead streams raw RGB frames into an `ffmpeg`
+//! process, which picks a codec from the output file's extension. `ffmpeg`
+//! must be installed separately.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+
+use super::*;
+use palette::Palette;
+use recorder::{Capture, CapturePolicy};
+use runner::Progress;
+
+#[derive(Debug)]
+pub enum VideoError {
+    /// `ffmpeg` couldn't be started, usually because it isn't installed.
+    Spawn(io::Error),
+    Io(io::Error),
+    Ffmpeg {
+        status: ExitStatus,
+        stderr: String,
+    },
+}
+
+impl Display for VideoError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use VideoError::*;
+        match self {
+            Spawn(err) => write!(fmt, "failed to start ffmpeg: {}", err),
+            Io(err) => write!(fmt, "failed to write video frames: {}", err),
+            Ffmpeg { status, stderr } => {
+                write!(fmt, "ffmpeg exited with {}: {}", status, stderr.trim())
+            }
+        }
+    }
+}
+
+impl std::error::Error for VideoError {}
+
+impl From<io::Error> for VideoError {
+    fn from(err: io::Error) -> Self {
+        VideoError::Io(err)
+    }
+}
+
+/// Streams grid frames into an `ffmpeg` process as they are captured.
+///
+/// `ffmpeg` is started when the first frame is written, so the public
+/// fields can be changed after creating the recorder.
+pub struct VideoRecorder {
+    out: PathBuf,
+    width: usize,
+    height: usize,
+    tile_size: u16,
+    capture: Capture,
+    process: Option<(Child, ChildStdin)>,
+
+    /// Frames per second of the output video.
+    pub fps: u32,
+
+    /// How many times the final frame is repeated, so that the finished
+    /// grid stays on screen.
+    pub final_frames: usize,
+
+    /// The `ffmpeg` executable to run.
+    pub ffmpeg: PathBuf,
+
+    /// The colors frames are drawn in.
+    pub palette: Palette,
+}
+
+impl VideoRecorder {
+    /// Creates a recorder for a `width` by `height` grid, drawing each cell
+    /// as a square `tile_size` pixels wide.
+    pub fn new(out: impl AsRef<Path>, width: usize, height: usize, tile_size: u16) -> Self {
+        Self {
+            out: out.as_ref().to_path_buf(),
+            width,
+            height,
+            tile_size,
+            capture: Capture::new(Default::default()),
+            process: None,
+            fps: 30,
+            final_frames: 60,
+            ffmpeg: PathBuf::from("ffmpeg"),
+            palette: Palette::Default,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: CapturePolicy) -> Self {
+        self.capture = Capture::new(policy);
+        self
+    }
+
+    /// Records a frame if the capture policy calls for one.
+    pub fn update(&mut self, progress: &Progress, grid: &Grid) -> Result<(), VideoError> {
+        if self.capture.update(progress) {
+            self.record(grid)?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders and writes a single intermediate frame.
+    pub fn record(&mut self, grid: &Grid) -> Result<(), VideoError> {
+        let frame = render_rgb(grid, self.tile_size, self.palette);
+        self.write(&frame)
+    }
+
+    /// Writes the final frame, then waits for `ffmpeg` to finish the file.
+    pub fn finish(mut self, grid: &Grid) -> Result<(), VideoError> {
+        let frame = render_rgb(grid, self.tile_size, self.palette);
+        for _ in 0..self.final_frames.max(1) {
+            self.write(&frame)?;
+        }
+
+        let (child, stdin) = self.process.take().expect("no frames were written");
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        match output.status.success() {
+            true => Ok(()),
+            false => Err(VideoError::Ffmpeg {
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }),
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), VideoError> {
+        if self.process.is_none() {
+            let mut child = Command::new(&self.ffmpeg)
+                .args(self.arguments())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(VideoError::Spawn)?;
+
+            let stdin = child.stdin.take().expect("ffmpeg stdin is piped");
+            self.process = Some((child, stdin));
+        }
+
+        let (_, stdin) = self.process.as_mut().unwrap();
+        stdin.write_all(frame)?;
+        Ok(())
+    }
+
+    /// The command line arguments passed to `ffmpeg`.
+    fn arguments(&self) -> Vec<String> {
+        let tile = self.tile_size as usize;
+        let size = format!("{}x{}", self.width * tile, self.height * tile);
+        let mut arguments: Vec<String> = [
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgb24",
+            "-s",
+            &size,
+            "-r",
+            &self.fps.to_string(),
+            "-i",
+            "-",
+            // Most encoders need even dimensions for yuv420p, which is what
+            // players expect.
+            "-vf",
+            "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+            "-pix_fmt",
+            "yuv420p",
+        ]
+        .map(String::from)
+        .into();
+
+        arguments.push(self.out.display().to_string());
+        arguments
+    }
+}
+
+/// Renders each cell as a square tile of RGB pixels.
+fn render_rgb(grid: &Grid, tile_size: u16, palette: Palette) -> Vec<u8> {
+    let colors = palette.colors();
+    grid.render_indexed(tile_size)
+        .into_iter()
+        .flat_map(|index| {
+            let rgb = &colors[index as usize * 3..][..3];
+            [rgb[0], rgb[1], rgb[2]]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffmpeg_arguments() {
+        let mut recorder = VideoRecorder::new("out.webm", 5, 3, 4);
+        recorder.fps = 60;
+        let arguments = recorder.arguments();
+        assert_eq!(arguments[8], "20x12");
+        assert_eq!(arguments[10], "60");
+        assert_eq!(arguments.last().unwrap(), "out.webm");
+
+        let mut grid = Grid::new(2, 1);
+        grid[Point { x: 1, y: 0 }] = Symbol::Red;
+        let frame = render_rgb(&grid, 2, Palette::Default);
+        assert_eq!(frame.len(), 4 * 2 * 3);
+        assert_eq!(&frame[6..9], &[0xff, 0x00, 0x4d]);
+        let frame = render_rgb(&grid, 2, Palette::Grayscale);
+        assert_eq!(&frame[6..9], &[0x55, 0x55, 0x55]);
+
+        recorder.ffmpeg = PathBuf::from("/nonexistent/ffmpeg");
+        let result = recorder.record(&grid);
+        assert!(matches!(result, Err(VideoError::Spawn(_))));
+    }
+}
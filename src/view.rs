@@ -0,0 +1,171 @@
+//! Borrowed views of rectangular regions of a grid, and matching restricted
+//! to them.
+//!
+//! A [GridView] reads a region in place, addressed from its own top-left
+//! corner, where [GenericGrid::crop] would copy it out.
+//! [Grid::find_matches_in] only reports matches that lie entirely inside a
+//! region, so that different parts of a map can be generated differently.
+
+use super::*;
+
+/// A rectangular region of a grid, indexed relative to its top-left corner.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GridView<'a, T> {
+    grid: &'a GenericGrid<T>,
+    rect: Rect,
+}
+
+impl<T> Clone for GridView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GridView<'_, T> {}
+
+impl<'a, T> GridView<'a, T> {
+    /// The region of the underlying grid this view covers.
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn width(&self) -> usize {
+        self.rect.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rect.height
+    }
+
+    /// The grid this view borrows from.
+    pub fn grid(&self) -> &'a GenericGrid<T> {
+        self.grid
+    }
+
+    /// The bottom-layer cell at `at`, or `None` if it lies outside the view.
+    pub fn get(&self, at: Point) -> Option<&'a T> {
+        let inside = at.x < self.rect.width && at.y < self.rect.height;
+        inside.then(|| &self.grid[self.rect.origin() + at])
+    }
+
+    /// The cells of the bottom layer inside the view with their positions
+    /// relative to the view, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &'a T)> {
+        let (grid, origin) = (self.grid, self.rect.origin());
+        Rect::new(Point::ZERO, self.rect.width, self.rect.height)
+            .points()
+            .map(move |at| (at, &grid[origin + at]))
+    }
+
+    /// Converts a point relative to the view to one on the underlying grid.
+    pub fn to_grid_point(&self, at: Point) -> Point {
+        self.rect.origin() + at
+    }
+}
+
+impl<T: Clone + Default> GridView<'_, T> {
+    /// Copies the viewed cells out into a grid of their own.
+    pub fn to_grid(&self) -> GenericGrid<T> {
+        self.grid.crop(self.rect)
+    }
+}
+
+impl<T> Index<Point> for GridView<'_, T> {
+    type Output = T;
+
+    fn index(&self, at: Point) -> &T {
+        self.get(at)
+            .unwrap_or_else(|| panic!("at {} is outside the {} view", at, self.rect))
+    }
+}
+
+impl<T> Index<(Point, usize)> for GridView<'_, T> {
+    type Output = T;
+
+    fn index(&self, (at, layer): (Point, usize)) -> &T {
+        if at.x >= self.rect.width || at.y >= self.rect.height {
+            panic!("at {} is outside the {} view", at, self.rect);
+        }
+
+        &self.grid[(self.rect.origin() + at, layer)]
+    }
+}
+
+impl<T> GenericGrid<T> {
+    /// Borrows the cells inside `rect`.
+    ///
+    /// Panics if `rect` doesn't fit inside this grid.
+    pub fn view(&self, rect: Rect) -> GridView<'_, T> {
+        if rect.x + rect.width > self.width || rect.y + rect.height > self.height {
+            panic!("{} is out-of-bounds", rect);
+        }
+
+        GridView { grid: self, rect }
+    }
+}
+
+impl Grid {
+    /// Finds every position at which `pattern` matches entirely inside
+    /// `rect`, in row order. Matches never wrap around the region's edges,
+    /// even on periodic grids.
+    ///
+    /// Panics if `rect` doesn't fit inside this grid.
+    pub fn find_matches_in(&self, pattern: &Pattern, rect: Rect) -> Vec<Point> {
+        let view = self.view(rect);
+        if pattern.layers > self.layers {
+            panic!("pattern has more layers than the grid");
+        }
+
+        if pattern.width > view.width() || pattern.height > view.height() {
+            return Vec::new();
+        }
+
+        let free = Rect::new(
+            rect.origin(),
+            view.width() - pattern.width + 1,
+            view.height() - pattern.height + 1,
+        );
+
+        free.points()
+            .filter(|at| self.test_match_unchecked(pattern, *at))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_region() {
+        let grid = Grid::from_display_str(
+            "
+            RBBW
+            BWWB
+            BBWR
+            ",
+        )
+        .unwrap();
+
+        let rect = Rect::new(Point { x: 1, y: 1 }, 3, 2);
+        let view = grid.view(rect);
+        assert_eq!(view[Point::ZERO], Symbol::White);
+        assert_eq!(view[Point { x: 2, y: 1 }], Symbol::Red);
+        assert_eq!(view.get(Point { x: 3, y: 0 }), None);
+        assert_eq!(view.iter().count(), 6);
+        assert_eq!(view.to_grid(), grid.crop(rect));
+
+        let pattern = Pattern::from_string("W");
+        assert_eq!(grid.find_matches_in(&pattern, rect).len(), 3);
+        assert_eq!(
+            grid.find_matches_in(&pattern, Rect::new(Point::ZERO, 2, 3)),
+            [Point { x: 1, y: 1 }]
+        );
+
+        let tall = Pattern::from_string("W/W");
+        assert_eq!(grid.find_matches_in(&tall, rect), [Point { x: 2, y: 1 }]);
+        assert!(grid
+            .find_matches_in(&Pattern::from_string("WWWW"), rect)
+            .is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+//! `wasm-bindgen` bindings for driving a model from JavaScript.
+//!
+//! ```js
+//! const runner = new Runner(modelXml, 64, 64, 2n);
+//! while (runner.step_for(64) > 0) {
+//!     const image = new ImageData(runner.rgba(4), runner.width * 4);
+//!     context.putImageData(image, 0, 0);
+//! }
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use super::*;
+use model::Model;
+
+#[wasm_bindgen(js_name = Runner)]
+pub struct WasmRunner {
+    runner: runner::Runner,
+}
+
+#[wasm_bindgen(js_class = Runner)]
+impl WasmRunner {
+    /// Loads an XML model and prepares a grid to run it on.
+    #[wasm_bindgen(constructor)]
+    pub fn new(model: &str, width: usize, height: usize, seed: u64) -> Result<WasmRunner, JsError> {
+        let model = Model::from_xml(model)?;
+        let grid = model.make_grid(width, height);
+
+        Ok(Self {
+            runner: runner::Runner::with_seed(&model.root, grid, seed),
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.runner.grid.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.runner.grid.height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn steps(&self) -> usize {
+        self.runner.progress().steps
+    }
+
+    /// Performs a single step. Returns false once the model has finished.
+    pub fn step(&mut self) -> bool {
+        self.runner.step()
+    }
+
+    /// Performs up to `n` steps and returns how many were taken.
+    pub fn step_for(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.runner.step()).count()
+    }
+
+    /// Returns the palette index of the cell at (x, y).
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.runner.grid[Point { x, y }].palette_index()
+    }
+
+    /// Returns every cell's palette index in row-major order.
+    pub fn cells(&self) -> Vec<u8> {
+        self.runner.grid.render_indexed(1)
+    }
+
+    /// Renders the grid as RGBA pixels, suitable for `ImageData`.
+    pub fn rgba(&self, tile_size: u16) -> Vec<u8> {
+        self.runner.grid.render_rgba(tile_size)
+    }
+
+    /// The RGB palette indexed by [WasmRunner::get] and [WasmRunner::cells].
+    pub fn palette() -> Vec<u8> {
+        Symbol::PALETTE.to_vec()
+    }
+}
@@ -0,0 +1,89 @@
+//! Reloading model files when they change, for a fast edit-and-run loop.
+//!
+//! [ModelWatcher] polls the file's metadata rather than subscribing to
+//! filesystem events, so it needs no extra dependencies or threads. Callers
+//! poll it whenever convenient, e.g. once per frame or between commands.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::*;
+use model::{Model, ModelResult, Parameters};
+
+/// Watches a model file for changes.
+#[derive(Clone, Debug)]
+pub struct ModelWatcher {
+    path: PathBuf,
+    version: Option<(SystemTime, u64)>,
+    parameters: Parameters,
+}
+
+impl ModelWatcher {
+    /// Starts watching `path` from its current contents.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let version = version(&path);
+        Self {
+            path,
+            version,
+            parameters: Parameters::new(),
+        }
+    }
+
+    /// Reloads the model with these parameters set.
+    pub fn with_parameters(mut self, parameters: Parameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reloads the model if the file has changed since the watcher was
+    /// created or last reloaded it. Returns `None` if it hasn't changed.
+    ///
+    /// A file that fails to load is not retried until it changes again.
+    pub fn poll(&mut self) -> Option<ModelResult<Model>> {
+        let version = version(&self.path);
+        if version.is_none() || version == self.version {
+            return None;
+        }
+
+        self.version = version;
+        Some(Model::load_with(&self.path, &self.parameters))
+    }
+}
+
+/// Identifies a version of a file by its modification time and length, as
+/// some filesystems only record times to the second.
+fn version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_on_change() {
+        let path = std::env::temp_dir().join(format!("crabby-watch-{}.xml", std::process::id()));
+        std::fs::write(&path, r#"<one in="B" out="W"/>"#).unwrap();
+
+        let mut watcher = ModelWatcher::new(&path);
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&path, r#"<one values="BR" in="B" out="R"/>"#).unwrap();
+        let model = watcher.poll().unwrap().unwrap();
+        assert_eq!(model.values, Some(vec![Symbol::Black, Symbol::Red]));
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&path, "<one").unwrap();
+        assert!(watcher.poll().unwrap().is_err());
+        assert!(watcher.poll().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.poll().is_none());
+    }
+}